@@ -0,0 +1,81 @@
+//! Times reading, looking up, and writing the tasks of a project with 10,000
+//! tasks, run with `cargo bench`.  Criterion runs each benchmark many times
+//! and reports how the time compares to the last run.
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use std::path::{Path, PathBuf};
+use tisk::io::{find_task_dir_from, initialize_at};
+use tisk::tasks::TaskList;
+
+const TASKS: u32 = 10_000;
+
+/// A project with `TASKS` tasks, each with a tag and a note, which is
+/// removed when dropped.
+struct Project(PathBuf);
+
+impl Project {
+    fn new() -> Project {
+        let root = std::env::temp_dir().join(format!("tisk-bench-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+        initialize_at(&root).unwrap();
+
+        let task_path = find_task_dir_from(&root).unwrap();
+        let mut tasks = TaskList::new();
+        for i in 0..TASKS {
+            let id = tasks.add_task(&format!("synthetic task number {}", i), i % 10);
+            let task = tasks.get_mut(id).unwrap();
+            task.add_tag(&format!("tag-{}", i % 50));
+            task.add_note(&format!("a note about task {}", i)).unwrap();
+        }
+        tasks.write_all(&task_path).unwrap();
+        Project(root)
+    }
+
+    fn task_path(&self) -> PathBuf {
+        find_task_dir_from(&self.0).unwrap()
+    }
+}
+
+impl Drop for Project {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn read(task_path: &Path) -> TaskList {
+    TaskList::read_tasks(&task_path.to_path_buf()).unwrap()
+}
+
+fn large_project(c: &mut Criterion) {
+    let project = Project::new();
+    let task_path = project.task_path();
+
+    c.bench_function("read_tasks", |b| b.iter(|| read(&task_path)));
+
+    let tasks = read(&task_path);
+    c.bench_function("next_id", |b| b.iter(|| tasks.next_id()));
+    c.bench_function("get", |b| b.iter(|| tasks.get(black_box(TASKS / 2)).map(|t| t.id())));
+
+    // Every task is unchanged, so nothing needs to be written
+    c.bench_function("write_all, unchanged", |b| b.iter(|| tasks.write_all(&task_path).unwrap()));
+
+    // One task is changed, as by most commands
+    let mut priority = 0;
+    let mut one_changed = || {
+        let mut tasks = read(&task_path);
+        priority += 1;
+        tasks.set_priority(1, priority);
+        tasks
+    };
+    c.bench_function("write_all, one changed", |b| {
+        b.iter_batched(&mut one_changed, |tasks| tasks.write_all(&task_path).unwrap(), BatchSize::LargeInput)
+    });
+}
+
+criterion_group! {
+    name = benches;
+    // Reading and writing 10,000 tasks is slow, so fewer samples are taken than by default
+    config = Criterion::default().sample_size(10);
+    targets = large_project
+}
+criterion_main!(benches);
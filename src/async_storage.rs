@@ -0,0 +1,236 @@
+/*!
+ * Reads and writes tisk projects without blocking the thread, using
+ * `tokio::fs`.  These mirror the blocking functions on `TaskList`, `Config`,
+ * and in `io`, and store projects in exactly the same files, so a project
+ * can be read by one and written by the other.  Only available with the
+ * `async` feature.
+ */
+use crate::config::Config;
+use crate::io::{local_path, parse_checkout, temp_path, CHECKOUT_FILE, LOCAL_DIR};
+use crate::meta::{Layout, Meta, DEFAULT_DIR_NAME, META_FILE, SINGLE_FILE};
+use crate::project::Project;
+use crate::tasks::{is_task_file, Task, TaskList};
+use std::io::{Error, ErrorKind, Result};
+use std::path::Path;
+use tokio::fs;
+
+/// Reads the metadata of the project whose directory is `task_path`, like
+/// `Meta::read`.
+pub async fn read_meta(task_path: &Path) -> Result<Meta> {
+    match fs::read_to_string(task_path.join(META_FILE)).await {
+        Ok(s) => Meta::from_yaml(&s),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            let dir_name = task_path.file_name().and_then(|n| n.to_str()).unwrap_or(DEFAULT_DIR_NAME);
+            Ok(Meta { dir_name: String::from(dir_name), ..Meta::default() })
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Reads every task in the `.tisk` directory at `task_path`.
+pub async fn read_tasks(task_path: &Path) -> Result<TaskList> {
+    let meta = read_meta(task_path).await?;
+    let mut tasks = read_task_files(task_path, meta.layout).await?;
+    tasks.reserve_ids(meta.last_id);
+    tasks.mark_read();
+    Ok(tasks)
+}
+
+/// Reads the tasks stored with `layout`, without looking at the metadata.
+async fn read_task_files(task_path: &Path, layout: Layout) -> Result<TaskList> {
+    if layout == Layout::SingleFile {
+        return match fs::read_to_string(task_path.join(SINGLE_FILE)).await {
+            Ok(s) => serde_yaml::from_str(&s).map_err(|why| Error::new(ErrorKind::InvalidData, why)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(TaskList::new()),
+            Err(err) => Err(err),
+        };
+    }
+
+    let mut tasks = vec![];
+    let mut entries = fs::read_dir(task_path).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if is_task_file(&path) {
+            let s = fs::read_to_string(&path).await?;
+            let mut task = Task::from_stored_yaml(&s, task_path)?;
+            if let Some(notes_file) = task.unread_notes_file() {
+                let notes = fs::read_to_string(notes_file).await?;
+                task.read_notes_from(&notes)?;
+            }
+            tasks.push(task);
+        }
+    }
+    Ok(tasks.into_iter().collect())
+}
+
+/// Writes `contents` to the file at `path` unless it already holds exactly
+/// `contents`, like `io::write_if_changed`.
+async fn write_if_changed(path: &Path, contents: &str) -> Result<()> {
+    match fs::read_to_string(path).await {
+        Ok(existing) if existing == contents => Ok(()),
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => write_atomic(path, contents).await,
+    }
+}
+
+/// Writes `contents` to a temporary file and renames it over `path`, like
+/// `io::write_atomic`.
+async fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let temp = temp_path(path);
+    let written = match fs::write(&temp, contents).await {
+        Ok(()) => fs::rename(&temp, path).await,
+        Err(err) => Err(err),
+    };
+    if written.is_err() {
+        let _ = fs::remove_file(&temp).await;
+    }
+    written
+}
+
+/// Writes the tasks in `tasks` which have been added or changed since they
+/// were read to the `.tisk` directory at `task_path`.  Files which already
+/// hold what would be written are left alone.  The
+/// largest ID given to a task is recorded in the metadata.
+pub async fn write_tasks(tasks: &TaskList, task_path: &Path) -> Result<()> {
+    let mut meta = read_meta(task_path).await?;
+    if tasks.next_id() - 1 > meta.last_id {
+        meta.last_id = tasks.next_id() - 1;
+        write_if_changed(&task_path.join(META_FILE), &meta.to_yaml()?).await?;
+    }
+    if meta.layout == Layout::SingleFile {
+        let s = serde_yaml::to_string(tasks).map_err(|why| Error::new(ErrorKind::InvalidData, why))?;
+        return write_if_changed(&task_path.join(SINGLE_FILE), &s).await;
+    }
+
+    for task in tasks.get_modified() {
+        let (yaml, notes) = task.to_stored_yaml()?;
+        match notes {
+            None => (),
+            Some(None) => remove_if_exists(&task.notes_path(task_path)).await?,
+            Some(Some(notes)) => write_if_changed(&task.notes_path(task_path), &notes).await?,
+        }
+        write_if_changed(&task.file_path(task_path), &yaml).await?;
+    }
+    Ok(())
+}
+
+/// Reads which task is checked out, if any.  Projects created by older
+/// versions of tisk keep the checkout in the project directory rather than
+/// the local directory.
+pub async fn read_checkout(task_path: &Path) -> Result<Option<u32>> {
+    for path in &[local_path(task_path, CHECKOUT_FILE), task_path.join(CHECKOUT_FILE)] {
+        match fs::read_to_string(path).await {
+            Ok(s) => return parse_checkout(&s).map(Some),
+            Err(err) if err.kind() == ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(None)
+}
+
+/// Commit that task `id` has been checked out.
+pub async fn commit_checkout(id: u32, task_path: &Path) -> Result<()> {
+    fs::create_dir_all(task_path.join(LOCAL_DIR)).await?;
+    write_if_changed(&local_path(task_path, CHECKOUT_FILE), &format!("{}", id)).await?;
+    remove_if_exists(&task_path.join(CHECKOUT_FILE)).await
+}
+
+/// Commit that the currently checked out task has been checked in.
+pub async fn commit_checkin(task_path: &Path) -> Result<()> {
+    remove_if_exists(&local_path(task_path, CHECKOUT_FILE)).await?;
+    remove_if_exists(&task_path.join(CHECKOUT_FILE)).await
+}
+
+/// Removes the file at `path`, if there is one.
+async fn remove_if_exists(path: &Path) -> Result<()> {
+    match fs::remove_file(path).await {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Reads the project's config, if there is no config file then the
+/// default config is used.
+pub async fn read_config(task_path: &Path) -> Result<Config> {
+    match fs::read_to_string(task_path.join("config.yaml")).await {
+        Ok(s) => Config::from_yaml(&s),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes the project's config.
+pub async fn write_config(config: &Config, task_path: &Path) -> Result<()> {
+    write_atomic(&task_path.join("config.yaml"), &config.to_yaml()?).await
+}
+
+/// Reads every part of the project whose `.tisk` directory is `task_path`.
+pub async fn read_project(task_path: &Path) -> Result<Project> {
+    Ok(Project {
+        tasks: read_tasks(task_path).await?,
+        checked_out: read_checkout(task_path).await?,
+        config: read_config(task_path).await?,
+    })
+}
+
+/// Writes every part of `project` into the `.tisk` directory at `task_path`.
+pub async fn write_project(project: &Project, task_path: &Path) -> Result<()> {
+    write_tasks(&project.tasks, task_path).await?;
+    match project.checked_out {
+        Some(id) => commit_checkout(id, task_path).await?,
+        None => commit_checkin(task_path).await?,
+    }
+    write_config(&project.config, task_path).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_and_read_project() {
+        let task_path = std::env::temp_dir().join(format!("tisk-async-{}", std::process::id()));
+        std::fs::create_dir_all(&task_path).unwrap();
+
+        let mut project = Project::default();
+        let id = project.tasks.add_task("test", 2);
+        project.tasks.add_task("test 2", 1);
+        project.checked_out = Some(id);
+        write_project(&project, &task_path).await.unwrap();
+
+        // The blocking and async storage share the same files
+        let read = Project::read(&task_path).unwrap();
+        assert_eq!(Some(id), read.checked_out);
+        assert_eq!(2, read.tasks.get_all().len());
+
+        commit_checkin(&task_path).await.unwrap();
+        let read = read_project(&task_path).await.unwrap();
+        assert_eq!(None, read.checked_out);
+        assert_eq!("test", read.tasks.get(id).unwrap().name());
+        assert_eq!(2, read.tasks.get(id).unwrap().priority());
+
+        std::fs::remove_dir_all(&task_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn write_and_read_single_file() {
+        let task_path = std::env::temp_dir().join(format!("tisk-async-single-{}", std::process::id()));
+        std::fs::create_dir_all(&task_path).unwrap();
+        Meta { layout: Layout::SingleFile, ..Meta::default() }.write(&task_path).unwrap();
+
+        let mut tasks = TaskList::new();
+        let id = tasks.add_task("test", 2);
+        tasks.get_mut(id).unwrap().add_note("a note").unwrap();
+        write_tasks(&tasks, &task_path).await.unwrap();
+        assert_eq!(true, task_path.join(SINGLE_FILE).exists());
+        assert_eq!(1, read_meta(&task_path).await.unwrap().last_id);
+
+        // Each task, with its notes, reads back the same with the async and the blocking storage
+        let read = read_tasks(&task_path).await.unwrap();
+        assert_eq!(vec!["a note"], read.get(id).unwrap().notes().iter().map(|n| n.note()).collect::<Vec<_>>());
+        let blocking = TaskList::read_tasks(&task_path).unwrap();
+        assert_eq!(read.get(id).unwrap().to_yaml().unwrap(), blocking.get(id).unwrap().to_yaml().unwrap());
+
+        std::fs::remove_dir_all(&task_path).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+/*!
+ * Busy time: the meetings and other events, imported from a calendar with
+ * `tisk import --from ics --as-busy`, during which there is no time to
+ * work on tasks.  `tisk plan` will not make a task due while the project is
+ * busy.  Busy time is kept in the `busy.yaml` file in the project's local
+ * directory, since it comes from one person's calendar.
+ */
+use crate::dates::Zone;
+use crate::error::TiskError;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+/// The name of the file, inside a project's local directory, which busy time is kept in.
+pub const BUSY_FILE: &str = "busy.yaml";
+
+/// One event during which there is no time to work on tasks.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Busy {
+    pub summary: String,
+
+    #[serde(with = "crate::dates::stored")]
+    pub starts_at: DateTime<Utc>,
+
+    #[serde(with = "crate::dates::stored")]
+    pub ends_at: DateTime<Utc>,
+}
+
+impl Busy {
+    /// Whether `time` is during this event.
+    pub fn contains(&self, time: DateTime<Utc>) -> bool {
+        self.starts_at <= time && time < self.ends_at
+    }
+}
+
+/// Every busy event in a project, earliest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BusyTime(pub Vec<Busy>);
+
+impl BusyTime {
+    /// Adds the events in `events` which are not already busy time, e.g.
+    /// because the same calendar was imported before.  Returns how many
+    /// were added.
+    pub fn add(&mut self, events: &[Busy]) -> usize {
+        let new: Vec<Busy> = events.iter().filter(|event| !self.0.contains(event)).cloned().collect();
+        self.0.extend(new.iter().cloned());
+        self.0.sort_by_key(|event| event.starts_at);
+        self.0.dedup();
+        new.len()
+    }
+
+    /// The event which `time` is during, if there is one.
+    pub fn during(&self, time: DateTime<Utc>) -> Option<&Busy> {
+        self.0.iter().find(|event| event.contains(time))
+    }
+
+    /// The events which overlap the time from `from` until `until`.
+    pub fn between(&self, from: DateTime<Utc>, until: DateTime<Utc>) -> Vec<&Busy> {
+        self.0.iter().filter(|event| event.starts_at < until && event.ends_at > from).collect()
+    }
+
+    /// Reads the busy time of the project whose directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn read(task_path: &std::path::Path) -> std::io::Result<BusyTime> {
+        match std::fs::read_to_string(crate::io::read_local_path(task_path, BUSY_FILE)) {
+            Ok(s) => BusyTime::from_yaml(&s),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Ok(BusyTime::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Parses busy time from the contents of a `busy.yaml` file.
+    pub fn from_yaml(s: &str) -> std::io::Result<BusyTime> {
+        serde_yaml::from_str::<BusyTime>(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// The contents of the `busy.yaml` file for this busy time.
+    pub fn to_yaml(&self) -> std::io::Result<String> {
+        serde_yaml::to_string(self).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// Writes this busy time to the project whose directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn write(&self, task_path: &std::path::Path) -> std::io::Result<()> {
+        crate::io::write_local(task_path, BUSY_FILE, &self.to_yaml()?).map(|_| ())
+    }
+}
+
+/// Reads the events of an iCalendar (`.ics`) file as busy time.  Times in
+/// UTC (`20240601T090000Z`) are read as they are, other times, including
+/// those with a `TZID`, are read in `zone`, the project's, and dates on
+/// their own are all day.  An event without an end lasts all day if it is
+/// on a date and is a moment otherwise.
+pub fn parse_ics(s: &str, zone: Zone) -> Result<Vec<Busy>, TiskError> {
+    // Long lines are folded onto the following lines, which start with a space or a tab
+    let mut lines: Vec<String> = vec![];
+    for line in s.lines() {
+        match (line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(String::from(line.trim_end())),
+        }
+    }
+
+    let mut events = vec![];
+    let mut event: Option<Event> = None;
+    for line in &lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value),
+            None => continue,
+        };
+        let (property, params) = name.split_once(';').unwrap_or((name, ""));
+        match (property.to_uppercase().as_str(), event.as_mut()) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VEVENT") => event = Some(Event::default()),
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                let Event { summary, starts_at, ends_at, all_day } = event.take().unwrap();
+                let starts_at = starts_at.ok_or_else(|| ics_error(&format!("The event {} has no start", summary)))?;
+                let ends_at = match ends_at {
+                    Some(ends_at) => ends_at,
+                    None if all_day => starts_at + Duration::days(1),
+                    None => starts_at,
+                };
+                events.push(Busy { summary, starts_at, ends_at });
+            }
+            ("SUMMARY", Some(event)) => event.summary = value.replace("\\,", ",").replace("\\;", ";"),
+            ("DTSTART", Some(event)) => {
+                let (time, all_day) = parse_ics_time(value, params, zone)?;
+                event.starts_at = Some(time);
+                event.all_day = all_day;
+            }
+            ("DTEND", Some(event)) => event.ends_at = Some(parse_ics_time(value, params, zone)?.0),
+            _ => (),
+        }
+    }
+    Ok(events)
+}
+
+/// The properties of a `VEVENT` read so far.
+#[derive(Default)]
+struct Event {
+    summary: String,
+    starts_at: Option<DateTime<Utc>>,
+    ends_at: Option<DateTime<Utc>>,
+    all_day: bool,
+}
+
+/// Parses an iCalendar date or time, along with whether it is a date on
+/// its own.  Times other than those in UTC are in `zone`.
+fn parse_ics_time(value: &str, params: &str, zone: Zone) -> Result<(DateTime<Utc>, bool), TiskError> {
+    let is_date = params.to_uppercase().contains("VALUE=DATE") && !params.to_uppercase().contains("VALUE=DATE-TIME");
+    if is_date || value.len() == 8 {
+        let invalid = || ics_error(&format!("Invalid date {}", value));
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").map_err(|_| invalid())?;
+        let start = zone.utc(date.and_hms(0, 0, 0)).ok_or_else(invalid)?;
+        return Ok((start, true));
+    }
+
+    let invalid = || ics_error(&format!("Invalid time {}", value));
+    match value.strip_suffix('Z') {
+        Some(utc) => NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .map(|time| (DateTime::from_utc(time, Utc), false))
+            .map_err(|_| invalid()),
+        None => NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .and_then(|time| zone.utc(time))
+            .map(|time| (time, false))
+            .ok_or_else(invalid),
+    }
+}
+
+fn ics_error(msg: &str) -> TiskError {
+    TiskError::Serde(format!("Invalid calendar: {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+SUMMARY:Standup\r
+DTSTART:20200601T090000Z\r
+DTEND:20200601T091500Z\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Planning\\, with the\r
+  whole team\r
+DTSTART;VALUE=DATE:20200602\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    #[test]
+    fn parse_events() {
+        let events = parse_ics(CALENDAR, Zone::Local).unwrap();
+        assert_eq!(2, events.len());
+        assert_eq!("Standup", events[0].summary);
+        assert_eq!(Utc.ymd(2020, 6, 1).and_hms(9, 15, 0), events[0].ends_at);
+        assert_eq!("Planning, with the whole team", events[1].summary);
+        assert_eq!(Duration::days(1), events[1].ends_at - events[1].starts_at);
+        assert!(parse_ics("BEGIN:VEVENT\nSUMMARY:Lunch\nEND:VEVENT\n", Zone::Local).is_err());
+    }
+
+    #[test]
+    fn busy_time() {
+        let mut busy = BusyTime::default();
+        let events = parse_ics(CALENDAR, Zone::Local).unwrap();
+        assert_eq!(2, busy.add(&events));
+        assert_eq!(0, busy.add(&events));
+
+        let standup = Utc.ymd(2020, 6, 1).and_hms(9, 5, 0);
+        assert_eq!(Some("Standup"), busy.during(standup).map(|event| event.summary.as_str()));
+        assert_eq!(None, busy.during(Utc.ymd(2020, 6, 1).and_hms(9, 15, 0)));
+        assert_eq!(1, busy.between(standup, standup + Duration::hours(1)).len());
+    }
+}
@@ -0,0 +1,355 @@
+/*!
+ * Settings for a tisk project.  These are read from the `config.yaml` file
+ * in the project's `.tisk` directory.  Any setting which is not in the file
+ * uses its default value and if there is no file then every setting uses its
+ * default.
+ */
+use crate::dates::{DateContext, Zone};
+use crate::engine::Limits;
+use crate::jira::JiraMapping;
+use crate::notify::{Notifier, NotifierKind};
+use chrono::NaiveDate;
+use crate::table::TableStyle;
+use crate::tasks::SortPolicy;
+use crate::text::Matching;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The borders drawn around tables.  Overridden by the `--style` flag.
+    pub table_style: TableStyle,
+
+    /// Dims every other row of a table so that rows are easier to tell apart.
+    pub zebra: bool,
+
+    /// Asks before making destructive changes or changing many tasks at
+    /// once.  The `--yes` flag skips the question for a single command.
+    pub confirm: bool,
+
+    /// A command which changes more than this many tasks asks before making
+    /// its changes.
+    pub confirm_threshold: usize,
+
+    /// Prints the labels of the columns before a list of tasks.  Overridden
+    /// by the `--no-header` flag of `tisk list`.
+    pub list_header: bool,
+
+    /// Prints counts of the project's tasks after a list of tasks.
+    /// Overridden by the `--no-footer` flag of `tisk list`.
+    pub list_footer: bool,
+
+    /// Wraps task names which are too long for the Name column onto more
+    /// lines, instead of cutting them short with `…`.  Overridden by the
+    /// `--wrap` and `--nowrap` flags of `tisk list`.
+    pub wrap_names: bool,
+
+    /// The order tasks are listed in, unless `--sort` is given.
+    pub sort_policy: SortPolicy,
+
+    /// Searches, filters, and tags match text regardless of its case.
+    pub ignore_case: bool,
+
+    /// Searches, filters, and tags match text regardless of its accents,
+    /// e.g. `cafe` matches `café`.
+    pub ignore_accents: bool,
+
+    /// The tags which each tag implies.  A task given a tag is given the
+    /// tags it implies too, e.g. `frontend` implying `ui` so that listing
+    /// the `ui` tasks finds the `frontend` tasks.
+    pub tag_implications: BTreeMap<String, Vec<String>>,
+
+    /// The priority and row color which each tag gives the tasks it is on.
+    pub tag_defaults: BTreeMap<String, TagDefaults>,
+
+    /// The time zone dates are shown and parsed in, e.g. `+02:00` so that
+    /// a team spread across time zones agrees on what is due today.
+    pub timezone: Zone,
+
+    /// The days which business days, e.g. `--due +3bd`, skip along with
+    /// weekends.
+    pub holidays: Vec<NaiveDate>,
+
+    /// How issues imported from Jira become tasks.
+    pub jira: JiraMapping,
+
+    /// How the user is told about things which need their attention, e.g.
+    /// reminders which are due.
+    pub notifier: NotifierKind,
+
+    /// The URL the webhook notifier posts notifications to.
+    pub webhook_url: Option<String>,
+
+    /// Warns after a command which writes tasks when the project has more
+    /// than this many tasks, since every command reads all of them.  0
+    /// turns the warning off.
+    pub task_limit: usize,
+
+    /// Warns after a command which writes tasks when a task has more than
+    /// this many notes.  0 turns the warning off.
+    pub note_limit: usize,
+}
+
+/// What a tag gives the tasks it is on, set with `tag_defaults`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TagDefaults {
+    /// The priority of a task added with this tag, unless `--priority` is
+    /// given.  If several of its tags have one the highest is used.
+    pub priority: Option<u32>,
+
+    /// The color of the task's row when tasks are listed, e.g. `red` or
+    /// `yellow.bold`.
+    pub color: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            table_style: TableStyle::default(),
+            zebra: false,
+            confirm: true,
+            confirm_threshold: 10,
+            list_header: true,
+            list_footer: true,
+            wrap_names: true,
+            sort_policy: SortPolicy::default(),
+            ignore_case: false,
+            ignore_accents: false,
+            tag_implications: BTreeMap::new(),
+            tag_defaults: BTreeMap::new(),
+            timezone: Zone::default(),
+            holidays: vec![],
+            jira: JiraMapping::default(),
+            notifier: NotifierKind::default(),
+            webhook_url: None,
+            task_limit: 2000,
+            note_limit: 100,
+        }
+    }
+}
+
+/// The `config.yaml` written by `tisk init --with-config`.  It sets every
+/// setting to its default and explains what each one does.
+pub const TEMPLATE: &str = "\
+# Settings for this tisk project.  Any setting left out uses its default.
+
+# The borders drawn around tables: plain, ascii, or unicode.
+# Overridden by the --style flag.
+table_style: plain
+
+# Dim every other row of a table so that rows are easier to tell apart.
+zebra: false
+
+# Ask before making destructive changes or changing many tasks at once.
+# The --yes flag skips the question for a single command.
+confirm: true
+
+# Commands which change more than this many tasks ask first.
+confirm_threshold: 10
+
+# Print the labels of the columns before a list of tasks.
+# Overridden by the --no-header flag of tisk list.
+list_header: true
+
+# Print counts of the project's tasks after a list of tasks.
+# Overridden by the --no-footer flag of tisk list.
+list_footer: true
+
+# Wrap task names which are too long for the Name column onto more lines,
+# false cuts them short with an ellipsis so that each task is one line.
+# Overridden by the --wrap and --nowrap flags of tisk list.
+wrap_names: true
+
+# The order tasks are listed in, unless --sort is given.  due-first lists the
+# tasks due today (or overdue) before any others, priority-first lists the
+# highest priority tasks first.
+sort_policy: due-first
+
+# Match text regardless of its case when searching, filtering, and looking
+# up tags.
+ignore_case: false
+
+# Match text regardless of its accents, e.g. cafe matches café.
+ignore_accents: false
+
+# The tags which each tag implies, a task given one of these tags is given
+# the tags it implies too.  For example:
+#   tag_implications:
+#     frontend: [ui]
+tag_implications: {}
+
+# The priority given to tasks added with a tag, unless --priority is given,
+# and the color of their rows when tasks are listed.  For example:
+#   tag_defaults:
+#     bug: {priority: 5, color: red}
+tag_defaults: {}
+
+# The time zone dates are shown and parsed in: local, UTC, or an offset from
+# UTC such as +02:00.  Setting one means everyone sharing the project agrees
+# on which day a task is due.  Named zones such as Europe/Berlin are not
+# supported, and an offset does not follow daylight saving time.
+timezone: local
+
+# The days which business days, e.g. --due +3bd, skip along with weekends.
+# For example:
+#   holidays: [2024-12-25, 2024-12-26]
+holidays: []
+
+# How issues imported from Jira, with tisk import --from jira, become tasks:
+# the Jira site which --jql searches, the status each Jira status becomes (any
+# other becomes Open), and the priority each Jira priority becomes (any other
+# becomes 1).
+jira:
+  url: ~
+  statuses:
+    To Do: Open
+    In Progress: InProgress
+    Blocked: Blocked
+    Done: Closed
+  priorities:
+    Highest: 5
+    High: 4
+    Medium: 3
+    Low: 2
+    Lowest: 1
+
+# How you are told about things which need your attention, e.g. reminders
+# which are due: stdout prints them, desktop shows a desktop notification
+# (with notify-send on Linux or osascript on macOS), and webhook posts them
+# as JSON, with curl, to webhook_url.  For example:
+#   notifier: webhook
+#   webhook_url: https://hooks.slack.com/services/...
+notifier: stdout
+webhook_url: ~
+
+# Warn after a command when the project has more than task_limit tasks, or a
+# task has more than note_limit notes, before commands become slow.  tisk
+# compact suggests how to shrink the project.  0 turns a warning off.
+task_limit: 2000
+note_limit: 100
+";
+
+impl Config {
+    /// How text is compared when searching and filtering.
+    pub fn matching(&self) -> Matching {
+        Matching {
+            ignore_case: self.ignore_case,
+            ignore_accents: self.ignore_accents,
+        }
+    }
+
+    /// How dates are shown and parsed, in the `timezone` and skipping the
+    /// `holidays`.
+    pub fn dates(&self) -> DateContext {
+        DateContext::new(self.timezone, self.holidays.clone())
+    }
+
+    /// The priority a new task with `tags` is given by their defaults, the
+    /// highest if more than one tag has a default priority.
+    pub fn default_priority(&self, tags: &[String]) -> Option<u32> {
+        tags.iter().filter_map(|tag| self.tag_defaults(tag)).filter_map(|d| d.priority).max()
+    }
+
+    /// The color of the row of a task with `tags`, that of its first tag
+    /// which has a color.
+    pub fn tag_color(&self, tags: &[String]) -> Option<&str> {
+        tags.iter().filter_map(|tag| self.tag_defaults(tag)).find_map(|d| d.color.as_deref())
+    }
+
+    /// How large the project, and its tasks, can grow before tisk warns.
+    pub fn limits(&self) -> Limits {
+        Limits { tasks: self.task_limit, notes: self.note_limit }
+    }
+
+    /// The notifier which the `notifier` setting picks.
+    pub fn notifier(&self) -> Result<Box<dyn Notifier>, crate::error::TiskError> {
+        crate::notify::notifier(self.notifier, self.webhook_url.as_deref())
+    }
+
+    fn tag_defaults(&self, tag: &str) -> Option<&TagDefaults> {
+        let matching = self.matching();
+        self.tag_defaults.iter().find(|(t, _)| matching.eq(t, tag)).map(|(_, defaults)| defaults)
+    }
+
+    /// Reads the configuration for the project whose `.tisk` directory is
+    /// `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn read(task_path: &std::path::PathBuf) -> std::io::Result<Config> {
+        let mut path = std::path::PathBuf::from(task_path);
+        path.push("config.yaml");
+
+        let s = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(err) if err.kind() == IoErrorKind::NotFound => return Ok(Config::default()),
+            Err(err) => return Err(err),
+        };
+
+        Config::from_yaml(&s)
+    }
+
+    /// Parses a configuration from the contents of a `config.yaml` file.
+    pub fn from_yaml(s: &str) -> std::io::Result<Config> {
+        serde_yaml::from_str::<Config>(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// The contents of the `config.yaml` file for this configuration.
+    pub fn to_yaml(&self) -> std::io::Result<String> {
+        serde_yaml::to_string(self).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// Writes this configuration to the project whose `.tisk` directory is
+    /// `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn write(&self, task_path: &std::path::PathBuf) -> std::io::Result<()> {
+        let mut path = std::path::PathBuf::from(task_path);
+        path.push("config.yaml");
+
+        crate::io::write_atomic(&path, &self.to_yaml()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_is_the_default() {
+        let config = Config::from_yaml(TEMPLATE).unwrap();
+        let default = Config::default();
+        assert_eq!(default.table_style, config.table_style);
+        assert_eq!(default.zebra, config.zebra);
+        assert_eq!(default.confirm, config.confirm);
+        assert_eq!(default.confirm_threshold, config.confirm_threshold);
+        assert_eq!(default.list_header, config.list_header);
+        assert_eq!(default.list_footer, config.list_footer);
+        assert_eq!(default.wrap_names, config.wrap_names);
+        assert_eq!(default.sort_policy, config.sort_policy);
+        assert_eq!(default.matching(), config.matching());
+        assert_eq!(default.tag_implications, config.tag_implications);
+        assert_eq!(default.tag_defaults, config.tag_defaults);
+        assert_eq!(default.timezone, config.timezone);
+        assert_eq!(default.holidays, config.holidays);
+        assert_eq!(default.jira, config.jira);
+        assert_eq!(default.notifier, config.notifier);
+        assert_eq!(default.webhook_url, config.webhook_url);
+        assert_eq!(default.limits(), config.limits());
+    }
+
+    #[test]
+    fn tag_defaults() {
+        let config = Config::from_yaml(
+            "tag_defaults:\n  bug: {priority: 5, color: red}\n  urgent: {priority: 9}\n  docs: {color: blue}\n",
+        )
+        .unwrap();
+        let tags = |tags: &[&str]| tags.iter().map(|t| String::from(*t)).collect::<Vec<String>>();
+
+        assert_eq!(Some(5), config.default_priority(&tags(&["docs", "bug"])));
+        assert_eq!(Some(9), config.default_priority(&tags(&["bug", "urgent"])));
+        assert_eq!(None, config.default_priority(&tags(&["docs"])));
+        assert_eq!(Some("blue"), config.tag_color(&tags(&["docs", "bug"])));
+        assert_eq!(None, config.tag_color(&tags(&["urgent", "ui"])));
+    }
+}
@@ -0,0 +1,442 @@
+/*!
+ * Parses the dates given on the command line, e.g. `--due 2020-06-01`,
+ * `--due friday`, or `--due "2020-06-01 17:00"`, and fixes the format times
+ * are written to files in.  Dates are parsed and shown in the project's
+ * time zone, with the project's holidays, as its `DateContext` says.
+ */
+use crate::error::TiskError;
+use chrono::prelude::*;
+
+/// The format times are written in, for use with `#[serde(with)]`: RFC 3339
+/// in UTC with only as many digits of the second as it needs, e.g.
+/// `2020-06-01T09:00:00Z` or `2020-06-01T09:00:00.250Z`.  A time which was
+/// read is written back exactly as it was, so rewriting an unchanged task does
+/// not change its file.  Times with other offsets are read too.
+pub mod stored {
+    use chrono::{DateTime, SecondsFormat, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        DateTime::<Utc>::deserialize(deserializer)
+    }
+
+    /// The same format for optional times, which are written as `~` when
+    /// they are not set.
+    pub mod option {
+        use chrono::{DateTime, Utc};
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(time: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::<DateTime<Utc>>::deserialize(deserializer)
+        }
+    }
+}
+
+/// The time zone dates are shown and parsed in, set by the `timezone`
+/// setting of a project so that everyone sharing its tasks sees the same
+/// days, e.g. `+02:00`.  Time zones are fixed offsets from UTC, there is no
+/// daylight saving time unless the zone is the local one, and named zones
+/// such as `Europe/Berlin` are not supported.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Zone {
+    /// The local time zone of the computer tisk is run on.
+    #[default]
+    Local,
+    Offset(FixedOffset),
+}
+
+impl Zone {
+    /// The time of day, and day, `time` is in this zone.
+    pub fn naive(&self, time: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => time.with_timezone(&Local).naive_local(),
+            Zone::Offset(offset) => time.with_timezone(offset).naive_local(),
+        }
+    }
+
+    /// The time at `local`, a time of day in this zone.  `None` if there is
+    /// no such time, e.g. because the clocks skip it.
+    pub fn utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Local => Local.from_local_datetime(&local).earliest().map(|t| t.with_timezone(&Utc)),
+            Zone::Offset(offset) => offset.from_local_datetime(&local).earliest().map(|t| t.with_timezone(&Utc)),
+        }
+    }
+
+    /// The day it is now in this zone.
+    pub fn today(&self) -> NaiveDate {
+        self.naive(Utc::now()).date()
+    }
+}
+
+impl std::str::FromStr for Zone {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Zone, TiskError> {
+        let invalid = || {
+            TiskError::ParseError(format!(
+                "Invalid time zone: {} (expected local, UTC, or +HH:MM; named zones such as Europe/Berlin are not \
+                 supported, and an offset does not follow daylight saving time)",
+                s
+            ))
+        };
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Zone::Local);
+        } else if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(Zone::Offset(FixedOffset::east(0)));
+        }
+
+        let sign = match s.chars().next() {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = s[1..].split_once(':').unwrap_or((&s[1..], "0"));
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 23 || minutes > 59 {
+            return Err(invalid());
+        }
+        Ok(Zone::Offset(FixedOffset::east(sign * (hours * 3600 + minutes * 60))))
+    }
+}
+
+impl std::fmt::Display for Zone {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Zone::Local => write!(f, "local"),
+            Zone::Offset(offset) if offset.local_minus_utc() == 0 => write!(f, "UTC"),
+            Zone::Offset(offset) => write!(f, "{}", offset),
+        }
+    }
+}
+
+impl serde::Serialize for Zone {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Zone {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Zone, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// How the dates of one project are shown and parsed: the time zone set by
+/// its `timezone` setting and the holidays business days skip.  Each command
+/// is given the context of the project it is run against, so that several
+/// projects can be open at once, e.g. through the C interface.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DateContext {
+    pub zone: Zone,
+    pub holidays: Vec<NaiveDate>,
+}
+
+impl DateContext {
+    pub fn new(zone: Zone, holidays: Vec<NaiveDate>) -> DateContext {
+        DateContext { zone, holidays }
+    }
+
+    /// The day it is now in the project's time zone.
+    pub fn today(&self) -> NaiveDate {
+        self.zone.today()
+    }
+
+    /// Parses `s` as either a date, which is taken as the start of that day
+    /// in the project's time zone, a date and a time of day `HH:MM` (or
+    /// `9am`) in that zone, or an RFC 3339 timestamp.  A date is either
+    /// `YYYY-MM-DD` or one of the days which `relative_day` understands, a
+    /// time on its own is today.
+    pub fn parse_date(&self, s: &str) -> Result<DateTime<Utc>, TiskError> {
+        let s = s.trim();
+        let today = self.today();
+        let parse_day = |day: &str| -> Result<Option<NaiveDate>, TiskError> {
+            Ok(self.relative_day(day, today)?.or_else(|| NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()))
+        };
+        let local = match s.rsplit_once(char::is_whitespace) {
+            Some((day, time)) => parse_day(day.trim())?.zip(parse_time_of_day(time)),
+            None => parse_day(s)?
+                .map(|day| (day, NaiveTime::from_hms(0, 0, 0)))
+                .or_else(|| Some(today).zip(parse_time_of_day(s))),
+        };
+        if let Some((date, time)) = local {
+            return match self.zone.utc(date.and_time(time)) {
+                Some(time) => Ok(time),
+                None => Err(TiskError::ParseError(format!("{} does not exist in the time zone {}", s, self.zone))),
+            };
+        }
+
+        DateTime::parse_from_rfc3339(s).map(|dt| dt.with_timezone(&Utc)).map_err(|_| {
+            TiskError::ParseError(format!("Invalid date: {}, expected YYYY-MM-DD or YYYY-MM-DD HH:MM", s))
+        })
+    }
+
+    /// Parses a day relative to `today`: `today`, `tomorrow`, `+N` for `N`
+    /// days from today, `+Nbd` for `N` business days from today, skipping
+    /// the project's holidays, or the name of a day of the week (e.g. `fri`
+    /// or `friday`) for the next such day within the coming week.  It is an
+    /// error for `N` to be more than `MAX_DAYS_AHEAD`.
+    pub fn relative_day(&self, s: &str, today: NaiveDate) -> Result<Option<NaiveDate>, TiskError> {
+        relative_day(s, today, &self.holidays)
+    }
+
+    /// Whether `time` has a time of day, i.e. it is not the start of a day
+    /// in the project's time zone as a date on its own is.
+    pub fn has_time(&self, time: DateTime<Utc>) -> bool {
+        self.zone.naive(time).time() != NaiveTime::from_hms(0, 0, 0)
+    }
+
+    /// Shows a due date in the project's time zone, e.g. `2020-06-01`,
+    /// along with its time of day if it has one, e.g. `2020-06-01 17:00`.
+    pub fn format_due(&self, due: DateTime<Utc>) -> String {
+        if self.has_time(due) {
+            self.format_time(due)
+        } else {
+            self.format_day(due)
+        }
+    }
+
+    /// Shows the day `time` is on in the project's time zone, e.g.
+    /// `2020-06-01`.
+    pub fn format_day(&self, time: DateTime<Utc>) -> String {
+        self.zone.naive(time).format("%Y-%m-%d").to_string()
+    }
+
+    /// Shows `time` in the project's time zone, e.g. `2020-06-01 17:00`.
+    pub fn format_time(&self, time: DateTime<Utc>) -> String {
+        self.zone.naive(time).format("%Y-%m-%d %H:%M").to_string()
+    }
+}
+
+/// Parses a time of day, either `HH:MM` or on a 12 hour clock, e.g. `9am`
+/// or `5:30pm`.
+fn parse_time_of_day(s: &str) -> Option<NaiveTime> {
+    let lower = s.to_lowercase();
+    let (time, pm) = match (lower.strip_suffix("am"), lower.strip_suffix("pm")) {
+        (Some(time), _) => (time.trim(), false),
+        (_, Some(time)) => (time.trim(), true),
+        _ => return NaiveTime::parse_from_str(s, "%H:%M").ok(),
+    };
+    let (hour, minute) = time.split_once(':').unwrap_or((time, "0"));
+    let (hour, minute): (u32, u32) = (hour.parse().ok()?, minute.parse().ok()?);
+    if !(1..=12).contains(&hour) || minute >= 60 {
+        return None;
+    }
+    NaiveTime::from_hms_opt(hour % 12 + if pm { 12 } else { 0 }, minute, 0)
+}
+
+/// Parses an age such as `30d`, `2w`, or `12h`: a number of days, weeks,
+/// or hours.  A number on its own is a number of days.
+pub fn parse_age(s: &str) -> Result<chrono::Duration, TiskError> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let invalid = || TiskError::ParseError(format!("Invalid age: {}, expected e.g. 30d, 2w, or 12h", s));
+    let n: i64 = s[..split].parse().map_err(|_| invalid())?;
+    match &s[split..] {
+        "" | "d" => Ok(chrono::Duration::days(n)),
+        "w" => Ok(chrono::Duration::weeks(n)),
+        "h" => Ok(chrono::Duration::hours(n)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses a day relative to `today` like `DateContext::relative_day` does,
+/// with business days skipping `holidays`.
+pub fn relative_day(s: &str, today: NaiveDate, holidays: &[NaiveDate]) -> Result<Option<NaiveDate>, TiskError> {
+    let s = s.trim().to_lowercase();
+    let too_far = || TiskError::ParseError(format!("{} is too far ahead, it can be at most +{}", s, MAX_DAYS_AHEAD));
+    let days = match s.as_str() {
+        "today" => 0,
+        "tomorrow" => 1,
+        _ if s.starts_with('+') && s.ends_with("bd") => {
+            let business_days = match days_ahead(&s, &s[1..s.len() - 2])? {
+                Some(business_days) => business_days,
+                None => return Ok(None),
+            };
+            return add_business_days(today, business_days, holidays).map(Some).ok_or_else(too_far);
+        }
+        _ if s.starts_with('+') => match days_ahead(&s, &s[1..])? {
+            Some(days) => days as i64,
+            None => return Ok(None),
+        },
+        _ => {
+            let weekday = match s.parse::<Weekday>() {
+                Ok(weekday) => weekday,
+                Err(_) => return Ok(None),
+            };
+            let ahead = weekday.num_days_from_monday() as i64 - today.weekday().num_days_from_monday() as i64;
+            if ahead > 0 {
+                ahead
+            } else {
+                ahead + 7
+            }
+        }
+    };
+    today.checked_add_signed(chrono::Duration::days(days)).map(Some).ok_or_else(too_far)
+}
+
+/// The most days, or business days, ahead that `+N` and `+Nbd` may be,
+/// about a hundred years.
+pub const MAX_DAYS_AHEAD: u32 = 36_600;
+
+/// Parses `digits`, the `N` of the relative day `s`, `+N` or `+Nbd`.  It is
+/// `None` if `digits` is not a number, so `s` is not a relative day.
+fn days_ahead(s: &str, digits: &str) -> Result<Option<u32>, TiskError> {
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    match digits.parse::<u32>() {
+        Ok(n) if n <= MAX_DAYS_AHEAD => Ok(Some(n)),
+        _ => Err(TiskError::ParseError(format!("{} is too far ahead, it can be at most +{}", s, MAX_DAYS_AHEAD))),
+    }
+}
+
+/// The day `n` business days after `day`, counting only the weekdays which
+/// are not in `holidays`.  Zero business days after a weekend or a holiday
+/// is the next business day.  It is `None` if that is after the last day
+/// there can be.
+pub fn add_business_days(day: NaiveDate, n: u32, holidays: &[NaiveDate]) -> Option<NaiveDate> {
+    let is_business_day = |d: NaiveDate| d.weekday().num_days_from_monday() < 5 && !holidays.contains(&d);
+    let mut day = day;
+    let mut left = n;
+    while left > 0 || !is_business_day(day) {
+        day = day.succ_opt()?;
+        if is_business_day(day) {
+            left = left.saturating_sub(1);
+        }
+    }
+    Some(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_day() {
+        let dates = DateContext::default();
+        let due = dates.parse_date("2020-06-01").unwrap().with_timezone(&Local);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1), due.naive_local().date());
+        assert_eq!(0, due.hour());
+    }
+
+    #[test]
+    fn parse_day_and_time() {
+        let dates = DateContext::default();
+        let due = dates.parse_date("2020-06-01 17:30").unwrap().with_timezone(&Local);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1).and_hms(17, 30, 0), due.naive_local());
+        assert_eq!("2020-06-01 17:30", dates.format_due(due.with_timezone(&Utc)));
+        assert_eq!("2020-06-01", dates.format_due(dates.parse_date("2020-06-01").unwrap()));
+
+        let tomorrow = dates.parse_date("tomorrow 9:05").unwrap().with_timezone(&Local);
+        assert_eq!(Local::today().naive_local().succ().and_hms(9, 5, 0), tomorrow.naive_local());
+        let today = dates.parse_date("08:00").unwrap().with_timezone(&Local);
+        assert_eq!(Local::today().naive_local().and_hms(8, 0, 0), today.naive_local());
+        assert!(dates.parse_date("2020-06-01 25:00").is_err());
+
+        let morning = dates.parse_date("2020-06-01 9am").unwrap().with_timezone(&Local);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1).and_hms(9, 0, 0), morning.naive_local());
+        let evening = dates.parse_date("2020-06-01 5:30PM").unwrap().with_timezone(&Local);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1).and_hms(17, 30, 0), evening.naive_local());
+        let noon = dates.parse_date("2020-06-01 12pm").unwrap().with_timezone(&Local);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1).and_hms(12, 0, 0), noon.naive_local());
+        assert!(dates.parse_date("2020-06-01 13pm").is_err());
+        assert!(dates.parse_date("2020-06-01 later").is_err());
+    }
+
+    #[test]
+    fn parse_ages() {
+        assert_eq!(chrono::Duration::days(30), parse_age("30d").unwrap());
+        assert_eq!(chrono::Duration::days(7), parse_age("7").unwrap());
+        assert_eq!(chrono::Duration::weeks(2), parse_age("2w").unwrap());
+        assert_eq!(chrono::Duration::hours(12), parse_age("12h").unwrap());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("3 months").is_err());
+    }
+
+    #[test]
+    fn parse_in_zone() {
+        let kolkata: Zone = "+05:30".parse().unwrap();
+        let dates = DateContext::new(kolkata, vec![]);
+        let due = dates.parse_date("2020-06-01 17:30").unwrap();
+        assert_eq!(Utc.ymd(2020, 6, 1).and_hms(12, 0, 0), due);
+        assert_eq!(NaiveDate::from_ymd(2020, 6, 1).and_hms(17, 30, 0), kolkata.naive(due));
+        assert_eq!(Utc.ymd(2020, 5, 31).and_hms(18, 30, 0), dates.parse_date("2020-06-01").unwrap());
+        assert_eq!("2020-06-01 17:30", dates.format_time(due));
+        assert_eq!("2020-06-01 12:00", DateContext::new("UTC".parse().unwrap(), vec![]).format_time(due));
+
+        assert_eq!("+05:30", kolkata.to_string());
+        assert_eq!(Zone::Offset(FixedOffset::west(8 * 3600)), "-08".parse().unwrap());
+        assert_eq!("UTC", "utc".parse::<Zone>().unwrap().to_string());
+        assert_eq!(Zone::Local, "local".parse().unwrap());
+        assert!("Europe/Berlin".parse::<Zone>().is_err());
+        assert!("+25:00".parse::<Zone>().is_err());
+    }
+
+    #[test]
+    fn parse_timestamp() {
+        let dates = DateContext::default();
+        let due = dates.parse_date("2020-06-01T12:30:00Z").unwrap();
+        assert_eq!(Utc.ymd(2020, 6, 1).and_hms(12, 30, 0), due);
+        assert!(dates.parse_date("next week").is_err());
+    }
+
+    #[test]
+    fn parse_relative_days() {
+        let wednesday = NaiveDate::from_ymd(2020, 6, 3);
+        assert_eq!(Some(wednesday), relative_day("today", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 4)), relative_day("Tomorrow", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 13)), relative_day("+10", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 5)), relative_day("fri", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 8)), relative_day("monday", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 10)), relative_day("wed", wednesday, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 8)), relative_day("+3bd", wednesday, &[]).unwrap());
+        assert_eq!(None, relative_day("someday", wednesday, &[]).unwrap());
+
+        let thursday = NaiveDate::from_ymd(2020, 6, 4);
+        let off = DateContext::new(Zone::Local, vec![thursday]);
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 5)), off.relative_day("+1bd", wednesday).unwrap());
+        assert_eq!(Some(thursday), DateContext::default().relative_day("+1bd", wednesday).unwrap());
+    }
+
+    #[test]
+    fn relative_days_too_far_ahead() {
+        let wednesday = NaiveDate::from_ymd(2020, 6, 3);
+        let last = wednesday + chrono::Duration::days(MAX_DAYS_AHEAD as i64);
+        assert_eq!(Some(last), relative_day(&format!("+{}", MAX_DAYS_AHEAD), wednesday, &[]).unwrap());
+        for s in ["+36601", "+99999999999", "+36601bd", "+99999999999bd"] {
+            assert_eq!("invalid_argument", relative_day(s, wednesday, &[]).unwrap_err().code());
+        }
+        assert_eq!(None, relative_day("+tenbd", wednesday, &[]).unwrap());
+        assert_eq!("invalid_argument", relative_day("+1bd", chrono::naive::MAX_DATE, &[]).unwrap_err().code());
+
+        let dates = DateContext::default();
+        assert_eq!("invalid_argument", dates.parse_date("+99999999999").unwrap_err().code());
+        assert_eq!("invalid_argument", dates.parse_date("+4294967295bd 17:00").unwrap_err().code());
+    }
+
+    #[test]
+    fn business_days() {
+        let friday = NaiveDate::from_ymd(2020, 6, 5);
+        let monday = NaiveDate::from_ymd(2020, 6, 8);
+        assert_eq!(monday, add_business_days(friday, 1, &[]).unwrap());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 9)), add_business_days(friday, 1, &[monday]));
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 12)), add_business_days(friday, 5, &[]));
+        assert_eq!(Some(monday), add_business_days(NaiveDate::from_ymd(2020, 6, 6), 0, &[]));
+        assert_eq!(Some(friday), add_business_days(friday, 0, &[]));
+        assert_eq!(None, add_business_days(chrono::naive::MAX_DATE, 1, &[]));
+    }
+}
@@ -0,0 +1,2706 @@
+/*!
+ * Executes commands against a tisk project.  A `Command` is first applied
+ * to the in memory `TaskList`, which produces the effects the command had
+ * and an `Output` describing what should be shown to the user.  The effects
+ * are then committed to disk.  Nothing here parses arguments or prints, so
+ * tisk can be driven by other programs (e.g. editors or tests) without
+ * spawning the binary.
+ */
+#[cfg(feature = "storage")]
+use crate::busy;
+use crate::busy::{Busy, BusyTime};
+#[cfg(feature = "storage")]
+use crate::config::Config;
+use crate::dates::DateContext;
+use crate::error::TiskError;
+use crate::export::{self, ExportFormat};
+#[cfg(feature = "storage")]
+use crate::focus;
+use crate::focus::{FocusStats, Sessions};
+#[cfg(feature = "storage")]
+use crate::io;
+use crate::jira::{self, Issue, JiraMapping};
+use crate::query::Query;
+use crate::table::{Order, TableFormat};
+use crate::tasks::{Conflict, IdPolicy, Note, Orphans, SortPolicy, Status, Task, TaskList};
+use crate::taskwarrior::{self, Exported};
+use crate::text::Matching;
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use log::debug;
+
+/**
+ * This indicates what effect executing  a command had on the task list.
+ * `Read` means that the command only read from the task list and thus
+ * no changes were made and nothing needs to be written.
+ *
+ * `Write` means that the command modified the TaskList or a Task in the
+ * TaskList and the changes will need to be written to disk.
+ *
+ * `DeleteTask` means that the task was removed from the TaskList and the
+ * files it was stored in need to be moved to the trash.
+ *
+ * `RestoreTask` means that the task was put back into the TaskList from
+ * the trash, which it needs to be taken out of.
+ *
+ * `AddBusy` means that the events need to be added to the busy time kept
+ * in the project's local directory.
+ *
+ * `ArchiveTask` means that the task was removed from the TaskList and the
+ * files it was stored in need to be moved to the archive.
+ */
+#[derive(Debug, PartialEq)]
+pub enum CommandEffect {
+    Write,
+    Read,
+    CheckoutTask(u32),
+    CheckinTask,
+    DeleteTask(u32),
+    RestoreTask(u32),
+    AddBusy(Vec<Busy>),
+    ArchiveTask(u32),
+}
+
+/// The tag given to the tasks captured by `tisk in`, which `tisk triage`
+/// walks through.
+pub const INBOX_TAG: &str = "inbox";
+
+pub type Effects = Vec<CommandEffect>;
+
+/// Which tasks are shown by `Command::List`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ListFilter {
+    /// Every task which is not closed, including those in progress or
+    /// blocked.
+    Open,
+    Closed,
+    InProgress,
+    Blocked,
+    All,
+}
+
+/// The task which `Command::Checkout` checks out: either an existing task
+/// or a new task with the given name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Checkout {
+    Id(u32),
+    Add {
+        name: String,
+        priority: u32,
+        note: Option<String>,
+        tags: Vec<String>,
+        due_at: Option<DateTime<Utc>>,
+        /// The task the new task is a subtask of.
+        parent: Option<u32>,
+    },
+}
+
+/// The values already used in a project which `Command::Complete` lists,
+/// e.g. for shell completion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Completion {
+    Tags,
+    Assignees,
+}
+
+impl std::str::FromStr for Completion {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Completion, TiskError> {
+        match s {
+            "tags" => Ok(Completion::Tags),
+            "assignees" => Ok(Completion::Assignees),
+            _ => Err(TiskError::ParseError(format!("Invalid completion: {}", s))),
+        }
+    }
+}
+
+/// What `Command::Triage` does with a task from the inbox.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Triage {
+    /// Makes the edits to the task and takes it out of the inbox.
+    Keep(Edits),
+    Delete,
+}
+
+/// What `Command::Review` does with a task which has gone stale.  Every
+/// task which is not deleted is marked as reviewed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Review {
+    Keep,
+    Reprioritize(u32),
+    Close,
+    Delete,
+}
+
+/// The commands which can be executed against a tisk project.  Commands
+/// which take an optional `id` apply to the checked out task if no `id`
+/// is given.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Add {
+        name: String,
+        priority: u32,
+        note: Option<String>,
+        tags: Vec<String>,
+        due_at: Option<DateTime<Utc>>,
+        /// The task the new task is a subtask of.
+        parent: Option<u32>,
+    },
+    /// Closes the task, and if there is a `follow_up` adds a new task with
+    /// that name, the same priority and tags, and notes linking the two.
+    Close {
+        id: Option<u32>,
+        note: Option<String>,
+        follow_up: Option<String>,
+        /// Closes the task's open subtasks too, without this a task with
+        /// open subtasks is not closed.
+        cascade: bool,
+    },
+    /// Makes every one of `edits` to the task, or none of them if any is
+    /// not valid.
+    Edit {
+        id: Option<u32>,
+        edits: Edits,
+    },
+    /// Makes the edits to each of the tasks, or none of them if any edit is
+    /// not valid.
+    EditMany(Vec<(u32, Edits)>),
+    /// Makes every one of the decisions about tasks in the inbox, or none
+    /// of them if any is not valid.
+    Triage(Vec<(u32, Triage)>),
+    /// Makes every one of the decisions about stale tasks, or none of them
+    /// if any is not valid.
+    Review(Vec<(u32, Review)>),
+    /// Adds `note` to the task, or if there is no `note` then shows the
+    /// notes on the task.
+    Note {
+        id: Option<u32>,
+        note: Option<String>,
+    },
+    /// Adds `note` to each of `ids` and to every task `query` selects, or
+    /// to none of them if any of `ids` does not exist.
+    NoteMany {
+        ids: Vec<u32>,
+        query: Option<Query>,
+        note: String,
+    },
+    /// Moves the task to the trash, along with what `orphans` says to do
+    /// with its subtasks.
+    Delete {
+        id: Option<u32>,
+        orphans: Orphans,
+    },
+    /// Puts a task which was taken out of the trash back into the project.
+    Restore(Task),
+    /// Replaces the task which has the same ID as the given task, e.g. with
+    /// a copy of it which the user has edited.
+    Replace(Task),
+    /// Marks the task as in progress.
+    Start(Option<u32>),
+    /// Marks the task as blocked, with a note saying why if there is a
+    /// `reason`.
+    Block {
+        id: Option<u32>,
+        reason: Option<String>,
+    },
+    /// Makes the task depend on each of the tasks in `on`, or on none of
+    /// them if any cannot be depended on.
+    Depend {
+        id: Option<u32>,
+        on: Vec<u32>,
+    },
+    /// Shows the tree of what the task depends on, or if there is no ID the
+    /// trees of every task which depends on others and which nothing
+    /// depends on.
+    Graph(Option<u32>),
+    /// Shows every detail of the task.
+    Show(Option<u32>),
+    /// Shows how long the task has been checked out for, in total and each
+    /// time it was checked out.
+    Time(Option<u32>),
+    /// Adds a reminder about the task at `at`.
+    Remind { id: Option<u32>, at: DateTime<Utc> },
+    /// Marks the reminders which are due, and have not been delivered yet,
+    /// as delivered so that the frontend delivers each exactly once.
+    Notify,
+    /// Suggests how to shrink a project which has grown past, or towards,
+    /// its `limits`.
+    Compact(Limits),
+    Checkout(Checkout),
+    Checkin,
+    List {
+        query: Query,
+
+        /// The order tasks are listed in, unless they are sorted by a column.
+        policy: SortPolicy,
+        sort: Option<(String, Order)>,
+        format: TableFormat,
+
+        /// Whether the tasks moved to the archive are listed too.
+        archived: bool,
+    },
+    /// Moves the closed tasks which were closed at least this long ago
+    /// into the archive.
+    Archive { older_than: chrono::Duration },
+    /// Lists every distinct value of a field across all of the tasks.
+    Complete(Completion),
+    /// Closes the open tasks which have expired.  Any command which writes
+    /// to the project does this too.
+    Expire,
+    /// Lists the tasks whose notes refer to the task with this ID.
+    Backlinks(u32),
+    /// Counts the open and closed tasks with each tag.
+    Tags,
+    /// Picks one of the open tasks `query` selects at random, tasks with a
+    /// higher priority being more likely to be picked.  The same `seed`
+    /// picks the same task from the same tasks.
+    Roulette {
+        query: Query,
+        seed: u64,
+    },
+    /// Adds tasks from another project, e.g. a backup, with their IDs
+    /// assigned by `policy`.
+    Import {
+        tasks: Vec<Task>,
+        policy: IdPolicy,
+    },
+    /// Adds events, e.g. meetings imported from a calendar, to the busy
+    /// time during which tasks are not planned.
+    ImportBusy(Vec<Busy>),
+    /// Imports the tasks Taskwarrior's `task export` printed, skipping those
+    /// which were imported before.
+    ImportTaskwarrior(Vec<Exported>),
+    /// Imports Jira issues as the tasks `mapping` makes of them, skipping
+    /// those which were imported before.
+    ImportJira {
+        issues: Vec<Issue>,
+        mapping: JiraMapping,
+    },
+    /// Summarizes the focus sessions of this week, those since Monday,
+    /// along with the tasks which were worked on the most.
+    FocusStats,
+    /// Lists the open tasks which have no due date, highest priority first,
+    /// to be planned around the project's busy time.
+    Unplanned,
+    /// Lists the open tasks in the inbox, with the tag `INBOX_TAG` as
+    /// `Matching` compares tags, oldest first, to be triaged.
+    Inbox(Matching),
+    /// Lists the open tasks which nothing has been done with for `days`
+    /// days, least recently touched first, to be reviewed.
+    Stale { days: i64 },
+    /// Lists the open tasks, in the order `policy` lists them in, for a
+    /// picker such as fzf to choose one of them from.
+    Pick { policy: SortPolicy },
+    /// Exports the tasks `query` selects as a document in `format`.  In
+    /// Markdown open tasks are in the order `policy` lists them in.
+    Export {
+        query: Query,
+        format: ExportFormat,
+        policy: SortPolicy,
+    },
+    /// Exports the task and all of its notes as a Markdown document.
+    ExportNotes(Option<u32>),
+}
+
+/// The changes `Command::Edit` makes to a task.  Fields which are `None`
+/// are left as they are, `Some(None)` clears an optional field.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Edits {
+    pub name: Option<String>,
+    pub priority: Option<u32>,
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    pub add_tags: Vec<String>,
+    pub remove_tags: Vec<String>,
+    pub assignee: Option<Option<String>>,
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+
+    /// How the tags being added or removed are matched to the task's tags.
+    pub matching: Matching,
+}
+
+impl Edits {
+    /// Whether there are no changes to make.
+    pub fn is_empty(&self) -> bool {
+        *self == Edits { matching: self.matching, ..Edits::default() }
+    }
+
+    /// Makes these changes to `task`, which is in `tasks`.  Each tag added
+    /// brings the tags it implies in `tasks` with it.
+    fn apply(&self, task: &mut Task, tasks: &TaskList) {
+        if let Some(name) = &self.name {
+            task.set_name(name);
+        }
+        if let Some(priority) = self.priority {
+            task.set_priority(priority);
+        }
+        if let Some(due_at) = self.due_at {
+            task.set_due_at(due_at);
+        }
+        for tag in self.add_tags.iter().flat_map(|tag| tasks.implied_tags(tag)) {
+            if !task.tags().iter().any(|t| self.matching.eq(t, &tag)) {
+                task.add_tag(&tag);
+            }
+        }
+        for tag in &self.remove_tags {
+            let matched: Vec<String> = task.tags().iter().filter(|t| self.matching.eq(t, tag)).cloned().collect();
+            matched.iter().for_each(|t| task.remove_tag(t));
+        }
+        if let Some(assignee) = &self.assignee {
+            task.set_assignee(assignee.as_deref());
+        }
+        if let Some(expires_at) = self.expires_at {
+            task.set_expires_at(expires_at);
+        }
+    }
+}
+
+/// Counts of the tasks in the whole project, shown after a list of tasks.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub open: usize,
+    pub closed: usize,
+
+    /// Of the open tasks, those which are in progress and those which are
+    /// blocked.
+    pub in_progress: usize,
+    pub blocked: usize,
+
+    /// Open tasks which are due within the next week, or are overdue.
+    pub due_this_week: usize,
+
+    pub checked_out: Option<u32>,
+}
+
+impl Summary {
+    /// Counts the tasks in `tasks`, as of `now`.
+    pub fn of(tasks: &TaskList, checked_out: Option<u32>, now: DateTime<Utc>) -> Summary {
+        let week = now + chrono::Duration::days(7);
+        let open = tasks.get_open();
+        Summary {
+            open: open.len(),
+            closed: tasks.get_closed().len(),
+            in_progress: tasks.filter(Status::InProgress).len(),
+            blocked: tasks.filter(Status::Blocked).len(),
+            due_this_week: open.iter().filter(|t| t.due_at().map(|d| d <= week).unwrap_or(false)).count(),
+            checked_out,
+        }
+    }
+}
+
+/// What a command has to show the user once it has been executed.
+#[derive(Debug)]
+pub enum Output {
+    /// The command has nothing to show.
+    Nothing,
+
+    /// A message describing what the command did.
+    Message(String),
+
+    /// A new task was added with this ID.
+    Added(u32),
+
+    /// A list of tasks, which should be shown sorted by `sort` (if given)
+    /// in the given format.
+    Tasks {
+        tasks: Vec<Task>,
+        checked_out: Option<u32>,
+        sort: Option<(String, Order)>,
+        format: TableFormat,
+
+        /// Text to highlight in the name of each task, and how it is matched.
+        highlight: Option<(String, Matching)>,
+        summary: Summary,
+
+        /// Whether to show how long each task has been checked out for, as
+        /// listing every task does.
+        time: bool,
+    },
+
+    /// A single task, whose notes should be shown.
+    Notes(Task),
+
+    /// A task which was edited, as it was before and after the edit.
+    Edited { before: Box<Task>, after: Box<Task> },
+
+    /// Values to show one per line, sorted and without duplicates.
+    Values(Vec<String>),
+
+    /// Each tag, sorted, with how many open and how many closed tasks have it.
+    Tags(Vec<(String, usize, usize)>),
+
+    /// What importing tasks did, or would do in a dry run.
+    Imported(ImportReport),
+
+    /// A task picked by `Command::Roulette`.
+    Picked(Task),
+
+    /// The tasks in dependency trees, each with its depth in its tree.  A
+    /// task is followed by the tasks it depends on, one deeper.
+    Graph(Vec<(usize, Task)>),
+
+    /// A task shown in full by `Command::Show`, along with whether it is
+    /// the checked out task.
+    Details { task: Box<Task>, checked_out: bool },
+
+    /// A task whose time entries should be shown, by `Command::Time`.
+    Time(Box<Task>),
+
+    /// The reminders which are due, each with the task it is about, which
+    /// should be delivered by `Command::Notify`.
+    Reminders(Vec<(DateTime<Utc>, Task)>),
+
+    /// Ways to shrink the project, from `Command::Compact`.
+    Suggestions(Vec<String>),
+
+    /// This week's focus sessions, from `Command::FocusStats`: the Monday
+    /// they are counted from, their statistics, and the tasks among
+    /// `stats.most_worked` which are still in the project.
+    Focus {
+        since: NaiveDate,
+        stats: FocusStats,
+        tasks: Vec<Task>,
+    },
+
+    /// The tasks to plan, from `Command::Unplanned`, and the busy time to
+    /// plan them around.
+    Unplanned { tasks: Vec<Task>, busy: BusyTime },
+
+    /// The tasks to triage, from `Command::Inbox`.
+    Inbox(Vec<Task>),
+
+    /// The tasks to review, from `Command::Stale`, along with the IDs of
+    /// those which have open subtasks and so cannot be closed.
+    Stale { tasks: Vec<Task>, open_subtasks: Vec<u32> },
+
+    /// The tasks to pick from, from `Command::Pick`.
+    Picks(Vec<Task>),
+
+    /// An exported document, along with what is in it, e.g. `4 tasks`.
+    Document { text: String, contents: String },
+}
+
+/// What `Command::Import` does with one of the imported tasks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportAction {
+    Create,
+    Update,
+    Skip,
+}
+
+/// One imported task: its ID in the imported file and the ID it has in the
+/// project, which is its existing ID if it was skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportRow {
+    pub action: ImportAction,
+    pub from: u32,
+    pub id: u32,
+    pub name: String,
+}
+
+/// The tasks `Command::Import` created, updated, and skipped, along with
+/// the conflicts found between the imported tasks and the project's.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+
+    /// Imported tasks whose ID is already used, or which have the same
+    /// name as a task already in the project.
+    pub conflicts: Vec<String>,
+}
+
+/**
+ * Executes `command` against the project whose `.tisk` directory is
+ * `task_path`: the tasks and checkout are read, the command is applied,
+ * and any changes it made are written back to disk.
+ */
+#[cfg(feature = "storage")]
+pub fn run(task_path: &std::path::PathBuf, command: &Command) -> Result<Output, TiskError> {
+    let (mut tasks, config) = read_tasks(task_path, command)?;
+
+    // TODO: This was an experiment to look at the idea of decoupling the
+    // application of a command to the in memory data and the act of then
+    // writing any changes to disk.  Now that the implementation is more or
+    // less done, think about if the design actually works.  My hypothesis
+    // was that doing this decoupling would make it harder to fail to write
+    // changed data to disk.
+    //
+    // 1. Does it make it easer to reason about the code.  What this design
+    //    does  do is explicitly show the user what effects they can have.
+    // 2. Does it make the code safer or more robust
+    // 3. What risks does this design bring: the changes you make and their
+    //    being committed are decoupled and far away, so it is hard to reason
+    //    about them.
+    //    - Having multiple effects which you want to make dependent would not
+    //    work in the present design; what could be done is some kind of
+    //    chaining where you have an effect and then something that is executed
+    //    if the effect is successfully resolved (e.g. `(Effect::Write,
+    //    and_then: () -> Effect)`
+
+    // load checked out task, if one is checked out
+    let checked_out_task =
+        io::read_checkout(task_path).map_err(|err| TiskError::io("Failed to read the checked out task", err))?;
+
+    // Apply the given command to the in memory TaskList
+    let local = read_local(task_path, command)?;
+    let (effects, output) = execute_and_expire(&mut tasks, checked_out_task, &config.dates(), &local, command)?;
+    commit(effects, &tasks, task_path)?;
+    Ok(output)
+}
+
+/// Reads the tasks of the project whose `.tisk` directory is `task_path`,
+/// with the tag implications from the project's config, for `command`, and
+/// the config.
+/// The archived tasks are only read if the command lists them.
+#[cfg(feature = "storage")]
+fn read_tasks(task_path: &std::path::PathBuf, command: &Command) -> Result<(TaskList, Config), TiskError> {
+    let mut tasks = TaskList::read_tasks(task_path).map_err(|why| TiskError::io("Failed to read tasks", why))?;
+    if let Command::List { archived: true, .. } = command {
+        tasks.read_archive(task_path).map_err(|why| TiskError::io("Failed to read archived tasks", why))?;
+    }
+    let config = Config::read(task_path).map_err(|why| TiskError::io("Failed to read config", why))?;
+    tasks.set_implications(config.tag_implications.clone());
+    Ok((tasks, config))
+}
+
+/// What the project whose `.tisk` directory is `task_path` keeps in its local
+/// directory for `command`, which is only read if the command needs it.
+#[cfg(feature = "storage")]
+fn read_local(task_path: &std::path::Path, command: &Command) -> Result<LocalState, TiskError> {
+    let mut local = LocalState::default();
+    match command {
+        Command::FocusStats => {
+            local.sessions = Sessions::read(task_path).map_err(|why| TiskError::io("Failed to read sessions", why))?
+        }
+        Command::Unplanned => {
+            local.busy = BusyTime::read(task_path).map_err(|why| TiskError::io("Failed to read busy time", why))?
+        }
+        _ => (),
+    }
+    Ok(local)
+}
+
+/// Executes `command` against the project whose `.tisk` directory is
+/// `task_path` just like `run`, except that nothing is written to disk.
+/// Along with the output of the command, the plan of the changes which
+/// would have been made is returned.
+#[cfg(feature = "storage")]
+pub fn dry_run(task_path: &std::path::PathBuf, command: &Command) -> Result<(Output, Plan), TiskError> {
+    let (mut tasks, config) = read_tasks(task_path, command)?;
+    let checked_out_task =
+        io::read_checkout(task_path).map_err(|err| TiskError::io("Failed to read the checked out task", err))?;
+
+    let local = read_local(task_path, command)?;
+    let (dates, before) = (config.dates(), tasks.clone());
+    let (effects, output) = execute_and_expire(&mut tasks, checked_out_task, &dates, &local, command)?;
+    Ok((output, plan(&effects, &before, &tasks, &dates)))
+}
+
+/// Executes `command` against the project whose `.tisk` directory is
+/// `task_path` just like `run`, except that the changes are only committed
+/// if `approve` accepts the plan of them.  If the changes are not approved
+/// then nothing is written and `None` is returned, otherwise the output of
+/// the command is returned along with what was committed.
+#[cfg(feature = "storage")]
+pub fn run_approved<F>(
+    task_path: &std::path::PathBuf,
+    command: &Command,
+    approve: F,
+) -> Result<Option<(Output, Committed)>, TiskError>
+where
+    F: FnOnce(&Plan) -> Result<bool, TiskError>,
+{
+    let (mut tasks, config) = read_tasks(task_path, command)?;
+    let checked_out_task =
+        io::read_checkout(task_path).map_err(|err| TiskError::io("Failed to read the checked out task", err))?;
+
+    let local = read_local(task_path, command)?;
+    let (dates, before) = (config.dates(), tasks.clone());
+    let (effects, output) = execute_and_expire(&mut tasks, checked_out_task, &dates, &local, command)?;
+    if !approve(&plan(&effects, &before, &tasks, &dates))? {
+        return Ok(None);
+    }
+    let wrote = effects.contains(&CommandEffect::Write);
+    let mut committed = commit(effects, &tasks, task_path)?;
+    if wrote {
+        committed.warnings = check_limits(&tasks, config.limits());
+    }
+    Ok(Some((output, committed)))
+}
+
+/// The changes which committing the effects of a command would make.
+#[derive(Debug, Default, PartialEq)]
+pub struct Plan {
+    /// A description of each change, e.g. `would close task 7`.
+    pub changes: Vec<String>,
+
+    /// The IDs of the tasks which would be added or changed.
+    pub changed_tasks: Vec<u32>,
+
+    /// Whether any change would lose data, e.g. by removing a task.
+    pub destructive: bool,
+}
+
+/// Describes, one line per change, what committing `effects` would do.
+/// `before` and `after` are the tasks before and after the command was
+/// applied, and are compared to say how each task was changed.  Dates are
+/// shown as `dates` says.
+pub fn plan(effects: &Effects, before: &TaskList, after: &TaskList, dates: &DateContext) -> Plan {
+    let mut plan = Plan::default();
+    for effect in effects {
+        match effect {
+            CommandEffect::Read => (),
+            CommandEffect::Write => {
+                for task in after.get_all() {
+                    let changes = describe_change(before.get(task.id()), task, dates);
+                    if !changes.is_empty() {
+                        plan.changed_tasks.push(task.id());
+                    }
+                    plan.changes.extend(changes);
+                }
+                let count = after.get_modified().len();
+                plan.changes.push(format!("would write {} task{}", count, if count == 1 { "" } else { "s" }));
+            }
+            CommandEffect::CheckoutTask(id) => plan.changes.push(format!("would check out task {}", id)),
+            CommandEffect::CheckinTask => plan.changes.push(String::from("would check in the checked out task")),
+            CommandEffect::DeleteTask(id) => {
+                let name = before.get(*id).map(|t| t.name()).unwrap_or("");
+                plan.changes.push(format!("would delete task {}: {}", id, name));
+                plan.changed_tasks.push(*id);
+                plan.destructive = true;
+            }
+            CommandEffect::RestoreTask(id) => plan.changes.push(format!("would take task {} out of the trash", id)),
+            CommandEffect::ArchiveTask(id) => {
+                let name = before.get(*id).map(|t| t.name()).unwrap_or("");
+                plan.changes.push(format!("would archive task {}: {}", id, name));
+                plan.changed_tasks.push(*id);
+            }
+            CommandEffect::AddBusy(events) => plan.changes.push(format!(
+                "would add {} busy block{}",
+                events.len(),
+                if events.len() == 1 { "" } else { "s" }
+            )),
+        }
+    }
+    plan
+}
+
+fn describe_change(before: Option<&Task>, after: &Task, dates: &DateContext) -> Vec<String> {
+    let id = after.id();
+    let before = match before {
+        None => return vec![format!("would add task {}: {}", id, after.name())],
+        Some(before) => before,
+    };
+
+    let mut changes = vec![];
+    if before.name() != after.name() {
+        changes.push(format!("would rename task {} to {}", id, after.name()));
+    }
+    if before.status() != after.status() {
+        match after.status() {
+            Status::Closed => changes.push(format!("would close task {}", id)),
+            Status::Open => changes.push(format!("would reopen task {}", id)),
+            Status::InProgress => changes.push(format!("would start task {}", id)),
+            Status::Blocked => changes.push(format!("would block task {}", id)),
+        }
+    }
+    if before.priority() != after.priority() {
+        changes.push(format!(
+            "would change the priority of task {} from {} to {}",
+            id,
+            before.priority(),
+            after.priority()
+        ));
+    }
+    if before.due_at() != after.due_at() {
+        match after.due_at() {
+            Some(due) => changes.push(format!("would set task {} due {}", id, dates.format_due(due))),
+            None => changes.push(format!("would clear the due date of task {}", id)),
+        }
+    }
+    if before.tags() != after.tags() {
+        changes.push(format!("would set the tags of task {} to [{}]", id, after.tags().join(", ")));
+    }
+    if before.assignee() != after.assignee() {
+        match after.assignee() {
+            Some(assignee) => changes.push(format!("would assign task {} to {}", id, assignee)),
+            None => changes.push(format!("would unassign task {}", id)),
+        }
+    }
+    if before.expires_at() != after.expires_at() {
+        match after.expires_at() {
+            Some(expires) => changes.push(format!("would set task {} to expire {}", id, dates.format_day(expires))),
+            None => changes.push(format!("would stop task {} from expiring", id)),
+        }
+    }
+    for reminder in after.reminders().iter().filter(|r| !before.reminders().iter().any(|b| b.at == r.at)) {
+        changes.push(format!("would remind about task {} at {}", id, dates.format_time(reminder.at)));
+    }
+    let delivered = |task: &Task| task.reminders().iter().filter(|r| r.delivered_at.is_some()).count();
+    let delivered = delivered(after).saturating_sub(delivered(before));
+    if delivered > 0 {
+        changes.push(format!(
+            "would deliver {} reminder{} about task {}",
+            delivered,
+            if delivered == 1 { "" } else { "s" },
+            id
+        ));
+    }
+    if before.reviewed_at() != after.reviewed_at() {
+        changes.push(format!("would mark task {} as reviewed", id));
+    }
+    for dep in after.depends_on().iter().filter(|d| !before.depends_on().contains(d)) {
+        changes.push(format!("would make task {} depend on task {}", id, dep));
+    }
+    let added_notes = after.notes().len().saturating_sub(before.notes().len());
+    if added_notes > 0 {
+        changes.push(format!(
+            "would add {} note{} to task {}",
+            added_notes,
+            if added_notes == 1 { "" } else { "s" },
+            id
+        ));
+    }
+    changes
+}
+
+/// What committing the effects of a command did.
+#[derive(Debug, Default, PartialEq)]
+pub struct Committed {
+    /// The effects which were committed.
+    pub effects: Effects,
+
+    /// The files which were written.
+    pub files_written: Vec<std::path::PathBuf>,
+
+    /// Warnings about the project having grown past its limits, from
+    /// `check_limits`, if tasks were written.
+    pub warnings: Vec<String>,
+}
+
+/// How large a project, and each of its tasks, can grow before commands
+/// warn that they will become slow.  0 is no limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// How many tasks, open or closed, the project can have.
+    pub tasks: usize,
+
+    /// How many notes a task can have.
+    pub notes: usize,
+}
+
+/// A warning for each way in which `tasks` has grown past `limits`.
+pub fn check_limits(tasks: &TaskList, limits: Limits) -> Vec<String> {
+    let mut warnings = vec![];
+    let count = tasks.get_all().len();
+    if limits.tasks > 0 && count > limits.tasks {
+        warnings.push(format!(
+            "The project has {} tasks, more than its task_limit of {}, and every command reads all of them.  \
+             Run tisk compact for suggestions",
+            count, limits.tasks
+        ));
+    }
+    for task in over_note_limit(tasks, limits) {
+        warnings.push(format!(
+            "Task {} has {} notes, more than the note_limit of {}.  Run tisk compact for suggestions",
+            task.id(),
+            task.note_count(),
+            limits.notes
+        ));
+    }
+    warnings
+}
+
+/// The tasks which have more notes than `limits` allows, by ID.
+fn over_note_limit(tasks: &TaskList, limits: Limits) -> Vec<&Task> {
+    let mut over: Vec<&Task> =
+        tasks.get_all().into_iter().filter(|t| limits.notes > 0 && t.note_count() > limits.notes).collect();
+    over.sort_by_key(|t| t.id());
+    over
+}
+
+/// Writes the `effects` of a command, which was applied to `tasks`, to the
+/// project whose `.tisk` directory is `task_path`.
+#[cfg(feature = "storage")]
+pub fn commit(effects: Effects, tasks: &TaskList, task_path: &std::path::PathBuf) -> Result<Committed, TiskError> {
+    let mut committed = Committed::default();
+    for effect in effects {
+        match effect {
+            CommandEffect::Read => (),
+            CommandEffect::Write => {
+                debug!("Writing tasks");
+                let written = tasks.write_all(task_path).map_err(|err| TiskError::io("Failed to write tasks", err))?;
+                committed.files_written.extend(written);
+            }
+            CommandEffect::CheckoutTask(id) => {
+                debug!("Checkout task {}", id);
+                io::commit_checkout(id, task_path).map_err(|err| TiskError::io("Failed to check out the task", err))?;
+                committed.files_written.push(io::local_path(task_path, io::CHECKOUT_FILE));
+                record_session(task_path, |sessions| sessions.start(id, Utc::now()))?;
+                committed.files_written.push(io::local_path(task_path, focus::SESSIONS_FILE));
+            }
+            CommandEffect::CheckinTask => {
+                debug!("Checkin task");
+                io::commit_checkin(task_path).map_err(|err| TiskError::io("Failed to check in the task", err))?;
+                record_session(task_path, |sessions| sessions.end(Utc::now()))?;
+                committed.files_written.push(io::local_path(task_path, focus::SESSIONS_FILE));
+            }
+            CommandEffect::DeleteTask(id) => {
+                debug!("Delete task {}", id);
+                let task = tasks.removed(id).ok_or(TiskError::TaskNotFound(id))?;
+                let trashed = io::trash_task(task, task_path)
+                    .map_err(|err| TiskError::io(&format!("Failed to move task {} to the trash", id), err))?;
+                committed.files_written.push(trashed);
+            }
+            CommandEffect::RestoreTask(id) => {
+                debug!("Restore task {}", id);
+                io::untrash_task(id, task_path)
+                    .map_err(|err| TiskError::io(&format!("Failed to take task {} out of the trash", id), err))?;
+            }
+            CommandEffect::ArchiveTask(id) => {
+                debug!("Archive task {}", id);
+                let task = tasks.removed(id).ok_or(TiskError::TaskNotFound(id))?;
+                let archived = io::archive_task(task, task_path)
+                    .map_err(|err| TiskError::io(&format!("Failed to move task {} to the archive", id), err))?;
+                committed.files_written.push(archived);
+            }
+            CommandEffect::AddBusy(ref events) => {
+                debug!("Add {} busy blocks", events.len());
+                let mut busy = BusyTime::read(task_path).map_err(|err| TiskError::io("Failed to read busy time", err))?;
+                busy.add(events);
+                busy.write(task_path).map_err(|err| TiskError::io("Failed to write busy time", err))?;
+                committed.files_written.push(io::local_path(task_path, busy::BUSY_FILE));
+            }
+        }
+        committed.effects.push(effect);
+    }
+    Ok(committed)
+}
+
+/// Makes `change` to the focus sessions of the project whose `.tisk`
+/// directory is `task_path`.
+#[cfg(feature = "storage")]
+fn record_session<F: FnOnce(&mut Sessions)>(task_path: &std::path::Path, change: F) -> Result<(), TiskError> {
+    let mut sessions = Sessions::read(task_path).map_err(|err| TiskError::io("Failed to read sessions", err))?;
+    change(&mut sessions);
+    sessions.write(task_path).map_err(|err| TiskError::io("Failed to write sessions", err))
+}
+
+/// What a project keeps in its local directory, besides which task is
+/// checked out, for the commands which read it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LocalState {
+    pub sessions: Sessions,
+    pub busy: BusyTime,
+}
+
+/// Applies `command` to the in memory `tasks`.  Nothing is written to disk,
+/// instead the returned effects say what must be committed.  Dates are
+/// shown and parsed in the local time zone, without holidays, and commands
+/// which read the project's `LocalState` find it empty, `execute_with` gives
+/// them the project's.
+pub fn execute(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    command: &Command,
+) -> Result<(Effects, Output), TiskError> {
+    execute_with(tasks, checked_out_task, &DateContext::default(), &LocalState::default(), command)
+}
+
+/// Applies `command` to the in memory `tasks` just like `execute`, with the
+/// project's `dates` and `local` state.
+pub fn execute_with(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    dates: &DateContext,
+    local: &LocalState,
+    command: &Command,
+) -> Result<(Effects, Output), TiskError> {
+    match command {
+        Command::Add {
+            name,
+            priority,
+            note,
+            tags,
+            due_at,
+            parent,
+        } => handle_add(tasks, name, *priority, note.as_deref(), tags, *due_at, *parent),
+        Command::Close { id, note, follow_up, cascade } => {
+            handle_close(tasks, id.or(checked_out_task), note.as_deref(), follow_up.as_deref(), *cascade)
+        }
+        Command::Edit { id, edits } => handle_edit(tasks, id.or(checked_out_task), edits),
+        Command::EditMany(edits) => handle_edit_many(tasks, edits),
+        Command::Triage(decisions) => handle_triage(tasks, decisions),
+        Command::Review(decisions) => handle_review(tasks, decisions, Utc::now()),
+        Command::Note { id, note } => handle_note(tasks, id.or(checked_out_task), note.as_deref()),
+        Command::NoteMany { ids, query, note } => handle_note_many(tasks, ids, query.as_ref(), note),
+        Command::Delete { id, orphans } => handle_delete(tasks, checked_out_task, id.or(checked_out_task), *orphans),
+        Command::Restore(task) => handle_restore(tasks, task),
+        Command::Replace(task) => handle_replace(tasks, task),
+        Command::Start(id) => handle_set_status(tasks, id.or(checked_out_task), Status::InProgress, None),
+        Command::Block { id, reason } => {
+            handle_set_status(tasks, id.or(checked_out_task), Status::Blocked, reason.as_deref())
+        }
+        Command::Depend { id, on } => handle_depend(tasks, id.or(checked_out_task), on),
+        Command::Graph(id) => handle_graph(tasks, *id),
+        Command::Show(id) => handle_show(tasks, checked_out_task, id.or(checked_out_task)),
+        Command::Time(id) => handle_time(tasks, id.or(checked_out_task)),
+        Command::Remind { id, at } => handle_remind(tasks, id.or(checked_out_task), *at, dates),
+        Command::Notify => handle_notify(tasks, Utc::now()),
+        Command::Compact(limits) => handle_compact(tasks, *limits, Utc::now()),
+        Command::Checkout(checkout) => handle_checkout(tasks, checked_out_task, checkout, Utc::now()),
+        Command::Checkin => handle_checkin(tasks, checked_out_task, Utc::now()),
+        Command::List { query, policy, sort, format, .. } => {
+            handle_list(tasks, checked_out_task, query, *policy, sort.clone(), *format, dates)
+        }
+        Command::Archive { older_than } => handle_archive(tasks, checked_out_task, *older_than, Utc::now()),
+        Command::Complete(completion) => handle_complete(tasks, *completion),
+        Command::Expire => handle_expire(tasks, dates),
+        Command::Backlinks(id) => handle_backlinks(tasks, checked_out_task, *id, dates),
+        Command::Tags => handle_tags(tasks),
+        Command::Roulette { query, seed } => handle_roulette(tasks, query, *seed),
+        Command::Import { tasks: imported, policy } => handle_import(tasks, imported, *policy),
+        Command::ImportBusy(events) => handle_import_busy(events),
+        Command::ImportTaskwarrior(exported) => {
+            let imported = taskwarrior::to_tasks(exported, tasks)?;
+            handle_import(tasks, &imported, IdPolicy::Keep(Conflict::Skip))
+        }
+        Command::ImportJira { issues, mapping } => {
+            let imported = jira::to_tasks(issues, mapping, tasks)?;
+            handle_import(tasks, &imported, IdPolicy::Keep(Conflict::Skip))
+        }
+        Command::FocusStats => handle_focus_stats(tasks, &local.sessions, Utc::now(), dates),
+        Command::Unplanned => handle_unplanned(tasks, &local.busy),
+        Command::Inbox(matching) => handle_inbox(tasks, *matching),
+        Command::Stale { days } => handle_stale(tasks, *days, Utc::now()),
+        Command::Pick { policy } => handle_pick(tasks, *policy, dates),
+        Command::Export { query, format, policy } => handle_export(tasks, query, *format, *policy, dates),
+        Command::ExportNotes(id) => handle_export_notes(tasks, id.or(checked_out_task), dates),
+    }
+}
+
+/// Executes `command` just like `execute_with` and then, if the command writes
+/// to the project, closes the tasks which have expired.
+#[cfg(feature = "storage")]
+fn execute_and_expire(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    dates: &DateContext,
+    local: &LocalState,
+    command: &Command,
+) -> Result<(Effects, Output), TiskError> {
+    let (effects, output) = execute_with(tasks, checked_out_task, dates, local, command)?;
+    if effects.contains(&CommandEffect::Write) {
+        expire(tasks, Utc::now(), dates)?;
+    }
+    Ok((effects, output))
+}
+
+/// Closes every open task in `tasks` which has expired as of `now`, noting
+/// why, with the day shown as `dates` says, on each, and returns their IDs.
+pub fn expire(tasks: &mut TaskList, now: DateTime<Utc>, dates: &DateContext) -> Result<Vec<u32>, TiskError> {
+    let expired: Vec<u32> = tasks.get_open().iter().filter(|t| t.has_expired(now)).map(|t| t.id()).collect();
+    for &id in &expired {
+        if let Some(task) = tasks.get_mut(id) {
+            let expires_at = task.expires_at().unwrap_or(now);
+            task.add_note(&format!("Closed automatically because it expired on {}", dates.format_day(expires_at)))?;
+            task.close();
+        }
+    }
+    Ok(expired)
+}
+
+fn handle_add(
+    tasks: &mut TaskList,
+    name: &str,
+    priority: u32,
+    note: Option<&str>,
+    tags: &[String],
+    due_at: Option<DateTime<Utc>>,
+    parent: Option<u32>,
+) -> Result<(Effects, Output), TiskError> {
+    let id = add_task(tasks, name, priority, note, tags, due_at, parent)?;
+    Ok((vec![CommandEffect::Write], Output::Added(id)))
+}
+
+/// Adds a new task, with an optional first note, tags, due date, and
+/// parent task, to `tasks` and returns its ID.
+fn add_task(
+    tasks: &mut TaskList,
+    name: &str,
+    priority: u32,
+    note: Option<&str>,
+    tags: &[String],
+    due_at: Option<DateTime<Utc>>,
+    parent: Option<u32>,
+) -> Result<u32, TiskError> {
+    debug!("Adding new task to task list");
+    if let Some(note) = note {
+        check_references(tasks, note)?;
+    }
+    if let Some(parent) = parent.filter(|parent| tasks.get(*parent).is_none()) {
+        return Err(TiskError::ParseError(format!("Could not find the parent task with ID {}", parent)));
+    }
+    let id = tasks.add_task(name, priority);
+
+    tags.iter().for_each(|tag| {
+        tasks.add_tag(id, tag);
+    });
+    if let Some(task) = tasks.get_mut(id) {
+        if let Some(note) = note {
+            task.add_note(note)?;
+        }
+        task.set_due_at(due_at);
+        task.set_parent(parent);
+    }
+    Ok(id)
+}
+
+/// Lists task IDs for a message, e.g. `3, 5, 9`.
+fn join_ids(ids: &[u32]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+/// Checks that every task which `note` refers to with `#ID` exists.
+fn check_references(tasks: &TaskList, note: &str) -> Result<(), TiskError> {
+    match Note::new(note).references().into_iter().find(|id| tasks.get(*id).is_none()) {
+        Some(id) => Err(TiskError::ParseError(format!("The note refers to task #{}, which does not exist", id))),
+        None => Ok(()),
+    }
+}
+
+fn handle_close(
+    tasks: &mut TaskList,
+    id: Option<u32>,
+    note: Option<&str>,
+    follow_up: Option<&str>,
+    cascade: bool,
+) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    if follow_up.map(|name| name.trim().is_empty()).unwrap_or(false) {
+        return Err(TiskError::ParseError(String::from("The follow-up task must have a name")));
+    }
+
+    debug!("Closing task with ID: {}", id);
+    if let Some(note) = note {
+        check_references(tasks, note)?;
+    }
+    let closed = tasks.close_task(id, cascade)?;
+    let task = tasks.get_mut(id).ok_or(TiskError::TaskNotFound(id))?;
+    if let Some(note) = note {
+        task.add_note(note)?;
+    }
+    let (priority, tags) = (task.priority(), task.tags().to_vec());
+
+    let mut message = format!("Task {} was closed", id);
+    if closed.len() > 1 {
+        message.push_str(&format!(", along with subtasks {}", join_ids(&closed[1..])));
+    }
+    let dependents: Vec<u32> =
+        tasks.dependents(id).iter().filter(|t| t.status().is_open()).map(|t| t.id()).collect();
+    if !dependents.is_empty() {
+        message.push_str(&format!(", warning: open tasks {} depend on it", join_ids(&dependents)));
+    }
+    if let Some(name) = follow_up {
+        let follow_up = add_task(tasks, name, priority, Some(&format!("Follow-up of #{}", id)), &tags, None, None)?;
+        if let Some(task) = tasks.get_mut(id) {
+            task.add_note(&format!("Followed up by #{}", follow_up))?;
+        }
+        message.push_str(&format!(", added follow-up task {}", follow_up));
+    }
+    Ok((vec![CommandEffect::Write], Output::Message(message)))
+}
+
+fn handle_delete(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    id: Option<u32>,
+    orphans: Orphans,
+) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    let parent = tasks.get(id).and_then(|t| t.parent());
+
+    debug!("Deleting task with ID: {}", id);
+    let removed = tasks.remove_task(id, orphans).ok_or(TiskError::TaskNotFound(id))?;
+    let mut effects = vec![CommandEffect::Write];
+    effects.extend(removed.tasks.iter().map(|t| CommandEffect::DeleteTask(t.id())));
+    if removed.tasks.iter().any(|t| Some(t.id()) == checked_out_task) {
+        effects.push(CommandEffect::CheckinTask);
+    }
+
+    let mut message = format!("Task {} was moved to the trash", id);
+    if removed.tasks.len() > 1 {
+        let subtasks: Vec<u32> = removed.tasks[1..].iter().map(|t| t.id()).collect();
+        message.push_str(&format!(", along with subtasks {}", join_ids(&subtasks)));
+    }
+    if !removed.moved.is_empty() {
+        let moved = join_ids(&removed.moved);
+        match (orphans, parent) {
+            (Orphans::Reparent, Some(parent)) => {
+                message.push_str(&format!(", its subtasks {} are now subtasks of task {}", moved, parent))
+            }
+            _ => message.push_str(&format!(", its subtasks {} no longer have a parent", moved)),
+        }
+    }
+    Ok((effects, Output::Message(message)))
+}
+
+fn handle_restore(tasks: &mut TaskList, task: &Task) -> Result<(Effects, Output), TiskError> {
+    let id = task.id();
+    debug!("Restoring task with ID: {}", id);
+    tasks.restore(task.clone())?;
+    Ok((
+        vec![CommandEffect::Write, CommandEffect::RestoreTask(id)],
+        Output::Message(format!("Task {} was restored from the trash", id)),
+    ))
+}
+
+fn handle_replace(tasks: &mut TaskList, task: &Task) -> Result<(Effects, Output), TiskError> {
+    let id = task.id();
+    if task.name().trim().is_empty() {
+        return Err(TiskError::ParseError(format!("Task {} must have a name", id)));
+    }
+
+    debug!("Replacing task with ID: {}", id);
+    match tasks.replace(task.clone()) {
+        None => Err(TiskError::TaskNotFound(id)),
+        Some(_) => Ok((vec![CommandEffect::Write], Output::Message(format!("Task {} was updated", id)))),
+    }
+}
+
+/// Moves an open task to another open status, e.g. starts it, adding
+/// `reason` as a note.
+fn handle_set_status(
+    tasks: &mut TaskList,
+    id: Option<u32>,
+    status: Status,
+    reason: Option<&str>,
+) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    if let Some(reason) = reason {
+        check_references(tasks, reason)?;
+    }
+    let task = tasks.get_mut(id).ok_or(TiskError::TaskNotFound(id))?;
+    if !task.status().is_open() {
+        return Err(TiskError::Conflict(format!("Task {} is closed, reopen it first", id)));
+    }
+
+    debug!("Setting the status of task {} to {:?}", id, status);
+    task.set_status(status);
+    if let Some(reason) = reason {
+        task.add_note(&format!("Blocked: {}", reason))?;
+    }
+    let message = match status {
+        Status::InProgress => format!("Task {} was started", id),
+        Status::Blocked => format!("Task {} was blocked", id),
+        _ => format!("Task {} was updated", id),
+    };
+    Ok((vec![CommandEffect::Write], Output::Message(message)))
+}
+
+fn handle_depend(tasks: &mut TaskList, id: Option<u32>, on: &[u32]) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    if on.is_empty() {
+        return Err(TiskError::ParseError(format!("No tasks given for task {} to depend on", id)));
+    }
+
+    debug!("Making task {} depend on {:?}", id, on);
+    let mut depended = tasks.clone();
+    for dep in on {
+        depended.depend(id, *dep)?;
+    }
+    *tasks = depended;
+    Ok((
+        vec![CommandEffect::Write],
+        Output::Message(format!("Task {} now depends on {}", id, join_ids(on))),
+    ))
+}
+
+fn handle_graph(tasks: &TaskList, id: Option<u32>) -> Result<(Effects, Output), TiskError> {
+    let roots: Vec<u32> = match id {
+        Some(id) => vec![tasks.get(id).ok_or(TiskError::TaskNotFound(id))?.id()],
+        None => tasks
+            .get_all()
+            .iter()
+            .filter(|t| !t.depends_on().is_empty() && tasks.dependents(t.id()).is_empty())
+            .map(|t| t.id())
+            .collect(),
+    };
+
+    // Depth first, so that each task is followed by what it depends on
+    let mut rows = vec![];
+    let mut next: Vec<(usize, u32)> = roots.into_iter().rev().map(|id| (0, id)).collect();
+    while let Some((depth, id)) = next.pop() {
+        if let Some(task) = tasks.get(id) {
+            next.extend(task.depends_on().iter().rev().map(|dep| (depth + 1, *dep)));
+            rows.push((depth, task.clone()));
+        }
+    }
+    Ok((vec![CommandEffect::Read], Output::Graph(rows)))
+}
+
+/// Checks out a task, ending the time entry of the task which was checked
+/// out before and starting one at `now` on the newly checked out task.
+fn handle_checkout(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    checkout: &Checkout,
+    now: DateTime<Utc>,
+) -> Result<(Effects, Output), TiskError> {
+    let id = match checkout {
+        Checkout::Add {
+            name,
+            priority,
+            note,
+            tags,
+            due_at,
+            parent,
+        } => add_task(tasks, name, *priority, note.as_deref(), tags, *due_at, *parent)?,
+        Checkout::Id(id) => *id,
+    };
+
+    if tasks.get(id).is_none() {
+        return Err(TiskError::TaskNotFound(id));
+    }
+    if let Some(previous) = checked_out_task.filter(|&previous| previous != id).and_then(|p| tasks.get_mut(p)) {
+        previous.stop_timer(now);
+    }
+    if let Some(task) = tasks.get_mut(id) {
+        task.start_timer(now);
+    }
+
+    debug!("Checkout task {}", id);
+    let effects = vec![CommandEffect::Write, CommandEffect::CheckoutTask(id)];
+    Ok((effects, Output::Message(format!("Checkout task {}", id))))
+}
+
+/// Checks in the checked out task, ending its time entry at `now`.
+fn handle_checkin(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    now: DateTime<Utc>,
+) -> Result<(Effects, Output), TiskError> {
+    let mut effects = vec![];
+    if let Some(task) = checked_out_task.and_then(|id| tasks.get_mut(id)) {
+        task.stop_timer(now);
+        effects.push(CommandEffect::Write);
+    }
+
+    // Generate a signal to delete the checkout file
+    effects.push(CommandEffect::CheckinTask);
+    Ok((effects, Output::Nothing))
+}
+
+fn handle_remind(
+    tasks: &mut TaskList,
+    id: Option<u32>,
+    at: DateTime<Utc>,
+    dates: &DateContext,
+) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    let task = tasks.get_mut(id).ok_or(TiskError::TaskNotFound(id))?;
+    task.add_reminder(at);
+    let message = format!("Will remind you about task {} at {}", id, dates.format_time(at));
+    Ok((vec![CommandEffect::Write], Output::Message(message)))
+}
+
+/// Marks every reminder which is due at `now`, on the open tasks, as
+/// delivered and returns them to be delivered.
+fn handle_notify(tasks: &mut TaskList, now: DateTime<Utc>) -> Result<(Effects, Output), TiskError> {
+    let ids: Vec<u32> = tasks
+        .get_open()
+        .iter()
+        .filter(|t| t.reminders().iter().any(|r| r.delivered_at.is_none() && r.at <= now))
+        .map(|t| t.id())
+        .collect();
+    let mut due = vec![];
+    for id in ids {
+        if let Some(task) = tasks.get_mut(id) {
+            let delivered = task.deliver_reminders(now);
+            due.extend(delivered.into_iter().map(|at| (at, task.clone())));
+        }
+    }
+    due.sort_by_key(|(at, _)| *at);
+
+    let effects = if due.is_empty() { vec![CommandEffect::Read] } else { vec![CommandEffect::Write] };
+    Ok((effects, Output::Reminders(due)))
+}
+
+/// How long ago a task was closed for `tisk compact` to suggest archiving it.
+const COMPACT_CLOSED_DAYS: i64 = 30;
+
+/// How long an open task has gone untouched for `tisk compact` to suggest
+/// reviewing it.
+const COMPACT_STALE_DAYS: i64 = 90;
+
+fn handle_compact(tasks: &TaskList, limits: Limits, now: DateTime<Utc>) -> Result<(Effects, Output), TiskError> {
+    let mut suggestions = vec![];
+    let cutoff = now - chrono::Duration::days(COMPACT_CLOSED_DAYS);
+    let closed = tasks
+        .get_closed()
+        .iter()
+        .filter(|t| t.closed_at().unwrap_or_else(|| t.last_touched()) <= cutoff)
+        .count();
+    if closed > 0 {
+        suggestions.push(format!(
+            "Archive the {} task{} closed more than {} days ago with tisk archive",
+            closed,
+            if closed == 1 { "" } else { "s" },
+            COMPACT_CLOSED_DAYS
+        ));
+    }
+    let stale = tasks.get_open().iter().filter(|t| t.is_stale(now, COMPACT_STALE_DAYS)).count();
+    if stale > 0 {
+        suggestions.push(format!(
+            "Close or delete the {} open task{} untouched for {} days, tisk review --days {} walks through them",
+            stale,
+            if stale == 1 { "" } else { "s" },
+            COMPACT_STALE_DAYS,
+            COMPACT_STALE_DAYS
+        ));
+    }
+    let open = tasks.get_open().len();
+    if limits.tasks > 0 && open > limits.tasks {
+        suggestions.push(format!(
+            "{} tasks are open, more than the task_limit of {} on their own: split the project into smaller ones",
+            open, limits.tasks
+        ));
+    }
+    for task in over_note_limit(tasks, limits) {
+        suggestions.push(format!(
+            "Task {} has {} notes: export them with tisk note {} --export and carry on in a new task",
+            task.id(),
+            task.note_count(),
+            task.id()
+        ));
+    }
+    Ok((vec![CommandEffect::Read], Output::Suggestions(suggestions)))
+}
+
+fn handle_time(tasks: &TaskList, id: Option<u32>) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    let task = tasks.get(id).ok_or(TiskError::TaskNotFound(id))?;
+    Ok((vec![CommandEffect::Read], Output::Time(Box::new(task.clone()))))
+}
+
+fn handle_edit(tasks: &mut TaskList, id: Option<u32>, edits: &Edits) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    if edits.is_empty() {
+        return Ok((vec![CommandEffect::Read], Output::Nothing));
+    }
+
+    let before = tasks.get(id).ok_or(TiskError::TaskNotFound(id))?.clone();
+    let mut after = before.clone();
+    edits.apply(&mut after, tasks);
+    if after.name().trim().is_empty() {
+        return Err(TiskError::ParseError(format!("Task {} must have a name", id)));
+    }
+
+    debug!("Editing task with ID: {}", id);
+    tasks.replace(after.clone());
+    Ok((vec![CommandEffect::Write], Output::Edited { before: Box::new(before), after: Box::new(after) }))
+}
+
+fn handle_edit_many(tasks: &mut TaskList, edits: &[(u32, Edits)]) -> Result<(Effects, Output), TiskError> {
+    let mut edited = tasks.clone();
+    let mut count = 0;
+    for (id, edits) in edits {
+        if let (_, Output::Edited { .. }) = handle_edit(&mut edited, Some(*id), edits)? {
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Ok((vec![CommandEffect::Read], Output::Nothing));
+    }
+
+    *tasks = edited;
+    Ok((vec![CommandEffect::Write], Output::Message(format!("{} tasks were edited", count))))
+}
+
+fn handle_triage(tasks: &mut TaskList, decisions: &[(u32, Triage)]) -> Result<(Effects, Output), TiskError> {
+    let mut triaged = tasks.clone();
+    let mut effects = vec![];
+    let mut orphaned = vec![];
+    for (id, decision) in decisions {
+        match decision {
+            Triage::Keep(edits) => {
+                let mut edits = edits.clone();
+                edits.remove_tags.push(String::from(INBOX_TAG));
+                handle_edit(&mut triaged, Some(*id), &edits)?;
+            }
+            Triage::Delete => {
+                let removed =
+                    triaged.remove_task(*id, Orphans::Orphan).ok_or(TiskError::TaskNotFound(*id))?;
+                effects.push(CommandEffect::DeleteTask(*id));
+                orphaned.extend(removed.moved);
+            }
+        }
+    }
+    if decisions.is_empty() {
+        return Ok((vec![CommandEffect::Read], Output::Nothing));
+    }
+
+    *tasks = triaged;
+    effects.insert(0, CommandEffect::Write);
+    let count = decisions.len();
+    let mut message = format!("{} task{} triaged", count, if count == 1 { " was" } else { "s were" });
+    if !orphaned.is_empty() {
+        message.push_str(&format!(", subtasks {} of the deleted tasks no longer have a parent", join_ids(&orphaned)));
+    }
+    Ok((effects, Output::Message(message)))
+}
+
+fn handle_review(
+    tasks: &mut TaskList,
+    decisions: &[(u32, Review)],
+    now: DateTime<Utc>,
+) -> Result<(Effects, Output), TiskError> {
+    if decisions.is_empty() {
+        return Ok((vec![CommandEffect::Read], Output::Nothing));
+    }
+    let mut reviewed = tasks.clone();
+    let mut effects = vec![CommandEffect::Write];
+    for (id, decision) in decisions {
+        match decision {
+            Review::Delete => {
+                reviewed.remove_task(*id, Orphans::Orphan).ok_or(TiskError::TaskNotFound(*id))?;
+                effects.push(CommandEffect::DeleteTask(*id));
+                continue;
+            }
+            Review::Close => {
+                reviewed.close_task(*id, false)?;
+            }
+            Review::Reprioritize(priority) => {
+                reviewed.set_priority(*id, *priority).ok_or(TiskError::TaskNotFound(*id))?;
+            }
+            Review::Keep => (),
+        }
+        let task = reviewed.get_mut(*id).ok_or(TiskError::TaskNotFound(*id))?;
+        task.set_reviewed_at(Some(now));
+    }
+
+    *tasks = reviewed;
+    let count = decisions.len();
+    Ok((effects, Output::Message(format!("{} task{} reviewed", count, if count == 1 { " was" } else { "s were" }))))
+}
+
+fn handle_import(tasks: &mut TaskList, imported: &[Task], policy: IdPolicy) -> Result<(Effects, Output), TiskError> {
+    // IDs only conflict if the imported tasks keep their IDs
+    let used: Vec<u32> = match policy {
+        IdPolicy::Keep(_) => imported.iter().map(|t| t.id()).filter(|id| tasks.get(*id).is_some()).collect(),
+        IdPolicy::Renumber => vec![],
+    };
+    if let (IdPolicy::Keep(Conflict::Fail), false) = (policy, used.is_empty()) {
+        let used: Vec<String> = used.iter().map(u32::to_string).collect();
+        return Err(TiskError::Conflict(format!(
+            "Cannot import, these task IDs are already used: {}.  Use --on-conflict to choose what to do with them",
+            used.join(", ")
+        )));
+    }
+
+    let mut report = ImportReport::default();
+    for task in imported {
+        if let Some(existing) = tasks.get(task.id()).filter(|_| used.contains(&task.id())) {
+            report.conflicts.push(format!("ID {} is already used by task {}: {}", task.id(), existing.id(), existing.name()));
+        }
+        if let Some(existing) = tasks.get_all().into_iter().find(|t| t.name() == task.name()) {
+            report.conflicts.push(format!("Task {} has the same name as task {}: {}", task.id(), existing.id(), existing.name()));
+        }
+    }
+
+    let before: Vec<u32> = tasks.get_all().iter().map(|t| t.id()).collect();
+    let merged = tasks.merge(imported.iter().cloned().collect(), policy)?;
+    for task in imported {
+        let (action, id) = match merged.iter().find(|(from, _)| *from == task.id()) {
+            Some(&(from, id)) if from == id && before.contains(&id) => (ImportAction::Update, id),
+            Some(&(_, id)) => (ImportAction::Create, id),
+            None => (ImportAction::Skip, task.id()),
+        };
+        report.rows.push(ImportRow { action, from: task.id(), id, name: String::from(task.name()) });
+    }
+
+    let effect = if merged.is_empty() { CommandEffect::Read } else { CommandEffect::Write };
+    Ok((vec![effect], Output::Imported(report)))
+}
+
+fn handle_import_busy(events: &[Busy]) -> Result<(Effects, Output), TiskError> {
+    let message = format!("Imported {} busy block{}", events.len(), if events.len() == 1 { "" } else { "s" });
+    Ok((vec![CommandEffect::AddBusy(events.to_vec())], Output::Message(message)))
+}
+
+fn handle_expire(tasks: &mut TaskList, dates: &DateContext) -> Result<(Effects, Output), TiskError> {
+    match expire(tasks, Utc::now(), dates)?.len() {
+        0 => Ok((vec![CommandEffect::Read], Output::Message(String::from("No tasks have expired")))),
+        1 => Ok((vec![CommandEffect::Write], Output::Message(String::from("1 expired task was closed")))),
+        n => Ok((vec![CommandEffect::Write], Output::Message(format!("{} expired tasks were closed", n)))),
+    }
+}
+
+fn handle_note(tasks: &mut TaskList, id: Option<u32>, note: Option<&str>) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+
+    match note {
+        None => {
+            let task = tasks.get(id).ok_or(TiskError::TaskNotFound(id))?;
+            Ok((vec![CommandEffect::Read], Output::Notes(task.clone())))
+        }
+        Some(note) => {
+            check_references(tasks, note)?;
+            match tasks.get_mut(id) {
+                Some(task) => {
+                    task.add_note(note)?;
+                    Ok((vec![CommandEffect::Write], Output::Nothing))
+                }
+                None => Err(TiskError::TaskNotFound(id)),
+            }
+        }
+    }
+}
+
+fn handle_show(
+    tasks: &TaskList,
+    checked_out_task: Option<u32>,
+    id: Option<u32>,
+) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    let task = tasks.get(id).ok_or(TiskError::TaskNotFound(id))?;
+    let output = Output::Details { task: Box::new(task.clone()), checked_out: checked_out_task == Some(id) };
+    Ok((vec![CommandEffect::Read], output))
+}
+
+fn handle_note_many(
+    tasks: &mut TaskList,
+    ids: &[u32],
+    query: Option<&Query>,
+    note: &str,
+) -> Result<(Effects, Output), TiskError> {
+    check_references(tasks, note)?;
+    if let Some(id) = ids.iter().find(|id| tasks.get(**id).is_none()) {
+        return Err(TiskError::TaskNotFound(*id));
+    }
+    let mut selected: Vec<u32> = ids.to_vec();
+    if let Some(query) = query {
+        selected.extend(query.select(tasks).iter().map(|t| t.id()));
+    }
+    selected.sort_unstable();
+    selected.dedup();
+    if selected.is_empty() {
+        return Err(TiskError::ParseError(String::from("No tasks were selected to add the note to")));
+    }
+
+    for id in &selected {
+        if let Some(task) = tasks.get_mut(*id) {
+            task.add_note(note)?;
+        }
+    }
+    Ok((
+        vec![CommandEffect::Write],
+        Output::Message(format!("Added the note to {} task(s): {}", selected.len(), join_ids(&selected))),
+    ))
+}
+
+fn handle_list(
+    tasks: &TaskList,
+    checked_out_task: Option<u32>,
+    query: &Query,
+    policy: SortPolicy,
+    sort: Option<(String, Order)>,
+    format: TableFormat,
+    dates: &DateContext,
+) -> Result<(Effects, Output), TiskError> {
+    let mut task_slice = query.select(tasks);
+    policy.sort(&mut task_slice, dates.zone);
+
+    let output = Output::Tasks {
+        tasks: task_slice.into_iter().cloned().collect(),
+        checked_out: checked_out_task,
+        sort,
+        format,
+        highlight: query.contains.as_ref().map(|text| (text.clone(), query.matching)),
+        summary: Summary::of(tasks, checked_out_task, Utc::now()),
+        time: query.filter == ListFilter::All,
+    };
+    Ok((vec![CommandEffect::Read], output))
+}
+
+/// Moves the tasks which were closed at least `older_than` before `now`
+/// out of `tasks`, to be archived.  Tasks closed by older versions of tisk,
+/// which did not record when, are taken to be closed when they were last
+/// touched.
+fn handle_archive(
+    tasks: &mut TaskList,
+    checked_out_task: Option<u32>,
+    older_than: chrono::Duration,
+    now: DateTime<Utc>,
+) -> Result<(Effects, Output), TiskError> {
+    let cutoff = now - older_than;
+    let old: Vec<u32> = tasks
+        .get_closed()
+        .iter()
+        .filter(|t| t.closed_at().unwrap_or_else(|| t.last_touched()) <= cutoff)
+        .map(|t| t.id())
+        .collect();
+    if old.is_empty() {
+        return Ok((vec![CommandEffect::Read], Output::Message(String::from("No closed tasks to archive"))));
+    }
+
+    let mut effects = vec![CommandEffect::Write];
+    for &id in &old {
+        tasks.remove(id);
+        effects.push(CommandEffect::ArchiveTask(id));
+    }
+    if checked_out_task.map(|id| old.contains(&id)).unwrap_or(false) {
+        effects.push(CommandEffect::CheckinTask);
+    }
+    let message = format!("Archived {} task{}: {}", old.len(), if old.len() == 1 { "" } else { "s" }, join_ids(&old));
+    Ok((effects, Output::Message(message)))
+}
+
+fn handle_backlinks(
+    tasks: &TaskList,
+    checked_out_task: Option<u32>,
+    id: u32,
+    dates: &DateContext,
+) -> Result<(Effects, Output), TiskError> {
+    if tasks.get(id).is_none() {
+        return Err(TiskError::TaskNotFound(id));
+    }
+    let mut linking: Vec<&Task> = tasks
+        .get_all()
+        .into_iter()
+        .filter(|task| task.notes().iter().any(|note| note.references().contains(&id)))
+        .collect();
+    SortPolicy::default().sort(&mut linking, dates.zone);
+
+    let output = Output::Tasks {
+        tasks: linking.into_iter().cloned().collect(),
+        checked_out: checked_out_task,
+        sort: None,
+        format: TableFormat::Table,
+        highlight: None,
+        summary: Summary::of(tasks, checked_out_task, Utc::now()),
+        time: false,
+    };
+    Ok((vec![CommandEffect::Read], output))
+}
+
+fn handle_complete(tasks: &TaskList, completion: Completion) -> Result<(Effects, Output), TiskError> {
+    let values: Vec<String> = match completion {
+        Completion::Tags => tasks.tags().into_iter().map(String::from).collect(),
+        Completion::Assignees => {
+            let assignees: std::collections::BTreeSet<&str> =
+                tasks.get_all().iter().filter_map(|t| t.assignee()).collect();
+            assignees.into_iter().map(String::from).collect()
+        }
+    };
+    Ok((vec![CommandEffect::Read], Output::Values(values)))
+}
+
+fn handle_tags(tasks: &TaskList) -> Result<(Effects, Output), TiskError> {
+    let counts = tasks
+        .tags()
+        .into_iter()
+        .map(|tag| {
+            let tagged = tasks.filter_by_tag(tag);
+            let open = tagged.iter().filter(|t| t.status().is_open()).count();
+            (String::from(tag), open, tagged.len() - open)
+        })
+        .collect();
+    Ok((vec![CommandEffect::Read], Output::Tags(counts)))
+}
+
+fn handle_roulette(tasks: &TaskList, query: &Query, seed: u64) -> Result<(Effects, Output), TiskError> {
+    let eligible: Vec<&Task> = query.select(tasks).into_iter().filter(|t| t.status().is_open()).collect();
+    // Every task has a chance, even those with priority 0
+    let weights: Vec<u64> = eligible.iter().map(|t| t.priority() as u64 + 1).collect();
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return Err(TiskError::Conflict(String::from("There are no open tasks to pick from")));
+    }
+
+    // splitmix64, so that seeds which are close together, e.g. the times
+    // of two runs, still pick far apart
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    let mut roll = (z ^ (z >> 31)) % total;
+
+    let mut picked = eligible[0];
+    for (task, weight) in eligible.iter().zip(&weights) {
+        if roll < *weight {
+            picked = task;
+            break;
+        }
+        roll -= weight;
+    }
+    Ok((vec![CommandEffect::Read], Output::Picked(picked.clone())))
+}
+
+/// Summarizes the `sessions` since Monday, in the project's time zone, of
+/// the week `now` is in.
+fn handle_focus_stats(
+    tasks: &TaskList,
+    sessions: &Sessions,
+    now: DateTime<Utc>,
+    dates: &DateContext,
+) -> Result<(Effects, Output), TiskError> {
+    let zone = dates.zone;
+    let today = zone.naive(now).date();
+    let monday = today - chrono::Duration::days(today.weekday().num_days_from_monday() as i64);
+    let stats = sessions.stats(zone.utc(monday.and_hms(0, 0, 0)).unwrap_or(now), now, zone);
+    let worked = stats.most_worked.iter().filter_map(|(id, _)| tasks.get(*id)).cloned().collect();
+    Ok((vec![CommandEffect::Read], Output::Focus { since: monday, stats, tasks: worked }))
+}
+
+fn handle_unplanned(tasks: &TaskList, busy: &BusyTime) -> Result<(Effects, Output), TiskError> {
+    let mut unplanned: Vec<&Task> = tasks.get_open().into_iter().filter(|t| t.due_at().is_none()).collect();
+    unplanned.sort_by_key(|t| std::cmp::Reverse(t.priority()));
+    Ok((
+        vec![CommandEffect::Read],
+        Output::Unplanned { tasks: unplanned.into_iter().cloned().collect(), busy: busy.clone() },
+    ))
+}
+
+fn handle_inbox(tasks: &TaskList, matching: Matching) -> Result<(Effects, Output), TiskError> {
+    let mut inbox: Vec<&Task> = tasks
+        .get_open()
+        .into_iter()
+        .filter(|t| t.tags().iter().any(|tag| matching.eq(tag, INBOX_TAG)))
+        .collect();
+    inbox.sort_by_key(|t| t.created_at());
+    Ok((vec![CommandEffect::Read], Output::Inbox(inbox.into_iter().cloned().collect())))
+}
+
+fn handle_stale(tasks: &TaskList, days: i64, now: DateTime<Utc>) -> Result<(Effects, Output), TiskError> {
+    let mut stale: Vec<&Task> = tasks.get_open().into_iter().filter(|t| t.is_stale(now, days)).collect();
+    stale.sort_by_key(|t| t.last_touched());
+    let open_subtasks = stale
+        .iter()
+        .filter(|t| tasks.subtasks(t.id()).iter().any(|s| s.status().is_open()))
+        .map(|t| t.id())
+        .collect();
+    Ok((vec![CommandEffect::Read], Output::Stale { tasks: stale.into_iter().cloned().collect(), open_subtasks }))
+}
+
+fn handle_pick(tasks: &TaskList, policy: SortPolicy, dates: &DateContext) -> Result<(Effects, Output), TiskError> {
+    let mut open = tasks.get_open();
+    policy.sort(&mut open, dates.zone);
+    Ok((vec![CommandEffect::Read], Output::Picks(open.into_iter().cloned().collect())))
+}
+
+fn handle_export(
+    tasks: &TaskList,
+    query: &Query,
+    format: ExportFormat,
+    policy: SortPolicy,
+    dates: &DateContext,
+) -> Result<(Effects, Output), TiskError> {
+    let text = export::export(tasks, query, format, policy, dates);
+    Ok((vec![CommandEffect::Read], Output::Document { text, contents: format!("{} tasks", query.select(tasks).len()) }))
+}
+
+fn handle_export_notes(tasks: &TaskList, id: Option<u32>, dates: &DateContext) -> Result<(Effects, Output), TiskError> {
+    let id = id.ok_or(TiskError::NoTask)?;
+    let task = tasks.get(id).ok_or(TiskError::TaskNotFound(id))?;
+    let contents = format!("the notes of task {}", id);
+    Ok((vec![CommandEffect::Read], Output::Document { text: task.to_markdown(dates), contents }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn list(filter: ListFilter) -> Command {
+        Command::List {
+            query: Query::new(filter),
+            policy: SortPolicy::default(),
+            sort: None,
+            format: TableFormat::Table,
+            archived: false,
+        }
+    }
+
+    #[test]
+    fn add_and_list() {
+        let mut tasks = TaskList::new();
+        let add = Command::Add {
+            name: "test".into(),
+            priority: 1,
+            note: Some("a note".into()),
+            tags: vec![],
+            due_at: None,
+            parent: None,
+        };
+        let (effects, output) = execute(&mut tasks, None, &add).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+        match output {
+            Output::Added(id) => assert_eq!(1, id),
+            output => panic!("Expected the new task's ID, got {:?}", output),
+        }
+
+        let add = Command::Add {
+            name: "test 2".into(),
+            priority: 3,
+            note: None,
+            tags: vec![],
+            due_at: None,
+            parent: None,
+        };
+        execute(&mut tasks, None, &add).unwrap();
+
+        match execute(&mut tasks, None, &list(ListFilter::Open)).unwrap() {
+            (effects, Output::Tasks { tasks, .. }) => {
+                assert_eq!(vec![CommandEffect::Read], effects);
+                let names: Vec<&str> = tasks.iter().map(|t| t.name()).collect();
+                assert_eq!(vec!["test 2", "test"], names);
+                assert_eq!(1, tasks[1].notes().len());
+            }
+            (_, output) => panic!("Expected a list of tasks, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn list_contains() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("fix the build", 1);
+        tasks.add_task("write docs", 1);
+        tasks.add_task("fix the docs", 2);
+
+        let mut query = Query::new(ListFilter::Open);
+        query.contains = Some("DOCS".into());
+        query.matching = Matching { ignore_case: true, ignore_accents: false };
+        let list = Command::List {
+            query,
+            policy: SortPolicy::default(),
+            sort: None,
+            format: TableFormat::Table,
+            archived: false,
+        };
+        match execute(&mut tasks, None, &list).unwrap() {
+            (_, Output::Tasks { tasks, highlight, .. }) => {
+                let names: Vec<&str> = tasks.iter().map(|t| t.name()).collect();
+                assert_eq!(vec!["fix the docs", "write docs"], names);
+                assert_eq!(Some("DOCS"), highlight.as_ref().map(|(text, _)| text.as_str()));
+            }
+            (_, output) => panic!("Expected a list of tasks, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn complete_values() {
+        let mut tasks = TaskList::new();
+        for (name, tags, assignee) in &[("a", vec!["ui", "bug"], Some("sam")), ("b", vec!["bug"], None)] {
+            let id = tasks.add_task(name, 1);
+            let task = tasks.get_mut(id).unwrap();
+            for tag in tags {
+                task.add_tag(tag);
+            }
+            task.set_assignee(*assignee);
+        }
+        tasks.close_task(2, false).unwrap();
+
+        let values = |completion| match execute(&mut tasks.clone(), None, &Command::Complete(completion)) {
+            Ok((_, Output::Values(values))) => values,
+            output => panic!("Expected values, got {:?}", output),
+        };
+        assert_eq!(vec!["bug", "ui"], values(Completion::Tags));
+        assert_eq!(vec!["sam"], values(Completion::Assignees));
+    }
+
+    #[test]
+    fn count_tags() {
+        let mut tasks = TaskList::new();
+        for (name, tag) in &[("login", "ui"), ("crash", "bug"), ("typo", "bug")] {
+            let id = tasks.add_task(name, 1);
+            tasks.get_mut(id).unwrap().add_tag(tag);
+        }
+        tasks.close_task(3, false).unwrap();
+
+        match execute(&mut tasks, None, &Command::Tags).unwrap() {
+            (_, Output::Tags(counts)) => {
+                assert_eq!(vec![(String::from("bug"), 1, 1), (String::from("ui"), 1, 0)], counts)
+            }
+            (_, output) => panic!("Expected tag counts, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn close_checked_out_task() {
+        let mut tasks = TaskList::new();
+        let id = tasks.add_task("test", 1);
+
+        let close = Command::Close { id: None, note: None, follow_up: None, cascade: false };
+        assert!(execute(&mut tasks, None, &close).is_err());
+
+        let (effects, output) = execute(&mut tasks, Some(id), &close).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+        match output {
+            Output::Message(msg) => assert_eq!("Task 1 was closed", msg),
+            output => panic!("Expected a message, got {:?}", output),
+        }
+
+        match execute(&mut tasks, None, &list(ListFilter::Closed)).unwrap() {
+            (_, Output::Tasks { tasks, .. }) => assert_eq!(1, tasks.len()),
+            (_, output) => panic!("Expected a list of tasks, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn close_with_follow_up() {
+        let mut tasks = TaskList::new();
+        let id = tasks.add_task("ship the importer", 3);
+        tasks.get_mut(id).unwrap().add_tag("import");
+
+        let close = |follow_up: &str| Command::Close {
+            id: Some(id),
+            note: None,
+            follow_up: Some(follow_up.into()),
+            cascade: false,
+        };
+        assert!(execute(&mut tasks, None, &close(" ")).is_err());
+        assert_eq!(Status::Open, tasks.get(id).unwrap().status());
+
+        let (_, output) = execute(&mut tasks, None, &close("remove the old importer")).unwrap();
+        match output {
+            Output::Message(msg) => assert_eq!("Task 1 was closed, added follow-up task 2", msg),
+            output => panic!("Expected a message, got {:?}", output),
+        }
+        assert_eq!(Status::Closed, tasks.get(1).unwrap().status());
+        assert_eq!("Followed up by #2", tasks.get(1).unwrap().notes()[0].note());
+
+        let follow_up = tasks.get(2).unwrap();
+        assert_eq!(Status::Open, follow_up.status());
+        assert_eq!(3, follow_up.priority());
+        assert_eq!(vec!["import"], follow_up.tags());
+        assert_eq!("Follow-up of #1", follow_up.notes()[0].note());
+    }
+
+    #[test]
+    fn close_with_subtasks() {
+        let mut tasks = TaskList::new();
+        let add = |name: &str, parent| Command::Add {
+            name: name.into(),
+            priority: 1,
+            note: None,
+            tags: vec![],
+            due_at: None,
+            parent,
+        };
+        assert!(execute(&mut tasks, None, &add("orphan", Some(1))).is_err());
+        execute(&mut tasks, None, &add("release", None)).unwrap();
+        execute(&mut tasks, None, &add("tag", Some(1))).unwrap();
+        assert_eq!(Some(1), tasks.get(2).unwrap().parent());
+
+        let close = |cascade| Command::Close { id: Some(1), note: Some("shipped".into()), follow_up: None, cascade };
+        assert!(execute(&mut tasks, None, &close(false)).is_err());
+        match execute(&mut tasks, None, &close(true)).unwrap() {
+            (_, Output::Message(msg)) => assert_eq!("Task 1 was closed, along with subtasks 2", msg),
+            (_, output) => panic!("Expected a message, got {:?}", output),
+        }
+        assert_eq!(Status::Closed, tasks.get(2).unwrap().status());
+        assert_eq!(1, tasks.get(1).unwrap().notes().len());
+    }
+
+    #[test]
+    fn checkout_new_task() {
+        let mut tasks = TaskList::new();
+        let checkout = Command::Checkout(Checkout::Add {
+            name: "test".into(),
+            priority: 4,
+            note: Some("a note".into()),
+            tags: vec!["ui".into()],
+            due_at: Some(Utc.ymd(2020, 6, 3).and_hms(9, 0, 0)),
+            parent: None,
+        });
+        let (effects, _) = execute(&mut tasks, None, &checkout).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::CheckoutTask(1)], effects);
+
+        let task = tasks.get(1).unwrap();
+        assert_eq!(4, task.priority());
+        assert_eq!(1, task.notes().len());
+        assert_eq!(vec!["ui"], task.tags());
+        assert_eq!(Some(Utc.ymd(2020, 6, 3).and_hms(9, 0, 0)), task.due_at());
+
+        let checkout = Command::Checkout(Checkout::Id(2));
+        assert!(execute(&mut tasks, None, &checkout).is_err());
+    }
+
+    #[test]
+    fn time_checked_out_tasks() {
+        let mut tasks = TaskList::new();
+        let first = tasks.add_task("first", 1);
+        let second = tasks.add_task("second", 1);
+        let start = Utc.ymd(2020, 6, 1).and_hms(9, 0, 0);
+
+        let (effects, _) = handle_checkout(&mut tasks, None, &Checkout::Id(first), start).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::CheckoutTask(first)], effects);
+        let switch = start + chrono::Duration::minutes(25);
+        handle_checkout(&mut tasks, Some(first), &Checkout::Id(second), switch).unwrap();
+        let (effects, _) = handle_checkin(&mut tasks, Some(second), switch + chrono::Duration::hours(1)).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::CheckinTask], effects);
+
+        let later = start + chrono::Duration::days(1);
+        assert_eq!(chrono::Duration::minutes(25), tasks.get(first).unwrap().time_spent(later));
+        assert_eq!(chrono::Duration::hours(1), tasks.get(second).unwrap().time_spent(later));
+        match execute(&mut tasks, Some(second), &Command::Time(None)).unwrap() {
+            (_, Output::Time(task)) => assert_eq!(1, task.time_entries().len()),
+            _ => panic!("Expected the time entries of the checked out task"),
+        }
+        assert_eq!("task_not_found", execute(&mut tasks, None, &Command::Time(Some(9))).unwrap_err().code());
+    }
+
+    #[test]
+    fn add_and_show_notes() {
+        let mut tasks = TaskList::new();
+        let id = tasks.add_task("test", 1);
+
+        let note = Command::Note { id: Some(id), note: Some("a note".into()) };
+        let (effects, _) = execute(&mut tasks, None, &note).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+
+        match execute(&mut tasks, Some(id), &Command::Note { id: None, note: None }).unwrap() {
+            (_, Output::Notes(task)) => assert_eq!("a note", task.notes()[0].note()),
+            (_, output) => panic!("Expected notes, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn archive_old_tasks() {
+        let mut tasks = TaskList::new();
+        let open = tasks.add_task("open", 1);
+        let old = tasks.add_task("old", 1);
+        let closed = tasks.add_task("closed", 1);
+        tasks.get_mut(old).unwrap().close();
+        tasks.get_mut(closed).unwrap().close();
+
+        let later = Utc::now() + chrono::Duration::days(10);
+        let (effects, _) = handle_archive(&mut tasks, None, chrono::Duration::days(30), later).unwrap();
+        assert_eq!(vec![CommandEffect::Read], effects);
+
+        let (effects, _) = handle_archive(&mut tasks, Some(old), chrono::Duration::days(7), later).unwrap();
+        let expected = vec![
+            CommandEffect::Write,
+            CommandEffect::ArchiveTask(old),
+            CommandEffect::ArchiveTask(closed),
+            CommandEffect::CheckinTask,
+        ];
+        assert_eq!(expected, effects);
+        assert_eq!(vec![open], tasks.get_all().iter().map(|t| t.id()).collect::<Vec<u32>>());
+        assert_eq!(Some("old"), tasks.removed(old).map(|t| t.name()));
+    }
+
+    #[test]
+    fn limits_and_compact() {
+        let now = Utc::now();
+        let mut tasks = TaskList::new();
+        for name in ["old", "stale", "noted", "new"] {
+            let id = tasks.add_task(name, 1);
+            tasks.get_mut(id).unwrap().set_created_at(now - chrono::Duration::days(100));
+        }
+        tasks.get_mut(1).unwrap().close_at(now - chrono::Duration::days(40));
+        for note in ["one", "two", "three"] {
+            tasks.get_mut(3).unwrap().add_note(note).unwrap();
+        }
+        tasks.get_mut(4).unwrap().set_created_at(now);
+
+        let limits = Limits { tasks: 3, notes: 2 };
+        let warnings = check_limits(&tasks, limits);
+        assert_eq!(2, warnings.len());
+        assert!(warnings[0].starts_with("The project has 4 tasks"));
+        assert!(warnings[1].starts_with("Task 3 has 3 notes"));
+        assert_eq!(0, check_limits(&tasks, Limits { tasks: 0, notes: 0 }).len());
+
+        match handle_compact(&tasks, limits, now).unwrap() {
+            (effects, Output::Suggestions(suggestions)) => {
+                assert_eq!(vec![CommandEffect::Read], effects);
+                assert_eq!(3, suggestions.len());
+                assert!(suggestions[0].starts_with("Archive the 1 task closed"));
+                assert!(suggestions[1].starts_with("Close or delete the 1 open task"));
+                assert!(suggestions[2].starts_with("Task 3 has 3 notes"));
+            }
+            _ => panic!("Expected suggestions"),
+        }
+    }
+
+    #[test]
+    fn deliver_reminders_once() {
+        let mut tasks = TaskList::new();
+        let first = tasks.add_task("first", 1);
+        let closed = tasks.add_task("closed", 1);
+        let now = Utc::now();
+        execute(&mut tasks, None, &Command::Remind { id: Some(first), at: now - chrono::Duration::hours(1) }).unwrap();
+        execute(&mut tasks, None, &Command::Remind { id: Some(first), at: now + chrono::Duration::hours(1) }).unwrap();
+        execute(&mut tasks, Some(closed), &Command::Remind { id: None, at: now }).unwrap();
+        tasks.get_mut(closed).unwrap().close();
+        let dates = DateContext::default();
+        assert_eq!("task_not_found", handle_remind(&mut tasks, Some(9), now, &dates).unwrap_err().code());
+
+        match handle_notify(&mut tasks, now).unwrap() {
+            (effects, Output::Reminders(due)) => {
+                assert_eq!(vec![CommandEffect::Write], effects);
+                assert_eq!(vec![first], due.iter().map(|(_, t)| t.id()).collect::<Vec<u32>>());
+            }
+            _ => panic!("Expected the due reminders"),
+        }
+        match handle_notify(&mut tasks, now).unwrap() {
+            (effects, Output::Reminders(due)) => assert_eq!((vec![CommandEffect::Read], 0), (effects, due.len())),
+            _ => panic!("Expected the due reminders"),
+        }
+        match handle_notify(&mut tasks, now + chrono::Duration::days(1)).unwrap() {
+            (_, Output::Reminders(due)) => assert_eq!(1, due.len()),
+            _ => panic!("Expected the due reminders"),
+        }
+    }
+
+    #[test]
+    fn show_task() {
+        let mut tasks = TaskList::new();
+        let first = tasks.add_task("first", 1);
+        let second = tasks.add_task("second", 1);
+
+        match execute(&mut tasks, Some(second), &Command::Show(None)).unwrap() {
+            (effects, Output::Details { task, checked_out }) => {
+                assert_eq!(vec![CommandEffect::Read], effects);
+                assert_eq!((second, true), (task.id(), checked_out));
+            }
+            (_, output) => panic!("Expected details, got {:?}", output),
+        }
+        match execute(&mut tasks, Some(second), &Command::Show(Some(first))).unwrap() {
+            (_, Output::Details { task, checked_out }) => assert_eq!((first, false), (task.id(), checked_out)),
+            (_, output) => panic!("Expected details, got {:?}", output),
+        }
+        assert_eq!(true, execute(&mut tasks, None, &Command::Show(None)).is_err());
+        assert_eq!("task_not_found", execute(&mut tasks, None, &Command::Show(Some(9))).unwrap_err().code());
+    }
+
+    #[test]
+    fn note_many_tasks() {
+        let mut tasks = TaskList::new();
+        for name in &["plan", "build", "ship", "celebrate"] {
+            tasks.add_task(name, 1);
+        }
+        tasks.get_mut(4).unwrap().add_tag("sprint-4");
+        let note = |ids: Vec<u32>, query: Option<Query>| Command::NoteMany { ids, query, note: "moved to next sprint".into() };
+
+        assert!(execute(&mut tasks, None, &note(vec![1, 9], None)).is_err());
+        assert_eq!(0, tasks.get(1).unwrap().notes().len());
+        assert!(execute(&mut tasks, None, &note(vec![], None)).is_err());
+
+        let mut sprint = Query::new(ListFilter::Open);
+        sprint.conditions.push("tag=sprint-4".parse().unwrap());
+        match execute(&mut tasks, None, &note(vec![3, 1, 3], Some(sprint))).unwrap() {
+            (_, Output::Message(msg)) => assert_eq!("Added the note to 3 task(s): 1, 3, 4", msg),
+            (_, output) => panic!("Expected a message, got {:?}", output),
+        }
+        let counts: Vec<usize> = (1..=4).map(|id| tasks.get(id).unwrap().notes().len()).collect();
+        assert_eq!(vec![1, 0, 1, 1], counts);
+    }
+
+    #[test]
+    fn plan_describes_changes() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("first", 1);
+        tasks.add_task("second", 1);
+
+        // Only the tasks which were changed since they were read are written
+        tasks.mark_read();
+        let before = tasks.clone();
+        let (effects, _) = execute(&mut tasks, None, &Command::Start(Some(1))).unwrap();
+        assert_eq!(
+            vec!["would start task 1", "would write 1 task"],
+            plan(&effects, &before, &tasks, &DateContext::default()).changes
+        );
+
+        let before = tasks.clone();
+        let close = Command::Close { id: Some(2), note: Some("done".into()), follow_up: None, cascade: false };
+        let (effects, _) = execute(&mut tasks, None, &close).unwrap();
+        assert_eq!(
+            vec!["would close task 2", "would add 1 note to task 2", "would write 2 tasks"],
+            plan(&effects, &before, &tasks, &DateContext::default()).changes
+        );
+
+        let checkout = Command::Checkout(Checkout::Add {
+            name: "third".into(),
+            priority: 1,
+            note: None,
+            tags: vec![],
+            due_at: None,
+            parent: None,
+        });
+        let before = tasks.clone();
+        let (effects, _) = execute(&mut tasks, None, &checkout).unwrap();
+        assert_eq!(
+            vec!["would add task 3: third", "would write 3 tasks", "would check out task 3"],
+            plan(&effects, &before, &tasks, &DateContext::default()).changes
+        );
+    }
+
+    #[test]
+    fn replace_requires_a_name() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("test", 1);
+
+        let mut edited = tasks.get(1).unwrap().clone();
+        edited.set_priority(4);
+        let (effects, _) = execute(&mut tasks, None, &Command::Replace(edited)).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+        assert_eq!(4, tasks.get(1).unwrap().priority());
+
+        let nameless = Task::new(1, "  ".into(), Status::Open, 1);
+        assert!(execute(&mut tasks, None, &Command::Replace(nameless)).is_err());
+        assert_eq!("test", tasks.get(1).unwrap().name());
+    }
+
+    #[test]
+    fn edit_many_fields() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("test", 1);
+        tasks.get_mut(1).unwrap().add_tag("old");
+
+        let edits = Edits {
+            name: Some("renamed".into()),
+            priority: Some(3),
+            add_tags: vec!["ui".into()],
+            remove_tags: vec!["old".into()],
+            assignee: Some(Some("sam".into())),
+            ..Edits::default()
+        };
+        let (effects, output) = execute(&mut tasks, None, &Command::Edit { id: Some(1), edits }).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+        match output {
+            Output::Edited { before, after } => {
+                assert_eq!("test", before.name());
+                assert_eq!("renamed", after.name());
+            }
+            output => panic!("Expected the edited task, got {:?}", output),
+        }
+
+        let task = tasks.get(1).unwrap();
+        assert_eq!(3, task.priority());
+        assert_eq!(vec!["ui"], task.tags());
+        assert_eq!(Some("sam"), task.assignee());
+    }
+
+    #[test]
+    fn edit_tags_ignoring_case() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("test", 1);
+        tasks.get_mut(1).unwrap().add_tag("Bug");
+        tasks.get_mut(1).unwrap().add_tag("UI");
+
+        let edits = Edits {
+            add_tags: vec!["BUG".into()],
+            remove_tags: vec!["ui".into()],
+            matching: Matching { ignore_case: true, ignore_accents: false },
+            ..Edits::default()
+        };
+        execute(&mut tasks, None, &Command::Edit { id: Some(1), edits }).unwrap();
+        assert_eq!(vec!["Bug"], tasks.get(1).unwrap().tags());
+    }
+
+    #[test]
+    fn edit_many_is_all_or_nothing() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("a", 1);
+        tasks.add_task("b", 1);
+        let priority = |p| Edits { priority: Some(p), ..Edits::default() };
+
+        let invalid = Command::EditMany(vec![(1, priority(5)), (3, priority(5))]);
+        assert!(execute(&mut tasks, None, &invalid).is_err());
+        assert_eq!(1, tasks.get(1).unwrap().priority());
+
+        let valid = Command::EditMany(vec![(1, priority(5)), (2, priority(4))]);
+        let (effects, _) = execute(&mut tasks, None, &valid).unwrap();
+        assert_eq!(vec![CommandEffect::Write], effects);
+        assert_eq!(5, tasks.get(1).unwrap().priority());
+        assert_eq!(4, tasks.get(2).unwrap().priority());
+    }
+
+    #[test]
+    #[cfg(feature = "storage")]
+    fn writes_close_expired_tasks() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("reminder", 1);
+        tasks.add_task("later", 1);
+        let now = Utc::now();
+        tasks.get_mut(1).unwrap().set_expires_at(Some(now - chrono::Duration::days(1)));
+        tasks.get_mut(2).unwrap().set_expires_at(Some(now + chrono::Duration::days(1)));
+
+        let (dates, local) = (DateContext::default(), LocalState::default());
+        execute_and_expire(&mut tasks, None, &dates, &local, &list(ListFilter::Open)).unwrap();
+        assert_eq!(Status::Open, tasks.get(1).unwrap().status());
+
+        let add = Command::Add {
+            name: "new".into(),
+            priority: 1,
+            note: None,
+            tags: vec![],
+            due_at: None,
+            parent: None,
+        };
+        execute_and_expire(&mut tasks, None, &dates, &local, &add).unwrap();
+        let reminder = tasks.get(1).unwrap();
+        assert_eq!(Status::Closed, reminder.status());
+        assert_eq!(true, reminder.notes()[0].note().starts_with("Closed automatically"));
+        assert_eq!(Status::Open, tasks.get(2).unwrap().status());
+
+        let (effects, _) = execute(&mut tasks, None, &Command::Expire).unwrap();
+        assert_eq!(vec![CommandEffect::Read], effects);
+    }
+
+    #[test]
+    fn note_references() {
+        let mut tasks = TaskList::new();
+        let first = tasks.add_task("first", 1);
+        let second = tasks.add_task("second", 1);
+        tasks.add_task("third", 1);
+
+        let note = |id, note: &str| Command::Note { id: Some(id), note: Some(note.into()) };
+        execute(&mut tasks, None, &note(second, &format!("Waiting on #{}", first))).unwrap();
+        let missing = execute(&mut tasks, None, &note(second, "See #9"));
+        assert_eq!("The note refers to task #9, which does not exist", missing.unwrap_err().to_string());
+        assert_eq!(1, tasks.get(second).unwrap().notes().len());
+
+        match execute(&mut tasks, None, &Command::Backlinks(first)).unwrap() {
+            (_, Output::Tasks { tasks, .. }) => assert_eq!(vec![second], tasks.iter().map(|t| t.id()).collect::<Vec<_>>()),
+            (_, output) => panic!("Unexpected output: {:?}", output),
+        }
+        assert_eq!(true, execute(&mut tasks, None, &Command::Backlinks(9)).is_err());
+    }
+
+    #[test]
+    fn import_report() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("a", 1);
+        tasks.add_task("b", 1);
+        let imported: Vec<Task> = vec![Task::new(2, "b".into(), Status::Open, 3), Task::new(5, "e".into(), Status::Open, 1)];
+
+        let import = |policy| Command::Import { tasks: imported.clone(), policy };
+        let failed = execute(&mut tasks, None, &import(IdPolicy::Keep(Conflict::Fail)));
+        assert_eq!(true, failed.unwrap_err().to_string().contains("task IDs are already used: 2."));
+        assert_eq!(2, tasks.get_all().len());
+
+        let report = match execute(&mut tasks.clone(), None, &import(IdPolicy::Keep(Conflict::Skip))).unwrap() {
+            (_, Output::Imported(report)) => report,
+            (_, output) => panic!("Unexpected output: {:?}", output),
+        };
+        let actions: Vec<(ImportAction, u32, u32)> = report.rows.iter().map(|r| (r.action, r.from, r.id)).collect();
+        assert_eq!(vec![(ImportAction::Skip, 2, 2), (ImportAction::Create, 5, 5)], actions);
+        assert_eq!(
+            vec![String::from("ID 2 is already used by task 2: b"), String::from("Task 2 has the same name as task 2: b")],
+            report.conflicts
+        );
+
+        match execute(&mut tasks, None, &import(IdPolicy::Keep(Conflict::Replace))).unwrap() {
+            (effects, Output::Imported(report)) => {
+                assert_eq!(vec![CommandEffect::Write], effects);
+                assert_eq!(ImportAction::Update, report.rows[0].action);
+            }
+            (_, output) => panic!("Unexpected output: {:?}", output),
+        }
+        assert_eq!(3, tasks.get(2).unwrap().priority());
+    }
+
+    #[test]
+    fn triage_inbox() {
+        let mut tasks = TaskList::new();
+        for name in &["keep", "drop"] {
+            let id = tasks.add_task(name, 1);
+            tasks.get_mut(id).unwrap().add_tag(INBOX_TAG);
+        }
+        let subtask = tasks.add_task("subtask of drop", 1);
+        tasks.get_mut(subtask).unwrap().set_parent(Some(2));
+        let before = tasks.clone();
+
+        let keep = Edits { priority: Some(4), ..Edits::default() };
+        let triage = Command::Triage(vec![(1, Triage::Keep(keep)), (2, Triage::Delete)]);
+        let (effects, output) = execute(&mut tasks, None, &triage).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::DeleteTask(2)], effects);
+        match output {
+            Output::Message(msg) => {
+                assert_eq!("2 tasks were triaged, subtasks 3 of the deleted tasks no longer have a parent", msg)
+            }
+            output => panic!("Expected a message, got {:?}", output),
+        }
+        assert_eq!(None, tasks.get(3).unwrap().parent());
+
+        let kept = tasks.get(1).unwrap();
+        assert_eq!(4, kept.priority());
+        assert_eq!(false, kept.has_tag(INBOX_TAG));
+        assert_eq!(None, tasks.get(2));
+
+        let plan = plan(&effects, &before, &tasks, &DateContext::default());
+        assert_eq!(true, plan.destructive);
+        assert_eq!(true, plan.changes.contains(&String::from("would delete task 2: drop")));
+    }
+
+    #[test]
+    fn tasks_to_triage_review_and_plan() {
+        let mut tasks = TaskList::new();
+        for name in &["new", "inbox", "old", "subtask", "due"] {
+            tasks.add_task(name, 1);
+        }
+        let now = Utc::now();
+        tasks.get_mut(1).unwrap().add_tag(INBOX_TAG);
+        tasks.get_mut(2).unwrap().add_tag("Inbox");
+        tasks.get_mut(2).unwrap().set_created_at(now - chrono::Duration::days(40));
+        tasks.get_mut(3).unwrap().set_created_at(now - chrono::Duration::days(30));
+        tasks.get_mut(3).unwrap().set_priority(3);
+        tasks.get_mut(4).unwrap().set_parent(Some(3));
+        tasks.get_mut(5).unwrap().set_due_at(Some(now));
+
+        let folded = Matching { ignore_case: true, ignore_accents: false };
+        match execute(&mut tasks, None, &Command::Inbox(folded)).unwrap() {
+            (_, Output::Inbox(inbox)) => assert_eq!(vec![2, 1], inbox.iter().map(|t| t.id()).collect::<Vec<_>>()),
+            (_, output) => panic!("Expected the inbox, got {:?}", output),
+        }
+        match execute(&mut tasks, None, &Command::Stale { days: 14 }).unwrap() {
+            (_, Output::Stale { tasks, open_subtasks }) => {
+                assert_eq!(vec![2, 3], tasks.iter().map(|t| t.id()).collect::<Vec<_>>());
+                assert_eq!(vec![3], open_subtasks);
+            }
+            (_, output) => panic!("Expected stale tasks, got {:?}", output),
+        }
+
+        let busy = BusyTime(vec![Busy { starts_at: now, ends_at: now, summary: String::from("Standup") }]);
+        let local = LocalState { busy: busy.clone(), ..LocalState::default() };
+        let dates = DateContext::default();
+        let (effects, output) = execute_with(&mut tasks, None, &dates, &local, &Command::Unplanned).unwrap();
+        assert_eq!(vec![CommandEffect::Read], effects);
+        match output {
+            Output::Unplanned { tasks, busy: planned_around } => {
+                assert_eq!(vec![3, 1, 2, 4], tasks.iter().map(|t| t.id()).collect::<Vec<_>>());
+                assert_eq!(busy, planned_around);
+            }
+            output => panic!("Expected unplanned tasks, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn focus_stats_since_monday() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("write", 1);
+        let now = Utc::now();
+        let mut sessions = Sessions::default();
+        sessions.start(1, now - chrono::Duration::minutes(30));
+        sessions.start(7, now - chrono::Duration::minutes(10));
+        sessions.end(now);
+
+        let local = LocalState { sessions, ..LocalState::default() };
+        let dates = DateContext::default();
+        let (effects, output) = execute_with(&mut tasks, None, &dates, &local, &Command::FocusStats).unwrap();
+        assert_eq!(vec![CommandEffect::Read], effects);
+        match output {
+            Output::Focus { since, stats, tasks } => {
+                assert_eq!(chrono::Weekday::Mon, since.weekday());
+                assert_eq!(2, stats.sessions);
+                assert_eq!(vec![1], tasks.iter().map(|t| t.id()).collect::<Vec<_>>());
+            }
+            output => panic!("Expected focus statistics, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn export_documents() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("write", 1);
+        tasks.add_task("test", 2);
+        tasks.get_mut(2).unwrap().add_note("Flaky on CI").unwrap();
+
+        let query = Query::new(ListFilter::All);
+        let export = Command::Export { query, format: ExportFormat::Csv, policy: SortPolicy::DueFirst };
+        match execute(&mut tasks, None, &export).unwrap() {
+            (_, Output::Document { text, contents }) => {
+                assert_eq!(3, text.lines().count());
+                assert_eq!("2 tasks", contents);
+            }
+            (_, output) => panic!("Expected a document, got {:?}", output),
+        }
+        match execute(&mut tasks, Some(2), &Command::ExportNotes(None)).unwrap() {
+            (_, Output::Document { text, contents }) => {
+                assert_eq!(true, text.contains("Flaky on CI"));
+                assert_eq!("the notes of task 2", contents);
+            }
+            (_, output) => panic!("Expected a document, got {:?}", output),
+        }
+        assert!(execute(&mut tasks, None, &Command::ExportNotes(Some(9))).is_err());
+
+        match execute(&mut tasks, None, &Command::Pick { policy: SortPolicy::PriorityFirst }).unwrap() {
+            (_, Output::Picks(picks)) => assert_eq!(vec![2, 1], picks.iter().map(|t| t.id()).collect::<Vec<_>>()),
+            (_, output) => panic!("Expected tasks to pick from, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn delete_and_restore() {
+        let mut tasks = TaskList::new();
+        for name in &["release", "tag", "announce"] {
+            tasks.add_task(name, 1);
+        }
+        tasks.get_mut(2).unwrap().set_parent(Some(1));
+        let delete = |id| Command::Delete { id: Some(id), orphans: Orphans::Orphan };
+
+        let before = tasks.clone();
+        let (effects, output) = execute(&mut tasks, Some(1), &delete(1)).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::DeleteTask(1), CommandEffect::CheckinTask], effects);
+        match output {
+            Output::Message(msg) => {
+                assert_eq!("Task 1 was moved to the trash, its subtasks 2 no longer have a parent", msg)
+            }
+            output => panic!("Expected a message, got {:?}", output),
+        }
+        assert_eq!(None, tasks.get(2).unwrap().parent());
+        assert_eq!(true, plan(&effects, &before, &tasks, &DateContext::default()).destructive);
+        assert!(execute(&mut tasks, None, &delete(1)).is_err());
+
+        let trashed = tasks.removed(1).unwrap().clone();
+        let (effects, _) = execute(&mut tasks, None, &Command::Restore(trashed.clone())).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::RestoreTask(1)], effects);
+        assert_eq!("release", tasks.get(1).unwrap().name());
+        assert!(execute(&mut tasks, None, &Command::Restore(trashed)).is_err());
+    }
+
+    #[test]
+    fn start_and_block() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("deploy", 1);
+        tasks.add_task("done", 1);
+        tasks.close_task(2, false).unwrap();
+
+        execute(&mut tasks, Some(1), &Command::Start(None)).unwrap();
+        assert_eq!(Status::InProgress, tasks.get(1).unwrap().status());
+
+        let block = |id| Command::Block { id: Some(id), reason: Some(String::from("waiting on #2")) };
+        match execute(&mut tasks, None, &block(1)).unwrap() {
+            (_, Output::Message(msg)) => assert_eq!("Task 1 was blocked", msg),
+            (_, output) => panic!("Expected a message, got {:?}", output),
+        }
+        let task = tasks.get(1).unwrap();
+        assert_eq!(Status::Blocked, task.status());
+        assert_eq!("Blocked: waiting on #2", task.notes()[0].note());
+        assert_eq!(1, Summary::of(&tasks, None, Utc::now()).blocked);
+
+        assert!(execute(&mut tasks, None, &block(2)).is_err());
+        assert!(execute(&mut tasks, None, &Command::Start(Some(3))).is_err());
+    }
+
+    #[test]
+    fn roulette() {
+        let mut tasks = TaskList::new();
+        for (name, priority) in &[("low", 0), ("high", 9), ("done", 9)] {
+            tasks.add_task(name, *priority);
+        }
+        tasks.close_task(3, false).unwrap();
+        let picks: Vec<u32> = (0..200)
+            .map(|seed| match handle_roulette(&tasks, &Query::new(ListFilter::All), seed).unwrap() {
+                (_, Output::Picked(task)) => task.id(),
+                (_, output) => panic!("Expected a picked task, got {:?}", output),
+            })
+            .collect();
+        let count = |id| picks.iter().filter(|p| **p == id).count();
+        assert_eq!(0, count(3));
+        assert!(count(1) > 0 && count(2) > count(1) * 3, "picked {} and {}", count(1), count(2));
+
+        tasks.close_task(1, false).unwrap();
+        tasks.close_task(2, false).unwrap();
+        assert!(handle_roulette(&tasks, &Query::new(ListFilter::Open), 1).is_err());
+    }
+
+    #[test]
+    fn review() {
+        let mut tasks = TaskList::new();
+        for name in &["keep", "bump", "close", "delete"] {
+            tasks.add_task(name, 1);
+        }
+        let now = Utc::now();
+        let decisions = [(1, Review::Keep), (2, Review::Reprioritize(4)), (3, Review::Close), (4, Review::Delete)];
+        let (effects, _) = handle_review(&mut tasks, &decisions, now).unwrap();
+        assert_eq!(vec![CommandEffect::Write, CommandEffect::DeleteTask(4)], effects);
+        assert_eq!(Some(now), tasks.get(1).unwrap().reviewed_at());
+        assert_eq!(4, tasks.get(2).unwrap().priority());
+        assert_eq!(Status::Closed, tasks.get(3).unwrap().status());
+        assert_eq!(None, tasks.get(4));
+
+        let before = tasks.clone();
+        assert!(handle_review(&mut tasks, &[(1, Review::Reprioritize(9)), (7, Review::Keep)], now).is_err());
+        assert_eq!(before.get(1), tasks.get(1));
+    }
+
+    #[test]
+    fn depend_and_graph() {
+        let mut tasks = TaskList::new();
+        for name in &["release", "write notes", "tag", "draft"] {
+            tasks.add_task(name, 1);
+        }
+        let depend = |id, on: &[u32]| Command::Depend { id: Some(id), on: on.to_vec() };
+        execute(&mut tasks, None, &depend(1, &[2, 3])).unwrap();
+        execute(&mut tasks, None, &depend(2, &[4])).unwrap();
+        assert!(execute(&mut tasks, None, &depend(4, &[3, 1])).is_err());
+        assert_eq!(true, tasks.get(4).unwrap().depends_on().is_empty());
+
+        match execute(&mut tasks, None, &Command::Graph(None)).unwrap() {
+            (_, Output::Graph(rows)) => {
+                let rows: Vec<(usize, u32)> = rows.iter().map(|(depth, t)| (*depth, t.id())).collect();
+                assert_eq!(vec![(0, 1), (1, 2), (2, 4), (1, 3)], rows);
+            }
+            (_, output) => panic!("Expected a graph, got {:?}", output),
+        }
+
+        let close = Command::Close { id: Some(2), note: None, follow_up: None, cascade: false };
+        match execute(&mut tasks, None, &close).unwrap() {
+            (_, Output::Message(msg)) => assert_eq!("Task 2 was closed, warning: open tasks 1 depend on it", msg),
+            (_, output) => panic!("Expected a message, got {:?}", output),
+        }
+    }
+
+    #[test]
+    fn tags_bring_the_tags_they_imply() {
+        let mut tasks = TaskList::new();
+        tasks.set_implications(vec![(String::from("frontend"), vec![String::from("ui")])].into_iter().collect());
+        let add = Command::Add {
+            name: "fix the header".into(),
+            priority: 1,
+            note: None,
+            tags: vec!["frontend".into()],
+            due_at: None,
+            parent: None,
+        };
+        execute(&mut tasks, None, &add).unwrap();
+        assert_eq!(vec!["frontend", "ui"], tasks.get(1).unwrap().tags());
+
+        tasks.add_task("fix the footer", 1);
+        let edits = Edits { add_tags: vec!["frontend".into()], ..Edits::default() };
+        execute(&mut tasks, None, &Command::Edit { id: Some(2), edits }).unwrap();
+        assert_eq!(vec!["frontend", "ui"], tasks.get(2).unwrap().tags());
+    }
+
+    #[test]
+    fn invalid_edit_changes_nothing() {
+        let mut tasks = TaskList::new();
+        tasks.add_task("test", 1);
+
+        let edits = Edits { name: Some("".into()), priority: Some(3), ..Edits::default() };
+        assert!(execute(&mut tasks, None, &Command::Edit { id: Some(1), edits }).is_err());
+        assert_eq!("test", tasks.get(1).unwrap().name());
+        assert_eq!(1, tasks.get(1).unwrap().priority());
+    }
+
+    #[test]
+    fn summary_counts() {
+        let now = Utc::now();
+        let mut tasks = TaskList::new();
+        for name in &["a", "b", "c", "d"] {
+            tasks.add_task(name, 1);
+        }
+        tasks.get_mut(1).unwrap().set_due_at(Some(now + chrono::Duration::days(2)));
+        tasks.get_mut(2).unwrap().set_due_at(Some(now - chrono::Duration::days(1)));
+        tasks.get_mut(3).unwrap().set_due_at(Some(now + chrono::Duration::days(30)));
+        tasks.close_task(4, false).unwrap();
+
+        let summary = Summary::of(&tasks, Some(1), now);
+        assert_eq!(
+            Summary { open: 3, closed: 1, in_progress: 0, blocked: 0, due_this_week: 2, checked_out: Some(1) },
+            summary
+        );
+    }
+}
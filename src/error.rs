@@ -0,0 +1,117 @@
+/*!
+ * The errors tisk fails with.  Every fallible function in this library
+ * returns a `TiskError` so that a frontend, e.g. the command line or the C
+ * interface, can tell failures apart and describe them in its own way.
+ */
+
+/// Why something tisk was asked to do failed.
+#[derive(Debug)]
+pub enum TiskError {
+    /// There is no tisk project where one was looked for, the message says
+    /// where that was.
+    NotInitialized(String),
+
+    /// There is no task with this ID.
+    TaskNotFound(u32),
+
+    /// A command which applies to the checked out task was given no ID,
+    /// and no task is checked out.
+    NoTask,
+
+    /// A value, e.g. an argument or a task being saved, is not valid or a
+    /// command cannot be carried out with it.
+    ParseError(String),
+
+    /// The project is not in a state the command can be carried out in,
+    /// e.g. the task is closed or the ID is already used.
+    Conflict(String),
+
+    /// A program tisk ran, e.g. curl or the user's editor, failed while
+    /// doing `context`, which says how it failed.
+    External { context: String },
+
+    /// Reading or writing the project failed while doing `context`, e.g.
+    /// `Failed to read tasks`.
+    Io { context: String, source: std::io::Error },
+
+    /// A file could not be read or written in its format, e.g. YAML.
+    Serde(String),
+}
+
+pub type Result<T> = std::result::Result<T, TiskError>;
+
+impl TiskError {
+    /// An I/O failure while doing `context`.
+    pub fn io(context: &str, source: std::io::Error) -> TiskError {
+        TiskError::Io { context: String::from(context), source }
+    }
+
+    /// A short name for the kind of failure, e.g. `task_not_found`, which
+    /// programs can match on.
+    pub fn code(&self) -> &'static str {
+        match self {
+            TiskError::NotInitialized(_) => "not_initialized",
+            TiskError::TaskNotFound(_) => "task_not_found",
+            TiskError::NoTask => "no_task",
+            TiskError::ParseError(_) => "invalid_argument",
+            TiskError::Conflict(_) => "conflict",
+            TiskError::External { .. } => "external",
+            TiskError::Io { .. } => "io",
+            TiskError::Serde(_) => "serde",
+        }
+    }
+
+    /// The task the failure is about, if it is about one.
+    pub fn task_id(&self) -> Option<u32> {
+        match self {
+            TiskError::TaskNotFound(id) => Some(*id),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TiskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TiskError::NotInitialized(msg)
+            | TiskError::ParseError(msg)
+            | TiskError::Conflict(msg)
+            | TiskError::External { context: msg }
+            | TiskError::Serde(msg) => write!(f, "{}", msg),
+            TiskError::TaskNotFound(id) => write!(f, "Could not find task with ID {}", id),
+            TiskError::NoTask => write!(f, "No ID provided and no task checked out"),
+            TiskError::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for TiskError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TiskError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describe() {
+        let missing = TiskError::TaskNotFound(7);
+        assert_eq!("Could not find task with ID 7", missing.to_string());
+        assert_eq!(("task_not_found", Some(7)), (missing.code(), missing.task_id()));
+
+        let io = TiskError::io("Failed to read tasks", std::io::Error::other("denied"));
+        assert_eq!("Failed to read tasks: denied", io.to_string());
+        assert_eq!("io", io.code());
+        assert!(std::error::Error::source(&io).is_some());
+        assert_eq!("invalid_argument", TiskError::ParseError(String::from("Task 4 must have a name")).code());
+        assert_eq!(("no_task", None), (TiskError::NoTask.code(), TiskError::NoTask.task_id()));
+        let curl = TiskError::External { context: String::from("Posting a notification failed, curl exited with 7") };
+        assert_eq!("Posting a notification failed, curl exited with 7", curl.to_string());
+        assert_eq!("external", curl.code());
+    }
+}
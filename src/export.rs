@@ -0,0 +1,200 @@
+/*!
+ * Exports a project's tasks as a document to be read outside of tisk, e.g.
+ * a Markdown status report to paste into a wiki or a pull request.  Unlike
+ * the tables `tisk list` prints, an export has the tasks a `Query` selects,
+ * e.g. every task, open and closed, along with their notes, or as CSV how
+ * many notes they have.
+ */
+use crate::dates::DateContext;
+use crate::error::TiskError;
+use crate::query::Query;
+use crate::tasks::{SortPolicy, Status, Task, TaskList};
+use chrono::{DateTime, SecondsFormat, Utc};
+
+/// The formats `tisk export` writes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Markdown,
+    Csv,
+}
+
+impl std::str::FromStr for ExportFormat {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<ExportFormat, TiskError> {
+        match s {
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(TiskError::ParseError(format!("Invalid export format: {}, expected markdown or csv", s))),
+        }
+    }
+}
+
+/// The tasks in `tasks` which `query` selects, in the format `format`.  In
+/// Markdown open tasks are in the order `policy` lists them in, and times
+/// are shown as `dates` says.
+pub fn export(
+    tasks: &TaskList,
+    query: &Query,
+    format: ExportFormat,
+    policy: SortPolicy,
+    dates: &DateContext,
+) -> String {
+    let selected = query.select(tasks);
+    match format {
+        ExportFormat::Markdown => to_markdown(&selected, policy, dates),
+        ExportFormat::Csv => to_csv(&selected),
+    }
+}
+
+/// `tasks` as CSV, one record per task in the order of their IDs, e.g. to
+/// open in a spreadsheet.  Times are RFC 3339 in UTC, as tasks store them,
+/// and `closed_at` is empty for an open task.
+pub fn to_csv(tasks: &[&Task]) -> String {
+    let time = |time: DateTime<Utc>| time.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let mut sorted = tasks.to_vec();
+    sorted.sort_by_key(|t| t.id());
+    let records: Vec<_> = sorted
+        .into_iter()
+        .map(|task| {
+            vec![
+                task.id().to_string(),
+                String::from(task.name()),
+                task.status().to_string(),
+                task.priority().to_string(),
+                time(task.created_at()),
+                task.closed_at().map(time).unwrap_or_default(),
+                task.notes().len().to_string(),
+            ]
+        })
+        .collect();
+    let labels = ["id", "name", "status", "priority", "created_at", "closed_at", "notes"];
+    let mut csv = String::new();
+    crate::table::write_csv(&mut csv, &labels, &records).expect("Writing to a String cannot fail");
+    csv
+}
+
+/// `tasks` as a Markdown document, with a checklist of the open tasks, in
+/// the order `policy` lists them in, followed by the closed tasks, most
+/// recently closed first.  Each task's notes are nested bullets under it.
+pub fn to_markdown(tasks: &[&Task], policy: SortPolicy, dates: &DateContext) -> String {
+    let (mut open, mut closed): (Vec<&Task>, Vec<&Task>) = tasks.iter().partition(|t| t.status().is_open());
+    policy.sort(&mut open, dates.zone);
+    closed.sort_by(|a, b| b.closed_at().cmp(&a.closed_at()).then(a.id().cmp(&b.id())));
+
+    let mut md = String::from("# Tasks\n");
+    for (heading, tasks) in [("Open", open), ("Closed", closed)] {
+        md.push_str(&format!("\n## {} ({})\n\n", heading, tasks.len()));
+        if tasks.is_empty() {
+            md.push_str(&format!("There are no {} tasks.\n", heading.to_lowercase()));
+        }
+        for task in tasks {
+            push_task(&mut md, task, dates);
+        }
+    }
+    md
+}
+
+/// Adds `task` to `md` as a checklist item, with its notes, oldest first,
+/// as bullets under it.
+fn push_task(md: &mut String, task: &Task, dates: &DateContext) {
+    let checkbox = if task.status().is_open() { ' ' } else { 'x' };
+    let mut details = vec![format!("priority {}", task.priority())];
+    if let Status::InProgress | Status::Blocked = task.status() {
+        details.push(task.status().to_string());
+    }
+    if let Some(due_at) = task.due_at() {
+        details.push(format!("due {}", dates.format_due(due_at)));
+    }
+    if let Some(closed_at) = task.closed_at() {
+        details.push(format!("closed {}", dates.format_time(closed_at)));
+    }
+    md.push_str(&format!("- [{}] #{} {} ({})\n", checkbox, task.id(), task.name(), details.join(", ")));
+    for note in task.notes() {
+        // The lines after the first are indented to stay within the note's bullet
+        let text = note.note().trim_end().replace('\n', "\n    ");
+        md.push_str(&format!("  - {}: {}\n", dates.format_time(note.created_at()), text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ListFilter;
+    use chrono::TimeZone;
+
+    #[test]
+    fn export_markdown() {
+        let dates = DateContext::default();
+        let mut tasks = TaskList::new();
+        let docs = tasks.add_task("Write docs", 1);
+        let login = tasks.add_task("Fix login", 4);
+        tasks.add_task("Old idea", 2);
+        tasks.get_mut(login).unwrap().add_note("Only on Safari\nand only on Fridays").unwrap();
+        tasks.get_mut(login).unwrap().set_status(Status::InProgress);
+        tasks.get_mut(docs).unwrap().close();
+
+        let all = Query::new(ListFilter::All);
+        let md = export(&tasks, &all, "md".parse().unwrap(), SortPolicy::PriorityFirst, &dates);
+        let lines: Vec<_> = md.lines().collect();
+        assert_eq!(vec!["# Tasks", "", "## Open (2)", ""], lines[..4]);
+        assert_eq!("- [ ] #2 Fix login (priority 4, in progress)", lines[4]);
+        assert_eq!(true, lines[5].starts_with("  - ") && lines[5].ends_with(": Only on Safari"));
+        assert_eq!("    and only on Fridays", lines[6]);
+        assert_eq!("- [ ] #3 Old idea (priority 2)", lines[7]);
+        assert_eq!("## Closed (1)", lines[9]);
+        assert_eq!(true, lines[11].starts_with("- [x] #1 Write docs (priority 1, closed "));
+
+        assert!(to_markdown(&[], SortPolicy::DueFirst, &dates).contains("There are no closed tasks.\n"));
+        assert_eq!(true, "pdf".parse::<ExportFormat>().is_err());
+    }
+
+    #[test]
+    fn export_markdown_selected() {
+        let dates = DateContext::default();
+        let mut tasks = TaskList::new();
+        let docs = tasks.add_task("Write docs", 1);
+        let login = tasks.add_task("Fix login", 4);
+        tasks.add_task("Old idea", 2);
+        tasks.get_mut(docs).unwrap().add_tag("sprint-4");
+        tasks.get_mut(login).unwrap().add_tag("sprint-4");
+        tasks.get_mut(docs).unwrap().close();
+
+        let mut sprint = Query::new(ListFilter::All);
+        sprint.conditions.push("tag=sprint-4".parse().unwrap());
+        let md = export(&tasks, &sprint, ExportFormat::Markdown, SortPolicy::PriorityFirst, &dates);
+        assert_eq!(true, md.contains("## Open (1)\n\n- [ ] #2 Fix login (priority 4)\n"));
+        assert_eq!(true, md.contains("## Closed (1)\n\n- [x] #1 Write docs"));
+        assert_eq!(false, md.contains("Old idea"));
+    }
+
+    #[test]
+    fn export_csv() {
+        let dates = DateContext::default();
+        let mut tasks = TaskList::new();
+        let docs = tasks.add_task("Write \"docs\", then ship", 1);
+        tasks.add_task("Fix login", 4);
+        tasks.get_mut(docs).unwrap().add_note("Started").unwrap();
+        tasks.get_mut(docs).unwrap().close_at(Utc.ymd(2020, 6, 5).and_hms(17, 0, 0));
+        tasks.get_mut(docs).unwrap().set_created_at(Utc.ymd(2020, 6, 1).and_hms(9, 0, 0));
+
+        let all = Query::new(ListFilter::All);
+        let csv = export(&tasks, &all, "csv".parse().unwrap(), SortPolicy::DueFirst, &dates);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("id,name,status,priority,created_at,closed_at,notes", lines[0]);
+        assert_eq!(
+            "1,\"Write \"\"docs\"\", then ship\",closed,1,2020-06-01T09:00:00Z,2020-06-05T17:00:00Z,1",
+            lines[1]
+        );
+        assert_eq!(true, lines[2].starts_with("2,Fix login,open,4,") && lines[2].ends_with("Z,,0"));
+
+        let mut open = Query::new(ListFilter::Open);
+        open.conditions.push("priority=4".parse().unwrap());
+        let csv = export(&tasks, &open, ExportFormat::Csv, SortPolicy::DueFirst, &dates);
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(2, lines.len());
+        assert_eq!(true, lines[1].starts_with("2,Fix login,"));
+        assert_eq!(1, to_csv(&[]).lines().count());
+    }
+}
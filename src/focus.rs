@@ -0,0 +1,200 @@
+/*!
+ * Focus sessions: the time from when a task is checked out until it is
+ * checked in, or another task is checked out.  Sessions are kept in the
+ * `sessions.yaml` file in the project's local directory, since they are
+ * the history of one checkout, and are summarized by `tisk focus-stats`.
+ */
+use crate::dates::Zone;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+/// The name of the file, inside a project's local directory, which sessions are kept in.
+pub const SESSIONS_FILE: &str = "sessions.yaml";
+
+/// The time spent on one checked out task.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub task: u32,
+
+    #[serde(with = "crate::dates::stored")]
+    pub started_at: DateTime<Utc>,
+
+    /// When the task was checked in, `None` while it is still checked out.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl Session {
+    /// How long the session lasted, or has lasted so far if it is ongoing.
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        self.ended_at.unwrap_or(now) - self.started_at
+    }
+}
+
+/// Every focus session in a project, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Sessions(pub Vec<Session>);
+
+impl Sessions {
+    /// Starts a session on task `id`, ending the ongoing session if it is
+    /// on another task.
+    pub fn start(&mut self, id: u32, now: DateTime<Utc>) {
+        match self.0.last() {
+            Some(last) if last.ended_at.is_none() && last.task == id => (),
+            _ => {
+                self.end(now);
+                self.0.push(Session { task: id, started_at: now, ended_at: None });
+            }
+        }
+    }
+
+    /// Ends the ongoing session, if there is one.
+    pub fn end(&mut self, now: DateTime<Utc>) {
+        if let Some(last) = self.0.last_mut().filter(|last| last.ended_at.is_none()) {
+            last.ended_at = Some(now);
+        }
+    }
+
+    /// Summarizes the sessions which started at or after `since`.  Streaks
+    /// are counted over every session, by the days of `zone`.
+    pub fn stats(&self, since: DateTime<Utc>, now: DateTime<Utc>, zone: Zone) -> FocusStats {
+        let recent: Vec<&Session> = self.0.iter().filter(|s| s.started_at >= since).collect();
+        let total = recent.iter().fold(Duration::zero(), |total, s| total + s.duration(now));
+        let average = match recent.len() {
+            0 => None,
+            n => Some(total / n as i32),
+        };
+
+        let mut by_task: Vec<(u32, Duration)> = vec![];
+        for session in &recent {
+            match by_task.iter_mut().find(|(task, _)| *task == session.task) {
+                Some((_, worked)) => *worked = *worked + session.duration(now),
+                None => by_task.push((session.task, session.duration(now))),
+            }
+        }
+        by_task.sort_by_key(|&(_, worked)| std::cmp::Reverse(worked));
+
+        let (longest_streak, current_streak) = streaks(&self.0, zone.naive(now).date(), zone);
+        FocusStats {
+            sessions: recent.len(),
+            total,
+            average,
+            longest_streak,
+            current_streak,
+            most_worked: by_task,
+        }
+    }
+
+    /// Reads the sessions of the project whose directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn read(task_path: &std::path::Path) -> std::io::Result<Sessions> {
+        match std::fs::read_to_string(crate::io::read_local_path(task_path, SESSIONS_FILE)) {
+            Ok(s) => Sessions::from_yaml(&s),
+            Err(err) if err.kind() == IoErrorKind::NotFound => Ok(Sessions::default()),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Parses sessions from the contents of a `sessions.yaml` file.
+    pub fn from_yaml(s: &str) -> std::io::Result<Sessions> {
+        serde_yaml::from_str::<Sessions>(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// The contents of the `sessions.yaml` file for these sessions.
+    pub fn to_yaml(&self) -> std::io::Result<String> {
+        serde_yaml::to_string(self).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// Writes these sessions to the project whose directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn write(&self, task_path: &std::path::Path) -> std::io::Result<()> {
+        crate::io::write_local(task_path, SESSIONS_FILE, &self.to_yaml()?).map(|_| ())
+    }
+}
+
+/// A summary of focus sessions, see `Sessions::stats`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusStats {
+    pub sessions: usize,
+    pub total: Duration,
+
+    /// The average length of a session, `None` if there were no sessions.
+    pub average: Option<Duration>,
+
+    /// The most days in a row on which a session was started.
+    pub longest_streak: usize,
+
+    /// The days in a row, up to today or yesterday, on which a session was started.
+    pub current_streak: usize,
+
+    /// The time spent on each task, the most worked task first.
+    pub most_worked: Vec<(u32, Duration)>,
+}
+
+/// The longest and the current run of days, in `zone`, on which a session
+/// was started.
+fn streaks(sessions: &[Session], today: NaiveDate, zone: Zone) -> (usize, usize) {
+    let days: std::collections::BTreeSet<NaiveDate> =
+        sessions.iter().map(|s| zone.naive(s.started_at).date()).collect();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in &days {
+        run = match previous {
+            Some(previous) if day - previous == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(day);
+    }
+
+    let current = match previous {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+    (longest, current)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Local, TimeZone};
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Local.ymd(2020, 6, day).and_hms(hour, 0, 0).with_timezone(&Utc)
+    }
+
+    #[test]
+    fn checkout_starts_and_ends_sessions() {
+        let mut sessions = Sessions::default();
+        sessions.start(1, at(1, 9));
+        sessions.start(1, at(1, 10));
+        sessions.start(2, at(1, 11));
+        sessions.end(at(1, 12));
+        sessions.end(at(1, 13));
+
+        assert_eq!(2, sessions.0.len());
+        assert_eq!(Duration::hours(2), sessions.0[0].duration(at(1, 13)));
+        assert_eq!(Some(at(1, 12)), sessions.0[1].ended_at);
+    }
+
+    #[test]
+    fn stats() {
+        let mut sessions = Sessions::default();
+        for &(task, day, start, end) in &[(1, 1, 9, 10), (1, 3, 9, 10), (1, 4, 9, 11), (2, 5, 9, 12), (1, 8, 9, 10)] {
+            sessions.start(task, at(day, start));
+            sessions.end(at(day, end));
+        }
+        sessions.start(2, at(9, 9));
+
+        let stats = sessions.stats(at(4, 0), at(9, 10), Zone::Local);
+        assert_eq!(4, stats.sessions);
+        assert_eq!(Duration::hours(7), stats.total);
+        assert_eq!(Some(Duration::minutes(105)), stats.average);
+        assert_eq!(vec![(2, Duration::hours(4)), (1, Duration::hours(3))], stats.most_worked);
+        assert_eq!(3, stats.longest_streak);
+        assert_eq!(2, stats.current_streak);
+    }
+}
@@ -0,0 +1,347 @@
+/*!
+ * Help for the command line: the examples shown by `tisk help <command>`
+ * and suggestions for mistyped commands.  This sits between `main` and
+ * clap, clap still parses the arguments and prints the generated help.
+ */
+use clap::{App, ArgMatches, ErrorKind};
+
+/// Every subcommand along with examples of how it is used.
+pub const COMMANDS: &[(&str, &str)] = &[
+    (
+        "add",
+        "EXAMPLES:
+    tisk add \"Write the release notes\"
+    tisk add \"Fix the login bug\" --priority 5 --tag bug
+    tisk add \"Renew the domain\" --due 2024-03-01
+    tisk add \"Send the report\" --due \"friday 17:00\"
+    tisk add \"Write the changelog\" --parent 7
+    tisk add \"Call the printer company\" --note \"Ask about the warranty\"",
+    ),
+    (
+        "close",
+        "EXAMPLES:
+    tisk close 7
+    tisk close --note \"Fixed in the last release\"
+    tisk close 7 --follow-up \"Remove the old importer\"
+    tisk close 7 --cascade",
+    ),
+    (
+        "start",
+        "EXAMPLES:
+    tisk start 7
+    tisk start",
+    ),
+    (
+        "block",
+        "EXAMPLES:
+    tisk block 7 --reason \"Waiting on #4\"
+    tisk list --blocked",
+    ),
+    (
+        "checkout",
+        "EXAMPLES:
+    tisk checkout 7
+    tisk checkout --add \"Investigate the slow build\" --priority 3",
+    ),
+    (
+        "checkin",
+        "EXAMPLES:
+    tisk checkin",
+    ),
+    (
+        "edit",
+        "EXAMPLES:
+    tisk edit 7 --priority 2
+    tisk edit 7 --name \"Fix the logout bug\" --tag bug --untag ui
+    tisk edit 7 --due 2020-06-01 --assignee sam
+    tisk edit 7 --due friday
+    tisk edit 7 --expires +3
+    tisk edit 7 --editor",
+    ),
+    (
+        "backlinks",
+        "EXAMPLES:
+    tisk note 4 \"Waiting on #2\"
+    tisk backlinks 2",
+    ),
+    (
+        "delete",
+        "EXAMPLES:
+    tisk delete 7
+    tisk delete 7 --subtasks cascade",
+    ),
+    (
+        "restore",
+        "EXAMPLES:
+    tisk restore 7",
+    ),
+    (
+        "depend",
+        "EXAMPLES:
+    tisk depend 7 --on 4
+    tisk depend --on 4,5",
+    ),
+    (
+        "show",
+        "EXAMPLES:
+    tisk show 7
+    tisk show
+    tisk show 7 --format json",
+    ),
+    (
+        "time",
+        "EXAMPLES:
+    tisk time 7
+    tisk time
+    tisk list --all",
+    ),
+    (
+        "remind",
+        "EXAMPLES:
+    tisk remind 7 --at \"tomorrow 9am\"
+    tisk remind --at \"friday 14:00\"",
+    ),
+    (
+        "compact",
+        "EXAMPLES:
+    tisk compact
+    tisk archive --older-than 2w",
+    ),
+    (
+        "notify",
+        "EXAMPLES:
+    tisk notify
+    */5 * * * * cd ~/work && tisk notify",
+    ),
+    (
+        "graph",
+        "EXAMPLES:
+    tisk graph
+    tisk graph 7
+    tisk list --blocked-by 4",
+    ),
+    (
+        "tag",
+        "EXAMPLES:
+    tisk tag 7 backend
+    tisk tag 7 bug sprint-4",
+    ),
+    (
+        "untag",
+        "EXAMPLES:
+    tisk untag 7 sprint-4",
+    ),
+    (
+        "tags",
+        "EXAMPLES:
+    tisk tags
+    tisk list --tag backend",
+    ),
+    (
+        "pick",
+        "EXAMPLES:
+    tisk pick | fzf | tisk pick --then checkout
+    tisk pick | fzf --preview 'tisk show {1}' | tisk pick --then close",
+    ),
+    (
+        "roulette",
+        "EXAMPLES:
+    tisk roulette
+    tisk roulette --tag chores",
+    ),
+    (
+        "export",
+        "EXAMPLES:
+    tisk export
+    tisk export --format markdown --out status.md
+    tisk export --tag sprint-4 --since 2021-03-01
+    tisk export --format csv --filter assignee=sam --out tasks.csv",
+    ),
+    (
+        "import",
+        "EXAMPLES:
+    tisk --dry-run import backup.yaml
+    tisk import backup.yaml --on-conflict skip
+    tisk import other-project.yaml --ids renumber
+    tisk import --from ics --as-busy calendar.ics
+    tisk import --from jira jira-export.csv
+    task export > export.json && tisk import --from taskwarrior export.json
+    JIRA_TOKEN=... tisk import --jql \"project = ABC\" --jira-url https://example.atlassian.net",
+    ),
+    (
+        "archive",
+        "EXAMPLES:
+    tisk archive
+    tisk archive --older-than 2w
+    tisk list --archived",
+    ),
+    (
+        "expire",
+        "EXAMPLES:
+    tisk expire",
+    ),
+    (
+        "focus-stats",
+        "EXAMPLES:
+    tisk focus-stats",
+    ),
+    (
+        "in",
+        "EXAMPLES:
+    tisk in \"Look into the flaky upload test\"",
+    ),
+    (
+        "triage",
+        "EXAMPLES:
+    tisk triage",
+    ),
+    (
+        "review",
+        "EXAMPLES:
+    tisk review
+    tisk review --days 30",
+    ),
+    (
+        "plan",
+        "EXAMPLES:
+    tisk plan",
+    ),
+    (
+        "note",
+        "EXAMPLES:
+    tisk note 7 \"Found the cause, it is the cache\"
+    tisk note \"A note on the checked out task\"
+    tisk note 7 --list
+    tisk note 7 --list --format json
+    tisk note --export 7 --out login-bug.md
+    tisk note --ids 3,5,9 \"Moved to the next sprint\"
+    tisk note --filter tag=sprint-4 \"Moved to the next sprint\"",
+    ),
+    (
+        "list",
+        "EXAMPLES:
+    tisk list
+    tisk list --all --sort Name
+    tisk list --nowrap
+    tisk list --no-header --no-footer | wc -l
+    tisk list --contains docs
+    tisk list --closed --format csv
+    tisk list --all --archived
+    tisk list --in-progress
+    tisk list --blocked-by 4
+    tisk list --all --tag sprint-4 --format csv
+    tisk list --filter assignee=ann --since mon
+    tisk list --all --format json",
+    ),
+    (
+        "init",
+        "EXAMPLES:
+    tisk init
+    tisk init --dir-name .todo --layout single-file --with-config
+    tisk init --global
+    tisk init --force
+    tisk init --git-ignore local
+    tisk init --name backlog",
+    ),
+    (
+        "info",
+        "EXAMPLES:
+    tisk info
+    tisk --global info",
+    ),
+    (
+        "gitignore",
+        "EXAMPLES:
+    tisk gitignore
+    tisk gitignore local",
+    ),
+    (
+        "which",
+        "EXAMPLES:
+    tisk which
+    tisk --global which",
+    ),
+    (
+        "migrate-dir",
+        "EXAMPLES:
+    tisk migrate-dir
+    tisk migrate-dir --to .todo",
+    ),
+];
+
+/// The examples for the subcommand `name`.
+pub fn examples(name: &str) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|(command, _)| *command == name)
+        .map(|(_, examples)| *examples)
+        .unwrap_or("")
+}
+
+/// The candidates which `input` may have been meant to be: those which it
+/// is the start of or, failing that, those which are only a few typos away.
+pub fn suggest<'a>(input: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    if input.is_empty() {
+        return vec![];
+    }
+
+    let prefixed: Vec<&str> = candidates.iter().copied().filter(|c| c.starts_with(input)).collect();
+    if !prefixed.is_empty() {
+        return prefixed;
+    }
+
+    let max_distance = (input.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|c| (strsim::damerau_levenshtein(input, c), *c))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close.sort();
+    close.into_iter().map(|(_, c)| c).collect()
+}
+
+/// Parses the command line with `app`.  If a subcommand is not recognized
+/// and clap has no suggestion then the subcommands it may have been meant
+/// to be are suggested, before exiting.
+pub fn get_matches<'a>(app: App<'a, '_>) -> ArgMatches<'a> {
+    let err = match app.get_matches_safe() {
+        Ok(matches) => return matches,
+        Err(err) => err,
+    };
+
+    let unrecognized = err.kind == ErrorKind::InvalidSubcommand || err.kind == ErrorKind::UnrecognizedSubcommand;
+    if !unrecognized || err.message.contains("Did you mean") {
+        err.exit();
+    }
+
+    let names: Vec<&str> = COMMANDS.iter().map(|(name, _)| *name).collect();
+    let input = err.info.as_ref().and_then(|info| info.first()).map(String::as_str).unwrap_or("");
+    let suggestions = suggest(input, &names);
+    eprintln!("{}", err.message);
+    if !suggestions.is_empty() {
+        let quoted: Vec<String> = suggestions.iter().map(|s| format!("'{}'", s)).collect();
+        eprintln!("\n\tDid you mean {}?", quoted.join(" or "));
+    }
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAMES: &[&str] = &["add", "close", "checkout", "checkin", "list"];
+
+    #[test]
+    fn suggest_prefixes() {
+        assert_eq!(vec!["checkout", "checkin"], suggest("ch", NAMES));
+        assert_eq!(vec!["list"], suggest("li", NAMES));
+    }
+
+    #[test]
+    fn suggest_typos() {
+        assert_eq!(vec!["close"], suggest("clsoe", NAMES));
+        assert_eq!(vec!["add"], suggest("dad", NAMES));
+        assert!(suggest("frobnicate", NAMES).is_empty());
+        assert!(suggest("", NAMES).is_empty());
+    }
+}
@@ -1,54 +1,48 @@
-/// Extract the text message from an `Err` and format the text 
-/// by prepending it with "Error" in red.
-#[macro_export]
-macro_rules! ferror {
-    () => {{
-        Err(format!("Unknown error"))
-    }};
-    ($($arg:tt)*) => {{
-        let msg = format!($($arg)*);
-        Err(msg)
-    }};
-}
+use crate::config;
+use crate::error::TiskError;
+use crate::meta::{Meta, DEFAULT_DIR_NAME, LEGACY_DIR_NAME, META_FILE};
+use log::debug;
 
 /// `up_search` will search for `file_name` starting in `dir` and, 
 /// if not found, each parent directory of `dir`. Returning the 
 /// canonical path of `file_name` if found and `None` if not found.
-pub fn up_search(dir: &str, file_name: &str) -> std::io::Result<Option<std::path::PathBuf>> {
-    let path = std::fs::canonicalize(dir)?;
+/// Directories, and entries, which cannot be read, e.g. because of their
+/// permissions, are skipped and the search continues with the next parent.
+pub fn up_search<P: AsRef<std::path::Path>>(dir: P, file_name: &str) -> std::io::Result<Option<std::path::PathBuf>> {
+    up_search_with(dir.as_ref(), file_name, subdirs)
+}
 
-    let mut found = None;
+/// `up_search` with the subdirectories of each directory listed by
+/// `subdirs`, which lets tests simulate unreadable directories.
+fn up_search_with<F>(dir: &std::path::Path, file_name: &str, subdirs: F) -> std::io::Result<Option<std::path::PathBuf>>
+where
+    F: Fn(&std::path::Path) -> std::io::Result<Vec<std::ffi::OsString>>,
+{
+    let path = std::fs::canonicalize(dir)?;
 
     for parent in path.ancestors() {
-        let mut files = parent.read_dir()?;
-        found = files.find(|f| {
-            let file = f.as_ref().unwrap();
-            let meta = file.metadata();
-            match meta {
-                Ok(md) => {
-                    let ty = md.file_type();
-                    if ty.is_dir() {
-                        file.file_name() == file_name
-                    } else {
-                        false
-                    }
-                }
-                Err(_) => false,
-            }
-        });
-
-        if found.is_some() {
-            break;
+        match subdirs(parent) {
+            Ok(names) if names.iter().any(|name| name == file_name) => return Ok(Some(parent.join(file_name))),
+            Ok(_) => (),
+            Err(why) => debug!("Skipping {} while searching for {}: {}", parent.display(), file_name, why),
         }
     }
 
-    match found {
-        Some(result) => match result {
-            Err(why) => Err(why),
-            Ok(v) => Ok(Some(v.path())),
-        },
-        None => Ok(None),
+    Ok(None)
+}
+
+/// The names of the directories in `dir`.  Entries which cannot be read
+/// are left out.
+fn subdirs(dir: &std::path::Path) -> std::io::Result<Vec<std::ffi::OsString>> {
+    let mut names = vec![];
+    for entry in dir.read_dir()? {
+        match entry.and_then(|entry| entry.metadata().map(|md| (entry, md))) {
+            Ok((entry, md)) if md.is_dir() => names.push(entry.file_name()),
+            Ok(_) => (),
+            Err(why) => debug!("Skipping an entry of {}: {}", dir.display(), why),
+        }
     }
+    Ok(names)
 }
 
 #[derive(Debug, PartialEq)]
@@ -61,46 +55,537 @@ pub enum InitResult {
 /// project.  If successful will return `Initialied`, if the current
 /// directory is already initialized will return `AlreadyInitialized`.
 pub fn initialize() -> std::io::Result<InitResult> {
-    match std::fs::read_dir("./.tisk") {
-        Ok(_) => Ok(InitResult::AlreadyInitialized),
-        Err(_) => match std::fs::create_dir("./.tisk") {
-            Err(why) => Err(why),
-            Ok(_) => Ok(InitResult::Initialized),
+    initialize_at(".")
+}
+
+/// Will attempt to initialize the directory `root` as a `tisk` project,
+/// just like `initialize` does for the current directory.
+pub fn initialize_at<P: AsRef<std::path::Path>>(root: P) -> std::io::Result<InitResult> {
+    initialize_with(root, &InitOptions::default())
+}
+
+/// The choices made by `tisk init`.
+#[derive(Debug, Clone, Default)]
+pub struct InitOptions {
+    /// The name of the project directory and how tasks are stored in it.
+    pub meta: Meta,
+
+    /// Write a `config.yaml` which documents every setting.
+    pub with_config: bool,
+}
+
+/// Will attempt to initialize the directory `root` as a `tisk` project
+/// using the given `options`.  The options are recorded in the project's
+/// metadata so that later commands find and store the project the same way.
+pub fn initialize_with<P: AsRef<std::path::Path>>(root: P, options: &InitOptions) -> std::io::Result<InitResult> {
+    validate_dir_name(&options.meta.dir_name)?;
+
+    let task_path = root.as_ref().join(&options.meta.dir_name);
+    if std::fs::read_dir(&task_path).is_ok() {
+        return Ok(InitResult::AlreadyInitialized);
+    }
+
+    std::fs::create_dir(&task_path)?;
+    let mut meta = options.meta.clone();
+    meta.created_at.get_or_insert_with(chrono::Utc::now);
+    if meta.name.is_none() {
+        meta.name = Some(meta.project_name(&std::fs::canonicalize(&task_path)?));
+    }
+    meta.write(&task_path)?;
+    if options.with_config {
+        write_atomic(&task_path.join("config.yaml"), config::TEMPLATE)?;
+    }
+    Ok(InitResult::Initialized)
+}
+
+/// The project directory in the nearest ancestor of `root`, found as
+/// `find_task_dir` would find it from `root` if `root` had no project of
+/// its own.  A project initialized at `root` would be nested inside it.
+pub fn enclosing_project_dir<P: AsRef<std::path::Path>>(
+    root: P,
+    boundaries: &[Boundary],
+) -> std::io::Result<Option<std::path::PathBuf>> {
+    let root = std::fs::canonicalize(root)?;
+    let home = canonical_home();
+    if boundaries.iter().any(|b| b.stops_at(&root, home.as_deref())) {
+        return Ok(None);
+    }
+    match root.parent() {
+        None => Ok(None),
+        Some(parent) => match search_project_dir(parent, boundaries)? {
+            Search::Found(path) => Ok(Some(path)),
+            Search::NotFound { .. } => Ok(None),
         },
     }
 }
 
-/// Searches for the location of the `.tisk` project directory in
-/// the current directory or any of the current directory's ancestors.
-pub fn find_task_dir() -> Result<std::path::PathBuf, String> {
-    match up_search(".", ".tisk") {
-        Err(why) => ferror!("Failure while searching for .tisk dir: {}", why),
-        Ok(path) => match path {
-            None => ferror!("Invalid tisk project, could not find .tisk dir in the current directory or any parent directory"),
-            Some(path) => Ok(path),
+/// Project directories must be hidden so that they can be found by
+/// `find_task_dir`, and must be directly inside the project's root.
+fn validate_dir_name(name: &str) -> std::io::Result<()> {
+    use std::io::{Error, ErrorKind};
+
+    if !name.starts_with('.') || name.len() < 2 || name.contains(std::path::is_separator) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("The project directory name must start with '.' and not contain a path separator: {}", name),
+        ));
+    }
+    Ok(())
+}
+
+/// Renames the project directory `task_path` to `new_name`, e.g. to move a
+/// legacy `.task` project to `.tisk`, and records the new name in the
+/// project's metadata.  Returns the new location of the project directory.
+pub fn migrate_dir(task_path: &std::path::Path, new_name: &str) -> std::io::Result<std::path::PathBuf> {
+    use std::io::{Error, ErrorKind};
+
+    validate_dir_name(new_name)?;
+    let root = task_path
+        .parent()
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "The project directory has no parent"))?;
+    let new_path = root.join(new_name);
+    if new_path.exists() {
+        return Err(Error::new(
+            ErrorKind::AlreadyExists,
+            format!("{} already exists", new_path.display()),
+        ));
+    }
+
+    let mut meta = Meta::read(task_path)?;
+    std::fs::rename(task_path, &new_path)?;
+    meta.dir_name = String::from(new_name);
+    meta.write(&new_path)?;
+    Ok(new_path)
+}
+
+/// A directory which the search for a project directory does not go above,
+/// so that a project far up the tree is not used by accident.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Boundary {
+    /// The user's home directory is the last directory searched.
+    Home,
+
+    /// A mount point is the last directory searched, so the search does
+    /// not leave the filesystem it started on.
+    Mount,
+
+    /// The root of a git repository, which holds `.git`, is the last
+    /// directory searched.
+    Git,
+}
+
+impl std::str::FromStr for Boundary {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Boundary, TiskError> {
+        match s {
+            "home" => Ok(Boundary::Home),
+            "mount" => Ok(Boundary::Mount),
+            "git" => Ok(Boundary::Git),
+            _ => Err(TiskError::ParseError(format!("Invalid search boundary: {} (expected home, mount, or git)", s))),
         }
     }
 }
 
-/// Commit that task `id` has been checked out to disk.
-pub fn commit_checkout(id: u32, path: &std::path::PathBuf) -> std::io::Result<()> {
-    use std::io::prelude::*;
+impl std::fmt::Display for Boundary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Boundary::Home => write!(f, "the home directory"),
+            Boundary::Mount => write!(f, "a mount point"),
+            Boundary::Git => write!(f, "the root of a git repository"),
+        }
+    }
+}
+
+impl Boundary {
+    /// Whether the search stops after searching `dir`, where `home` is the
+    /// user's home directory.
+    fn stops_at(&self, dir: &std::path::Path, home: Option<&std::path::Path>) -> bool {
+        match self {
+            Boundary::Home => home == Some(dir),
+            Boundary::Mount => match (dir.parent().and_then(device), device(dir)) {
+                (Some(parent), Some(dir)) => parent != dir,
+                _ => false,
+            },
+            Boundary::Git => dir.join(".git").exists(),
+        }
+    }
+}
+
+/// The ID of the device which holds `path`, if the platform has them.
+#[cfg(unix)]
+fn device(path: &std::path::Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|md| md.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// The environment variable which lists the boundaries, separated by
+/// commas, that the search for a project directory stops at.
+pub const TISK_STOP_AT_VAR: &str = "TISK_STOP_AT";
+
+/// The boundaries listed by the `TISK_STOP_AT` environment variable, none
+/// if it is not set.
+pub fn boundaries_from_env() -> Result<Vec<Boundary>, TiskError> {
+    match std::env::var(TISK_STOP_AT_VAR) {
+        Ok(list) => list.split(',').map(str::trim).filter(|b| !b.is_empty()).map(str::parse).collect(),
+        Err(std::env::VarError::NotPresent) => Ok(vec![]),
+        Err(why) => Err(TiskError::ParseError(format!("Invalid {}: {}", TISK_STOP_AT_VAR, why))),
+    }
+}
+
+/// What the search for a project directory found.
+#[derive(Debug, PartialEq)]
+enum Search {
+    Found(std::path::PathBuf),
+
+    /// No project directory was found in the directories up to and
+    /// including `last`, the search stopped at `boundary` unless it reached
+    /// the root of the filesystem.
+    NotFound { last: std::path::PathBuf, boundary: Option<Boundary> },
+}
+
+/// Finds the project directory in `dir` or the nearest of its ancestors,
+/// not going above any of the `boundaries`.  Ancestors which cannot be read
+/// are skipped.
+fn search_project_dir(dir: &std::path::Path, boundaries: &[Boundary]) -> std::io::Result<Search> {
+    search_project_dir_with(dir, boundaries, canonical_home().as_deref(), project_dir_in)
+}
+
+/// The home directory, as its canonical path so it can be compared with
+/// the directories searched.
+fn canonical_home() -> Option<std::path::PathBuf> {
+    home_dir().and_then(|home| std::fs::canonicalize(home).ok())
+}
+
+/// `search_project_dir` with the given home directory and each directory
+/// searched by `project_dir_in`, which lets tests simulate unreadable
+/// directories.
+fn search_project_dir_with<F>(
+    dir: &std::path::Path,
+    boundaries: &[Boundary],
+    home: Option<&std::path::Path>,
+    project_dir_in: F,
+) -> std::io::Result<Search>
+where
+    F: Fn(&std::path::Path) -> std::io::Result<Option<std::path::PathBuf>>,
+{
+    let path = std::fs::canonicalize(dir)?;
+
+    for parent in path.ancestors() {
+        match project_dir_in(parent) {
+            Ok(Some(found)) => return Ok(Search::Found(found)),
+            Ok(None) => (),
+            Err(why) => debug!("Skipping {} while searching for the project dir: {}", parent.display(), why),
+        }
+        if let Some(&boundary) = boundaries.iter().find(|b| b.stops_at(parent, home)) {
+            return Ok(Search::NotFound { last: parent.to_path_buf(), boundary: Some(boundary) });
+        }
+    }
+
+    let root = path.ancestors().last().unwrap_or(&path).to_path_buf();
+    Ok(Search::NotFound { last: root, boundary: None })
+}
+
+/// What of a project directory is kept out of git.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GitIgnore {
+    /// The whole project directory, for a backlog which is not versioned.
+    All,
+
+    /// Only the project's local state, so that the tasks are versioned
+    /// without the state which differs between each checkout.
+    Local,
+}
+
+impl std::str::FromStr for GitIgnore {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<GitIgnore, TiskError> {
+        match s {
+            "all" => Ok(GitIgnore::All),
+            "local" => Ok(GitIgnore::Local),
+            _ => Err(TiskError::ParseError(format!(
+                "Invalid choice of what to ignore: {} (expected all or local)",
+                s
+            ))),
+        }
+    }
+}
+
+/// The directory, inside a project directory, which holds the state local
+/// to a single checkout: the checked out task, focus sessions, and logs.
+/// It is kept apart from the tasks and config, which are shared, so that
+/// the tasks can be versioned without the local state.
+pub const LOCAL_DIR: &str = "local";
+
+/// The file, in the local directory, which holds the checked out task.
+pub const CHECKOUT_FILE: &str = ".checkout";
+
+/// The path of the local state `name` in the project directory `task_path`.
+pub fn local_path(task_path: &std::path::Path, name: &str) -> std::path::PathBuf {
+    task_path.join(LOCAL_DIR).join(name)
+}
+
+/// The path the local state `name` is read from.  Projects created by
+/// older versions of tisk keep their local state directly in the project
+/// directory, where it is read from until it is next written.
+pub fn read_local_path(task_path: &std::path::Path, name: &str) -> std::path::PathBuf {
+    let path = local_path(task_path, name);
+    let legacy = task_path.join(name);
+    if !path.exists() && legacy.exists() {
+        legacy
+    } else {
+        path
+    }
+}
+
+/// Writes the local state `name`, as `write_if_changed` does, and removes
+/// the copy an older version of tisk may have left in the project directory.
+pub fn write_local(task_path: &std::path::Path, name: &str, contents: &str) -> std::io::Result<bool> {
+    std::fs::create_dir_all(task_path.join(LOCAL_DIR))?;
+    let written = write_if_changed(&local_path(task_path, name), contents)?;
+    remove_file_if_exists(&task_path.join(name))?;
+    Ok(written)
+}
+
+/// Removes the local state `name`, wherever it is kept.
+pub fn remove_local(task_path: &std::path::Path, name: &str) -> std::io::Result<()> {
+    remove_file_if_exists(&local_path(task_path, name))?;
+    remove_file_if_exists(&task_path.join(name))
+}
+
+/// The `.gitignore` of the git repository which holds the project directory
+/// `task_path`, which need not exist yet, and the lines which `ignore` has
+/// to add to it, leaving out any which it already has.  The lines are anchored to the root of the
+/// repository.
+pub fn git_ignore_lines(task_path: &std::path::Path, ignore: GitIgnore) -> std::io::Result<(std::path::PathBuf, Vec<String>)> {
+    use std::io::{Error, ErrorKind};
+
+    // The project directory may not have been created yet
+    let parent = match task_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => std::path::Path::new("."),
+    };
+    let task_path = std::fs::canonicalize(parent)?.join(task_path.file_name().unwrap_or_default());
+    let repo = task_path
+        .ancestors()
+        .skip(1)
+        .find(|dir| Boundary::Git.stops_at(dir, None))
+        .ok_or_else(|| Error::new(ErrorKind::NotFound, "The project is not in a git repository"))?;
+
+    let dir = task_path
+        .strip_prefix(repo)
+        .unwrap_or(&task_path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<String>>()
+        .join("/");
+    let lines: Vec<String> = match ignore {
+        GitIgnore::All => vec![format!("/{}/", dir)],
+        GitIgnore::Local => vec![format!("/{}/{}/", dir, LOCAL_DIR)],
+    };
+
+    let file = repo.join(".gitignore");
+    let existing = match std::fs::read_to_string(&file) {
+        Ok(s) => s,
+        Err(err) if err.kind() == ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let missing = lines.into_iter().filter(|line| !existing.lines().any(|l| l.trim() == line)).collect();
+    Ok((file, missing))
+}
+
+/// Appends `lines` to the `.gitignore` at `file`, creating it if needed.
+pub fn append_git_ignore(file: &std::path::Path, lines: &[String]) -> std::io::Result<()> {
+    use std::io::Write;
+
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let existing = std::fs::read_to_string(file).unwrap_or_default();
+    let mut out = std::fs::OpenOptions::new().create(true).append(true).open(file)?;
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        writeln!(out)?;
+    }
+    writeln!(out, "# tisk")?;
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// Finds the project directory directly inside `parent`.  A project
+/// directory is either named `.tisk`, the legacy `.task`, or is a hidden
+/// directory with a project metadata file, which is how projects with a
+/// custom directory name are found.
+fn project_dir_in(parent: &std::path::Path) -> std::io::Result<Option<std::path::PathBuf>> {
+    for name in &[DEFAULT_DIR_NAME, LEGACY_DIR_NAME] {
+        let path = parent.join(name);
+        if path.is_dir() {
+            return Ok(Some(path));
+        }
+    }
+
+    for entry in parent.read_dir()? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(why) => {
+                debug!("Skipping an entry of {}: {}", parent.display(), why);
+                continue;
+            }
+        };
+        let hidden = entry.file_name().to_str().map(|n| n.starts_with('.')).unwrap_or(false);
+        if hidden && entry.path().join(META_FILE).is_file() {
+            return Ok(Some(entry.path()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// The current user's home directory, which holds the global project.
+pub fn home_dir() -> Option<std::path::PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|home| !home.is_empty())
+        .map(std::path::PathBuf::from)
+}
+
+/// The location of the global project directory, which lives in the user's
+/// home directory and is used for tasks which do not belong to any project.
+pub fn find_global_task_dir() -> Result<std::path::PathBuf, TiskError> {
+    let home = match home_dir() {
+        None => {
+            return Err(TiskError::NotInitialized(String::from(
+                "Could not find the home directory for the global tisk project",
+            )))
+        }
+        Some(home) => home,
+    };
+
+    match project_dir_in(&home) {
+        Err(why) => Err(TiskError::io("Failure while searching for the global .tisk dir", why)),
+        Ok(None) => Err(TiskError::NotInitialized(String::from(
+            "There is no global tisk project, run `tisk init --global` to create one",
+        ))),
+        Ok(Some(path)) => Ok(path),
+    }
+}
+
+/// The environment variable which names the project directory to use
+/// instead of searching for one.
+pub const TISK_DIR_VAR: &str = "TISK_DIR";
+
+/// Searches for the location of the `.tisk` project directory in
+/// the current directory or any of the current directory's ancestors, up
+/// to the boundaries listed by `TISK_STOP_AT`.  If the `TISK_DIR`
+/// environment variable is set then it is used instead and no search is
+/// done.
+pub fn find_task_dir() -> Result<std::path::PathBuf, TiskError> {
+    match std::env::var_os(TISK_DIR_VAR) {
+        Some(dir) if !dir.is_empty() => task_dir_at(dir),
+        _ => find_task_dir_within(".", &boundaries_from_env()?),
+    }
+}
+
+/// Uses `dir` as the project directory without searching its ancestors.
+/// `dir` may be the project directory itself or the root directory which
+/// contains it.
+pub fn task_dir_at<P: AsRef<std::path::Path>>(dir: P) -> Result<std::path::PathBuf, TiskError> {
+    let dir = dir.as_ref();
+    if !dir.is_dir() {
+        return Err(TiskError::NotInitialized(format!("The project directory {} does not exist", dir.display())));
+    }
 
-    let mut path = std::path::PathBuf::from(path);
-    path.push(".checkout");
-    let mut file = std::fs::File::create(path)?;
+    match project_dir_in(dir) {
+        Err(why) => Err(TiskError::io(&format!("Failure while reading {}", dir.display()), why)),
+        Ok(Some(path)) => Ok(path),
+        Ok(None) => Ok(dir.to_path_buf()),
+    }
+}
 
-    let s = format!("{}", id);
+/// Searches for the location of the project directory in `dir` or any of
+/// its ancestors.
+pub fn find_task_dir_from<P: AsRef<std::path::Path>>(dir: P) -> Result<std::path::PathBuf, TiskError> {
+    find_task_dir_within(dir, &[])
+}
 
-    file.write_all(s.as_bytes())
+/// Searches for the location of the project directory in `dir` or any of
+/// its ancestors, not going above any of the `boundaries`.
+pub fn find_task_dir_within<P: AsRef<std::path::Path>>(
+    dir: P,
+    boundaries: &[Boundary],
+) -> Result<std::path::PathBuf, TiskError> {
+    match search_project_dir(dir.as_ref(), boundaries) {
+        Err(why) => Err(TiskError::io("Failure while searching for .tisk dir", why)),
+        Ok(Search::Found(path)) => Ok(path),
+        Ok(Search::NotFound { boundary: None, .. }) => Err(TiskError::NotInitialized(String::from(
+            "Invalid tisk project, could not find a project dir in the current directory or any parent directory",
+        ))),
+        Ok(Search::NotFound { last, boundary: Some(boundary) }) => Err(TiskError::NotInitialized(format!(
+            "Invalid tisk project, could not find a project dir in the current directory or any parent directory up to {}, the search stopped at {} (set by {})",
+            last.display(),
+            boundary,
+            TISK_STOP_AT_VAR
+        ))),
+    }
+}
+
+/// Writes `contents` to the file at `path` unless the file already holds
+/// exactly `contents`, so that commands which change nothing leave the
+/// file's modified time and git status alone.  The file is written with
+/// `write_atomic`.  Returns whether the file was written.
+pub fn write_if_changed(path: &std::path::Path, contents: &str) -> std::io::Result<bool> {
+    match std::fs::read(path) {
+        Ok(existing) if existing == contents.as_bytes() => Ok(false),
+        Ok(_) => write_atomic(path, contents).map(|_| true),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => write_atomic(path, contents).map(|_| true),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it over
+/// `path`, so that a crash part way through the write leaves either the old
+/// file or the new one and never a truncated file.
+pub fn write_atomic(path: &std::path::Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let temp = temp_path(path);
+    let written = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+    written
+}
+
+/// The temporary file which is renamed over `path` once it has been
+/// written.  It is hidden, and in the same directory as `path` so that the
+/// rename does not cross file systems.
+pub(crate) fn temp_path(path: &std::path::Path) -> std::path::PathBuf {
+    let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    path.with_file_name(format!(".{}.{}.tmp", name, std::process::id()))
+}
+
+/// Commit that task `id` has been checked out to disk.
+pub fn commit_checkout(id: u32, path: &std::path::Path) -> std::io::Result<()> {
+    write_local(path, CHECKOUT_FILE, &format!("{}", id)).map(|_| ())
 }
 
 /// Read what task `id` has been checked out from disk. If not task is checked
 /// out, return `None`.
-pub fn read_checkout(path: &std::path::PathBuf) -> std::io::Result<Option<u32>> { 
+pub fn read_checkout(path: &std::path::Path) -> std::io::Result<Option<u32>> { 
     use std::io::prelude::*;
-    let mut path = std::path::PathBuf::from(path);
-    path.push(".checkout");
+    let path = read_local_path(path, CHECKOUT_FILE);
     let mut file = match std::fs::File::open(path) {
         Ok(file) => file,
         Err(err @ std::io::Error { .. }) if err.kind() == std::io::ErrorKind::NotFound => {
@@ -111,21 +596,507 @@ pub fn read_checkout(path: &std::path::PathBuf) -> std::io::Result<Option<u32>>
 
     let mut s = String::new();
     file.read_to_string(&mut s)?;
-    s.parse::<u32>()
-        .map(|id| Some(id))
-        .or_else(|e| Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e)))
+    parse_checkout(&s).map(Some)
 }
 
-/// Commit that the currently checked out task has been checked.
-pub fn commit_checkin(path: &std::path::PathBuf) -> std::io::Result<()> {
-    let mut path = std::path::PathBuf::from(path);
-    path.push(".checkout");
+/// Parses the ID of the checked out task from the contents of the `.checkout` file.
+pub(crate) fn parse_checkout(s: &str) -> std::io::Result<u32> {
+    s.parse::<u32>().map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
 
+/// Removes the file at `path`, if there is one.
+pub fn remove_file_if_exists(path: &std::path::Path) -> std::io::Result<()> {
     match std::fs::remove_file(path) {
-        Ok(_) => Ok(()),
-        Err(err @ std::io::Error { .. }) if err.kind() == std::io::ErrorKind::NotFound => {
-            return Ok(())
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Removes the files which task `id` and its notes are stored in, if there are any.
+pub fn remove_task_file(id: u32, path: &std::path::Path) -> std::io::Result<()> {
+    remove_file_if_exists(&path.join(format!("{}.yaml", id)))?;
+    remove_file_if_exists(&path.join(format!("{}.notes.yaml", id)))
+}
+
+/// The directory, within a project's `.tisk` directory, which deleted
+/// tasks are moved to so that they can be restored.
+pub const TRASH_DIR: &str = "trash";
+
+/// The path of the file deleted task `id` is kept in, within the trash of
+/// the project whose `.tisk` directory is `task_path`.
+pub fn trash_path(task_path: &std::path::Path, id: u32) -> std::path::PathBuf {
+    task_path.join(TRASH_DIR).join(format!("{}.yaml", id))
+}
+
+/// Moves `task`, which has been removed from the project whose `.tisk`
+/// directory is `task_path`, into the trash and returns the path it is
+/// kept at.  The task's file and notes file are moved if it has them,
+/// otherwise, e.g. with the single file layout, the task is written there.
+pub fn trash_task(task: &crate::tasks::Task, task_path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    move_task(task, task_path, &task_path.join(TRASH_DIR))
+}
+
+/// The directory, within a project's `.tisk` directory, which old closed
+/// tasks are moved to by `tisk archive`.  Tasks are only read from it
+/// when asked to, e.g. by `tisk list --archived`.
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Moves `task`, which has been removed from the project whose `.tisk`
+/// directory is `task_path`, into the archive and returns the path it is
+/// kept at, just as `trash_task` moves tasks into the trash.
+pub fn archive_task(task: &crate::tasks::Task, task_path: &std::path::Path) -> std::io::Result<std::path::PathBuf> {
+    move_task(task, task_path, &task_path.join(ARCHIVE_DIR))
+}
+
+fn move_task(
+    task: &crate::tasks::Task,
+    task_path: &std::path::Path,
+    dir: &std::path::Path,
+) -> std::io::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let moved = task.file_path(dir);
+    if task.file_path(task_path).is_file() {
+        std::fs::rename(task.file_path(task_path), &moved)?;
+        if task.notes_path(task_path).is_file() {
+            std::fs::rename(task.notes_path(task_path), task.notes_path(dir))?;
         }
-        Err(err) => return Err(err),
+    } else {
+        write_atomic(&moved, &task.to_yaml()?)?;
+    }
+    Ok(moved)
+}
+
+/// Reads deleted task `id` from the trash of the project whose `.tisk`
+/// directory is `task_path`.
+pub fn read_trashed(task_path: &std::path::Path, id: u32) -> std::io::Result<crate::tasks::Task> {
+    match crate::tasks::Task::read(&trash_path(task_path, id)) {
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("Task {} is not in the trash", id),
+        )),
+        result => result,
+    }
+}
+
+/// Takes task `id`, which has been written back to the project whose
+/// `.tisk` directory is `task_path`, out of the trash.  Its notes file is
+/// moved back to the project.
+pub fn untrash_task(id: u32, task_path: &std::path::Path) -> std::io::Result<()> {
+    let notes = task_path.join(TRASH_DIR).join(format!("{}.notes.yaml", id));
+    if notes.is_file() {
+        std::fs::rename(&notes, task_path.join(format!("{}.notes.yaml", id)))?;
+    }
+    remove_file_if_exists(&trash_path(task_path, id))
+}
+
+/// Commit that the currently checked out task has been checked.
+pub fn commit_checkin(path: &std::path::Path) -> std::io::Result<()> {
+    remove_local(path, CHECKOUT_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory, for a single test, which is removed when dropped.
+    struct TempDir(std::path::PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let path = std::env::temp_dir().join(format!("tisk-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(std::fs::canonicalize(path).unwrap())
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn initialize_and_find_from_subdirectory() {
+        let root = TempDir::new("find-task-dir");
+        let sub = root.0.join("src").join("nested");
+        std::fs::create_dir_all(&sub).unwrap();
+
+        assert_eq!(InitResult::Initialized, initialize_at(&root.0).unwrap());
+        assert_eq!(InitResult::AlreadyInitialized, initialize_at(&root.0).unwrap());
+        assert_eq!(root.0.join(".tisk"), find_task_dir_from(&sub).unwrap());
+        assert_eq!(root.0.join(".tisk"), find_task_dir_from(&root.0).unwrap());
+    }
+
+    #[test]
+    fn skip_unreadable_directories() {
+        use std::io::{Error, ErrorKind};
+
+        let root = TempDir::new("unreadable");
+        let middle = root.0.join("src");
+        let sub = middle.join("nested");
+        std::fs::create_dir_all(&sub).unwrap();
+        initialize_at(&root.0).unwrap();
+
+        // Running as root ignores permissions, so the failures are simulated
+        let denied = || Error::new(ErrorKind::PermissionDenied, "permission denied");
+        let found = up_search_with(&sub, ".tisk", |dir| if dir == middle { Err(denied()) } else { subdirs(dir) });
+        assert_eq!(Some(root.0.join(".tisk")), found.unwrap());
+        let found = search_project_dir_with(&sub, &[], None, |dir| if dir == middle { Err(denied()) } else { project_dir_in(dir) });
+        assert_eq!(Search::Found(root.0.join(".tisk")), found.unwrap());
+
+        let found = search_project_dir_with(&sub, &[], None, |_| Err(denied()));
+        let last = root.0.ancestors().last().unwrap().to_path_buf();
+        assert_eq!(Search::NotFound { last, boundary: None }, found.unwrap());
+        assert_eq!(true, up_search(root.0.join("missing"), ".tisk").is_err());
+    }
+
+    #[test]
+    fn stop_at_boundaries() {
+        let root = TempDir::new("boundaries");
+        let repo = root.0.join("repo");
+        let sub = repo.join("src");
+        std::fs::create_dir_all(repo.join(".git")).unwrap();
+        std::fs::create_dir_all(&sub).unwrap();
+        initialize_at(&root.0).unwrap();
+
+        let search = |boundaries: &[Boundary], home: Option<&std::path::Path>| {
+            search_project_dir_with(&sub, boundaries, home, project_dir_in).unwrap()
+        };
+        assert_eq!(Search::Found(root.0.join(".tisk")), search(&[], None));
+        assert_eq!(Search::Found(root.0.join(".tisk")), search(&[Boundary::Home], Some(&root.0)));
+        assert_eq!(Search::NotFound { last: repo.clone(), boundary: Some(Boundary::Git) }, search(&[Boundary::Git], None));
+        assert_eq!(Search::NotFound { last: repo.clone(), boundary: Some(Boundary::Home) }, search(&[Boundary::Home], Some(&repo)));
+
+        // A project at the root of the repository would be nested in the one above it, unless it stops at git roots
+        assert_eq!(Some(root.0.join(".tisk")), enclosing_project_dir(&repo, &[]).unwrap());
+        assert_eq!(None, enclosing_project_dir(&repo, &[Boundary::Git]).unwrap());
+
+        assert_eq!(Boundary::Mount, "mount".parse::<Boundary>().unwrap());
+        assert_eq!(true, "disk".parse::<Boundary>().is_err());
+    }
+
+    #[test]
+    fn git_ignore() {
+        let root = TempDir::new("git-ignore");
+        let project = root.0.join("app");
+        std::fs::create_dir_all(root.0.join(".git")).unwrap();
+        std::fs::create_dir_all(&project).unwrap();
+        initialize_at(&project).unwrap();
+        let task_path = find_task_dir_from(&project).unwrap();
+        std::fs::write(root.0.join(".gitignore"), "/target\n/app/.tisk/log/").unwrap();
+
+        let (file, lines) = git_ignore_lines(&task_path, GitIgnore::Local).unwrap();
+        assert_eq!(root.0.join(".gitignore"), file);
+        assert_eq!(vec!["/app/.tisk/local/"], lines);
+        append_git_ignore(&file, &lines).unwrap();
+        assert_eq!(true, git_ignore_lines(&task_path, GitIgnore::Local).unwrap().1.is_empty());
+        assert_eq!("/target\n/app/.tisk/log/\n# tisk\n/app/.tisk/local/\n", std::fs::read_to_string(&file).unwrap());
+        assert_eq!(vec!["/app/.tisk/"], git_ignore_lines(&task_path, GitIgnore::All).unwrap().1);
+
+        std::fs::remove_dir(root.0.join(".git")).unwrap();
+        assert_eq!(true, git_ignore_lines(&task_path, GitIgnore::All).is_err());
+    }
+
+    #[test]
+    fn checkout_and_checkin() {
+        let root = TempDir::new("checkout");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        assert_eq!(None, read_checkout(&task_path).unwrap());
+        commit_checkout(3, &task_path).unwrap();
+        assert_eq!(Some(3), read_checkout(&task_path).unwrap());
+        assert_eq!(true, task_path.join("local").join(".checkout").is_file());
+        commit_checkin(&task_path).unwrap();
+        assert_eq!(None, read_checkout(&task_path).unwrap());
+
+        // Older projects keep the checkout in the project directory, until it is next written
+        std::fs::write(task_path.join(".checkout"), "4").unwrap();
+        assert_eq!(Some(4), read_checkout(&task_path).unwrap());
+        commit_checkout(5, &task_path).unwrap();
+        assert_eq!(Some(5), read_checkout(&task_path).unwrap());
+        assert_eq!(false, task_path.join(".checkout").exists());
+    }
+
+    #[test]
+    fn custom_dir_name_and_single_file_layout() {
+        use crate::meta::Layout;
+        use crate::tasks::TaskList;
+
+        let root = TempDir::new("init-options");
+        let options = InitOptions {
+            meta: Meta {
+                dir_name: String::from(".todo"),
+                layout: Layout::SingleFile,
+                ..Meta::default()
+            },
+            with_config: true,
+        };
+        assert_eq!(InitResult::Initialized, initialize_with(&root.0, &options).unwrap());
+
+        let task_path = find_task_dir_from(&root.0).unwrap();
+        assert_eq!(root.0.join(".todo"), task_path);
+        assert!(task_path.join("config.yaml").is_file());
+        let meta = Meta::read(&task_path).unwrap();
+        assert_eq!(root.0.file_name().unwrap().to_str(), meta.name.as_deref());
+        assert_eq!(true, meta.created_at.is_some());
+
+        let mut tasks = TaskList::new();
+        tasks.add_task("one", 1);
+        tasks.add_task("two", 2);
+        tasks.write_all(&task_path).unwrap();
+        assert!(task_path.join("tasks.yaml").is_file());
+        assert!(!task_path.join("1.yaml").exists());
+
+        let tasks = TaskList::read_tasks(&task_path).unwrap();
+        assert_eq!("two", tasks.get(2).unwrap().name());
+    }
+
+    #[test]
+    fn unknown_backend_fails_to_open() {
+        let root = TempDir::new("backend");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        // A project stored with a backend this tisk does not know, e.g. from a newer tisk, fails to open
+        let meta = Meta::read(&task_path).unwrap().to_yaml().unwrap();
+        assert!(meta.contains("backend: yaml"));
+        std::fs::write(task_path.join(META_FILE), meta.replace("backend: yaml", "backend: sqlite")).unwrap();
+        assert!(crate::tasks::TaskList::read_tasks(&task_path).is_err());
+    }
+
+    #[test]
+    fn deleted_ids_are_not_reused() {
+        use crate::tasks::TaskList;
+
+        let root = TempDir::new("last-id");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        let mut tasks = TaskList::new();
+        tasks.add_task("one", 1);
+        tasks.add_task("two", 2);
+        tasks.write_all(&task_path).unwrap();
+        assert_eq!(2, Meta::read(&task_path).unwrap().last_id);
+
+        remove_task_file(2, &task_path).unwrap();
+        assert_eq!(3, TaskList::read_tasks(&task_path).unwrap().next_id());
+    }
+
+    #[test]
+    fn cached_tasks_are_read_once() {
+        use crate::tasks::{CachedStorage, Storage, TaskList};
+        use std::rc::Rc;
+
+        struct Counting(Box<dyn Storage>, Rc<std::cell::Cell<u32>>);
+        impl Storage for Counting {
+            fn read(&self) -> std::io::Result<TaskList> {
+                self.1.set(self.1.get() + 1);
+                self.0.read()
+            }
+            fn write(&self, tasks: &TaskList) -> std::io::Result<Vec<std::path::PathBuf>> {
+                self.0.write(tasks)
+            }
+        }
+
+        let root = TempDir::new("cached");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+        let mut tasks = TaskList::new();
+        tasks.add_task("one", 1);
+        tasks.write_all(&task_path).unwrap();
+
+        let reads = Rc::new(std::cell::Cell::new(0));
+        let storage = Counting(crate::tasks::open_storage(&task_path).unwrap(), reads.clone());
+        let cached = CachedStorage::new(Box::new(storage), &task_path);
+        assert_eq!(1, cached.read().unwrap().get_all().len());
+        assert_eq!(1, cached.read().unwrap().get_all().len());
+        assert_eq!(1, reads.get());
+
+        // A task written by something else, e.g. another tisk, is read
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        tasks.add_task("two", 1);
+        tasks.write_all(&task_path).unwrap();
+        assert_eq!(2, cached.read().unwrap().get_all().len());
+        assert_eq!(2, reads.get());
+
+        // And so is a file edited in place
+        let edited = std::fs::read_to_string(task_path.join("1.yaml")).unwrap().replace("one", "eins");
+        std::fs::write(task_path.join("1.yaml"), &edited).unwrap();
+        assert_eq!("eins", cached.read().unwrap().get(1).unwrap().name());
+        assert_eq!(3, reads.get());
+    }
+
+    #[test]
+    fn unchanged_tasks_are_not_rewritten() {
+        use crate::tasks::TaskList;
+
+        let root = TempDir::new("unchanged");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        let mut tasks = TaskList::new();
+        tasks.add_task("one", 1);
+        tasks.add_task("two", 2);
+        assert_eq!(2, tasks.write_all(&task_path).unwrap().len());
+
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        assert_eq!(true, tasks.write_all(&task_path).unwrap().is_empty());
+        tasks.set_priority(2, 5);
+        assert_eq!(vec![task_path.join("2.yaml")], tasks.write_all(&task_path).unwrap());
+
+        // Tasks which were not changed are not even compared to their files
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        let edited = std::fs::read_to_string(task_path.join("1.yaml")).unwrap() + "\n# edited";
+        std::fs::write(task_path.join("1.yaml"), &edited).unwrap();
+        tasks.set_priority(2, 6);
+        assert_eq!(vec![task_path.join("2.yaml")], tasks.write_all(&task_path).unwrap());
+        assert_eq!(edited, std::fs::read_to_string(task_path.join("1.yaml")).unwrap());
+    }
+
+    #[test]
+    fn writes_are_atomic() {
+        let root = TempDir::new("atomic");
+        let path = root.0.join("1.yaml");
+        write_atomic(&path, "id: 1").unwrap();
+        assert_eq!("id: 1", std::fs::read_to_string(&path).unwrap());
+        assert_eq!(1, std::fs::read_dir(&root.0).unwrap().count());
+
+        // A write which fails part way leaves the file as it was
+        std::fs::create_dir(temp_path(&path)).unwrap();
+        assert!(write_atomic(&path, "id: 2").is_err());
+        assert_eq!("id: 1", std::fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn notes_in_their_own_file() {
+        use crate::tasks::{Task, TaskList};
+
+        let root = TempDir::new("notes-file");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        // Older task files hold their notes, which move to a notes file
+        let legacy = "id: 1\nname: test\nstatus: Open\nnotes:\n  - created_at: \"2020-06-01T09:00:00Z\"\n    note: first\n";
+        std::fs::write(task_path.join("1.yaml"), legacy).unwrap();
+        TaskList::read_tasks(&task_path).unwrap().write_all(&task_path).unwrap();
+        let yaml = std::fs::read_to_string(task_path.join("1.yaml")).unwrap();
+        assert_eq!(true, yaml.contains("note_count: 1") && !yaml.contains("first"));
+        assert_eq!(true, task_path.join("1.notes.yaml").is_file());
+
+        // Notes are counted without being read, and read when they are needed
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        let task = tasks.get_mut(1).unwrap();
+        assert_eq!(1, task.note_count());
+        task.set_priority(3);
+        Task::write(task, &task_path).unwrap();
+        assert_eq!(true, task_path.join("1.notes.yaml").is_file());
+        task.add_note("second").unwrap();
+        Task::write(task, &task_path).unwrap();
+
+        let task = Task::read(&task_path.join("1.yaml")).unwrap();
+        assert_eq!(3, task.priority());
+        assert_eq!(vec!["first", "second"], task.notes().iter().map(|n| n.note()).collect::<Vec<&str>>());
+
+        // A note is not added to notes which cannot be read, nor is the task written
+        std::fs::write(task_path.join("1.notes.yaml"), "- [not notes").unwrap();
+        let note = crate::engine::Command::Note { id: Some(1), note: Some(String::from("third")) };
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        assert!(crate::engine::execute(&mut tasks, None, &note).is_err());
+        assert!(crate::engine::run(&task_path, &note).is_err());
+        assert_eq!(true, std::fs::read_to_string(task_path.join("1.notes.yaml")).unwrap().starts_with("- [not"));
+
+        remove_task_file(1, &task_path).unwrap();
+        assert_eq!(false, task_path.join("1.notes.yaml").exists());
+    }
+
+    #[test]
+    fn trash_and_restore() {
+        use crate::tasks::TaskList;
+
+        let root = TempDir::new("trash");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        let mut tasks = TaskList::new();
+        tasks.add_task("keep", 1);
+        tasks.add_task("delete", 2);
+        tasks.get_mut(2).unwrap().add_note("a note").unwrap();
+        tasks.write_all(&task_path).unwrap();
+
+        let task = TaskList::read_tasks(&task_path).unwrap().get(2).unwrap().clone();
+        assert_eq!(trash_path(&task_path, 2), trash_task(&task, &task_path).unwrap());
+        assert_eq!(false, task_path.join("2.yaml").exists() || task_path.join("2.notes.yaml").exists());
+        assert_eq!(vec![1], TaskList::read_tasks(&task_path).unwrap().get_all().iter().map(|t| t.id()).collect::<Vec<u32>>());
+
+        let trashed = read_trashed(&task_path, 2).unwrap();
+        assert_eq!("delete", trashed.name());
+        assert!(read_trashed(&task_path, 1).is_err());
+        untrash_task(2, &task_path).unwrap();
+        assert_eq!(true, task_path.join("2.notes.yaml").is_file());
+        assert_eq!(false, trash_path(&task_path, 2).exists());
+    }
+
+    #[test]
+    fn archive() {
+        use crate::tasks::TaskList;
+
+        let root = TempDir::new("archive");
+        initialize_at(&root.0).unwrap();
+        let task_path = find_task_dir_from(&root.0).unwrap();
+
+        let mut tasks = TaskList::new();
+        tasks.add_task("keep", 1);
+        tasks.add_task("archive", 2);
+        tasks.get_mut(2).unwrap().add_note("a note").unwrap();
+        tasks.write_all(&task_path).unwrap();
+
+        let task = TaskList::read_tasks(&task_path).unwrap().get(2).unwrap().clone();
+        assert_eq!(task_path.join(ARCHIVE_DIR).join("2.yaml"), archive_task(&task, &task_path).unwrap());
+        let mut tasks = TaskList::read_tasks(&task_path).unwrap();
+        assert_eq!(vec![1], tasks.get_all().iter().map(|t| t.id()).collect::<Vec<u32>>());
+
+        tasks.read_archive(&task_path).unwrap();
+        assert_eq!(vec![1, 2], tasks.get_all().iter().map(|t| t.id()).collect::<Vec<u32>>());
+        assert_eq!(1, tasks.get(2).unwrap().notes().len());
+        assert_eq!(true, tasks.get_modified().is_empty());
+    }
+
+    #[test]
+    fn dir_name_must_be_hidden() {
+        let root = TempDir::new("init-bad-name");
+        let mut options = InitOptions::default();
+        options.meta.dir_name = String::from("tasks");
+        assert!(initialize_with(&root.0, &options).is_err());
+    }
+
+    #[test]
+    fn find_and_migrate_legacy_dir() {
+        let root = TempDir::new("legacy-dir");
+        std::fs::create_dir(root.0.join(".task")).unwrap();
+        let mut tasks = crate::tasks::TaskList::new();
+        tasks.add_task("legacy", 1);
+        tasks.write_all(&root.0.join(".task")).unwrap();
+
+        let task_path = find_task_dir_from(&root.0).unwrap();
+        assert_eq!(root.0.join(".task"), task_path);
+
+        let new_path = migrate_dir(&task_path, ".tisk").unwrap();
+        assert_eq!(root.0.join(".tisk"), new_path);
+        assert!(!task_path.exists());
+        assert_eq!(new_path, find_task_dir_from(&root.0).unwrap());
+        let tasks = crate::tasks::TaskList::read_tasks(&new_path).unwrap();
+        assert_eq!("legacy", tasks.get(1).unwrap().name());
+    }
+
+    #[test]
+    fn task_dir_at_root_or_project_dir() {
+        let root = TempDir::new("task-dir-at");
+        initialize_at(&root.0).unwrap();
+
+        assert_eq!(root.0.join(".tisk"), task_dir_at(&root.0).unwrap());
+        assert_eq!(root.0.join(".tisk"), task_dir_at(root.0.join(".tisk")).unwrap());
+        assert_eq!("not_initialized", task_dir_at(root.0.join("missing")).unwrap_err().code());
     }
 }
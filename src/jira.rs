@@ -0,0 +1,443 @@
+/*!
+ * Imports issues from Jira, either from the CSV file Jira exports or from
+ * the results of a search with Jira's REST API, so that a team moving off
+ * Jira can bring its backlog into tisk.  Each issue becomes a task with the
+ * issue's key kept in the task's `jira` metadata, and its status and
+ * priority mapped by the project's `jira` setting.
+ */
+use crate::dates::Zone;
+use crate::error::TiskError;
+use crate::tasks::{Status, Task, TaskList};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The metadata which the key of the issue a task was imported from is kept in.
+pub const KEY: &str = "jira";
+
+/// How Jira issues become tasks, set with the `jira` setting.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct JiraMapping {
+    /// The Jira site which `tisk import --jql` searches, e.g.
+    /// `https://example.atlassian.net`.
+    pub url: Option<String>,
+
+    /// The status each Jira status becomes, a status which is not here
+    /// becomes `Open`.
+    pub statuses: BTreeMap<String, Status>,
+
+    /// The priority each Jira priority becomes, a priority which is not
+    /// here becomes 1.
+    pub priorities: BTreeMap<String, u32>,
+}
+
+impl Default for JiraMapping {
+    fn default() -> JiraMapping {
+        let statuses = [
+            ("To Do", Status::Open),
+            ("In Progress", Status::InProgress),
+            ("Blocked", Status::Blocked),
+            ("Done", Status::Closed),
+        ];
+        let priorities = [("Highest", 5), ("High", 4), ("Medium", 3), ("Low", 2), ("Lowest", 1)];
+        JiraMapping {
+            url: None,
+            statuses: statuses.iter().map(|&(name, status)| (String::from(name), status)).collect(),
+            priorities: priorities.iter().map(|&(name, priority)| (String::from(name), priority)).collect(),
+        }
+    }
+}
+
+impl JiraMapping {
+    /// The status of a task imported from an issue with Jira status `status`.
+    pub fn status(&self, status: &str) -> Status {
+        let mapped = self.statuses.iter().find(|(name, _)| name.eq_ignore_ascii_case(status));
+        mapped.map(|(_, s)| *s).unwrap_or(Status::Open)
+    }
+
+    /// The priority of a task imported from an issue with Jira priority
+    /// `priority`.
+    pub fn priority(&self, priority: &str) -> u32 {
+        let mapped = self.priorities.iter().find(|(name, _)| name.eq_ignore_ascii_case(priority));
+        mapped.map(|(_, p)| *p).unwrap_or(1)
+    }
+}
+
+/// A Jira issue, as read from a CSV export or a search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Issue {
+    /// The issue's key, e.g. `ABC-123`.
+    pub key: String,
+    pub summary: String,
+    pub status: String,
+    pub priority: Option<String>,
+    pub assignee: Option<String>,
+    pub labels: Vec<String>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub description: Option<String>,
+}
+
+impl Issue {
+    /// The task with ID `id` which this issue becomes.  Labels become tags
+    /// and the description becomes the task's first note.
+    pub fn to_task(&self, id: u32, mapping: &JiraMapping) -> Result<Task, TiskError> {
+        let priority = self.priority.as_deref().map(|p| mapping.priority(p)).unwrap_or(1);
+        let mut task = Task::new(id, self.summary.clone(), Status::Open, priority);
+        task.set_status(mapping.status(&self.status));
+        task.set_assignee(self.assignee.as_deref());
+        task.set_due_at(self.due_at);
+        for label in &self.labels {
+            task.add_tag(label);
+        }
+        if let Some(description) = self.description.as_deref().map(str::trim).filter(|d| !d.is_empty()) {
+            task.add_note(description)?;
+        }
+        task.set_metadata(KEY, &self.key);
+        Ok(task)
+    }
+}
+
+/// The tasks which `issues` become in the project with `tasks`.  An issue
+/// which was imported before is given the ID of the task it became, the
+/// others are given new IDs, so that importing them with
+/// `IdPolicy::Keep(Conflict::Skip)` only adds the issues which are new.
+pub fn to_tasks(issues: &[Issue], mapping: &JiraMapping, tasks: &TaskList) -> Result<Vec<Task>, TiskError> {
+    let mut next_id = tasks.next_id();
+    let mut imported = vec![];
+    for issue in issues {
+        let existing = tasks.get_all().into_iter().find(|t| t.metadata().get(KEY) == Some(&issue.key));
+        let id = match existing {
+            Some(task) => task.id(),
+            None => {
+                next_id += 1;
+                next_id - 1
+            }
+        };
+        imported.push(issue.to_task(id, mapping)?);
+    }
+    Ok(imported)
+}
+
+/// Reads the issues in a CSV file exported from Jira.  Only the columns
+/// which tisk has a use for are read: `Issue key`, `Summary`, `Status`,
+/// `Priority`, `Assignee`, every `Labels` column, `Due Date`, and
+/// `Description`.  Dates are read in `zone`, the project's time zone.
+pub fn parse_csv(s: &str, zone: Zone) -> Result<Vec<Issue>, TiskError> {
+    let mut rows = csv_rows(s.trim_start_matches('\u{feff}')).into_iter();
+    let header = rows.next().ok_or_else(|| jira_error("the file is empty"))?;
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let key_at = column("Issue key").ok_or_else(|| jira_error("there is no Issue key column"))?;
+    let summary_at = column("Summary").ok_or_else(|| jira_error("there is no Summary column"))?;
+    let labels_at: Vec<usize> =
+        (0..header.len()).filter(|&i| header[i].trim().eq_ignore_ascii_case("Labels")).collect();
+
+    let mut issues = vec![];
+    for row in rows.filter(|row| row.iter().any(|cell| !cell.trim().is_empty())) {
+        let cell = |at: Option<usize>| at.and_then(|i| row.get(i)).map(|c| c.trim()).filter(|c| !c.is_empty());
+        let key = cell(Some(key_at)).ok_or_else(|| jira_error("an issue has no key"))?;
+        issues.push(Issue {
+            key: String::from(key),
+            summary: String::from(cell(Some(summary_at)).unwrap_or_default()),
+            status: String::from(cell(column("Status")).unwrap_or_default()),
+            priority: cell(column("Priority")).map(String::from),
+            assignee: cell(column("Assignee")).map(String::from),
+            labels: labels_at.iter().filter_map(|&i| cell(Some(i))).map(String::from).collect(),
+            due_at: cell(column("Due Date")).map(|due| parse_time(due, zone)).transpose()?,
+            description: cell(column("Description")).map(String::from),
+        });
+    }
+    Ok(issues)
+}
+
+/// Splits CSV into rows of cells.  A cell quoted with `"` may contain
+/// commas and newlines, and `""` for a quote.  Carriage returns are dropped.
+fn csv_rows(s: &str) -> Vec<Vec<String>> {
+    let mut rows = vec![];
+    let mut row = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            ('"', _) => quoted = !quoted,
+            (',', false) => row.push(std::mem::take(&mut cell)),
+            ('\r', _) => (),
+            ('\n', false) => {
+                row.push(std::mem::take(&mut cell));
+                rows.push(std::mem::take(&mut row));
+            }
+            (c, _) => cell.push(c),
+        }
+    }
+    if !cell.is_empty() || !row.is_empty() {
+        row.push(cell);
+        rows.push(row);
+    }
+    rows
+}
+
+/// Parses a date or time the way Jira writes them, e.g. `12/Jun/20 9:30 AM`
+/// in a CSV export or `2020-06-12` in a search, in `zone`.
+fn parse_time(s: &str, zone: Zone) -> Result<DateTime<Utc>, TiskError> {
+    let times = ["%d/%b/%y %I:%M %p", "%d/%b/%Y %I:%M %p", "%Y-%m-%d %H:%M"];
+    let days = ["%d/%b/%y", "%d/%b/%Y", "%Y-%m-%d"];
+    let local = times
+        .iter()
+        .find_map(|f| NaiveDateTime::parse_from_str(s, f).ok())
+        .or_else(|| days.iter().find_map(|f| NaiveDate::parse_from_str(s, f).ok()).map(|day| day.and_hms(0, 0, 0)));
+    local
+        .and_then(|time| zone.utc(time))
+        .or_else(|| DateTime::parse_from_rfc3339(s).ok().map(|time| time.with_timezone(&Utc)))
+        .ok_or_else(|| jira_error(&format!("invalid date {}", s)))
+}
+
+fn jira_error(msg: &str) -> TiskError {
+    TiskError::Serde(format!("Invalid Jira issues: {}", msg))
+}
+
+/// A page of the results of a search with Jira's REST API, the response
+/// to `GET /rest/api/2/search`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    #[serde(default)]
+    pub start_at: usize,
+
+    /// How many issues the search found, over every page.
+    #[serde(default)]
+    pub total: usize,
+
+    pub issues: Vec<SearchIssue>,
+}
+
+/// An issue in the results of a search.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchIssue {
+    pub key: String,
+    pub fields: SearchFields,
+}
+
+/// The fields of an issue which tisk asks a search for.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchFields {
+    #[serde(default)]
+    pub summary: String,
+    pub status: Option<Named>,
+    pub priority: Option<Named>,
+    pub assignee: Option<Named>,
+
+    #[serde(default)]
+    pub labels: Vec<String>,
+    pub duedate: Option<String>,
+    pub description: Option<String>,
+}
+
+/// A status, priority, or user, which tisk only needs the name of.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Named {
+    #[serde(alias = "displayName")]
+    pub name: String,
+}
+
+/// The fields a search asks for, those which `SearchFields` reads.
+pub const SEARCH_FIELDS: &str = "summary,status,priority,assignee,labels,duedate,description";
+
+/// How many issues are asked for in each page of a search.
+pub const PAGE_SIZE: usize = 100;
+
+/// Searches a Jira site, e.g. with its REST API, so that `search` can be
+/// given pages which did not come from a real site.
+pub trait JiraClient {
+    /// The JSON of the page of the results of searching for `jql` which
+    /// starts at the `start_at`th issue and has at most `max_results`.
+    fn search_page(&self, jql: &str, start_at: usize, max_results: usize) -> Result<Vec<u8>, TiskError>;
+}
+
+/// The issues `jql` finds with `client`, reading page after page of the
+/// results until every issue the search found has been read.  Dates are
+/// read in `zone`.
+pub fn search(client: &dyn JiraClient, jql: &str, zone: Zone) -> Result<Vec<Issue>, TiskError> {
+    let mut issues = vec![];
+    loop {
+        let page = parse_page(&client.search_page(jql, issues.len(), PAGE_SIZE)?)?;
+        for issue in &page.issues {
+            issues.push(issue.to_issue(zone)?);
+        }
+        if page.issues.is_empty() || page.start_at + page.issues.len() >= page.total {
+            return Ok(issues);
+        }
+    }
+}
+
+/// Reads a page of the results of a search from its JSON.
+pub fn parse_page(json: &[u8]) -> Result<SearchResults, TiskError> {
+    serde_json::from_slice(json)
+        .map_err(|why| TiskError::Serde(format!("Failed to parse the Jira search results: {}", why)))
+}
+
+/// Searches the Jira site at `url` with its REST API, using curl.
+pub struct Curl {
+    pub url: String,
+
+    /// The curl config which authenticates each request, given to curl on
+    /// stdin so that the token is not in curl's arguments, which other
+    /// users can see.
+    auth: String,
+}
+
+impl Curl {
+    /// A client for the Jira site at `url`, e.g. `https://example.atlassian.net`.
+    /// The API token is read from `$JIRA_TOKEN` and sent as a bearer token,
+    /// or with `$JIRA_USER` as the user's API token on Jira Cloud.
+    pub fn from_env(url: &str) -> Result<Curl, TiskError> {
+        let token = std::env::var("JIRA_TOKEN")
+            .map_err(|_| TiskError::ParseError(String::from("Set JIRA_TOKEN to a Jira API token to search Jira")))?;
+        let auth = match std::env::var("JIRA_USER") {
+            Ok(user) => format!("user = \"{}:{}\"\n", user, token),
+            Err(_) => format!("header = \"Authorization: Bearer {}\"\n", token),
+        };
+        Ok(Curl { url: format!("{}/rest/api/2/search", url.trim_end_matches('/')), auth })
+    }
+}
+
+impl JiraClient for Curl {
+    fn search_page(&self, jql: &str, start_at: usize, max_results: usize) -> Result<Vec<u8>, TiskError> {
+        use std::process::{Command, Stdio};
+
+        let mut curl = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--get", "--config", "-", &self.url])
+            .args(["--data-urlencode", &format!("jql={}", jql)])
+            .args(["--data-urlencode", &format!("startAt={}", start_at)])
+            .args(["--data-urlencode", &format!("maxResults={}", max_results)])
+            .args(["--data-urlencode", &format!("fields={}", SEARCH_FIELDS)])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|why| TiskError::io("Failed to start curl to search Jira", why))?;
+        if let Some(mut stdin) = curl.stdin.take() {
+            std::io::Write::write_all(&mut stdin, self.auth.as_bytes())
+                .map_err(|why| TiskError::io("Failed to give curl the Jira token", why))?;
+        }
+        let output = curl.wait_with_output().map_err(|why| TiskError::io("Failed to search Jira", why))?;
+        if !output.status.success() {
+            let context = format!("Searching Jira failed, curl exited with {}", output.status);
+            return Err(TiskError::External { context });
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl SearchIssue {
+    pub fn to_issue(&self, zone: Zone) -> Result<Issue, TiskError> {
+        let fields = &self.fields;
+        Ok(Issue {
+            key: self.key.clone(),
+            summary: fields.summary.clone(),
+            status: fields.status.as_ref().map(|s| s.name.clone()).unwrap_or_default(),
+            priority: fields.priority.as_ref().map(|p| p.name.clone()),
+            assignee: fields.assignee.as_ref().map(|a| a.name.clone()),
+            labels: fields.labels.clone(),
+            due_at: fields.duedate.as_deref().map(|due| parse_time(due, zone)).transpose()?,
+            description: fields.description.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::{Conflict, IdPolicy};
+    use std::cell::RefCell;
+
+    const EXPORT: &str = "\u{feff}Summary,Issue key,Status,Priority,Labels,Labels,Due Date,Description\r
+Fix login,ABC-1,In Progress,High,auth,bug,12/Jun/20 12:00 AM,\"Fails with \"\"bad token\"\"\r
+on Safari\"\r
+Write docs,ABC-2,Done,,docs,,,\r
+\r
+";
+
+    #[test]
+    fn parse_export() {
+        let issues = parse_csv(EXPORT, Zone::Local).unwrap();
+        assert_eq!(2, issues.len());
+        assert_eq!(("ABC-1", "Fix login"), (issues[0].key.as_str(), issues[0].summary.as_str()));
+        assert_eq!(vec!["auth", "bug"], issues[0].labels);
+        assert_eq!(Some("Fails with \"bad token\"\non Safari"), issues[0].description.as_deref());
+        let due = issues[0].due_at.map(|due| Zone::Local.naive(due).date());
+        assert_eq!(Some(NaiveDate::from_ymd(2020, 6, 12)), due);
+        assert_eq!((None, None), (issues[1].priority.as_deref(), issues[1].due_at));
+        assert_eq!(true, parse_csv("Summary,Status\nFix login,Done\n", Zone::Local).is_err());
+    }
+
+    #[test]
+    fn map_issues() {
+        let mut mapping = JiraMapping::default();
+        mapping.statuses.insert(String::from("QA"), Status::Blocked);
+        let mut tasks = TaskList::new();
+        tasks.add_task("existing", 1);
+        let issues = parse_csv(EXPORT, Zone::Local).unwrap();
+
+        let imported = to_tasks(&issues, &mapping, &tasks).unwrap();
+        assert_eq!(vec![2, 3], imported.iter().map(|t| t.id()).collect::<Vec<_>>());
+        assert_eq!((Status::InProgress, 4), (imported[0].status(), imported[0].priority()));
+        assert_eq!((Status::Closed, 1), (imported[1].status(), imported[1].priority()));
+        assert_eq!(Some(&String::from("ABC-2")), imported[1].metadata().get(KEY));
+        assert_eq!(Status::Blocked, mapping.status("qa"));
+
+        let imported = imported.into_iter().collect();
+        tasks.merge(imported, IdPolicy::Keep(Conflict::Skip)).unwrap();
+        let again = to_tasks(&issues, &mapping, &tasks).unwrap();
+        assert_eq!(vec![2, 3], again.iter().map(|t| t.id()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn parse_search() {
+        let results = parse_page(
+            br#"{"startAt": 0, "total": 1, "issues": [{"key": "ABC-7", "fields": {
+                "summary": "Upgrade", "status": {"name": "To Do"}, "priority": null,
+                "assignee": {"displayName": "Ann"}, "labels": ["ops"], "duedate": "2020-06-12"}}]}"#,
+        )
+        .unwrap();
+        let issue = results.issues[0].to_issue(Zone::Local).unwrap();
+        assert_eq!(("ABC-7", "To Do"), (issue.key.as_str(), issue.status.as_str()));
+        assert_eq!(Some("Ann"), issue.assignee.as_deref());
+        assert_eq!(vec!["ops"], issue.labels);
+        assert_eq!(true, parse_page(b"<html>Unauthorized</html>").is_err());
+    }
+
+    /// Answers each search with the next of its pages, and remembers where
+    /// each page it was asked for started.
+    struct Pages {
+        pages: RefCell<Vec<String>>,
+        asked: RefCell<Vec<usize>>,
+    }
+
+    impl JiraClient for Pages {
+        fn search_page(&self, jql: &str, start_at: usize, _: usize) -> Result<Vec<u8>, TiskError> {
+            assert_eq!("project = ABC", jql);
+            self.asked.borrow_mut().push(start_at);
+            Ok(self.pages.borrow_mut().remove(0).into_bytes())
+        }
+    }
+
+    #[test]
+    fn search_every_page() {
+        let issue = |key| format!(r#"{{"key": "{}", "fields": {{"summary": "Issue {}"}}}}"#, key, key);
+        let first = format!(r#"{{"startAt": 0, "total": 3, "issues": [{}, {}]}}"#, issue("ABC-1"), issue("ABC-2"));
+        let second = format!(r#"{{"startAt": 2, "total": 3, "issues": [{}]}}"#, issue("ABC-3"));
+        let pages = Pages { pages: RefCell::new(vec![first, second]), asked: RefCell::default() };
+
+        let issues = search(&pages, "project = ABC", Zone::Local).unwrap();
+        assert_eq!(vec!["ABC-1", "ABC-2", "ABC-3"], issues.iter().map(|i| i.key.as_str()).collect::<Vec<_>>());
+        assert_eq!(vec![0, 2], *pages.asked.borrow());
+
+        let empty = String::from(r#"{"total": 5, "issues": []}"#);
+        let pages = Pages { pages: RefCell::new(vec![empty]), asked: RefCell::default() };
+        assert_eq!(true, search(&pages, "project = ABC", Zone::Local).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,32 @@
+/*!
+ * Tisk manages tasks which are localized to a project.  This library holds
+ * everything the `tisk` command line uses: the tasks themselves, how they
+ * are stored, the tables they are printed in, and the `engine` which
+ * executes commands against a project.
+ *
+ * Everything which touches the filesystem is part of the `storage` feature.
+ * Without it, the task model, tables, and engine have no dependency on the
+ * platform and can be compiled to wasm32, e.g. to view an exported
+ * `Project` in a browser.
+ */
+
+#[cfg(feature = "async")]
+pub mod async_storage;
+pub mod busy;
+pub mod config;
+pub mod dates;
+pub mod engine;
+pub mod error;
+pub mod export;
+pub mod focus;
+#[cfg(feature = "storage")]
+pub mod io;
+pub mod jira;
+pub mod meta;
+pub mod notify;
+pub mod project;
+pub mod query;
+pub mod table;
+pub mod taskwarrior;
+pub mod tasks;
+pub mod text;
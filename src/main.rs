@@ -1,190 +1,1722 @@
-mod io;
-mod table;
-mod tasks;
+/// Fails with an error message formatted like `format!`, for the messages
+/// which are only ever shown to the person running tisk.
+macro_rules! ferror {
+    ($($arg:tt)*) => {
+        Err(TiskError::ParseError(format!($($arg)*)))
+    };
+}
 
-use clap::{App, Arg, ArgMatches};
-use log::{debug, LevelFilter};
-use log4rs;
+mod help;
+mod report;
+
+use clap::{App, AppSettings, Arg, ArgMatches};
+use log::{info, warn, LevelFilter};
 use log4rs::{
-    append::console::ConsoleAppender,
+    append::{
+        console::{ConsoleAppender, Target},
+        file::FileAppender,
+    },
     config::{Appender, Root},
+    encode::{json::JsonEncoder, pattern::PatternEncoder},
+    filter::threshold::ThresholdFilter,
 };
-use table::{TableFormatter, TableRow};
-use tasks::{Task, TaskList};
-
-/**
- * This indicates what effect executing  a command had on the task list.
- * `Read` means that the command only read from the task list and thus
- * no changes were made and nothing needs to be written.
- *
- * `Write` means that the command modified the TaskList or a Task in the
- * TaskList and the changes will need to be written to disk.
- */
-enum CommandEffect {
-    Write,
-    Read,
-    CheckoutTask(u32),
-    CheckinTask,
-}
-
-type Effects = Vec<CommandEffect>;
+use tisk::config::Config;
+use tisk::dates::{self, DateContext};
+use tisk::engine::{self, Checkout, Command, Edits, ListFilter, Output};
+use tisk::error::TiskError;
+use tisk::focus;
+use tisk::io;
+use tisk::jira;
+use tisk::meta;
+use tisk::notify::Notification;
+use tisk::query::{Condition, Query};
+use tisk::table::{self, Alignment, Column, Order, Overflow, SortKey, TableBuilder, TableFormat, TableRow, TableStyle};
+use tisk::tasks;
+use tisk::taskwarrior;
+use tisk::text::Matching;
 
 fn main() {
-    configure_logger();
-
-    let args = configure_cli().get_matches();
+    let args = help::get_matches(configure_cli());
 
     std::process::exit(match run(&args) {
         Ok(_) => 0,
         Err(err) => {
-            let preface = console::style("Error").red();
-
-            eprintln!("{}: {}", preface, err);
+            let format = match global_value(&args, "errors") {
+                Some("json") => report::ErrorFormat::Json,
+                _ => report::ErrorFormat::Text,
+            };
+            let path = find_project(&args).ok();
+            report::report(format, &err, path.as_deref());
             1
         }
     });
 }
 
-fn run(args: &ArgMatches) -> Result<(), String> {
-    if args.subcommand_matches("init").is_some() {
-        match io::initialize() {
-            Ok(io::InitResult::Initialized) => Ok(println!("Initialized directory")),
-            Ok(io::InitResult::AlreadyInitialized) => Ok(println!("Already initialized")),
-            Err(why) => ferror!("Failed to initialize tisk project: {}", why),
+fn run(args: &ArgMatches) -> Result<(), TiskError> {
+    if let Some(init_args) = args.subcommand_matches("init") {
+        configure_logger(args, None)?;
+        let options = parse_init(init_args)?;
+        let root = if global_flag(args, "global") {
+            io::home_dir().ok_or_else(|| {
+                TiskError::NotInitialized(String::from("Could not find the home directory for the global tisk project"))
+            })?
+        } else {
+            std::path::PathBuf::from(".")
+        };
+        if !global_flag(args, "global") {
+            check_nested(&root, &options, init_args.is_present("force"))?;
+        }
+        let git_ignore = init_args.value_of("git-ignore").map(str::parse).transpose()?;
+        let task_path = root.join(&options.meta.dir_name);
+        if global_flag(args, "dry-run") {
+            println!("Dry run, nothing was changed:");
+            println!("  would initialize {}", task_path.display());
+            return match git_ignore {
+                Some(ignore) => add_to_git_ignore(&task_path, ignore, true),
+                None => Ok(()),
+            };
+        }
+        match io::initialize_with(&root, &options) {
+            Ok(io::InitResult::Initialized) => println!("Initialized directory"),
+            Ok(io::InitResult::AlreadyInitialized) => println!("Already initialized"),
+            Err(why) => return Err(TiskError::io("Failed to initialize tisk project", why)),
+        }
+        match git_ignore {
+            Some(ignore) => add_to_git_ignore(&task_path, ignore, false),
+            None => Ok(()),
+        }
+    } else if let Some(ignore_args) = args.subcommand_matches("gitignore") {
+        let task_path = find_project(args)?;
+        let ignore = ignore_args.value_of("WHAT").unwrap_or("all").parse()?;
+        let dry_run = global_flag(args, "dry-run");
+        if dry_run {
+            println!("Dry run, nothing was changed:");
+        }
+        add_to_git_ignore(&task_path, ignore, dry_run)
+    } else if args.subcommand_matches("info").is_some() {
+        let task_path = find_project(args)?;
+        configure_logger(args, Some(&task_path))?;
+        print_info(&task_path)
+    } else if args.subcommand_matches("which").is_some() {
+        let task_path = find_project(args)?;
+        println!("{}", task_path.display());
+        Ok(())
+    } else if let Some(migrate_args) = args.subcommand_matches("migrate-dir") {
+        let task_path = find_project(args)?;
+        configure_logger(args, Some(&task_path))?;
+        let new_name = migrate_args.value_of("to").unwrap_or(meta::DEFAULT_DIR_NAME);
+        if global_flag(args, "dry-run") {
+            let new_path = task_path.with_file_name(new_name);
+            println!("Dry run, nothing was changed:");
+            println!("  would move {} to {}", task_path.display(), new_path.display());
+            return Ok(());
+        }
+        let config =
+            Config::read(&task_path).map_err(|err| TiskError::io("Failed to read config", err))?;
+        let change = format!("would move {} to {}", task_path.display(), task_path.with_file_name(new_name).display());
+        if !confirm(args, &config, &[change], "Move the project directory?")? {
+            println!("Nothing was changed");
+            return Ok(());
+        }
+        match io::migrate_dir(&task_path, new_name) {
+            Ok(new_path) => {
+                println!("Moved {} to {}", task_path.display(), new_path.display());
+                Ok(())
+            }
+            Err(why) => Err(TiskError::io("Failed to migrate project directory", why)),
         }
     } else {
-        match io::find_task_dir() {
-            Err(why) => Err(why),
-            Ok(task_path) => {
-                match TaskList::read_tasks(&task_path) {
-                    Err(why) => ferror!("Failed to read tasks: {}", why),
-                    Ok(mut tasks) => {
-                        // TODO: This was an experiment to look at the idea of decoupling the
-                        // application of a command to the in memory data and the act of then
-                        // writing any changes to disk.  Now that the implementation is more or
-                        // less done, think about if the design actually works.  My hypothesis
-                        // was that doing this decoupling would make it harder to fail to write
-                        // changed data to disk.
-                        //
-                        // 1. Does it make it easer to reason about the code.  What this design
-                        //    does  do is explicitly show the user what effects they can have.
-                        // 2. Does it make the code safer or more robust
-                        // 3. What risks does this design bring: the changes you make and their
-                        //    being committed are decoupled and far away, so it is hard to reason
-                        //    about them.
-                        //    - Having multiple effects which you want to make dependent would not
-                        //    work in the present design; what could be done is some kind of
-                        //    chaining where you have an effect and then something that is executed
-                        //    if the effect is successfully resolved (e.g. `(Effect::Write,
-                        //    and_then: () -> Effect)`
-
-                        // load checked out task, if one is checked out
-                        let checked_out_task =
-                            io::read_checkout(&task_path).or_else(|err| ferror!("{}", err))?;
-
-                        // Apply the given command to the in memory TaskList
-                        let effects = execute_command(&mut tasks, checked_out_task, &args)?;
-                        effects
-                            .into_iter()
-                            .map(|effect| match effect {
-                                CommandEffect::Read => Ok(()),
-                                CommandEffect::Write => {
-                                    debug!("Writing tasks");
-                                    tasks
-                                        .write_all(&task_path)
-                                        .or_else(|err| ferror!("{}", err))
-                                }
-                                CommandEffect::CheckoutTask(id) => {
-                                    debug!("Checkout task {}", id);
-                                    io::commit_checkout(id, &task_path)
-                                        .or_else(|err| ferror!("{}", err))
-                                }
-                                CommandEffect::CheckinTask => {
-                                    debug!("Checkin task");
-                                    io::commit_checkin(&task_path).or_else(|err| ferror!("{}", err))
-                                }
-                            })
-                            .collect()
+        let task_path = find_project(args)?;
+        configure_logger(args, Some(&task_path))?;
+        let config =
+            Config::read(&task_path).map_err(|err| TiskError::io("Failed to read config", err))?;
+        let config = override_config(config, args)?;
+
+        let command = match args.subcommand() {
+            ("edit", Some(edit_args)) if edit_args.is_present("editor") => edit_in_editor(&task_path, edit_args)?,
+            ("plan", Some(_)) => plan_tasks(&task_path, &config)?,
+            ("triage", Some(_)) => triage_inbox(&task_path, &config)?,
+            ("review", Some(review_args)) => review_tasks(&task_path, review_args)?,
+            ("restore", Some(restore_args)) => parse_restore(&task_path, restore_args)?,
+            ("pick", Some(pick_args)) if pick_args.is_present("then") => {
+                let mut selected = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut selected)
+                    .map_err(|err| TiskError::io("Failed to read the picked task", err))?;
+                parse_pick(pick_args, &selected)?
+            }
+            ("import", Some(import_args))
+                if import_args.value_of("from") == Some("jira") && !import_args.is_present("as-busy")
+                    || import_args.is_present("jql") =>
+            {
+                import_jira(import_args, &config)?
+            }
+            ("import", Some(import_args)) if import_args.value_of("from") == Some("taskwarrior") => {
+                import_taskwarrior(import_args)?
+            }
+            _ => parse_command(args, &config)?,
+        };
+        if global_flag(args, "dry-run") {
+            let (output, plan) = engine::dry_run(&task_path, &command)?;
+            if plan.changes.is_empty() {
+                return print_output_as(output, &config, DataFormat::from_args(args));
+            }
+            if let Output::Imported(_) = output {
+                print_output(output, &config)?;
+            }
+            println!("Dry run, nothing was changed:");
+            plan.changes.iter().for_each(|change| println!("  {}", change));
+            return Ok(());
+        }
+        let started = std::time::Instant::now();
+        let output = engine::run_approved(&task_path, &command, |plan| {
+            if plan.destructive || plan.changed_tasks.len() > config.confirm_threshold {
+                confirm(args, &config, &plan.changes, "Make these changes?")
+            } else {
+                Ok(true)
+            }
+        })?;
+        let command_name = args.subcommand_name().unwrap_or("list");
+        match output {
+            None => {
+                log_event(command_name, started.elapsed(), None);
+                println!("Nothing was changed");
+                Ok(())
+            }
+            Some((Output::Picked(task), committed)) => {
+                log_event(command_name, started.elapsed(), Some(&committed));
+                print_task_list(vec![&task], None, None, TableFormat::Table, None, false, &config)?;
+                if ask(&[], &format!("Check out task {}?", task.id()))? {
+                    print_output(engine::run(&task_path, &Command::Checkout(Checkout::Id(task.id())))?, &config)?;
+                }
+                Ok(())
+            }
+            Some((Output::Document { text, contents }, committed)) => {
+                log_event(command_name, started.elapsed(), Some(&committed));
+                match args.subcommand().1.and_then(|sub_args| sub_args.value_of("out")) {
+                    Some(out) => {
+                        std::fs::write(out, text)
+                            .map_err(|why| TiskError::io(&format!("Failed to write {}", out), why))?;
+                        println!("Exported {} to {}", contents, out);
                     }
+                    None => print!("{}", text),
                 }
+                Ok(())
+            }
+            Some((output, committed)) => {
+                log_event(command_name, started.elapsed(), Some(&committed));
+                print_output_as(output, &config, DataFormat::from_args(args))?;
+                for warning in &committed.warnings {
+                    eprintln!("{}: {}", console::style("Warning").yellow(), warning);
+                }
+                Ok(())
             }
         }
     }
 }
 
-// TODO: I kind of feel like passing this &mut TaskList into this function breaks the concept of
-// the owner determining who can modify an entity
-fn execute_command(
-    tasks: &mut TaskList,
-    checked_out_task: Option<u32>,
-    args: &ArgMatches,
-) -> Result<Effects, String> {
+/// Asks the user whether to make `changes`.  The question is not asked, and
+/// the changes are made, if `--yes` is given or the project's config turns
+/// confirmations off.  If there is no terminal to ask on then the changes
+/// are not made.
+fn confirm(args: &ArgMatches, config: &Config, changes: &[String], question: &str) -> Result<bool, TiskError> {
+    if global_flag(args, "yes") || !config.confirm {
+        return Ok(true);
+    }
+
+    if !console::Term::stderr().is_term() {
+        return ferror!("{} Rerun with --yes to confirm", question);
+    }
+    ask(changes, question)
+}
+
+/// Shows `lines` and then asks a yes or no `question` on stderr.  If there
+/// is no terminal to ask on then the answer is no.
+fn ask(lines: &[String], question: &str) -> Result<bool, TiskError> {
+    let term = console::Term::stderr();
+    if !term.is_term() {
+        return Ok(false);
+    }
+    let ask = || -> std::io::Result<String> {
+        for line in lines {
+            term.write_line(&format!("  {}", line))?;
+        }
+        term.write_str(&format!("{} [y/N] ", question))?;
+        term.read_line()
+    };
+    let answer = ask().map_err(|err| TiskError::io("Failed to read the answer", err))?;
+    Ok(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+/// Opens the task given to `edit --editor` in the user's editor and returns
+/// the command which saves the edited task.  If the edited task is not
+/// valid the user is asked whether to fix it, otherwise nothing is changed.
+fn edit_in_editor(task_path: &std::path::PathBuf, args: &ArgMatches) -> Result<Command, TiskError> {
+    let id = parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?;
+    let task = match engine::run(task_path, &Command::Show(id))? {
+        Output::Details { task, .. } => task,
+        _ => return ferror!("No task to edit"),
+    };
+    let id = task.id();
+
+    let path = std::env::temp_dir().join(format!("tisk-{}-{}.yaml", id, std::process::id()));
+    let yaml = task.to_yaml().map_err(|why| TiskError::Serde(why.to_string()))?;
+    std::fs::write(&path, yaml).map_err(|why| TiskError::io(&format!("Failed to write {}", path.display()), why))?;
+
+    let result = loop {
+        if let Err(why) = run_editor(&path) {
+            break Err(why);
+        }
+        let edited = std::fs::read_to_string(&path)
+            .map_err(|why| TiskError::io(&format!("Failed to read {}", path.display()), why))
+            .and_then(|s| tasks::Task::from_yaml(&s).map_err(|why| TiskError::Serde(why.to_string())))
+            .and_then(|edited| match edited.id() {
+                edited_id if edited_id != id => ferror!("The ID of a task cannot be changed"),
+                _ => Ok(edited),
+            });
+        match edited {
+            Ok(edited) => break Ok(Command::Replace(edited)),
+            Err(why) => {
+                if !ask(&[why.to_string()], "The edited task is not valid, edit it again?")? {
+                    break ferror!("The edited task is not valid, nothing was changed: {}", why);
+                }
+            }
+        }
+    };
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Walks through the open tasks which have no due date, highest priority
+/// first, asking for a due date and a priority for each.  Every answer is
+/// made by the one returned command, so either all of them are saved or none
+/// of them are.
+fn plan_tasks(task_path: &std::path::PathBuf, config: &Config) -> Result<Command, TiskError> {
+    let term = console::Term::stderr();
+    if !term.is_term() {
+        return ferror!("Planning asks questions and there is no terminal to ask them on");
+    }
+    let (unscheduled, busy) = match engine::run(task_path, &Command::Unplanned)? {
+        Output::Unplanned { tasks, busy } => (tasks, busy),
+        _ => (vec![], tisk::busy::BusyTime::default()),
+    };
+
+    let read = |question: &str| -> Result<String, TiskError> {
+        term.write_str(question)
+            .and_then(|_| term.read_line())
+            .map(|answer| String::from(answer.trim()))
+            .map_err(|err| TiskError::io("Failed to read the answer", err))
+    };
+
+    let dates = config.dates();
+    let describe = |event: &tisk::busy::Busy| {
+        format!("{} to {}: {}", dates.format_due(event.starts_at), dates.format_due(event.ends_at), event.summary)
+    };
+    let now = chrono::Utc::now();
+    let coming = busy.between(now, now + chrono::Duration::weeks(1));
+    if !coming.is_empty() {
+        let _ = term.write_line("Busy in the coming week:");
+        for event in coming {
+            let _ = term.write_line(&format!("  {}", describe(event)));
+        }
+    }
+
+    let mut plan = vec![];
+    'tasks: for task in unscheduled {
+        let _ = term.write_line(&format!("Task {}: {} (priority {})", task.id(), task.name(), task.priority()));
+        let mut edits = Edits::default();
+        loop {
+            let due = read("  Due (YYYY-MM-DD, today, tomorrow, mon..sun, +N days, or +Nbd; blank to skip, q to stop): ")?;
+            match due.as_str() {
+                "" => break,
+                "q" => break 'tasks,
+                due => match dates.parse_date(due) {
+                    Ok(due) => match busy.during(due) {
+                        Some(event) => {
+                            let _ = term.write_line(&format!("  That is during busy time, {}", describe(event)));
+                        }
+                        None => {
+                            edits.due_at = Some(Some(due));
+                            break;
+                        }
+                    },
+                    Err(why) => {
+                        let _ = term.write_line(&format!("  {}", why));
+                    }
+                },
+            }
+        }
+        loop {
+            match read(&format!("  Priority [{}]: ", task.priority()))?.as_str() {
+                "" => break,
+                priority => match priority.parse() {
+                    Ok(priority) => {
+                        edits.priority = Some(priority);
+                        break;
+                    }
+                    Err(_) => {
+                        let _ = term.write_line("  The priority must be an integer greater than or equal to 0");
+                    }
+                },
+            }
+        }
+        if !edits.is_empty() {
+            plan.push((task.id(), edits));
+        }
+    }
+    Ok(Command::EditMany(plan))
+}
+
+/// Walks through the open tasks in the inbox, oldest first, asking for a
+/// priority and due date for each or whether to delete it.  The tasks which
+/// are kept are taken out of the inbox.  Every decision is made by the one
+/// returned command, so either all of them are saved or none of them are.
+fn triage_inbox(task_path: &std::path::PathBuf, config: &Config) -> Result<Command, TiskError> {
+    let term = console::Term::stderr();
+    if !term.is_term() {
+        return ferror!("Triage asks questions and there is no terminal to ask them on");
+    }
+    let (matching, dates) = (config.matching(), config.dates());
+    let inbox = match engine::run(task_path, &Command::Inbox(matching))? {
+        Output::Inbox(tasks) => tasks,
+        _ => vec![],
+    };
+
+    let read = |question: &str| -> Result<String, TiskError> {
+        term.write_str(question)
+            .and_then(|_| term.read_line())
+            .map(|answer| String::from(answer.trim()))
+            .map_err(|err| TiskError::io("Failed to read the answer", err))
+    };
+
+    let mut decisions = vec![];
+    'tasks: for task in inbox {
+        let _ = term.write_line(&format!("Task {}: {}", task.id(), task.name()));
+        let mut edits = Edits { matching, ..Edits::default() };
+        loop {
+            match read("  Priority (blank to keep it, d to delete, s to skip, q to stop): ")?.as_str() {
+                "" => break,
+                "d" => {
+                    decisions.push((task.id(), engine::Triage::Delete));
+                    continue 'tasks;
+                }
+                "s" => continue 'tasks,
+                "q" => break 'tasks,
+                priority => match priority.parse() {
+                    Ok(priority) => {
+                        edits.priority = Some(priority);
+                        break;
+                    }
+                    Err(_) => {
+                        let _ = term.write_line("  The priority must be an integer greater than or equal to 0");
+                    }
+                },
+            }
+        }
+        loop {
+            match read("  Due (YYYY-MM-DD, today, tomorrow, mon..sun, +N days, or +Nbd; blank for none): ")?.as_str() {
+                "" => break,
+                due => match dates.parse_date(due) {
+                    Ok(due) => {
+                        edits.due_at = Some(Some(due));
+                        break;
+                    }
+                    Err(why) => {
+                        let _ = term.write_line(&format!("  {}", why));
+                    }
+                },
+            }
+        }
+        decisions.push((task.id(), engine::Triage::Keep(edits)));
+    }
+    Ok(Command::Triage(decisions))
+}
+
+/// Walks through the open tasks which nothing has been done with for the
+/// number of days given by `--days`, least recently touched first, asking
+/// whether to keep, reprioritize, close, or delete each.  Every decision is
+/// made by the one returned command, so either all of them are saved or none
+/// of them are.
+fn review_tasks(task_path: &std::path::PathBuf, args: &ArgMatches) -> Result<Command, TiskError> {
+    let invalid = |_| TiskError::ParseError(String::from("The number of days must be an integer"));
+    let days: i64 = args.value_of("days").unwrap().parse().map_err(invalid)?;
+    let term = console::Term::stderr();
+    if !term.is_term() {
+        return ferror!("Reviewing asks questions and there is no terminal to ask them on");
+    }
+    let (stale, open_subtasks) = match engine::run(task_path, &Command::Stale { days })? {
+        Output::Stale { tasks, open_subtasks } => (tasks, open_subtasks),
+        _ => (vec![], vec![]),
+    };
+    let now = chrono::Utc::now();
+    if stale.is_empty() {
+        let _ = term.write_line(&format!("No open tasks have gone {} days without being touched", days));
+    }
+
+    let read = |question: &str| -> Result<String, TiskError> {
+        term.write_str(question)
+            .and_then(|_| term.read_line())
+            .map(|answer| String::from(answer.trim()))
+            .map_err(|err| TiskError::io("Failed to read the answer", err))
+    };
+
+    let mut decisions = vec![];
+    'tasks: for task in stale {
+        let untouched = (now - task.last_touched()).num_days();
+        let _ = term.write_line(&format!(
+            "Task {}: {} (priority {}, untouched for {} days)",
+            task.id(),
+            task.name(),
+            task.priority(),
+            untouched
+        ));
+        loop {
+            let answer = read("  Keep (blank), new priority, c to close, d to delete, s to skip, q to stop: ")?;
+            let decision = match answer.as_str() {
+                "" => engine::Review::Keep,
+                "c" if open_subtasks.contains(&task.id()) => {
+                    let _ = term.write_line("  The task has open subtasks, close them first");
+                    continue;
+                }
+                "c" => engine::Review::Close,
+                "d" => engine::Review::Delete,
+                "s" => continue 'tasks,
+                "q" => break 'tasks,
+                priority => match priority.parse() {
+                    Ok(priority) => engine::Review::Reprioritize(priority),
+                    Err(_) => {
+                        let _ = term.write_line("  The priority must be an integer greater than or equal to 0");
+                        continue;
+                    }
+                },
+            };
+            decisions.push((task.id(), decision));
+            break;
+        }
+    }
+    Ok(Command::Review(decisions))
+}
+
+/// Reads the issues to import from Jira, either from a CSV file exported
+/// from Jira or by searching a Jira site with `--jql`.
+fn import_jira(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let issues = match args.value_of("jql") {
+        Some(jql) => {
+            let url = args.value_of("jira-url").or(config.jira.url.as_deref()).ok_or_else(|| {
+                TiskError::ParseError(String::from(
+                    "There is no Jira site to search, give --jira-url or set jira.url in the config",
+                ))
+            })?;
+            jira::search(&jira::Curl::from_env(url)?, jql, config.timezone)?
+        }
+        None => {
+            let file = args.value_of("FILE").unwrap();
+            let s =
+                std::fs::read_to_string(file).map_err(|why| TiskError::io(&format!("Failed to read {}", file), why))?;
+            jira::parse_csv(&s, config.timezone)?
+        }
+    };
+    Ok(Command::ImportJira { issues, mapping: config.jira.clone() })
+}
+
+/// Imports the tasks in the JSON printed by Taskwarrior's `task export`,
+/// either an array of tasks or, as older versions print, a task on each
+/// line.
+fn import_taskwarrior(args: &ArgMatches) -> Result<Command, TiskError> {
+    let file = args.value_of("FILE").unwrap();
+    let s = std::fs::read_to_string(file).map_err(|why| TiskError::io(&format!("Failed to read {}", file), why))?;
+    let invalid = |why: serde_json::Error| TiskError::Serde(format!("Invalid Taskwarrior tasks: {}", why));
+    let exported: Vec<taskwarrior::Exported> = match s.trim_start().starts_with('[') {
+        true => serde_json::from_str(&s).map_err(invalid)?,
+        false => s
+            .lines()
+            .map(|line| line.trim().trim_end_matches(','))
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(line).map_err(invalid))
+            .collect::<Result<_, _>>()?,
+    };
+    Ok(Command::ImportTaskwarrior(exported))
+}
+
+/// Opens `path` in the editor named by `$VISUAL` or `$EDITOR`, falling back
+/// to `vi`, and waits for it to exit.
+fn run_editor(path: &std::path::Path) -> Result<(), TiskError> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|why| TiskError::io(&format!("Failed to start the editor {}", program), why))?;
+    if !status.success() {
+        let context = format!("The editor {} exited with {}, nothing was changed", program, status);
+        return Err(TiskError::External { context });
+    }
+    Ok(())
+}
+
+/// Checks whether a project initialized at `root` would be nested inside
+/// the project of a parent directory, which commands run in `root` use
+/// until then.  Nesting a project needs `force`, and is warned about.
+fn check_nested(root: &std::path::Path, options: &io::InitOptions, force: bool) -> Result<(), TiskError> {
+    if root.join(&options.meta.dir_name).is_dir() {
+        return Ok(());
+    }
+    let boundaries = io::boundaries_from_env()?;
+    let enclosing = io::enclosing_project_dir(root, &boundaries)
+        .map_err(|why| TiskError::io("Failure while searching for a project in a parent directory", why))?;
+    let (path, name) = match enclosing {
+        None => return Ok(()),
+        Some(path) => {
+            let meta =
+                meta::Meta::read(&path).map_err(|why| TiskError::io(&format!("Failed to read {}", path.display()), why))?;
+            let name = meta.project_name(&path);
+            (path, name)
+        }
+    };
+    if force {
+        eprintln!("{}: creating a project inside {} at {}", console::style("Warning").yellow(), name, path.display());
+        return Ok(());
+    }
+    Err(TiskError::Conflict(format!(
+        "There is already a project, {}, at {} which commands run here use.  Use --force to create a nested project",
+        name,
+        path.display()
+    )))
+}
+
+/// Prints what is known about the project whose directory is `task_path`.
+fn print_info(task_path: &std::path::PathBuf) -> Result<(), TiskError> {
+    let meta = meta::Meta::read(task_path).map_err(|why| TiskError::io("Failed to read the project's metadata", why))?;
+    let tasks = tasks::TaskList::read_tasks(task_path).map_err(|why| TiskError::io("Failed to read tasks", why))?;
+    let config = Config::read(task_path).map_err(|err| TiskError::io("Failed to read config", err))?;
+    let created = match meta.created_at {
+        Some(created_at) => config.dates().format_time(created_at),
+        None => String::from("unknown"),
+    };
+
+    println!("Name:       {}", meta.project_name(task_path));
+    println!("Directory:  {}", task_path.display());
+    println!("Created:    {}", created);
+    println!("Storage:    {} {}, schema version {}", meta.backend, meta.layout, meta.schema_version);
+    println!("Tasks:      {} open, {} closed", tasks.get_open().len(), tasks.get_closed().len());
+    println!("Last ID:    {}", tasks.next_id() - 1);
+    Ok(())
+}
+
+/// Adds the lines which keep `ignore` of the project directory `task_path`
+/// out of git to the repository's `.gitignore`, or only shows them in a dry
+/// run.
+fn add_to_git_ignore(task_path: &std::path::Path, ignore: io::GitIgnore, dry_run: bool) -> Result<(), TiskError> {
+    let (file, lines) =
+        io::git_ignore_lines(task_path, ignore).map_err(|why| TiskError::io("Failed to read .gitignore", why))?;
+    if lines.is_empty() {
+        println!("{} already ignores the project", file.display());
+        return Ok(());
+    }
+    if dry_run {
+        println!("  would add {} to {}", lines.join(", "), file.display());
+        return Ok(());
+    }
+    io::append_git_ignore(&file, &lines)
+        .map_err(|why| TiskError::io(&format!("Failed to write {}", file.display()), why))?;
+    println!("Added {} to {}", lines.join(", "), file.display());
+    Ok(())
+}
+
+/// Finds the project the command applies to.  This is the project given by
+/// `--dir`, or the global project if `--global` is given, otherwise
+/// `io::find_task_dir` looks for it.
+fn find_project(args: &ArgMatches) -> Result<std::path::PathBuf, TiskError> {
+    let global = global_flag(args, "global");
+    match global_value(args, "dir") {
+        Some(_) if global => ferror!("Cannot use --dir and --global at the same time"),
+        Some(dir) => io::task_dir_at(dir),
+        None if global => io::find_global_task_dir(),
+        None => io::find_task_dir(),
+    }
+}
+
+/// The value of the global argument `name`.  Global arguments may be given
+/// before or after the subcommand.
+fn global_value<'a>(args: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    args.subcommand()
+        .1
+        .and_then(|sub| sub.value_of(name))
+        .or_else(|| args.value_of(name))
+}
+
+/// Whether the global flag `name` was given, before or after the subcommand.
+fn global_flag(args: &ArgMatches, name: &str) -> bool {
+    args.is_present(name) || args.subcommand().1.map(|sub| sub.is_present(name)).unwrap_or(false)
+}
+
+/// Applies any settings given on the command line on top of the project's config.
+fn override_config(config: Config, args: &ArgMatches) -> Result<Config, TiskError> {
+    let style = match global_value(args, "style") {
+        Some(style) => style.parse::<TableStyle>()?,
+        None => config.table_style,
+    };
+    let wrap_names = match args.subcommand_matches("list") {
+        Some(list) if list.is_present("nowrap") => false,
+        Some(list) if list.is_present("wrap") => true,
+        _ => config.wrap_names,
+    };
+    let list = args.subcommand_matches("list");
+    Ok(Config {
+        table_style: style,
+        list_header: config.list_header && !list.map(|list| list.is_present("no-header")).unwrap_or(false),
+        list_footer: config.list_footer && !list.map(|list| list.is_present("no-footer")).unwrap_or(false),
+        wrap_names,
+        ..config
+    })
+}
+
+/// Turns the command line arguments into the `Command` they describe.  If no
+/// subcommand is given then the open tasks are listed.
+fn parse_command(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
     match args.subcommand() {
-        ("add", Some(args)) => handle_add(tasks, args),
-        ("close", Some(args)) => handle_close(tasks, checked_out_task, args),
-        ("edit", Some(args)) => handle_edit(tasks, checked_out_task, args),
-        ("note", Some(args)) => handle_note(tasks, checked_out_task, args),
-        ("checkout", Some(args)) => handle_checkout(tasks, args),
-        ("checkin", Some(_)) => handle_checkin(),
-        ("list", Some(args)) => handle_list(tasks, checked_out_task, args),
-        _ => handle_list(tasks, checked_out_task, &ArgMatches::new()),
+        ("add", Some(args)) => parse_add(args, config),
+        ("in", Some(args)) => Ok(Command::Add {
+            name: String::from(args.value_of("input").unwrap()),
+            priority: 1,
+            note: None,
+            tags: vec![String::from(engine::INBOX_TAG)],
+            due_at: None,
+            parent: None,
+        }),
+        ("close", Some(args)) => parse_close(args),
+        ("start", Some(args)) => {
+            Ok(Command::Start(parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?))
+        }
+        ("block", Some(args)) => Ok(Command::Block {
+            id: parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?,
+            reason: args.value_of("reason").map(String::from),
+        }),
+        ("edit", Some(args)) => parse_edit(args, config),
+        ("note", Some(args)) if args.is_present("export") => {
+            Ok(Command::ExportNotes(Some(parse_id(args.value_of("export").unwrap())?)))
+        }
+        ("note", Some(args)) => parse_note(args),
+        ("checkout", Some(args)) => parse_checkout(args, config),
+        ("checkin", Some(_)) => Ok(Command::Checkin),
+        ("list", Some(args)) => parse_list(args, config),
+        ("expire", Some(_)) => Ok(Command::Expire),
+        ("archive", Some(args)) => Ok(Command::Archive {
+            older_than: dates::parse_age(args.value_of("older-than").unwrap())?,
+        }),
+        ("import", Some(args)) => parse_import(args, config),
+        ("backlinks", Some(args)) => match parse_integer_arg(args.value_of("ID")) {
+            Ok(Some(id)) => Ok(Command::Backlinks(id)),
+            _ => ferror!("The task ID must be an integer"),
+        },
+        ("delete", Some(args)) => parse_delete(args),
+        ("tag", Some(args)) => parse_tag(args, config, true),
+        ("untag", Some(args)) => parse_tag(args, config, false),
+        ("tags", Some(_)) => Ok(Command::Tags),
+        ("roulette", Some(args)) => parse_roulette(args, config),
+        ("depend", Some(args)) => Ok(Command::Depend {
+            id: parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?,
+            on: args.value_of("on").unwrap().split(',').map(|id| parse_id(id.trim())).collect::<Result<_, _>>()?,
+        }),
+        ("show", Some(args)) => {
+            Ok(Command::Show(parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?))
+        }
+        ("time", Some(args)) => {
+            Ok(Command::Time(parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?))
+        }
+        ("remind", Some(args)) => Ok(Command::Remind {
+            id: parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?,
+            at: config.dates().parse_date(args.value_of("at").unwrap())?,
+        }),
+        ("notify", Some(_)) => Ok(Command::Notify),
+        ("compact", Some(_)) => Ok(Command::Compact(config.limits())),
+        ("graph", Some(args)) => {
+            Ok(Command::Graph(parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?))
+        }
+        ("focus-stats", Some(_)) => Ok(Command::FocusStats),
+        ("pick", Some(_)) => Ok(Command::Pick { policy: config.sort_policy }),
+        ("export", Some(args)) => {
+            // Unlike list, export has open and closed tasks unless the flags narrow them
+            let mut query = Query::new(ListFilter::All);
+            query.matching = config.matching();
+            parse_conditions(args, &mut query, config)?;
+            let format = args.value_of("format").unwrap_or("markdown").parse()?;
+            Ok(Command::Export { query, format, policy: config.sort_policy })
+        }
+        ("__complete", Some(args)) => Ok(Command::Complete(args.value_of("KIND").unwrap().parse()?)),
+        _ => parse_list(&ArgMatches::new(), config),
+    }
+}
+
+fn parse_add(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let name = args.value_of("input").unwrap();
+    let (priority, note, tags, due_at, parent) = parse_new_task(args, config)?;
+    Ok(Command::Add {
+        name: String::from(name),
+        priority,
+        note,
+        tags,
+        due_at,
+        parent,
+    })
+}
+
+/// The priority, first note, tags, due date, and parent of a new task.
+type NewTask = (u32, Option<String>, Vec<String>, Option<chrono::DateTime<chrono::Utc>>, Option<u32>);
+
+/// Parses the flags, from `new_task_args`, which set up a new task.  If
+/// there is no `--priority` the task's tags may give it one in `config`.
+fn parse_new_task(args: &ArgMatches, config: &Config) -> Result<NewTask, TiskError> {
+    let tags: Vec<String> = args
+        .values_of("tag")
+        .map(|v| v.map(String::from).collect())
+        .unwrap_or_default();
+    let priority = match parse_integer_arg(args.value_of("priority")) {
+        Err(_) => {
+            return ferror!("Invalid priority value: must be an integer greater than or equal to 0")
+        }
+        Ok(p) => p.or_else(|| config.default_priority(&tags)).unwrap_or(1),
+    };
+    let due_at = args.value_of("due").map(|due| config.dates().parse_date(due)).transpose()?;
+    let parent = parse_integer_arg(args.value_of("parent"))
+        .map_err(|_| TiskError::ParseError(String::from("The parent task ID must be an integer")))?;
+    Ok((priority, args.value_of("note").map(String::from), tags, due_at, parent))
+}
+
+fn parse_import(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let file = args.value_of("FILE").unwrap();
+    let s = std::fs::read_to_string(file).map_err(|why| TiskError::io(&format!("Failed to read {}", file), why))?;
+    match (args.value_of("from"), args.is_present("as-busy")) {
+        (Some("ics"), true) => return Ok(Command::ImportBusy(tisk::busy::parse_ics(&s, config.timezone)?)),
+        (Some("ics"), false) => return ferror!("Calendars can only be imported as busy time, with --as-busy"),
+        (_, true) => return ferror!("Only calendars can be imported as busy time, with --from ics"),
+        _ => (),
+    }
+    let project: tisk::project::Project =
+        serde_yaml::from_str(&s).map_err(|why| TiskError::Serde(format!("Failed to parse {}: {}", file, why)))?;
+
+    let policy = match (args.value_of("ids"), args.value_of("on-conflict")) {
+        (Some("renumber"), _) => tasks::IdPolicy::Renumber,
+        (_, Some("skip")) => tasks::IdPolicy::Keep(tasks::Conflict::Skip),
+        (_, Some("replace")) => tasks::IdPolicy::Keep(tasks::Conflict::Replace),
+        (_, Some("renumber")) => tasks::IdPolicy::Keep(tasks::Conflict::Renumber),
+        _ => tasks::IdPolicy::Keep(tasks::Conflict::Fail),
+    };
+    Ok(Command::Import {
+        tasks: project.tasks.get_all().into_iter().cloned().collect(),
+        policy,
+    })
+}
+
+fn parse_close(args: &ArgMatches) -> Result<Command, TiskError> {
+    let id = parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?;
+    Ok(Command::Close {
+        id,
+        note: args.value_of("note").map(String::from),
+        follow_up: args.value_of("follow-up").map(String::from),
+        cascade: args.is_present("cascade"),
+    })
+}
+
+fn parse_checkout(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    if args.is_present("ID") && args.is_present("add") {
+        return ferror!("Cannot have an ID and the --add flag set at the same time");
+    } else if !args.is_present("ID") && !args.is_present("add") {
+        return ferror!("Must specify either an ID to checkout or `--add` to add a new task");
+    }
+
+    let checkout = match args.value_of("add") {
+        Some(task) => {
+            let (priority, note, tags, due_at, parent) = parse_new_task(args, config)?;
+            Checkout::Add {
+                name: String::from(task),
+                priority,
+                note,
+                tags,
+                due_at,
+                parent,
+            }
+        }
+        None => match parse_integer_arg(args.value_of("ID")) {
+            Err(_) => {
+                return ferror!(
+                    "Invalid ID provided, must be an integer greater than or equal to 0"
+                )
+            }
+            Ok(None) => return ferror!("No ID provided"),
+            Ok(Some(id)) => Checkout::Id(id),
+        },
+    };
+    Ok(Command::Checkout(checkout))
+}
+
+fn parse_edit(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let id = parse_integer_arg(args.value_of("ID")).map_err(|e| TiskError::ParseError(e.to_string()))?;
+    let priority = match parse_integer_arg(args.value_of("priority")) {
+        Err(_) => {
+            return ferror!("Invalid priority value: must be an integer greater than or equal to 0")
+        }
+        Ok(p) => p,
+    };
+    let dates = config.dates();
+    let date = |name| -> Result<Option<Option<_>>, TiskError> {
+        match args.value_of(name) {
+            None => Ok(None),
+            Some("none") => Ok(Some(None)),
+            Some(date) => Ok(Some(Some(dates.parse_date(date)?))),
+        }
+    };
+    let values = |name| args.values_of(name).map(|v| v.map(String::from).collect()).unwrap_or_default();
+
+    let edits = Edits {
+        name: args.value_of("name").map(String::from),
+        priority,
+        due_at: date("due")?,
+        add_tags: values("tag"),
+        remove_tags: values("untag"),
+        assignee: args
+            .value_of("assignee")
+            .map(|a| if a == "none" { None } else { Some(String::from(a)) }),
+        expires_at: date("expires")?,
+        matching: config.matching(),
+    };
+    Ok(Command::Edit { id, edits })
+}
+
+/// The note command takes an optional ID and note as positional arguments,
+/// e.g. `tisk note 42 "text"`, the ID may instead be given with `--id` or
+/// `--task`.  A single positional integer is taken as the ID of the task
+/// whose notes are shown.  With no ID the checked out task is used.
+fn parse_note(args: &ArgMatches) -> Result<Command, TiskError> {
+    let flag_id = match args.value_of("ID").or_else(|| args.value_of("task")) {
+        Some(id) => Some(parse_id(id)?),
+        None => None,
+    };
+    let values: Vec<&str> = args.values_of("ARGS").map(|v| v.collect()).unwrap_or_default();
+
+    if args.is_present("ids") || args.is_present("filter") {
+        let note = match values.as_slice() {
+            [note] => String::from(*note),
+            _ => return ferror!("The note must be the only argument when --ids or --filter is given"),
+        };
+        let ids = match args.value_of("ids") {
+            Some(ids) => ids.split(',').map(|id| parse_id(id.trim())).collect::<Result<Vec<u32>, TiskError>>()?,
+            None => vec![],
+        };
+        let query = match args.values_of("filter") {
+            Some(conditions) => {
+                let mut query = Query::new(ListFilter::Open);
+                for condition in conditions {
+                    query.conditions.push(condition.parse()?);
+                }
+                Some(query)
+            }
+            None => None,
+        };
+        return Ok(Command::NoteMany { ids, query, note });
+    }
+
+    let (id, note) = match (flag_id, values.as_slice()) {
+        (Some(_), [_, _]) => return ferror!("The task ID was given both as a flag and as an argument"),
+        (Some(id), [note]) => (Some(id), Some(*note)),
+        (Some(id), _) => (Some(id), None),
+        (None, [id, note]) => (Some(parse_id(id)?), Some(*note)),
+        (None, [value]) => match value.parse::<u32>() {
+            Ok(id) => (Some(id), None),
+            Err(_) => (None, Some(*value)),
+        },
+        (None, _) => (None, None),
+    };
+
+    if args.is_present("list") && note.is_some() {
+        return ferror!("Cannot give a note and the --list flag at the same time");
+    }
+    Ok(Command::Note {
+        id,
+        note: note.map(String::from),
+    })
+}
+
+
+
+
+/// The command which `tisk pick --then` runs on the task picked from the
+/// lines `tisk pick` printed, `selected` being the line the picker wrote.
+fn parse_pick(args: &ArgMatches, selected: &str) -> Result<Command, TiskError> {
+    let picked = selected.lines().map(str::trim).find(|line| !line.is_empty());
+    let line = picked.ok_or_else(|| TiskError::ParseError(String::from("No task was picked")))?;
+    let id = parse_id(line.split('\t').next().unwrap_or(line).trim())?;
+    match args.value_of("then").unwrap() {
+        "checkout" => Ok(Command::Checkout(Checkout::Id(id))),
+        "close" => Ok(Command::Close { id: Some(id), note: None, follow_up: None, cascade: false }),
+        _ => Ok(Command::Show(Some(id))),
+    }
+}
+
+fn parse_delete(args: &ArgMatches) -> Result<Command, TiskError> {
+    let id = parse_integer_arg(args.value_of("ID"))
+        .map_err(|_| TiskError::ParseError(String::from("The task ID must be an integer")))?;
+    let orphans = match args.value_of("subtasks") {
+        Some(orphans) => orphans.parse()?,
+        None => tasks::Orphans::Orphan,
+    };
+    Ok(Command::Delete { id, orphans })
+}
+
+/// Takes the task to restore out of the trash of the project whose `.tisk`
+/// directory is `task_path`.
+fn parse_restore(task_path: &std::path::Path, args: &ArgMatches) -> Result<Command, TiskError> {
+    let id = match parse_integer_arg(args.value_of("ID")) {
+        Ok(Some(id)) => id,
+        _ => return ferror!("The task ID must be an integer"),
+    };
+    let task = io::read_trashed(task_path, id).map_err(|err| TiskError::io("Failed to read the trash", err))?;
+    Ok(Command::Restore(task))
+}
+
+/// Parses `tisk tag` or, if `add` is not set, `tisk untag` as an edit of
+/// the task's tags.
+fn parse_tag(args: &ArgMatches, config: &Config, add: bool) -> Result<Command, TiskError> {
+    let id = match parse_integer_arg(args.value_of("ID")) {
+        Ok(id) => id,
+        Err(_) => return ferror!("The task ID must be an integer"),
+    };
+    let tags: Vec<String> = args.values_of("TAG").unwrap().map(String::from).collect();
+    let mut edits = Edits { matching: config.matching(), ..Edits::default() };
+    if add {
+        edits.add_tags = tags;
+    } else {
+        edits.remove_tags = tags;
+    }
+    Ok(Command::Edit { id, edits })
+}
+
+fn parse_list(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let filter = if args.is_present("all") {
+        ListFilter::All
+    } else if args.is_present("closed") || args.is_present("archived") {
+        ListFilter::Closed
+    } else if args.is_present("in-progress") {
+        ListFilter::InProgress
+    } else if args.is_present("blocked") {
+        ListFilter::Blocked
+    } else {
+        ListFilter::Open
+    };
+    let order = if args.is_present("desc") { Order::Desc } else { Order::Asc };
+    let format = match args.value_of("format") {
+        Some("json") | Some("yaml") | None => TableFormat::Table,
+        Some(format) => format.parse()?,
+    };
+
+    let mut query = Query::new(filter);
+    query.contains = args.value_of("contains").map(String::from);
+    query.matching = config.matching();
+    parse_conditions(args, &mut query, config)?;
+    if let Some(id) = args.value_of("blocked-by") {
+        query.conditions.push(Condition::BlockedBy(parse_id(id)?));
+    }
+
+    Ok(Command::List {
+        query,
+        policy: config.sort_policy,
+        sort: args.value_of("sort").map(|column| (String::from(column), order)),
+        format,
+        archived: args.is_present("archived"),
+    })
+}
+
+/// Narrows `query` with the `--tag`, `--filter`, and `--since` flags, which
+/// `list` and `export` share.
+fn parse_conditions(args: &ArgMatches, query: &mut Query, config: &Config) -> Result<(), TiskError> {
+    for tag in args.values_of("tag").into_iter().flatten() {
+        query.conditions.push(Condition::Tag(String::from(tag)));
+    }
+    for condition in args.values_of("filter").into_iter().flatten() {
+        query.conditions.push(condition.parse()?);
+    }
+    if let Some(since) = args.value_of("since") {
+        query.since = Some(config.dates().parse_date(since)?);
+    }
+    Ok(())
+}
+
+fn parse_roulette(args: &ArgMatches, config: &Config) -> Result<Command, TiskError> {
+    let mut query = Query::new(ListFilter::Open);
+    query.matching = config.matching();
+    for tag in args.values_of("tag").into_iter().flatten() {
+        query.conditions.push(Condition::Tag(String::from(tag)));
+    }
+    for condition in args.values_of("filter").into_iter().flatten() {
+        query.conditions.push(condition.parse()?);
+    }
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+    Ok(Command::Roulette { query, seed: now.as_nanos() as u64 })
+}
+
+/// Shows the output of a command to the user.
+/// The formats `--format json` and `--format yaml` print a command's output
+/// in for scripts, instead of the tables printed for people.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DataFormat {
+    Json,
+    Yaml,
+}
+
+impl DataFormat {
+    /// The data format which the command in `args` is asked to print in, if
+    /// it is asked for one.  Only `list`, `note`, and `show` print data.
+    fn from_args(args: &ArgMatches) -> Option<DataFormat> {
+        let format = match args.subcommand_name() {
+            Some("list") | Some("note") | Some("show") => global_value(args, "format"),
+            _ => None,
+        };
+        match format {
+            Some("json") => Some(DataFormat::Json),
+            Some("yaml") => Some(DataFormat::Yaml),
+            _ => None,
+        }
+    }
+
+    fn render<T: serde::Serialize>(self, value: &T) -> Result<String, TiskError> {
+        match self {
+            DataFormat::Json => serde_json::to_string_pretty(value).map_err(|why| TiskError::Serde(why.to_string())),
+            DataFormat::Yaml => serde_yaml::to_string(value).map_err(|why| TiskError::Serde(why.to_string())),
+        }
+    }
+}
+
+/// A task as `tisk show --format json` prints it, every field of the task
+/// along with whether it is checked out.
+#[derive(serde::Serialize)]
+struct Details<'a> {
+    #[serde(flatten)]
+    task: &'a tasks::Task,
+    checked_out: bool,
+}
+
+/// Prints the data in `output` in `format`, e.g. the tasks which were
+/// listed.  Output without data, or without a `format`, is printed by
+/// `print_output`.
+fn print_output_as(output: Output, config: &Config, format: Option<DataFormat>) -> Result<(), TiskError> {
+    use std::io::Write;
+    let data = match (format, &output) {
+        (Some(format), Output::Tasks { tasks, .. }) => format.render(tasks)?,
+        (Some(format), Output::Notes(task)) => format.render(&task.notes())?,
+        (Some(format), Output::Details { task, checked_out }) => {
+            format.render(&Details { task, checked_out: *checked_out })?
+        }
+        _ => return print_output(output, config),
+    };
+    writeln!(std::io::stdout(), "{}", data.trim_end()).map_err(|err| TiskError::io("Failed to print the output", err))
+}
+
+fn print_output(output: Output, config: &Config) -> Result<(), TiskError> {
+    match output {
+        Output::Nothing | Output::Added(_) => Ok(()),
+        Output::Message(msg) => {
+            println!("{}", msg);
+            Ok(())
+        }
+        Output::Tasks {
+            tasks,
+            checked_out,
+            sort,
+            format,
+            highlight,
+            summary,
+            time,
+        } => {
+            let sort = sort.as_ref().map(|(column, order)| (column.as_str(), *order));
+            let highlight = highlight.as_ref().map(|(text, matching)| (text.as_str(), *matching));
+            print_task_list(tasks.iter().collect(), checked_out, sort, format, highlight, time, config)?;
+            if format == TableFormat::Table && config.list_footer {
+                println!("{}", footer(tasks.len(), &summary));
+            }
+            Ok(())
+        }
+        Output::Picked(task) => print_task_list(vec![&task], None, None, TableFormat::Table, None, false, config),
+        Output::Notes(task) => {
+            print_task_list(vec![&task], None, None, TableFormat::Table, None, false, config)?;
+            print_notes(task.notes(), config)
+        }
+        Output::Edited { before, after } => print_edit(&before, &after, config),
+        Output::Imported(report) => print_import(&report, config),
+        Output::Values(values) => {
+            for value in values {
+                println!("{}", value);
+            }
+            Ok(())
+        }
+        Output::Tags(counts) => print_tags(&counts, config),
+        Output::Graph(rows) => print_graph(&rows, config),
+        Output::Details { task, checked_out } => print_details(&task, checked_out, config),
+        Output::Time(task) => print_time(&task, config),
+        Output::Reminders(due) => deliver_reminders(&due, config),
+        Output::Suggestions(suggestions) if suggestions.is_empty() => {
+            println!("Nothing to compact, the project is within its limits");
+            Ok(())
+        }
+        Output::Suggestions(suggestions) => {
+            for suggestion in suggestions {
+                println!("- {}", suggestion);
+            }
+            Ok(())
+        }
+        Output::Focus { since, stats, tasks } => print_focus_stats(since, &stats, &tasks, config),
+        Output::Unplanned { tasks, .. } | Output::Inbox(tasks) | Output::Stale { tasks, .. } => {
+            print_task_list(tasks.iter().collect(), None, None, TableFormat::Table, None, false, config)
+        }
+        Output::Picks(tasks) => print_picks(&tasks),
+        Output::Document { text, .. } => {
+            print!("{}", text);
+            Ok(())
+        }
     }
 }
 
+/// Prints a line for each of `tasks`, its ID and name separated by a tab,
+/// for a picker such as fzf to choose from.
+fn print_picks(tasks: &[tasks::Task]) -> Result<(), TiskError> {
+    use std::io::Write;
+    let mut out = std::io::stdout().lock();
+    for task in tasks {
+        let name = task.name().replace(char::is_whitespace, " ");
+        writeln!(out, "{}\t{}", task.id(), name).map_err(|err| TiskError::io("Failed to print tasks", err))?;
+    }
+    Ok(())
+}
+
+/// Delivers each of the reminders in `due` with the project's notifier.
+/// Every reminder is tried, even after one fails, and the first failure is
+/// returned.
+fn deliver_reminders(due: &[(chrono::DateTime<chrono::Utc>, tasks::Task)], config: &Config) -> Result<(), TiskError> {
+    if due.is_empty() {
+        return Ok(());
+    }
+    let notifier = config.notifier()?;
+    let mut result = Ok(());
+    for (_, task) in due {
+        let notification = Notification::new(&format!("Reminder: task {}", task.id()), task.name());
+        if let Err(err) = notifier.notify(&notification) {
+            result = result.and(Err(err));
+        }
+    }
+    result
+}
+
+/// Prints every detail of `task` in sections: its name, then its
+/// properties and how it is related to other tasks, then its notes.
+fn print_details(task: &tasks::Task, checked_out: bool, config: &Config) -> Result<(), TiskError> {
+    let ids = |ids: &[u32]| ids.iter().map(|id| format!("#{}", id)).collect::<Vec<String>>().join(", ");
+    let dates = config.dates();
+    let mut fields = vec![
+        ("Status", if checked_out { format!("{}, checked out", task.status()) } else { task.status().to_string() }),
+        ("Priority", task.priority().to_string()),
+        ("Created", dates.format_time(task.created_at())),
+    ];
+    let reminders: Vec<String> = task
+        .reminders()
+        .iter()
+        .filter(|reminder| reminder.delivered_at.is_none())
+        .map(|reminder| dates.format_time(reminder.at))
+        .collect();
+    let optional = [
+        ("Closed", task.closed_at().map(|closed| dates.format_time(closed))),
+        ("Due", task.due_at().map(|due| dates.format_due(due))),
+        ("Expires", task.expires_at().map(|expires| dates.format_day(expires))),
+        ("Tags", Some(task.tags().join(", ")).filter(|tags| !tags.is_empty())),
+        ("Assignee", task.assignee().map(String::from)),
+        ("Subtask of", task.parent().map(|parent| ids(&[parent]))),
+        ("Depends on", Some(ids(task.depends_on())).filter(|ids| !ids.is_empty())),
+        ("Reminders", Some(reminders.join(", ")).filter(|reminders| !reminders.is_empty())),
+    ];
+    fields.extend(optional.iter().cloned().filter_map(|(name, value)| value.map(|value| (name, value))));
+    fields.extend(task.metadata().iter().map(|(key, value)| ("From", format!("{} {}", key, value))));
+
+    println!("Task {}: {}", task.id(), task.name());
+    println!();
+    for (name, value) in fields {
+        println!("{:<12}{}", format!("{}:", name), value);
+    }
+    println!();
+    if task.notes().is_empty() {
+        println!("No notes");
+        return Ok(());
+    }
+    println!("Notes:");
+    print_notes(task.notes(), config)
+}
+
+/// Prints how long `task` has been checked out for in total, and then when
+/// and for how long each time it was checked out.
+fn print_time(task: &tasks::Task, config: &Config) -> Result<(), TiskError> {
+    let now = chrono::Utc::now();
+    println!("Task {}: {}", task.id(), task.name());
+    if task.time_entries().is_empty() {
+        println!("Not checked out yet");
+        return Ok(());
+    }
+    println!("Total: {}", format_duration(task.time_spent(now)));
+    println!();
+
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("Started", Some(16)),
+            Column::new("Ended", Some(16)),
+            Column::new("Time", Some(8)).align(Alignment::Right),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
+    let dates = config.dates();
+    let rows = task.time_entries().iter().map(|entry| {
+        let mut row = TableRow::new();
+        row.push_string(dates.format_time(entry.started_at));
+        match entry.ended_at {
+            Some(ended_at) => row.push_string(dates.format_time(ended_at)),
+            None => row.push_str("checked out"),
+        }
+        row.push_string(format_duration(entry.duration(now)));
+        row
+    });
+    tf.write_rows_io(&mut std::io::stdout().lock(), rows, TableFormat::Table)
+        .map_err(|err| TiskError::io("Failed to print the time entries", err))
+}
+
 fn configure_cli<'a, 'b>() -> App<'a, 'b> {
     App::new("Tisk")
         .version(option_env!("CARGO_PKG_VERSION").unwrap_or(""))
         .about("Task Management with scoping")
+        .arg(
+            Arg::with_name("style")
+                .long("style")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["plain", "none", "ascii", "unicode"])
+                .help("Sets the borders drawn around tables, overriding the project config."),
+        )
+        .arg(
+            Arg::with_name("global")
+                .long("global")
+                .global(true)
+                .help("Use the global project in the home directory instead of the current project."),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .global(true)
+                .help("Show the changes a command would make without making them."),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["table", "md", "markdown", "csv", "tsv", "json", "yaml"])
+                .help("Print the output as a table, or as JSON or YAML for scripts. list also prints markdown, CSV, or TSV, and export writes markdown or CSV."),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .long("verbose")
+                .short("v")
+                .multiple(true)
+                .global(true)
+                .help("Log more detail: -v for info, -vv for debug, -vvv for trace. Debug logs go to the project's log directory."),
+        )
+        .arg(
+            Arg::with_name("log-format")
+                .long("log-format")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["text", "json"])
+                .help("Log one line of text, or one JSON object with the details of each event, per message."),
+        )
+        .arg(
+            Arg::with_name("errors")
+                .long("errors")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["text", "json"])
+                .help("Print errors as text, or as a JSON object with the code, message, task ID, and project path."),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .long("quiet")
+                .short("q")
+                .global(true)
+                .conflicts_with("verbose")
+                .help("Do not log anything, not even warnings."),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .long("yes")
+                .short("y")
+                .global(true)
+                .help("Make destructive or bulk changes without asking first."),
+        )
+        .arg(
+            Arg::with_name("dir")
+                .long("dir")
+                .takes_value(true)
+                .global(true)
+                .help("Use the project directory at this path instead of searching for one, overrides TISK_DIR."),
+        )
         .subcommand(
             App::new("add")
                 .about("Add a new task to the project")
+                .after_help(help::examples("add"))
                 .arg(Arg::with_name("input").index(1).required(true))
-                .arg(
-                    Arg::with_name("priority")
-                        .long("priority")
-                        .short("p")
-                        .takes_value(true)
-                        .help("Sets the priority for this task (0+)."),
-                )
+                .args(&new_task_args()),
+        )
+        .subcommand(
+            App::new("close")
+                .about("Close a given task")
+                .after_help(help::examples("close"))
+                .arg(Arg::with_name("ID").index(1))
                 .arg(
                     Arg::with_name("note")
                         .long("note")
                         .short("n")
                         .takes_value(true)
                         .help("Adds a note to the newly created task."),
+                )
+                .arg(
+                    Arg::with_name("follow-up")
+                        .long("follow-up")
+                        .takes_value(true)
+                        .value_name("NAME")
+                        .help("Adds a new task, with the same priority and tags, to follow up on the closed task"),
+                )
+                .arg(
+                    Arg::with_name("cascade")
+                        .long("cascade")
+                        .help("Closes the task's open subtasks too, a task with open subtasks is not closed without this"),
                 ),
         )
         .subcommand(
-            App::new("close")
-                .about("Close a given task")
+            App::new("start")
+                .about("Mark a task as in progress")
+                .after_help(help::examples("start"))
+                .arg(Arg::with_name("ID").index(1)),
+        )
+        .subcommand(
+            App::new("block")
+                .about("Mark a task as blocked, it stays open until it is closed")
+                .after_help(help::examples("block"))
                 .arg(Arg::with_name("ID").index(1))
                 .arg(
-                    Arg::with_name("note")
-                        .long("note")
-                        .short("n")
+                    Arg::with_name("reason")
+                        .long("reason")
+                        .short("r")
                         .takes_value(true)
-                        .help("Adds a note to the newly created task."),
+                        .help("Why the task is blocked, which is added as a note"),
                 ),
         )
         .subcommand(
             App::new("checkout")
                 .about("Checkout a task.  This will cause task specific actions to apply to the checked out task if an ID is not provided.")
+                .after_help(help::examples("checkout"))
+                .arg(Arg::with_name("ID").index(1))
+                .arg(
+                    Arg::with_name("add")
+                        .long("add")
+                        .short("a")
+                        .takes_value(true)
+                        .help("Adds a new task and immediately checks it out"),
+                )
+                .args(&new_task_args().into_iter().map(|arg| arg.requires("add")).collect::<Vec<_>>())
+        )
+        .subcommand(
+            App::new("checkin")
+                .about("Releases the currently checked out task. No task will be checked out afterwards.")
+                .after_help(help::examples("checkin"))
+        )
+        .subcommand(
+            App::new("backlinks")
+                .about("List the tasks whose notes refer to a task, which notes do by writing #ID")
+                .after_help(help::examples("backlinks"))
+                .arg(Arg::with_name("ID").required(true).index(1)),
+        )
+        .subcommand(
+            App::new("delete")
+                .about("Move a task to the trash, it can be brought back with restore.  Will delete the checked out task, unless an ID is given")
+                .after_help(help::examples("delete"))
+                .arg(Arg::with_name("ID").index(1))
+                .arg(
+                    Arg::with_name("subtasks")
+                        .long("subtasks")
+                        .takes_value(true)
+                        .possible_values(&["reparent", "orphan", "cascade"])
+                        .help("What happens to the task's subtasks: move them to its parent, leave them without a parent (the default), or delete them too"),
+                ),
+        )
+        .subcommand(
+            App::new("restore")
+                .about("Bring a deleted task back from the trash")
+                .after_help(help::examples("restore"))
+                .arg(Arg::with_name("ID").required(true).index(1)),
+        )
+        .subcommand(
+            App::new("depend")
+                .about("Make a task depend on other tasks, which must be done first")
+                .after_help(help::examples("depend"))
                 .arg(Arg::with_name("ID").index(1))
                 .arg(
-                    Arg::with_name("add")
-                        .long("add")
-                        .short("a")
+                    Arg::with_name("on")
+                        .long("on")
+                        .value_name("ID,ID,...")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The tasks which the task depends on"),
+                ),
+        )
+        .subcommand(
+            App::new("graph")
+                .about("Print the tree of tasks which a task depends on, or every such tree")
+                .after_help(help::examples("graph"))
+                .arg(Arg::with_name("ID").index(1)),
+        )
+        .subcommand(
+            App::new("show")
+                .about("Print every detail of a task and its notes.  Will use the checked out task, unless an ID is given")
+                .after_help(help::examples("show"))
+                .arg(Arg::with_name("ID").index(1)),
+        )
+        .subcommand(
+            App::new("time")
+                .about("Print how long a task has been checked out for.  Will use the checked out task, unless an ID is given")
+                .after_help(help::examples("time"))
+                .arg(Arg::with_name("ID").index(1)),
+        )
+        .subcommand(
+            App::new("remind")
+                .about("Add a reminder about a task.  Will use the checked out task, unless an ID is given")
+                .after_help(help::examples("remind"))
+                .arg(Arg::with_name("ID").index(1))
+                .arg(
+                    Arg::with_name("at")
+                        .help("When to be reminded, e.g. \"tomorrow 9am\", \"friday 14:00\", or 2024-06-01")
+                        .long("at")
+                        .required(true)
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("compact")
+                .about("Suggest how to shrink a project which has grown large, before its commands become slow")
+                .after_help(help::examples("compact")),
+        )
+        .subcommand(
+            App::new("notify")
+                .about("Deliver the reminders which are due, with the configured notifier.  Each reminder is only delivered once, so this can be run by cron")
+                .after_help(help::examples("notify")),
+        )
+        .subcommand(
+            App::new("tag")
+                .about("Add tags to a task")
+                .after_help(help::examples("tag"))
+                .arg(Arg::with_name("ID").required(true).index(1))
+                .arg(Arg::with_name("TAG").required(true).multiple(true).index(2)),
+        )
+        .subcommand(
+            App::new("untag")
+                .about("Remove tags from a task")
+                .after_help(help::examples("untag"))
+                .arg(Arg::with_name("ID").required(true).index(1))
+                .arg(Arg::with_name("TAG").required(true).multiple(true).index(2)),
+        )
+        .subcommand(
+            App::new("tags")
+                .about("List every tag with how many open and closed tasks have it")
+                .after_help(help::examples("tags")),
+        )
+        .subcommand(
+            App::new("pick")
+                .about("Print the ID and name of each open task for fzf, or with --then read the line picked back from stdin and act on its task")
+                .after_help(help::examples("pick"))
+                .arg(
+                    Arg::with_name("then")
+                        .help("What to do with the picked task")
+                        .long("then")
+                        .takes_value(true)
+                        .possible_values(&["checkout", "close", "show"]),
+                ),
+        )
+        .subcommand(
+            App::new("roulette")
+                .about("Pick an open task at random, higher priority tasks more often, and offer to check it out")
+                .after_help(help::examples("roulette"))
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only pick from the tasks with this tag, may be given more than once")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("filter")
+                        .help("Only pick from the tasks where FIELD (tag, assignee, priority, or blocked-by) is VALUE, may be given more than once")
+                        .long("filter")
+                        .value_name("FIELD=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                ),
+        )
+        .subcommand(
+            App::new("export")
+                .about("Write the tasks, open and closed, as a document, e.g. a Markdown report or CSV for a spreadsheet")
+                .after_help(help::examples("export"))
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only write the tasks with this tag, may be given more than once")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("filter")
+                        .help("Only write the tasks where FIELD (tag, assignee, priority, or blocked-by) is VALUE, may be given more than once")
+                        .long("filter")
+                        .value_name("FIELD=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .help("Only write the tasks created on or after DATE")
+                        .long("since")
+                        .value_name("DATE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .help("The file to write, instead of printing the document")
+                        .long("out")
+                        .value_name("FILE")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("import")
+                .about("Import the tasks from a project file, e.g. a backup, showing what was created, updated, or skipped")
+                .after_help(help::examples("import"))
+                .arg(
+                    Arg::with_name("FILE")
+                        .help("A YAML project or tasks file, an iCalendar file, or a CSV file exported from Jira")
+                        .required_unless("jql")
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("from")
+                        .help("The format of the file, a calendar's events are imported as busy time")
+                        .long("from")
+                        .takes_value(true)
+                        .possible_values(&["yaml", "ics", "jira", "taskwarrior"])
+                        .default_value("yaml"),
+                )
+                .arg(
+                    Arg::with_name("as-busy")
+                        .help("Import the events in a calendar as busy time, which tisk plan will not make tasks due during")
+                        .long("as-busy"),
+                )
+                .arg(
+                    Arg::with_name("jql")
+                        .help("Import the issues this Jira search finds, using the token in $JIRA_TOKEN")
+                        .long("jql")
+                        .takes_value(true)
+                        .conflicts_with_all(&["FILE", "as-busy"]),
+                )
+                .arg(
+                    Arg::with_name("jira-url")
+                        .help("The Jira site to search with --jql, instead of jira.url in the config")
+                        .long("jira-url")
+                        .value_name("URL")
+                        .takes_value(true)
+                        .requires("jql"),
+                )
+                .arg(
+                    Arg::with_name("ids")
+                        .help("Whether imported tasks keep their IDs or are given new ones")
+                        .long("ids")
+                        .takes_value(true)
+                        .possible_values(&["keep", "renumber"])
+                        .default_value("keep"),
+                )
+                .arg(
+                    Arg::with_name("on-conflict")
+                        .help("What to do with an imported task whose ID is already used, with --ids keep")
+                        .long("on-conflict")
+                        .takes_value(true)
+                        .possible_values(&["fail", "skip", "replace", "renumber"])
+                        .default_value("fail"),
+                ),
+        )
+        .subcommand(
+            App::new("archive")
+                .about("Move the closed tasks which were closed a while ago into .tisk/archive/, out of the way of other commands")
+                .after_help(help::examples("archive"))
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only archive the tasks closed at least this long ago, e.g. 30d, 2w, or 12h")
+                        .long("older-than")
+                        .value_name("AGE")
+                        .takes_value(true)
+                        .default_value("30d"),
+                ),
+        )
+        .subcommand(
+            App::new("expire")
+                .about("Close the open tasks which have expired, any command which changes tasks does this too")
+                .after_help(help::examples("expire")),
+        )
+        .subcommand(
+            App::new("focus-stats")
+                .about("Show how long tasks were checked out for this week")
+                .after_help(help::examples("focus-stats")),
+        )
+        .subcommand(
+            App::new("in")
+                .about("Capture a thought as a new task in the inbox, to triage later")
+                .after_help(help::examples("in"))
+                .arg(Arg::with_name("input").help("The task to capture").required(true).index(1)),
+        )
+        .subcommand(
+            App::new("triage")
+                .about("Walk through the tasks in the inbox, prioritizing, scheduling, or deleting each")
+                .after_help(help::examples("triage")),
+        )
+        .subcommand(
+            App::new("review")
+                .about("Walk through the open tasks nothing has been done with for a while, keeping, reprioritizing, closing, or deleting each")
+                .after_help(help::examples("review"))
+                .arg(
+                    Arg::with_name("days")
+                        .long("days")
+                        .value_name("N")
                         .takes_value(true)
-                        .help("Adds a new task and immediately checks it out"),
-                )
+                        .default_value("14")
+                        .help("Review the tasks which have not been added, noted, closed, or reviewed in N days"),
+                ),
         )
         .subcommand(
-            App::new("checkin")
-                .about("Releases the currently checked out task. No task will be checked out afterwards.")
+            App::new("plan")
+                .about("Walk through the open tasks without a due date, setting their due dates and priorities")
+                .after_help(help::examples("plan")),
         )
         .subcommand(
             App::new("edit")
                 .about("Change properties for an existing task")
+                .after_help(help::examples("edit"))
                 .arg(Arg::with_name("ID").index(1))
                 .arg(
                     Arg::with_name("priority")
@@ -192,18 +1724,121 @@ fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                         .short("p")
                         .takes_value(true)
                         .help("Sets the priority for this task (0+)."),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .takes_value(true)
+                        .help("Renames this task."),
+                )
+                .arg(
+                    Arg::with_name("due")
+                        .long("due")
+                        .takes_value(true)
+                        .help("Sets the date this task is due, YYYY-MM-DD, or none to clear it."),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Adds a tag to this task, may be given more than once."),
+                )
+                .arg(
+                    Arg::with_name("untag")
+                        .long("untag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Removes a tag from this task, may be given more than once."),
+                )
+                .arg(
+                    Arg::with_name("assignee")
+                        .long("assignee")
+                        .takes_value(true)
+                        .help("Assigns this task to someone, or none to unassign it."),
+                )
+                .arg(
+                    Arg::with_name("expires")
+                        .long("expires")
+                        .takes_value(true)
+                        .value_name("DATE")
+                        .help("Closes this task automatically once DATE has passed, or none to never expire it."),
+                )
+                .arg(
+                    Arg::with_name("editor")
+                        .long("editor")
+                        .short("e")
+                        .conflicts_with_all(&["priority", "name", "due", "tag", "untag", "assignee", "expires"])
+                        .help("Opens the task in $EDITOR to change any of its fields."),
                 ),
         )
         .subcommand(
             App::new("note")
-                .about("Add a note to a specific task.  Will attempt to add a note to the checked out task, unless the 'id' flag is used")
-                .arg(Arg::with_name("NOTE").index(1))
-                .arg(Arg::with_name("ID").long("id").help("Specify the Task ID, this overrides the checked out task and is required if no task is checked out"))
-                .arg(Arg::with_name("list").long("list").short("l")),
+                .about("Add a note to a specific task, or list its notes.  Will attempt to use the checked out task, unless an ID is given")
+                .after_help(help::examples("note"))
+                .arg(
+                    Arg::with_name("ARGS")
+                        .index(1)
+                        .multiple(true)
+                        .max_values(2)
+                        .value_names(&["ID", "NOTE"])
+                        .help("The ID of the task, which may be left out, and the note to add"),
+                )
+                .arg(
+                    Arg::with_name("ID")
+                        .long("id")
+                        .takes_value(true)
+                        .conflicts_with("task")
+                        .help("Specify the Task ID, this overrides the checked out task and is required if no task is checked out"),
+                )
+                .arg(
+                    Arg::with_name("task")
+                        .long("task")
+                        .takes_value(true)
+                        .help("The same as --id"),
+                )
+                .arg(Arg::with_name("list").long("list").short("l").help("List the notes on the task"))
+                .arg(
+                    Arg::with_name("export")
+                        .long("export")
+                        .value_name("ID")
+                        .takes_value(true)
+                        .conflicts_with_all(&["ARGS", "ID", "task", "list", "ids", "filter"])
+                        .help("Write the task and all of its notes, with when each was added, as markdown"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .requires("export")
+                        .help("The file --export writes to, instead of printing the markdown"),
+                )
+                .arg(
+                    Arg::with_name("ids")
+                        .long("ids")
+                        .value_name("ID,ID,...")
+                        .takes_value(true)
+                        .conflicts_with_all(&["ID", "task", "list"])
+                        .help("Adds the note to each of these tasks"),
+                )
+                .arg(
+                    Arg::with_name("filter")
+                        .long("filter")
+                        .value_name("FIELD=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .conflicts_with_all(&["ID", "task", "list"])
+                        .help("Adds the note to every open task where FIELD (tag, assignee, priority, or blocked-by) is VALUE, may be given more than once"),
+                ),
         )
         .subcommand(
             App::new("list")
                 .about("List the tasks in this project")
+                .after_help(help::examples("list"))
                 .arg(
                     Arg::with_name("all")
                         .help("Display all tasks, regardless of state")
@@ -214,296 +1849,818 @@ fn configure_cli<'a, 'b>() -> App<'a, 'b> {
                         .help("Display all closed tasks")
                         .long("closed"),
                 )
+                .arg(
+                    Arg::with_name("archived")
+                        .help("Also display the tasks moved to the archive, which are closed, by tisk archive")
+                        .long("archived"),
+                )
+                .arg(
+                    Arg::with_name("in-progress")
+                        .help("Display the tasks which are in progress")
+                        .long("in-progress"),
+                )
+                .arg(
+                    Arg::with_name("blocked")
+                        .help("Display the tasks which are blocked")
+                        .long("blocked"),
+                )
                 .arg(
                     Arg::with_name("open")
                         .help("Display all open tasks")
                         .long("open"),
+                )
+                .arg(
+                    Arg::with_name("contains")
+                        .help("Only display the tasks whose name contains TEXT, which is highlighted")
+                        .long("contains")
+                        .value_name("TEXT")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only display the tasks with this tag, may be given more than once")
+                        .long("tag")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("filter")
+                        .help("Only display the tasks where FIELD (tag, assignee, priority, or blocked-by) is VALUE, may be given more than once")
+                        .long("filter")
+                        .value_name("FIELD=VALUE")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("blocked-by")
+                        .help("Only display the tasks which depend on the task with this ID")
+                        .long("blocked-by")
+                        .value_name("ID")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("since")
+                        .help("Only display the tasks created on or after DATE")
+                        .long("since")
+                        .value_name("DATE")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .help("Sort the tasks by the given column (ID, Date, Name, Pri, Nts, or Due)")
+                        .long("sort")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("desc")
+                        .help("Sort in descending order, used with --sort")
+                        .long("desc")
+                        .requires("sort"),
+                )
+                .arg(
+                    Arg::with_name("nowrap")
+                        .help("Cut names which are too long short with an ellipsis, so that each task is one line")
+                        .long("nowrap")
+                        .conflicts_with("wrap"),
+                )
+                .arg(
+                    Arg::with_name("wrap")
+                        .help("Wrap names which are too long onto more lines, the default unless wrap_names is false")
+                        .long("wrap"),
+                )
+                .arg(
+                    Arg::with_name("no-header")
+                        .help("Leave out the labels of the columns, so that each line is a task, e.g. for wc -l or fzf")
+                        .long("no-header"),
+                )
+                .arg(
+                    Arg::with_name("no-footer")
+                        .help("Leave out the counts of the project's tasks after the table")
+                        .long("no-footer"),
+                ),
+        )
+        .subcommand(
+            App::new("init")
+                .about("Intialize a new tisk project based in this directory")
+                .after_help(help::examples("init"))
+                .arg(
+                    Arg::with_name("dir-name")
+                        .help("The name of the project directory, must start with '.'")
+                        .long("dir-name")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("layout")
+                        .help("Store each task in its own file or every task in one file")
+                        .long("layout")
+                        .takes_value(true)
+                        .possible_values(&["per-task", "single-file"]),
+                )
+                .arg(
+                    Arg::with_name("with-config")
+                        .help("Write a config file which documents every setting")
+                        .long("with-config"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("The name of the project, defaults to the name of this directory")
+                        .long("name")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .help("Create the project even if it is inside another project")
+                        .long("force"),
+                )
+                .arg(
+                    Arg::with_name("git-ignore")
+                        .help("Add the project directory, or only its local state, to the repository's .gitignore")
+                        .long("git-ignore")
+                        .takes_value(true)
+                        .value_name("WHAT")
+                        .possible_values(&["all", "local"]),
+                ),
+        )
+        .subcommand(
+            App::new("gitignore")
+                .about("Add the project directory, or only its local state, to the repository's .gitignore")
+                .after_help(help::examples("gitignore"))
+                .arg(
+                    Arg::with_name("WHAT")
+                        .help("all to keep the tasks out of git, local to version the tasks but not the local state")
+                        .possible_values(&["all", "local"])
+                        .index(1),
+                ),
+        )
+        .subcommand(
+            App::new("info")
+                .about("Show the project's name, when it was created, and how its tasks are stored")
+                .after_help(help::examples("info")),
+        )
+        .subcommand(
+            App::new("which")
+                .about("Print the project directory which commands run here use")
+                .after_help(help::examples("which")),
+        )
+        .subcommand(
+            App::new("migrate-dir")
+                .about("Rename the project directory, e.g. from the legacy .task to .tisk")
+                .after_help(help::examples("migrate-dir"))
+                .arg(
+                    Arg::with_name("to")
+                        .help("The new name of the project directory, defaults to .tisk")
+                        .long("to")
+                        .takes_value(true),
+                ),
+        )
+        .subcommand(
+            App::new("__complete")
+                .about("Print the tags or assignees already used in the project, for shell completion")
+                .setting(AppSettings::Hidden)
+                .arg(
+                    Arg::with_name("KIND")
+                        .help("The values to print")
+                        .possible_values(&["tags", "assignees"])
+                        .required(true),
                 ),
         )
-        .subcommand(App::new("init").about("Intialize a new tisk project based in this directory"))
 }
 
-fn handle_add(tasks: &mut TaskList, args: &ArgMatches) -> Result<Effects, String> {
-    let name = args.value_of("input").unwrap();
-    let priority: u32 = args.value_of("priority").unwrap_or("1").parse().unwrap();
+fn parse_init(args: &ArgMatches) -> Result<io::InitOptions, TiskError> {
+    let mut options = io::InitOptions::default();
+    if let Some(dir_name) = args.value_of("dir-name") {
+        options.meta.dir_name = String::from(dir_name);
+    }
+    if let Some(layout) = args.value_of("layout") {
+        options.meta.layout = layout.parse()?;
+    }
+    options.meta.name = args.value_of("name").map(String::from);
+    options.with_config = args.is_present("with-config");
+    Ok(options)
+}
+
+/// The flags which set up a new task, shared by `add` and `checkout --add`.
+fn new_task_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("priority")
+            .long("priority")
+            .short("p")
+            .takes_value(true)
+            .help("Sets the priority for this task (0+)."),
+        Arg::with_name("note")
+            .long("note")
+            .short("n")
+            .takes_value(true)
+            .help("Adds a note to the newly created task."),
+        Arg::with_name("tag")
+            .long("tag")
+            .takes_value(true)
+            .multiple(true)
+            .number_of_values(1)
+            .help("Adds a tag to the newly created task, may be given more than once."),
+        Arg::with_name("due")
+            .long("due")
+            .takes_value(true)
+            .help("Sets the date the newly created task is due, YYYY-MM-DD, today, tomorrow, mon..sun, +N days, or +Nbd business days."),
+        Arg::with_name("parent")
+            .long("parent")
+            .value_name("ID")
+            .takes_value(true)
+            .help("Makes the newly created task a subtask of the task ID."),
+    ]
+}
 
-    debug!("Adding new task to task list");
-    let id = tasks.add_task(name, priority);
+fn parse_integer_arg(arg: Option<&str>) -> Result<Option<u32>, std::num::ParseIntError> {
+    match arg {
+        None => Ok(None),
+        Some(v) => v.parse().map(Some),
+    }
+}
 
-    let note = args.value_of("note");
-    note.and_then(|n| tasks.get_mut(id).map(|t| t.add_note(n)));
-    Ok(vec![CommandEffect::Write])
+fn parse_id(id: &str) -> Result<u32, TiskError> {
+    id.parse::<u32>().map_err(|_| TiskError::ParseError(format!("Invalid task ID: {}, must be an integer", id)))
 }
 
-fn handle_close(
-    tasks: &mut TaskList,
-    checked_out_task: Option<u32>,
-    args: &ArgMatches,
-) -> Result<Effects, String> {
-    debug!("{:?}", checked_out_task);
-    let id = parse_integer_arg(args.value_of("ID"))
-        .or_else(|e| ferror!("{}", e))?
-        .or_else(|| checked_out_task)
-        .ok_or("No ID provided and no task checked out")
-        .or_else(|why| Err(why))?;
+/// The environment variable which sets the log level when neither
+/// `--verbose` nor `--quiet` is given.
+const TISK_LOG_VAR: &str = "TISK_LOG";
 
-    debug!("Closing task with ID: {}", id);
-    match args.value_of("note") {
-        Some(note) => tasks.get_mut(id).iter_mut().for_each(|t| t.add_note(note)),
-        None => (),
+/// The most detailed level of logging, set by `--quiet`, the number of
+/// times `-v` is given, or the `TISK_LOG` environment variable.  Only
+/// warnings and errors are logged by default.
+fn log_level(args: &ArgMatches) -> Result<LevelFilter, TiskError> {
+    if global_flag(args, "quiet") {
+        return Ok(LevelFilter::Off);
     }
-    match tasks.close_task(id) {
-        None => ferror!("Could not find task with ID {}", id),
-        Some(t) => {
-            println!("Task {} was closed", t.id());
-            Ok(vec![CommandEffect::Write])
-        }
+
+    let verbose = args
+        .subcommand()
+        .1
+        .map(|sub| sub.occurrences_of("verbose"))
+        .unwrap_or(0)
+        .max(args.occurrences_of("verbose"));
+    match verbose {
+        0 => (),
+        1 => return Ok(LevelFilter::Info),
+        2 => return Ok(LevelFilter::Debug),
+        _ => return Ok(LevelFilter::Trace),
     }
-}
 
-fn handle_checkout(tasks: &mut TaskList, args: &ArgMatches) -> Result<Effects, String> {
-    let mut effects = vec![];
-    if args.is_present("ID") && args.is_present("add") {
-        return ferror!("Cannot have an ID and the --add flag set at the same time");
-    } else if !args.is_present("ID") && !args.is_present("add") {
-        return ferror!("Must specify either an ID to checkout or `--add` to add a new task");
+    match std::env::var(TISK_LOG_VAR) {
+        Ok(level) if !level.is_empty() => level
+            .parse()
+            .map_err(|_| TiskError::ParseError(format!("Invalid {} level: {}", TISK_LOG_VAR, level))),
+        _ => Ok(LevelFilter::Warn),
     }
+}
 
-    let id = match args.value_of("add") {
-        Some(task) => {
-            effects.push(CommandEffect::Write);
-            tasks.add_task(task, 1)
-        }
-        None => match parse_integer_arg(args.value_of("ID")) {
-            Err(_) => {
-                return ferror!(
-                    "Invalid ID provided, must be an integer greater than or equal to 0"
-                )
-            }
-            Ok(None) => return ferror!("No ID provided"),
-            Ok(Some(id)) => id,
-        },
-    };
+/// How log messages are written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum LogFormat {
+    /// One line of text per message.
+    Text,
 
-    match tasks.get(id) {
-        None => ferror!("Could not find task with ID {}", id),
-        Some(_) => {
-            debug!("Checkout task {}", id);
-            println!("Checkout task {}", id);
-            effects.push(CommandEffect::CheckoutTask(id));
-            Ok(effects)
-        }
-    }
+    /// One JSON object per message, with the details of events (such as
+    /// which command ran and what it changed) as fields of the object.
+    Json,
 }
 
-fn handle_checkin() -> Result<Effects, String> {
-    // Generate a signal to delete the checkout file
-    Ok(vec![CommandEffect::CheckinTask])
+/// The encoder which writes messages in `format`, using `pattern` for text.
+fn log_encoder(format: LogFormat, pattern: &str) -> Box<dyn log4rs::encode::Encode> {
+    match format {
+        LogFormat::Text => Box::new(PatternEncoder::new(pattern)),
+        LogFormat::Json => Box::new(JsonEncoder::new()),
+    }
 }
 
-fn handle_edit(
-    tasks: &mut TaskList,
-    checked_out_task: Option<u32>,
-    args: &ArgMatches,
-) -> Result<Effects, String> {
-    debug!("{:?}", checked_out_task);
-    let id = parse_integer_arg(args.value_of("ID"))
-        .or_else(|e| ferror!("{}", e))?
-        .or_else(|| checked_out_task)
-        .ok_or("No ID provided and no task checked out")
-        .or_else(|why| ferror!("{}", why))?;
+/// Logs that `command` finished after `duration`, along with what it
+/// committed (if anything).  The details are set as the logging context
+/// so that they are fields of the event when logging JSON.
+fn log_event(command: &str, duration: std::time::Duration, committed: Option<&engine::Committed>) {
+    let effects = committed
+        .map(|c| c.effects.iter().map(|e| format!("{:?}", e)).collect::<Vec<_>>().join(","))
+        .unwrap_or_default();
+    let files_written = committed.map(|c| c.files_written.len()).unwrap_or(0);
 
-    let priority = match parse_integer_arg(args.value_of("priority")) {
-        Err(_) => {
-            return ferror!("Invalid priority value: must be an integer greater than or equal to 0")
-        }
-        Ok(p) => p,
+    log_mdc::insert("command", command);
+    log_mdc::insert("duration_ms", duration.as_millis().to_string());
+    log_mdc::insert("effects", &effects);
+    log_mdc::insert("files_written", files_written.to_string());
+    info!(
+        "{} finished in {}ms, effects: [{}], files written: {}",
+        command,
+        duration.as_millis(),
+        effects,
+        files_written
+    );
+    log_mdc::clear();
+}
+
+/// Sets up logging for the level chosen on the command line.  Messages up
+/// to `info` are written to stderr, so they never mix with the output of a
+/// command.  Debug and trace messages go to `local/log/tisk.log` in the project
+/// directory `task_path`, if there is one.
+fn configure_logger(args: &ArgMatches, task_path: Option<&std::path::PathBuf>) -> Result<(), TiskError> {
+    let level = log_level(args)?;
+    let format = match global_value(args, "log-format") {
+        Some("json") => LogFormat::Json,
+        _ => LogFormat::Text,
     };
 
-    match priority {
-        None => Ok(vec![CommandEffect::Read]),
-        Some(p) => match tasks.set_priority(id, p) {
-            None => ferror!("Could not find task with ID {}", id),
-            Some((old, new)) => {
-                println!(
-                    "Task {} priority set from {} to {}",
-                    id,
-                    old.priority(),
-                    new.priority()
-                );
-                Ok(vec![CommandEffect::Write])
+    let stderr = ConsoleAppender::builder()
+        .target(Target::Stderr)
+        .encoder(log_encoder(format, "{l}: {m}{n}"))
+        .build();
+    let mut config = log4rs::config::Config::builder().appender(
+        Appender::builder()
+            .filter(Box::new(ThresholdFilter::new(level.min(LevelFilter::Info))))
+            .build("stderr", Box::new(stderr)),
+    );
+    let mut root = Root::builder().appender("stderr");
+
+    let mut file_error = None;
+    if let (true, Some(task_path)) = (level > LevelFilter::Info, task_path) {
+        let file = FileAppender::builder()
+            .encoder(log_encoder(format, "{d} {l} {t} - {m}{n}"))
+            .build(io::local_path(task_path, "log").join("tisk.log"));
+        match file {
+            Ok(file) => {
+                config = config.appender(Appender::builder().build("file", Box::new(file)));
+                root = root.appender("file");
             }
-        },
+            Err(why) => file_error = Some(why),
+        }
+    }
+
+    let config = config
+        .build(root.build(level))
+        .map_err(|why| TiskError::ParseError(format!("Failed to configure logging: {}", why)))?;
+    log4rs::init_config(config).map_err(|why| TiskError::ParseError(format!("Failed to configure logging: {}", why)))?;
+    if let Some(why) = file_error {
+        warn!("Could not open the log file, debug messages will not be logged: {}", why);
     }
+    Ok(())
 }
 
-fn handle_note(
-    tasks: &mut TaskList,
+/// The width of the terminal in characters, which tables are fit to.  If
+/// stdout is not a terminal (e.g. it is piped into another program) then
+/// a width of 80 is used.
+fn terminal_width() -> usize {
+    console::Term::stdout()
+        .size_checked()
+        .map(|(_, cols)| cols as usize)
+        .unwrap_or(80)
+}
+
+pub fn print_task_list(
+    tasks: Vec<&tasks::Task>,
     checked_out_task: Option<u32>,
-    args: &ArgMatches,
-) -> Result<Effects, String> {
-    let id = match args.value_of("ID") {
-        Some(id) => match parse_integer_arg(Some(id)).or_else(|err| Err(format!("{}", err)))? {
-            Some(id) => id,
-            None => return ferror!("Must have a task checked out or provide an id"),
-        },
-        None => match checked_out_task {
-            Some(id) => id,
-            None => return ferror!("Must have a task checked out or provide an id"),
-        },
-    };
+    sort: Option<(&str, Order)>,
+    format: TableFormat,
+    highlight: Option<(&str, Matching)>,
+    time: bool,
+    config: &Config,
+) -> Result<(), TiskError> {
+    let id_width: usize = 4;
+    let date_width: usize = 10; // YYYY-mm-dd
+    let due_width: usize = 11; // today HH:MM
+    let priority_width: usize = 3;
+    let notes_width = 3;
+    let time_width = 7; // 12h 05m
+    let name_min_width = 16;
+    let dates = config.dates();
+    let today = dates.today();
 
-    if args.is_present("list") || !args.is_present("NOTE") {
-        let task = tasks.get(id).ok_or(format!("Could not find task with id {}", id))?;
-        let notes = task.notes();
-        print_task_list(vec![task], None);
-        print_notes(notes);
+    // Below the width the ID and the shortest name need, only they are listed, one line per task
+    let builder = table_builder(config).header(config.list_header);
+    let mut builder = builder.minimal_below(id_width + 1 + name_min_width).columns(vec![
+        Column::new("ID", Some(id_width))
+            .align(Alignment::Right)
+            .sort_key(SortKey::number)
+            .minimal(),
+        Column::new("Date", Some(date_width)).drop_priority(2),
+        Column::new("Name", None)
+            .min_width(name_min_width)
+            .overflow(if config.wrap_names { Overflow::Wrap } else { Overflow::Truncate })
+            .minimal(),
+        Column::new("Pri", Some(priority_width))
+            .align(Alignment::Right)
+            .sort_key(SortKey::number)
+            .drop_priority(3),
+        Column::new("Nts", Some(notes_width))
+            .align(Alignment::Right)
+            .sort_key(SortKey::number)
+            .drop_priority(1),
+        Column::new("Due", Some(due_width)).sort_key(move |cell| due_sort_key(cell, today)).drop_priority(2),
+    ]);
+    if time {
+        builder = builder.column(Column::new("Time", Some(time_width)).align(Alignment::Right).drop_priority(1));
+    }
+    if let Some((column, order)) = sort {
+        builder = builder.sort_by(column, order);
+    }
+    let tf = builder.build().map_err(|err| TiskError::ParseError(err.to_string()))?;
 
-        Ok(vec![CommandEffect::Read])
-    } else {
-        let note = match args.value_of("NOTE") {
-            None => {
-                return ferror!(
-                    "Must provide a note to add. Or use the --list flag to list notes on this task"
-                )
+    // Print the table
+    let checkout_style = console::Style::new().green();
+    let default_style = console::Style::new().white();
+    let highlight_style = console::Style::new().yellow().bold();
+    let overdue_style = console::Style::new().red();
+    let blocked_style = console::Style::new().magenta();
+    let in_progress_style = console::Style::new().cyan();
+    let now = chrono::Utc::now();
+    let rows = tasks.iter().map(|task| {
+        let mut row = TableRow::new();
+        row.push_number(task.id() as u64);
+        row.push(dates.format_day(task.created_at()));
+        match highlight {
+            Some((text, matching)) if format == TableFormat::Table => {
+                let found = matching.find_all(task.name(), text);
+                row.push_string(table::highlight(task.name(), &found, &highlight_style))
             }
-            Some(note) => note,
-        };
+            _ => row.push_str(task.name()),
+        }
+        row.push_number(task.priority() as u64);
+        row.push_number(task.note_count() as u64);
+        match task.due_at().map(|due_at| (dates.has_time(due_at), dates.zone.naive(due_at))) {
+            Some((true, due_at)) if due_at.date() == today => row.push(due_at.format("today %H:%M")),
+            Some((_, due_at)) => row.push(due_at.format("%Y-%m-%d")),
+            None => row.push_str(""),
+        }
+        if time && task.time_entries().is_empty() {
+            row.push_str("");
+        } else if time {
+            row.push_string(format_duration(task.time_spent(now)));
+        }
 
-        match tasks.get_mut(id) {
-            Some(task) => {
-                task.add_note(note);
-                Ok(vec![CommandEffect::Write])
-            }
-            None => ferror!("No task with id {} found.", id),
+        match checked_out_task {
+            Some(id) if id == task.id() => row.set_style(checkout_style.clone()),
+            _ if task.is_overdue(now, dates.zone) => row.set_style(overdue_style.clone()),
+            _ if task.status() == tasks::Status::Blocked => row.set_style(blocked_style.clone()),
+            _ if task.status() == tasks::Status::InProgress => row.set_style(in_progress_style.clone()),
+            _ => match config.tag_color(task.tags()) {
+                Some(color) => row.set_style(console::Style::from_dotted_str(color)),
+                None => row.set_style(default_style.clone()),
+            },
         }
-    }
+        row
+    });
+    tf.write_rows_io(&mut std::io::stdout().lock(), rows, format)
+        .map_err(|err| TiskError::io("Failed to print tasks", err))
 }
 
-fn handle_list(
-    tasks: &TaskList,
-    checked_out_task: Option<u32>,
-    args: &ArgMatches,
-) -> Result<Effects, String> {
-    if args.is_present("all") {
-        let mut task_slice = tasks.get_all();
-        task_slice.sort_by(|a, b| order_tasks(&b, &a));
-        print_task_list(task_slice, checked_out_task);
-    } else if args.is_present("closed") {
-        let mut task_slice = tasks.get_closed();
-        task_slice.sort_by(|a, b| order_tasks(&b, &a));
-        print_task_list(task_slice, checked_out_task);
-    } else {
-        let mut task_slice = tasks.get_open();
-        task_slice.sort_by(|a, b| order_tasks(&b, &a));
-        print_task_list(task_slice, checked_out_task);
+/// Orders the Due column by date, and by time for the tasks due today, with
+/// the tasks which have no due date last, as text is ordered after numbers.
+fn due_sort_key(cell: &str, today: chrono::NaiveDate) -> SortKey {
+    let cell = console::strip_ansi_codes(cell);
+    match cell.trim().strip_prefix("today ") {
+        Some(time) => {
+            let today = today.format("%Y%m%d");
+            SortKey::number(&format!("{}{}", today, time.replace(':', "")))
+        }
+        None if cell.trim().is_empty() => SortKey::text(&cell),
+        None => SortKey::number(&format!("{}0000", cell.trim().replace('-', ""))),
     }
-    Ok(vec![CommandEffect::Read])
 }
 
-fn order_tasks(a: &Task, b: &Task) -> std::cmp::Ordering {
-    let priority_cmp = a.priority().cmp(&b.priority());
-    if priority_cmp == std::cmp::Ordering::Equal {
-        b.created_at().cmp(&a.created_at())
+/// Starts a table which fits the terminal and is styled according to `config`.
+fn table_builder(config: &Config) -> TableBuilder {
+    let builder = TableBuilder::new(terminal_width()).style(config.table_style);
+    if config.zebra {
+        builder.zebra(console::Style::new().dim())
     } else {
-        priority_cmp
+        builder
     }
 }
 
-fn parse_integer_arg(arg: Option<&str>) -> Result<Option<u32>, std::num::ParseIntError> {
-    match arg {
-        None => Ok(None),
-        Some(v) => v.parse().map(|p| Some(p)),
+/// The line printed after a list of tasks, e.g. `3 shown: 12 open, 3
+/// closed, 2 in progress, 1 due this week, task 4 checked out`.
+fn footer(shown: usize, summary: &engine::Summary) -> String {
+    let mut counts = vec![
+        format!("{} open", summary.open),
+        format!("{} closed", summary.closed),
+    ];
+    if summary.in_progress > 0 {
+        counts.push(format!("{} in progress", summary.in_progress));
+    }
+    if summary.blocked > 0 {
+        counts.push(format!("{} blocked", summary.blocked));
+    }
+    if summary.due_this_week > 0 {
+        counts.push(format!("{} due this week", summary.due_this_week));
     }
+    if let Some(id) = summary.checked_out {
+        counts.push(format!("task {} checked out", id));
+    }
+    let line = format!("{} shown: {}", shown, counts.join(", "));
+    console::style(line).dim().to_string()
 }
 
-fn configure_logger() {
-    match log4rs::init_file("config/log4rs.yaml", Default::default()) {
-        Err(_) => {
-            let stdout = ConsoleAppender::builder().build();
-            let config = log4rs::config::Config::builder()
-                .appender(Appender::builder().build("stdout", Box::new(stdout)))
-                .build(Root::builder().appender("stdout").build(LevelFilter::Info))
-                .unwrap();
-            log4rs::init_config(config).unwrap();
+/// Prints a table of each field of a task which was changed by an edit,
+/// showing its value before and after the edit.
+fn print_edit(before: &tasks::Task, after: &tasks::Task, config: &Config) -> Result<(), TiskError> {
+    fn fields(task: &tasks::Task, dates: &DateContext) -> Vec<(&'static str, String)> {
+        vec![
+            ("Name", String::from(task.name())),
+            ("Priority", task.priority().to_string()),
+            ("Due", task.due_at().map(|due| dates.format_due(due)).unwrap_or_default()),
+            ("Tags", task.tags().join(", ")),
+            ("Assignee", String::from(task.assignee().unwrap_or(""))),
+            ("Expires", task.expires_at().map(|expires| dates.format_day(expires)).unwrap_or_default()),
+        ]
+    }
+
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("Field", Some(8)),
+            Column::new("Before", None),
+            Column::new("After", None),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
+
+    let dates = config.dates();
+    let mut rows = vec![];
+    for ((field, old), (_, new)) in fields(before, &dates).into_iter().zip(fields(after, &dates)) {
+        if old != new {
+            let mut row = TableRow::new();
+            row.push(field);
+            row.push(old);
+            row.push(new);
+            rows.push(row);
         }
-        Ok(_) => (),
     }
+    println!("Task {} was edited", after.id());
+    tf.write_table_io(&mut std::io::stdout(), rows)
+        .map_err(|err| TiskError::io("Failed to print the edit", err))
 }
 
-pub fn print_task_list(tasks: Vec<&tasks::Task>, checked_out_task: Option<u32>) {
-    use console::Term;
-
-    // Get terminal dimensions so that we can compute how wide columns can be and
-    // how to format text properly
-    // Assume that we'll always have at least 20 columns in the terminal (as even that small
-    // would be unuseable for a person.
-    let (_, cols) = Term::stdout()
-        .size_checked()
-        .expect("Could not get terminal details");
+/// Prints each tag with how many open and closed tasks have it.
+fn print_tags(counts: &[(String, usize, usize)], config: &Config) -> Result<(), TiskError> {
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("Tag", None),
+            Column::new("Open", Some(4)).align(Alignment::Right).sort_key(SortKey::number),
+            Column::new("Closed", Some(6)).align(Alignment::Right).sort_key(SortKey::number),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
 
-    let id_width: usize = 4;
-    let date_width: usize = 10; // YYYY-mm-dd
-    let priority_width: usize = 3;
-    let notes_width = 3;
+    let rows = counts.iter().map(|(tag, open, closed)| {
+        let mut row = TableRow::new();
+        row.push_str(tag);
+        row.push_number(*open as u64);
+        row.push_number(*closed as u64);
+        row
+    });
+    tf.write_rows_io(&mut std::io::stdout().lock(), rows, TableFormat::Table)
+        .map_err(|err| TiskError::io("Failed to print the tags", err))
+}
 
-    let mut tf = TableFormatter::new(cols as usize);
-    tf.set_columns(vec![
-        ("ID", Some(id_width)),
-        ("Date", Some(date_width)),
-        ("Name", None),
-        ("Pri", Some(priority_width)),
-        ("Nts", Some(notes_width)),
-    ]);
+/// Prints dependency trees, each task indented under the task which depends
+/// on it.
+fn print_graph(rows: &[(usize, tasks::Task)], config: &Config) -> Result<(), TiskError> {
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("ID", Some(4)).align(Alignment::Right),
+            Column::new("Name", None),
+            Column::new("Status", Some(10)),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
 
-    // Print the table
-    let checkout_style = console::Style::new().green();
-    let default_style = console::Style::new().white();
-    println!("{}", tf.print_header().expect("Failed to format table header"));
-    for task in tasks.iter() {
+    let rows = rows.iter().map(|(depth, task)| {
         let mut row = TableRow::new();
-        row.push(task.id());
-        row.push(task.created_at().format("%Y-%m-%d"));
-        row.push(task.name());
-        row.push(task.priority());
-        row.push(task.notes().len());
-
-        let print_row = match (checked_out_task, tf.print_row(row).expect("Failed to format table row")) {
-            (Some(id), row) if id == task.id() => checkout_style.apply_to(row),
-            (_, row) => default_style.apply_to(row),
-        };
+        row.push_number(task.id() as u64);
+        match depth {
+            0 => row.push_str(task.name()),
+            _ => row.push_string(format!("{}└─ {}", "   ".repeat(depth - 1), task.name())),
+        }
+        row.push_string(format!("{:?}", task.status()));
+        row
+    });
+    tf.write_rows_io(&mut std::io::stdout().lock(), rows, TableFormat::Table)
+        .map_err(|err| TiskError::io("Failed to print the dependency graph", err))
+}
 
-        print!("{}", print_row);
+/// Prints what importing tasks did, or would do, to each task followed by
+/// any conflicts with the tasks already in the project.
+fn print_import(report: &engine::ImportReport, config: &Config) -> Result<(), TiskError> {
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("Action", Some(7)),
+            Column::new("From", Some(4)).align(Alignment::Right),
+            Column::new("ID", Some(4)).align(Alignment::Right),
+            Column::new("Name", None),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
+
+    let mut rows = vec![];
+    for row in &report.rows {
+        let mut table_row = TableRow::new();
+        table_row.push(match row.action {
+            engine::ImportAction::Create => "create",
+            engine::ImportAction::Update => "update",
+            engine::ImportAction::Skip => "skip",
+        });
+        table_row.push(row.from);
+        table_row.push(row.id);
+        table_row.push(row.name.as_str());
+        rows.push(table_row);
     }
+    tf.write_table_io(&mut std::io::stdout(), rows)
+        .map_err(|err| TiskError::io("Failed to print the import", err))?;
 
+    if !report.conflicts.is_empty() {
+        println!("Conflicts:");
+        report.conflicts.iter().for_each(|conflict| println!("  {}", conflict));
+    }
+    Ok(())
 }
 
-pub fn print_notes(notes: Vec<&tasks::Note>) {
-    use console::Term;
+/// Prints a summary of the focus sessions since `monday`, along with the
+/// tasks which were worked on the most.
+fn print_focus_stats(
+    monday: chrono::NaiveDate,
+    stats: &focus::FocusStats,
+    tasks: &[tasks::Task],
+    config: &Config,
+) -> Result<(), TiskError> {
 
-    // Get terminal dimensions so that we can compute how wide columns can be and
-    // how to format text properly
-    // Assume that we'll always have at least 20 columns in the terminal (as even that small
-    // would be unuseable for a person.
-    let (_, cols) = Term::stdout()
-        .size_checked()
-        .expect("Could not get terminal details");
+    let days = |n: usize| if n == 1 { String::from("1 day") } else { format!("{} days", n) };
+    println!("Focus since {}:", monday.format("%Y-%m-%d"));
+    match stats.average {
+        Some(average) => println!(
+            "  {} sessions, {} in total, {} on average",
+            stats.sessions,
+            format_duration(stats.total),
+            format_duration(average)
+        ),
+        None => println!("  No sessions, check out a task to start one"),
+    }
+    println!(
+        "  Longest streak: {}, current streak: {}",
+        days(stats.longest_streak),
+        days(stats.current_streak)
+    );
+    if stats.most_worked.is_empty() {
+        return Ok(());
+    }
+
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("ID", Some(4)).align(Alignment::Right),
+            Column::new("Name", None),
+            Column::new("Time", Some(8)).align(Alignment::Right),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
+    let mut rows = vec![];
+    for (id, worked) in stats.most_worked.iter().take(5) {
+        let mut row = TableRow::new();
+        row.push(id);
+        row.push(tasks.iter().find(|t| t.id() == *id).map(|t| t.name()).unwrap_or("(deleted)"));
+        row.push(format_duration(*worked));
+        rows.push(row);
+    }
+    println!("\nMost worked tasks:");
+    tf.write_table_io(&mut std::io::stdout(), rows)
+        .map_err(|err| TiskError::io("Failed to print the focus statistics", err))
+}
+
+/// Formats a duration as hours and minutes, e.g. `1h 05m` or `25m`.
+fn format_duration(duration: chrono::Duration) -> String {
+    let minutes = duration.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {:02}m", minutes / 60, minutes % 60)
+    }
+}
 
+pub fn print_notes(notes: Vec<&tasks::Note>, config: &Config) -> Result<(), TiskError> {
     let id_width: usize = 4;
 
-    // Print the column headers
-    let mut tf = TableFormatter::new(cols as usize);
-    tf.set_columns(vec![("ID", Some(id_width)), ("Note", None)]);
-    println!("{}", tf.print_header().expect("Failed to format table header"));
+    let tf = table_builder(config)
+        .columns(vec![
+            Column::new("ID", Some(id_width)).align(Alignment::Right),
+            Column::new("Note", None),
+        ])
+        .build()
+        .map_err(|err| TiskError::ParseError(err.to_string()))?;
 
-    // print each task, in the order given by the input vector
-    let mut idx = 1;
-    for note in notes.iter() {
-        //Note::print_note(task, idx, id_width, note_width);
+    // print each note, in the order given by the input vector, with the
+    // references to other tasks styled
+    let reference_style = console::Style::new().cyan();
+    let mut rows = vec![];
+    for (idx, note) in notes.iter().enumerate() {
+        let references: Vec<std::ops::Range<usize>> =
+            tasks::find_references(note.note()).into_iter().map(|(range, _)| range).collect();
         let mut row = TableRow::new();
-        row.push(idx);
-        row.push(note.note());
-        print!("{}", tf.print_row(row).expect("Failed to format table row"));
-        idx += 1;
+        row.push(idx + 1);
+        row.push(table::highlight(note.note(), &references, &reference_style));
+        rows.push(row);
+    }
+    tf.write_table_io(&mut std::io::stdout(), rows)
+        .map_err(|err| TiskError::io("Failed to print notes", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        let args = configure_cli().get_matches_from_safe(args).map_err(|e| e.message)?;
+        parse_command(&args, &Config::default()).map_err(|e| e.to_string())
+    }
+
+    fn note(id: Option<u32>, note: Option<&str>) -> Command {
+        Command::Note { id, note: note.map(String::from) }
+    }
+
+    #[test]
+    fn every_command_has_examples() {
+        for (name, examples) in help::COMMANDS {
+            assert!(!examples.is_empty());
+            let err = configure_cli().get_matches_from_safe(["tisk", name, "--help"]).unwrap_err();
+            assert_eq!(clap::ErrorKind::HelpDisplayed, err.kind, "{} is not a command", name);
+        }
+    }
+
+    #[test]
+    fn pick_from_the_picker() {
+        let pick = |then: &str, selected: &str| {
+            let args = configure_cli().get_matches_from(["tisk", "pick", "--then", then]);
+            parse_pick(args.subcommand_matches("pick").unwrap(), selected).map_err(|e| e.to_string())
+        };
+        assert_eq!(Ok(Command::Checkout(Checkout::Id(7))), pick("checkout", "7\twrite the docs\n"));
+        assert_eq!(Ok(Command::Show(Some(12))), pick("show", "\n  12\tfix\tthe build\n"));
+        let close = Command::Close { id: Some(3), note: None, follow_up: None, cascade: false };
+        assert_eq!(Ok(close), pick("close", "3"));
+        assert_eq!(Err(String::from("No task was picked")), pick("show", "\n"));
+        assert!(pick("show", "write the docs").is_err());
+    }
+
+    #[test]
+    fn note_ids() {
+        assert_eq!(Ok(note(Some(42), Some("text"))), parse(&["tisk", "note", "42", "text"]));
+        assert_eq!(Ok(note(Some(42), Some("text"))), parse(&["tisk", "note", "--id", "42", "text"]));
+        assert_eq!(Ok(note(Some(42), Some("text"))), parse(&["tisk", "note", "--task", "42", "text"]));
+        assert_eq!(Ok(note(None, Some("text"))), parse(&["tisk", "note", "text"]));
+        assert_eq!(Ok(note(Some(42), None)), parse(&["tisk", "note", "42"]));
+        assert_eq!(Ok(note(None, None)), parse(&["tisk", "note", "--list"]));
+    }
+
+    #[test]
+    fn note_errors() {
+        assert!(parse(&["tisk", "note", "x", "text"]).is_err());
+        assert!(parse(&["tisk", "note", "--id", "x", "text"]).is_err());
+        assert!(parse(&["tisk", "note", "--id", "1", "2", "text"]).is_err());
+        assert!(parse(&["tisk", "note", "--list", "text"]).is_err());
+    }
+
+    #[test]
+    fn list_query() {
+        let conditions = |args: &[&str]| match parse(args) {
+            Ok(Command::List { query, .. }) => Ok(query.conditions),
+            other => Err(format!("{:?}", other)),
+        };
+        assert_eq!(
+            Ok(vec![Condition::Tag("sprint".into()), Condition::Assignee("ann".into())]),
+            conditions(&["tisk", "list", "--tag", "sprint", "--filter", "assignee=ann"])
+        );
+        assert!(parse(&["tisk", "list", "--filter", "owner=ann"]).is_err());
+        assert!(parse(&["tisk", "list", "--since", "soon"]).is_err());
+    }
+
+    #[test]
+    fn data_formats() {
+        let format = |args: &[&str]| DataFormat::from_args(&configure_cli().get_matches_from(args));
+        assert_eq!(Some(DataFormat::Json), format(&["tisk", "list", "--format", "json"]));
+        assert_eq!(Some(DataFormat::Yaml), format(&["tisk", "note", "7", "--list", "--format", "yaml"]));
+        assert_eq!(Some(DataFormat::Json), format(&["tisk", "--format", "json", "list"]));
+        assert_eq!(Some(DataFormat::Yaml), format(&["tisk", "--format", "yaml", "show", "7"]));
+        assert_eq!(None, format(&["tisk", "list", "--format", "csv"]));
+        assert_eq!(None, format(&["tisk", "--format", "json", "import", "backup.yaml", "--from", "yaml"]));
+        match parse(&["tisk", "list", "--format", "json"]) {
+            Ok(Command::List { format, .. }) => assert_eq!(TableFormat::Table, format),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        match parse(&["tisk", "--format", "csv", "list"]) {
+            Ok(Command::List { format, .. }) => assert_eq!(TableFormat::Csv, format),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        match parse(&["tisk", "--format", "csv", "export"]) {
+            Ok(Command::Export { format, .. }) => assert_eq!(tisk::export::ExportFormat::Csv, format),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        match parse(&["tisk", "export"]) {
+            Ok(Command::Export { format, .. }) => assert_eq!(tisk::export::ExportFormat::Markdown, format),
+            other => panic!("Unexpected command: {:?}", other),
+        }
+        assert!(parse(&["tisk", "export", "--format", "json"]).is_err());
     }
 }
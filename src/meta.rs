@@ -0,0 +1,202 @@
+/*!
+ * The choices made when a tisk project was initialized, and what is known
+ * about the project as a whole.  These are kept in the `project.yaml` file
+ * in the project directory so that every later command finds the
+ * project and reads and writes its tasks the same way.  A project without
+ * the file (e.g. one created by an older tisk) uses the defaults: a `.tisk`
+ * (or legacy `.task`) directory with one file per task.
+ */
+use crate::error::TiskError;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+
+/// The name of the metadata file inside a project directory.
+pub const META_FILE: &str = "project.yaml";
+
+/// The name of the project directory unless `tisk init --dir-name` says otherwise.
+pub const DEFAULT_DIR_NAME: &str = ".tisk";
+
+/// The name used for project directories by older versions of tisk, which
+/// is still recognized.
+pub const LEGACY_DIR_NAME: &str = ".task";
+
+/// The single file used to store every task with the `SingleFile` layout.
+pub const SINGLE_FILE: &str = "tasks.yaml";
+
+/// The version of the layout of a project's files which this tisk writes.
+/// It is raised whenever projects need to be migrated to be read.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How tasks are stored in the project directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Each task is stored in its own `<id>.yaml` file.
+    #[default]
+    PerTask,
+
+    /// Every task is stored in one `tasks.yaml` file.
+    SingleFile,
+}
+
+impl std::str::FromStr for Layout {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Layout, TiskError> {
+        match s {
+            "per-task" => Ok(Layout::PerTask),
+            "single-file" => Ok(Layout::SingleFile),
+            _ => Err(TiskError::ParseError(format!("Invalid storage layout: {}", s))),
+        }
+    }
+}
+
+/// What the project's tasks are stored with.  Each backend is a `Storage` in
+/// `tasks::io`, and YAML is the only one so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// YAML files in the project directory, laid out as the `Layout` says.
+    #[default]
+    Yaml,
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Backend::Yaml => write!(f, "yaml"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Meta {
+    /// The name of the project directory.
+    pub dir_name: String,
+
+    /// What tasks are stored with.
+    pub backend: Backend,
+
+    /// How tasks are stored in the project directory, by the YAML backend.
+    pub layout: Layout,
+
+    /// The project's name, if it has not been named then it is known by the
+    /// name of the directory it is in.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// When the project was initialized, unknown for projects created by
+    /// older versions of tisk.
+    #[serde(skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// The version of the layout of the project's files.
+    pub schema_version: u32,
+
+    /// The largest ID given to any task, so that the IDs of deleted tasks
+    /// are not given to new tasks.
+    pub last_id: u32,
+}
+
+impl Default for Meta {
+    fn default() -> Meta {
+        Meta {
+            dir_name: String::from(DEFAULT_DIR_NAME),
+            backend: Backend::default(),
+            layout: Layout::default(),
+            name: None,
+            created_at: None,
+            schema_version: SCHEMA_VERSION,
+            last_id: 0,
+        }
+    }
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Layout::PerTask => write!(f, "per-task"),
+            Layout::SingleFile => write!(f, "single-file"),
+        }
+    }
+}
+
+impl Meta {
+    /// Reads the metadata of the project whose directory is `task_path`.  A
+    /// project without a metadata file uses the defaults, with the name of
+    /// the directory it is in.
+    #[cfg(feature = "storage")]
+    pub fn read(task_path: &std::path::Path) -> std::io::Result<Meta> {
+        let s = match std::fs::read_to_string(task_path.join(META_FILE)) {
+            Ok(s) => s,
+            Err(err) if err.kind() == IoErrorKind::NotFound => {
+                let dir_name = task_path.file_name().and_then(|n| n.to_str()).unwrap_or(DEFAULT_DIR_NAME);
+                return Ok(Meta { dir_name: String::from(dir_name), ..Meta::default() });
+            }
+            Err(err) => return Err(err),
+        };
+
+        Meta::from_yaml(&s)
+    }
+
+    /// The name of the project whose directory is `task_path`: its own name,
+    /// or the name of the directory it is in.
+    pub fn project_name(&self, task_path: &std::path::Path) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => task_path
+                .parent()
+                .and_then(|root| root.file_name())
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| String::from("/")),
+        }
+    }
+
+    /// Parses metadata from the contents of a `project.yaml` file.
+    pub fn from_yaml(s: &str) -> std::io::Result<Meta> {
+        serde_yaml::from_str::<Meta>(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// The contents of the `project.yaml` file for this metadata.
+    pub fn to_yaml(&self) -> std::io::Result<String> {
+        serde_yaml::to_string(self).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// Writes this metadata to the project whose directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn write(&self, task_path: &std::path::Path) -> std::io::Result<()> {
+        crate::io::write_if_changed(&task_path.join(META_FILE), &self.to_yaml()?).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_fields_use_defaults() {
+        let meta = Meta::from_yaml("layout: single-file\n").unwrap();
+        assert_eq!(DEFAULT_DIR_NAME, meta.dir_name);
+        assert_eq!(Layout::SingleFile, meta.layout);
+        assert_eq!(Backend::Yaml, meta.backend);
+        assert_eq!(true, Meta::from_yaml("backend: sqlite\n").is_err());
+        assert_eq!(None, meta.created_at);
+        assert_eq!(SCHEMA_VERSION, meta.schema_version);
+        assert_eq!("app", meta.project_name(std::path::Path::new("/src/app/.tisk")));
+    }
+
+    #[test]
+    fn round_trip() {
+        let meta = Meta {
+            dir_name: String::from(".todo"),
+            layout: Layout::SingleFile,
+            name: Some(String::from("backlog")),
+            created_at: Some(Utc::now()),
+            last_id: 12,
+            ..Meta::default()
+        };
+        assert_eq!(meta, Meta::from_yaml(&meta.to_yaml().unwrap()).unwrap());
+    }
+}
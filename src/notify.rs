@@ -0,0 +1,212 @@
+/*!
+ * Notifications: how the user is told about something which needs their
+ * attention, e.g. a reminder which is due.  Each way of telling them is a
+ * `Notifier`, and the project's config picks which one is used with its
+ * `notifier` setting: printing to stdout, the desktop's notifications, or
+ * posting to a webhook, e.g. a chat channel.
+ */
+use crate::error::TiskError;
+use serde::{Deserialize, Serialize};
+
+/// Something the user is told about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Notification {
+    pub title: String,
+    pub body: String,
+}
+
+impl Notification {
+    pub fn new(title: &str, body: &str) -> Notification {
+        Notification {
+            title: String::from(title),
+            body: String::from(body),
+        }
+    }
+
+    /// The notification as the JSON object posted to a webhook.  The title
+    /// and body are repeated as `text`, which chat services such as Slack
+    /// show.
+    pub fn to_json(&self) -> String {
+        let text = if self.body.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{}: {}", self.title, self.body)
+        };
+        let payload = Payload { title: &self.title, body: &self.body, text: &text };
+        serde_json::to_string(&payload).expect("A payload of strings is always valid JSON")
+    }
+}
+
+/// The JSON object posted to a webhook.
+#[derive(Serialize)]
+struct Payload<'a> {
+    title: &'a str,
+    body: &'a str,
+    text: &'a str,
+}
+
+/// A way of telling the user about a notification.
+pub trait Notifier {
+    fn notify(&self, notification: &Notification) -> Result<(), TiskError>;
+}
+
+/// Which notifier is used, set with `notifier` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifierKind {
+    #[default]
+    Stdout,
+    Desktop,
+    Webhook,
+}
+
+impl std::str::FromStr for NotifierKind {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<NotifierKind, TiskError> {
+        match s {
+            "stdout" => Ok(NotifierKind::Stdout),
+            "desktop" => Ok(NotifierKind::Desktop),
+            "webhook" => Ok(NotifierKind::Webhook),
+            _ => Err(TiskError::ParseError(format!(
+                "Invalid notifier: {}, expected stdout, desktop, or webhook",
+                s
+            ))),
+        }
+    }
+}
+
+/// The notifier of kind `kind`.  A webhook notifier posts to `webhook_url`,
+/// which it must be given.
+pub fn notifier(kind: NotifierKind, webhook_url: Option<&str>) -> Result<Box<dyn Notifier>, TiskError> {
+    match (kind, webhook_url) {
+        (NotifierKind::Stdout, _) => Ok(Box::new(Stdout)),
+        (NotifierKind::Desktop, _) => Ok(Box::new(Desktop)),
+        (NotifierKind::Webhook, Some(url)) => Ok(Box::new(Webhook { url: String::from(url) })),
+        (NotifierKind::Webhook, None) => {
+            Err(TiskError::ParseError(String::from("The webhook notifier needs webhook_url to be set")))
+        }
+    }
+}
+
+/// Prints notifications to stdout, e.g. for a cron job which mails its
+/// output.
+pub struct Stdout;
+
+impl Notifier for Stdout {
+    fn notify(&self, notification: &Notification) -> Result<(), TiskError> {
+        use std::io::Write;
+        let line = if notification.body.is_empty() {
+            notification.title.clone()
+        } else {
+            format!("{}: {}", notification.title, notification.body)
+        };
+        writeln!(std::io::stdout(), "{}", line).map_err(|err| TiskError::io("Failed to print a notification", err))
+    }
+}
+
+/// Shows notifications with the desktop's notifications.  With the
+/// `desktop-notifications` feature they are shown with notify-rust, without
+/// it with `notify-send` on Linux and the BSDs, and `osascript` on macOS.
+pub struct Desktop;
+
+#[cfg(feature = "desktop-notifications")]
+impl Notifier for Desktop {
+    fn notify(&self, notification: &Notification) -> Result<(), TiskError> {
+        notify_rust::Notification::new()
+            .appname("tisk")
+            .summary(&notification.title)
+            .body(&notification.body)
+            .show()
+            .map(|_| ())
+            .map_err(|why| TiskError::External { context: format!("Showing a desktop notification failed: {}", why) })
+    }
+}
+
+#[cfg(not(feature = "desktop-notifications"))]
+impl Notifier for Desktop {
+    fn notify(&self, notification: &Notification) -> Result<(), TiskError> {
+        let mut command = if cfg!(target_os = "macos") {
+            let script = format!(
+                "display notification {} with title {}",
+                applescript_string(&notification.body),
+                applescript_string(&notification.title)
+            );
+            let mut command = std::process::Command::new("osascript");
+            command.args(["-e", &script]);
+            command
+        } else {
+            let mut command = std::process::Command::new("notify-send");
+            command.args(["--app-name", "tisk", &notification.title, &notification.body]);
+            command
+        };
+        let status = command.status().map_err(|err| TiskError::io("Failed to show a desktop notification", err))?;
+        match status.success() {
+            true => Ok(()),
+            false => {
+                Err(TiskError::External { context: format!("Showing a desktop notification failed with {}", status) })
+            }
+        }
+    }
+}
+
+/// Posts notifications as JSON, with `curl`, to a webhook.
+pub struct Webhook {
+    pub url: String,
+}
+
+impl Notifier for Webhook {
+    fn notify(&self, notification: &Notification) -> Result<(), TiskError> {
+        use std::process::{Command, Stdio};
+
+        // The notification is given to curl on stdin so that it is not in curl's arguments
+        let mut curl = Command::new("curl")
+            .args(["--silent", "--show-error", "--fail", "--output", "/dev/null"])
+            .args(["--header", "Content-Type: application/json", "--data-binary", "@-", &self.url])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| TiskError::io("Failed to start curl to post a notification", err))?;
+        if let Some(mut stdin) = curl.stdin.take() {
+            std::io::Write::write_all(&mut stdin, notification.to_json().as_bytes())
+                .map_err(|err| TiskError::io("Failed to give curl the notification", err))?;
+        }
+        let status = curl.wait().map_err(|err| TiskError::io("Failed to post a notification", err))?;
+        match status.success() {
+            true => Ok(()),
+            false => {
+                let context = format!("Posting a notification failed, curl exited with {}", status);
+                Err(TiskError::External { context })
+            }
+        }
+    }
+}
+
+/// `s` as an AppleScript string, with quotes around it.
+#[cfg(not(feature = "desktop-notifications"))]
+fn applescript_string(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn webhook_payload() {
+        let notification = Notification::new("Reminder", "Task 7: write the \"docs\"\n");
+        assert_eq!(
+            "{\"title\":\"Reminder\",\"body\":\"Task 7: write the \\\"docs\\\"\\n\",\
+             \"text\":\"Reminder: Task 7: write the \\\"docs\\\"\\n\"}",
+            notification.to_json()
+        );
+        assert_eq!(true, Notification::new("a\u{7}b", "").to_json().starts_with("{\"title\":\"a\\u0007b\""));
+    }
+
+    #[test]
+    fn pick_notifier() {
+        assert_eq!(Ok(NotifierKind::Desktop), "desktop".parse().map_err(|e: TiskError| e.to_string()));
+        assert_eq!(true, "email".parse::<NotifierKind>().is_err());
+        assert_eq!(true, notifier(NotifierKind::Webhook, None).is_err());
+        assert_eq!(true, notifier(NotifierKind::Webhook, Some("https://example.com/hook")).is_ok());
+    }
+}
@@ -0,0 +1,82 @@
+/*!
+ * A snapshot of everything in a tisk project: its tasks, which task is
+ * checked out, and its config.  A `Project` can be serialized as a single
+ * document, which makes it the one representation of a project shared by
+ * anything that needs to move a whole project around (e.g. backups).
+ */
+use crate::config::Config;
+#[cfg(feature = "storage")]
+use crate::io;
+use crate::tasks::TaskList;
+use serde::{Deserialize, Serialize};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Project {
+    pub tasks: TaskList,
+
+    #[serde(default)]
+    pub checked_out: Option<u32>,
+
+    #[serde(default)]
+    pub config: Config,
+}
+
+impl Project {
+    /// Reads the project whose `.tisk` directory is `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn read(task_path: &std::path::PathBuf) -> std::io::Result<Project> {
+        Ok(Project {
+            tasks: TaskList::read_tasks(task_path)?,
+            checked_out: io::read_checkout(task_path)?,
+            config: Config::read(task_path)?,
+        })
+    }
+
+    /// Writes every part of this project into the `.tisk` directory at
+    /// `task_path`.
+    #[cfg(feature = "storage")]
+    pub fn write(&self, task_path: &std::path::PathBuf) -> std::io::Result<()> {
+        self.tasks.write_all(task_path)?;
+        match self.checked_out {
+            Some(id) => io::commit_checkout(id, task_path)?,
+            None => io::commit_checkin(task_path)?,
+        }
+        self.config.write(task_path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::table::TableStyle;
+
+    #[test]
+    fn serialize_project() {
+        let mut project = Project::default();
+        let id = project.tasks.add_task("test", 2);
+        project.tasks.get_mut(id).unwrap().add_note("a note").unwrap();
+        project.tasks.add_task("test 2", 1);
+        project.checked_out = Some(id);
+        project.config.table_style = TableStyle::Unicode;
+
+        let s = serde_yaml::to_string(&project).unwrap();
+        let read: Project = serde_yaml::from_str(&s).unwrap();
+
+        assert_eq!(Some(1), read.checked_out);
+        assert_eq!(TableStyle::Unicode, read.config.table_style);
+        assert_eq!(2, read.tasks.get_all().len());
+        let task = read.tasks.get(1).unwrap();
+        assert_eq!("test", task.name());
+        assert_eq!(2, task.priority());
+        assert_eq!("a note", task.notes()[0].note());
+        assert_eq!(3, read.tasks.next_id());
+    }
+
+    #[test]
+    fn deserialize_tasks_only() {
+        let s = "---\ntasks:\n  - id: 4\n    name: test\n    status: Open\n";
+        let project: Project = serde_yaml::from_str(s).unwrap();
+        assert_eq!(None, project.checked_out);
+        assert_eq!("test", project.tasks.get(4).unwrap().name());
+    }
+}
@@ -0,0 +1,155 @@
+/*!
+ * Selects tasks from a project.  Listing tasks and exporting them, as
+ * markdown, CSV, or TSV from `tisk list` or as JSON through the C interface,
+ * all pick their tasks with a `Query` so that the same flags select the same
+ * tasks everywhere.
+ */
+use crate::engine::ListFilter;
+use crate::error::TiskError;
+use crate::tasks::{Status, Task, TaskList};
+use crate::text::Matching;
+use chrono::{DateTime, Utc};
+
+/// A condition a task must meet to be selected, written `FIELD=VALUE`
+/// on the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Tag(String),
+    Assignee(String),
+    Priority(u32),
+
+    /// The task depends directly on the task with this ID.
+    BlockedBy(u32),
+}
+
+impl std::str::FromStr for Condition {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Condition, TiskError> {
+        let (field, value) = match s.find('=') {
+            Some(idx) => (s[..idx].trim(), s[idx + 1..].trim()),
+            None => return Err(TiskError::ParseError(format!("Invalid filter, expected FIELD=VALUE: {}", s))),
+        };
+        match field {
+            "tag" => Ok(Condition::Tag(String::from(value))),
+            "assignee" => Ok(Condition::Assignee(String::from(value))),
+            "priority" => match value.parse() {
+                Ok(priority) => Ok(Condition::Priority(priority)),
+                Err(_) => Err(TiskError::ParseError(String::from(
+                    "Invalid priority value: must be an integer greater than or equal to 0",
+                ))),
+            },
+            "blocked-by" => match value.parse() {
+                Ok(id) => Ok(Condition::BlockedBy(id)),
+                Err(_) => Err(TiskError::ParseError(String::from("Invalid blocked-by value: must be a task ID"))),
+            },
+            _ => Err(TiskError::ParseError(format!(
+                "Invalid filter field: {} (expected tag, assignee, priority, or blocked-by)",
+                field
+            ))),
+        }
+    }
+}
+
+/// Which tasks to select, a task is selected only if it meets every part
+/// of the query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub filter: ListFilter,
+    pub conditions: Vec<Condition>,
+
+    /// Only select the tasks whose name contains this text.
+    pub contains: Option<String>,
+
+    /// Only select the tasks created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+
+    /// How `contains`, tags, and assignees are matched to the tasks.
+    pub matching: Matching,
+}
+
+impl Query {
+    /// A query which selects every task with the given status.
+    pub fn new(filter: ListFilter) -> Query {
+        Query {
+            filter,
+            conditions: vec![],
+            contains: None,
+            since: None,
+            matching: Matching::default(),
+        }
+    }
+
+    /// Whether `task` meets every part of this query.
+    pub fn matches(&self, task: &Task) -> bool {
+        let status = match self.filter {
+            ListFilter::All => true,
+            ListFilter::Open => task.status().is_open(),
+            ListFilter::Closed => task.status() == Status::Closed,
+            ListFilter::InProgress => task.status() == Status::InProgress,
+            ListFilter::Blocked => task.status() == Status::Blocked,
+        };
+        status
+            && self.contains.as_ref().map(|text| self.matching.contains(task.name(), text)).unwrap_or(true)
+            && self.since.map(|since| task.created_at() >= since).unwrap_or(true)
+            && self.conditions.iter().all(|condition| self.meets(task, condition))
+    }
+
+    fn meets(&self, task: &Task, condition: &Condition) -> bool {
+        match condition {
+            Condition::Tag(tag) => task.tags().iter().any(|t| self.matching.eq(t, tag)),
+            Condition::Assignee(name) => task.assignee().map(|a| self.matching.eq(a, name)).unwrap_or(false),
+            Condition::Priority(priority) => task.priority() == *priority,
+            Condition::BlockedBy(id) => task.depends_on().contains(id),
+        }
+    }
+
+    /// The tasks in `tasks` which this query selects, in ID order.
+    pub fn select<'a>(&self, tasks: &'a TaskList) -> Vec<&'a Task> {
+        tasks.get_all().into_iter().filter(|task| self.matches(task)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select() {
+        let mut tasks = TaskList::new();
+        for (name, tag, priority) in &[("plan sprint", "sprint-4", 2), ("fix bug", "sprint-4", 5), ("write docs", "docs", 5)] {
+            let id = tasks.add_task(name, *priority);
+            tasks.get_mut(id).unwrap().add_tag(tag);
+        }
+        tasks.close_task(1, false).unwrap();
+        tasks.get_mut(3).unwrap().set_status(Status::Blocked);
+        tasks.get_mut(2).unwrap().add_dependency(3);
+        let ids = |query: &Query| query.select(&tasks).iter().map(|t| t.id()).collect::<Vec<u32>>();
+
+        let mut sprint = Query::new(ListFilter::All);
+        sprint.conditions.push("tag=Sprint-4".parse().unwrap());
+        assert_eq!(Vec::<u32>::new(), ids(&sprint));
+        sprint.matching.ignore_case = true;
+        assert_eq!(vec![1, 2], ids(&sprint));
+
+        sprint.filter = ListFilter::Open;
+        assert_eq!(vec![2], ids(&sprint));
+
+        let mut urgent = Query::new(ListFilter::Open);
+        urgent.conditions.push("priority=5".parse().unwrap());
+        urgent.contains = Some(String::from("docs"));
+        assert_eq!(vec![3], ids(&urgent));
+
+        urgent.filter = ListFilter::Blocked;
+        assert_eq!(vec![3], ids(&urgent));
+        urgent.filter = ListFilter::InProgress;
+        assert_eq!(Vec::<u32>::new(), ids(&urgent));
+
+        let mut blocked = Query::new(ListFilter::All);
+        blocked.conditions.push("blocked-by=3".parse().unwrap());
+        assert_eq!(vec![2], ids(&blocked));
+
+        assert_eq!(true, "owner=me".parse::<Condition>().is_err());
+        assert_eq!(true, "tag".parse::<Condition>().is_err());
+    }
+}
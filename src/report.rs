@@ -0,0 +1,86 @@
+/*!
+ * Reports the error a command failed with, either as colored text for a
+ * person or, with `--errors json`, as a JSON object for the program (e.g.
+ * an editor integration) which ran tisk.
+ */
+use serde::Serialize;
+use tisk::error::TiskError;
+
+/// How errors are reported.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Text,
+    Json,
+}
+
+/// A failure described for programs.  Fields which are not known are `null`.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct ErrorReport {
+    /// The kind of failure: `not_initialized`, `task_not_found`, `no_task`,
+    /// `invalid_argument`, `conflict`, `external`, `io`, or `serde`.
+    pub code: &'static str,
+
+    pub message: String,
+
+    /// The task the failure is about.
+    pub task_id: Option<u32>,
+
+    /// The project directory the command was run against.
+    pub path: Option<String>,
+}
+
+impl ErrorReport {
+    pub fn new(err: &TiskError, path: Option<&std::path::Path>) -> ErrorReport {
+        ErrorReport {
+            code: err.code(),
+            message: err.to_string(),
+            task_id: err.task_id(),
+            path: path.map(|p| p.display().to_string()),
+        }
+    }
+}
+
+/// Prints `err` to stderr in `format`.
+pub fn report(format: ErrorFormat, err: &TiskError, path: Option<&std::path::Path>) {
+    match format {
+        ErrorFormat::Text => eprintln!("{}: {}", console::style("Error").red(), err),
+        ErrorFormat::Json => match serde_json::to_string(&ErrorReport::new(err, path)) {
+            Ok(json) => eprintln!("{}", json),
+            Err(_) => eprintln!("Error: {}", err),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_not_found() {
+        let report = ErrorReport::new(&TiskError::TaskNotFound(7), None);
+        assert_eq!("task_not_found", report.code);
+        assert_eq!("Could not find task with ID 7", report.message);
+        assert_eq!(Some(7), report.task_id);
+    }
+
+    #[test]
+    fn codes() {
+        let code = |err: TiskError| ErrorReport::new(&err, None).code;
+        assert_eq!("not_initialized", code(TiskError::NotInitialized(String::from("Invalid tisk project"))));
+        assert_eq!("invalid_argument", code(TiskError::ParseError(String::from("Invalid priority value"))));
+        assert_eq!("conflict", code(TiskError::Conflict(String::from("Task 3 is closed, reopen it first"))));
+        let denied = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "permission denied");
+        assert_eq!("io", code(TiskError::io("Failed to read tasks", denied)));
+        assert_eq!("serde", code(TiskError::Serde(String::from("Failed to parse tasks.yaml"))));
+    }
+
+    #[test]
+    fn json() {
+        let err = TiskError::ParseError(String::from("Task 4 must have a name"));
+        let report = ErrorReport::new(&err, Some(std::path::Path::new("/p/.tisk")));
+        assert_eq!(
+            r#"{"code":"invalid_argument","message":"Task 4 must have a name","task_id":null,"path":"/p/.tisk"}"#,
+            serde_json::to_string(&report).unwrap()
+        );
+    }
+}
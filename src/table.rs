@@ -1,3 +1,5 @@
+use crate::error::TiskError;
+use serde::{Deserialize, Serialize};
 
 /// Format a table with a custom number of columns, column types,
 /// and rows. TableFormatter manages the width of each column and
@@ -5,256 +7,1546 @@
 /// Cells can contain any type so long they implement the 
 /// `std::fmt::Display` trait.
 pub struct TableFormatter {
-    width: usize, // the width, in characters, of the table
+    style: TableStyle,
+    zebra: Option<console::Style>,
+    header: bool,
+    sort: Vec<(usize, Order)>, // the columns to sort rows by
     col_widths: Vec<usize>,
-    cols: Vec<String>,
+    cols: Vec<Column>,
+    shown: Vec<usize>, // the indices of the columns which fit in the table
+}
+
+/// The borders which are drawn around and between the cells of a table.
+///
+/// `Plain` draws no borders: columns are separated by a space and the
+/// header is underlined.  `Ascii` and `Unicode` draw a border around the
+/// table, a separator below the header, and a divider between each row
+/// using either `+-|` or box drawing characters.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableStyle {
+    #[default]
+    Plain,
+    Ascii,
+    Unicode,
+}
+
+impl std::str::FromStr for TableStyle {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" | "none" => Ok(TableStyle::Plain),
+            "ascii" => Ok(TableStyle::Ascii),
+            "unicode" => Ok(TableStyle::Unicode),
+            _ => Err(TiskError::ParseError(format!("Unknown table style: {}", s))),
+        }
+    }
+}
+
+/// The formats a table can be rendered in.  `Table` is the formatted table
+/// used for the console, the others render the same columns and rows as
+/// text which other programs can read.  These formats ignore the width of
+/// the table: every column is included and cells are never wrapped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    Table,
+    Markdown,
+    Csv,
+    Tsv,
+}
+
+impl std::str::FromStr for TableFormat {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(TableFormat::Table),
+            "md" | "markdown" => Ok(TableFormat::Markdown),
+            "csv" => Ok(TableFormat::Csv),
+            "tsv" => Ok(TableFormat::Tsv),
+            _ => Err(TiskError::ParseError(format!("Unknown table format: {}", s))),
+        }
+    }
+}
+
+/// The characters used to draw a horizontal line across the table: the
+/// left edge, the line itself, the crossing with a column border, and
+/// the right edge.
+struct Rule {
+    left: char,
+    line: char,
+    cross: char,
+    right: char,
+}
+
+impl TableStyle {
+    /// The string written to the left of the first column, between each
+    /// column, and to the right of the last column.
+    fn edges(&self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            TableStyle::Plain => ("", " ", ""),
+            TableStyle::Ascii => ("| ", " | ", " |"),
+            TableStyle::Unicode => ("│ ", " │ ", " │"),
+        }
+    }
+
+    /// The lines drawn at the top of the table, below the header, between
+    /// rows, and at the bottom of the table.
+    fn rules(&self) -> Option<[Rule; 4]> {
+        let rule = |left, line, cross, right| Rule {
+            left,
+            line,
+            cross,
+            right,
+        };
+        match self {
+            TableStyle::Plain => None,
+            TableStyle::Ascii => Some([
+                rule('+', '-', '+', '+'),
+                rule('+', '=', '+', '+'),
+                rule('+', '-', '+', '+'),
+                rule('+', '-', '+', '+'),
+            ]),
+            TableStyle::Unicode => Some([
+                rule('┌', '─', '┬', '┐'),
+                rule('╞', '═', '╪', '╡'),
+                rule('├', '─', '┼', '┤'),
+                rule('└', '─', '┴', '┘'),
+            ]),
+        }
+    }
+}
+
+/// How the contents of a cell are positioned within its column.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    Left,
+    Right,
+    Center,
+}
+
+/// What is done with the contents of a cell which are wider than its
+/// column.  `Wrap` breaks the contents across multiple lines, `Truncate`
+/// shortens the contents to a single line ending in `…`, and `Cut` shortens
+/// the contents to a single line without any marker.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Truncate,
+    Cut,
+}
+
+/// The value used to order the rows of a table by a column.  Numbers are
+/// ordered before text.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SortKey {
+    Number(i64),
+    Text(String),
+}
+
+impl SortKey {
+    /// Orders a cell by its text, ignoring any styling.
+    pub fn text(cell: &str) -> SortKey {
+        SortKey::Text(console::strip_ansi_codes(cell).into_owned())
+    }
+
+    /// Orders a cell numerically, if the cell is not a number then it is
+    /// ordered by its text.
+    pub fn number(cell: &str) -> SortKey {
+        let text = console::strip_ansi_codes(cell);
+        match text.trim().parse::<i64>() {
+            Ok(n) => SortKey::Number(n),
+            Err(_) => SortKey::Text(text.into_owned()),
+        }
+    }
+}
+
+/// The direction rows are sorted in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// A single column in a table: its label, how wide it is, and how
+/// the contents of its cells are aligned.
+pub struct Column {
+    label: String,
+    width: Option<usize>,
+    min_width: usize,
+    max_width: Option<usize>,
+    drop_priority: Option<u32>,
+    minimal: bool,
+    align: Alignment,
+    overflow: Overflow,
+    break_chars: Vec<char>,
+    sort_key: Box<dyn Fn(&str) -> SortKey>,
+}
+
+impl Column {
+    /// Creates a left aligned column.  If no width is provided, then
+    /// the column will share the space left over by the other columns.
+    pub fn new(label: &str, width: Option<usize>) -> Self {
+        Self {
+            label: String::from(label),
+            width,
+            min_width: 1,
+            max_width: None,
+            drop_priority: None,
+            minimal: false,
+            align: Alignment::Left,
+            overflow: Overflow::Wrap,
+            break_chars: vec!['-', '/'],
+            sort_key: Box::new(SortKey::text),
+        }
+    }
+
+    /// Sets how the cells of this column are turned into the values which
+    /// rows are sorted by.  By default, cells are sorted by their text.
+    pub fn sort_key(mut self, sort_key: impl Fn(&str) -> SortKey + 'static) -> Self {
+        self.sort_key = Box::new(sort_key);
+        self
+    }
+
+    /// Sets what is done with cells which are too wide for this column.
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets the characters after which a wrapped word may be broken onto
+    /// the next line without adding a hyphen.  By default, words break after
+    /// hyphens and slashes so that file paths and URLs wrap cleanly.
+    pub fn break_chars(mut self, break_chars: &[char]) -> Self {
+        self.break_chars = break_chars.to_vec();
+        self
+    }
+
+    /// Sets how the contents of each cell in this column are aligned.
+    pub fn align(mut self, align: Alignment) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// The narrowest a column without a width can be before the table
+    /// starts dropping columns to make room for it.
+    pub fn min_width(mut self, min_width: usize) -> Self {
+        self.min_width = min_width;
+        self
+    }
+
+    /// The widest a column without a width will grow, any extra space
+    /// is given to the other columns without a width.
+    pub fn max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Allows this column to be left out of the table when the table is
+    /// too narrow to fit every column.  Columns with the lowest priority
+    /// are dropped first.  Columns without a drop priority are never dropped.
+    pub fn drop_priority(mut self, priority: u32) -> Self {
+        self.drop_priority = Some(priority);
+        self
+    }
+
+    /// Keeps this column in the table's minimal layout, see
+    /// `TableBuilder::minimal_below`.
+    pub fn minimal(mut self) -> Self {
+        self.minimal = true;
+        self
+    }
+}
+
+/// The reasons a table cannot be laid out.
+#[derive(Debug, PartialEq)]
+pub enum TableError {
+    /// The table was given no columns.
+    NoColumns,
+
+    /// The table was asked to sort by a column it does not have.
+    UnknownColumn(String),
+
+    /// The table is `width` characters wide but the columns, which cannot
+    /// be dropped, need at least `required` characters.
+    TooNarrow { width: usize, required: usize },
+}
+
+impl std::fmt::Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TableError::NoColumns => write!(f, "A table must have at least one column"),
+            TableError::UnknownColumn(label) => write!(f, "The table has no column named {}", label),
+            TableError::TooNarrow { width, required } => write!(
+                f,
+                "The table needs to be at least {} characters wide but is only {} characters wide",
+                required, width
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TableError {}
+
+/// Configures and lays out a `TableFormatter`.  The columns are only sized
+/// when `build` is called, so the style and columns can be given in any order.
+pub struct TableBuilder {
+    width: usize,
+    style: TableStyle,
+    zebra: Option<console::Style>,
+    header: bool,
+    minimal_below: usize,
+    sort: Vec<(String, Order)>,
+    cols: Vec<Column>,
+}
+
+impl TableBuilder {
+    /// Starts a table which is `width` characters wide.
+    pub fn new(width: usize) -> Self {
+        Self {
+            width,
+            style: TableStyle::Plain,
+            zebra: None,
+            header: true,
+            minimal_below: 0,
+            sort: Vec::new(),
+            cols: Vec::new(),
+        }
+    }
+
+    /// Sorts the rows of the table by the column labeled `label`.  If this
+    /// is called more than once then rows which are equal in the first
+    /// column are ordered by the next, and so on.  Column labels are not
+    /// case sensitive.
+    pub fn sort_by(mut self, label: &str, order: Order) -> Self {
+        self.sort.push((String::from(label), order));
+        self
+    }
+
+    /// Applies `style` to every other row of the table, starting with the
+    /// second row, so that rows are easier to tell apart.
+    pub fn zebra(mut self, style: console::Style) -> Self {
+        self.zebra = Some(style);
+        self
+    }
+
+    /// Whether the labels of the columns are written before the rows, which
+    /// they are by default.  Without them each line of a plain table, or of
+    /// a CSV or TSV table, is one row, ready to be fed into another program.
+    pub fn header(mut self, header: bool) -> Self {
+        self.header = header;
+        self
+    }
+
+    /// Lays the table out minimally when it is narrower than `width`, e.g.
+    /// on a very narrow terminal: only the columns marked `Column::minimal`
+    /// are shown and cells which do not fit are truncated, so that every row
+    /// stays on one line.  Tables without minimal columns are laid out as
+    /// usual.
+    pub fn minimal_below(mut self, width: usize) -> Self {
+        self.minimal_below = width;
+        self
+    }
+
+    /// Sets which borders are drawn around the cells of the table.  Borders
+    /// take up space in the table, so the columns are resized to fit.
+    pub fn style(mut self, style: TableStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Adds a column to the right side of the table.
+    pub fn column(mut self, col: Column) -> Self {
+        self.cols.push(col);
+        self
+    }
+
+    /// Adds each of `cols`, in order, to the right side of the table.
+    pub fn columns(mut self, cols: Vec<Column>) -> Self {
+        self.cols.extend(cols);
+        self
+    }
+
+    /// Computes the width of each column and which columns fit within
+    /// the table.  If not width is provided for a column, then its width is
+    /// dynamically calculated based upon the width of the table and how
+    /// wide the other columns are.
+    ///
+    /// If the table is too narrow for the columns, then columns with a drop
+    /// priority are left out of the table, lowest priority first, until the
+    /// remaining columns fit.  If the columns still do not fit then an error
+    /// is returned.
+    pub fn build(self) -> Result<TableFormatter, TableError> {
+        let mut cols = self.cols;
+        if cols.is_empty() {
+            return Err(TableError::NoColumns);
+        }
+
+        let mut sort = vec![];
+        for (label, order) in self.sort {
+            match cols.iter().position(|c| c.label.eq_ignore_ascii_case(&label)) {
+                Some(idx) => sort.push((idx, order)),
+                None => return Err(TableError::UnknownColumn(label)),
+            }
+        }
+
+        // The space taken up by borders: a separator between each column and
+        // the left and right edges of the table
+        let (left, sep, right) = self.style.edges();
+        let sep_width = sep.chars().count();
+        let edge_width = left.chars().count() + right.chars().count();
+        let width = (self.width + sep_width).saturating_sub(edge_width);
+
+        let minimal = self.width < self.minimal_below && cols.iter().any(|c| c.minimal);
+        if minimal {
+            for col in cols.iter_mut().filter(|c| c.overflow == Overflow::Wrap) {
+                col.overflow = Overflow::Truncate;
+            }
+        }
+        let mut shown: Vec<usize> = (0..cols.len()).filter(|&c| !minimal || cols[c].minimal).collect();
+        let widths = loop {
+            let (widths, fits) = layout(width, sep_width, &cols, &shown);
+            if fits {
+                break widths;
+            }
+
+            let lowest = shown
+                .iter()
+                .enumerate()
+                .filter(|(_, &c)| cols[c].drop_priority.is_some())
+                .min_by_key(|(_, &c)| cols[c].drop_priority)
+                .map(|(idx, _)| idx);
+            match lowest {
+                Some(idx) => {
+                    shown.remove(idx);
+                }
+                None => break widths,
+            }
+        };
+
+        // Every column must be at least one character wide, even if the columns
+        // do not reach their minimum width
+        let required = shown
+            .iter()
+            .map(|&c| cols[c].width.unwrap_or(1).max(1))
+            .sum::<usize>()
+            + sep_width * shown.len().saturating_sub(1)
+            + edge_width;
+        if shown.is_empty() || self.width < required || shown.iter().any(|&c| widths[c] == 0) {
+            return Err(TableError::TooNarrow {
+                width: self.width,
+                required,
+            });
+        }
+
+        Ok(TableFormatter {
+            style: self.style,
+            zebra: self.zebra,
+            header: self.header,
+            sort,
+            col_widths: widths,
+            cols,
+            shown,
+        })
+    }
+}
+
+/// The contents of a single cell.  Numbers and borrowed text are kept as
+/// they are, so the common cells of a row need no allocation until the
+/// row is written, any other value is boxed.
+enum Cell<'a> {
+    Number(u64),
+    Text(&'a str),
+    Owned(String),
+    Display(Box<dyn std::fmt::Display + 'a>),
+}
+
+impl<'a> Cell<'a> {
+    /// The text of this cell, only numbers and boxed values are formatted.
+    fn text(&self) -> std::borrow::Cow<'_, str> {
+        use std::borrow::Cow;
+        match self {
+            Cell::Number(n) => Cow::Owned(n.to_string()),
+            Cell::Text(text) => Cow::Borrowed(text),
+            Cell::Owned(text) => Cow::Borrowed(text),
+            Cell::Display(value) => Cow::Owned(value.to_string()),
+        }
+    }
 }
 
 // A single row in a table.  Pass a `TableRow` to
 // `TableFormatter::print_row` which will format the cells
 // into a `String`.
+#[derive(Default)]
 pub struct TableRow<'a> {
-    row: Vec<Box<dyn std::fmt::Display + 'a>>,
+    row: Vec<Cell<'a>>,
+    style: Option<console::Style>,
 }
 
 impl<'a> TableRow<'a> {
     pub fn new() -> Self {
-        Self { row: Vec::new() }
+        Self::default()
     }
 
     pub fn push<S: std::fmt::Display + 'a>(&mut self, col: S) {
-        self.row.push(Box::new(col))
+        self.row.push(Cell::Display(Box::new(col)))
+    }
+
+    /// Adds a cell holding a number, without boxing it.
+    pub fn push_number(&mut self, n: u64) {
+        self.row.push(Cell::Number(n))
+    }
+
+    /// Adds a cell holding borrowed text, without copying it.
+    pub fn push_str(&mut self, text: &'a str) {
+        self.row.push(Cell::Text(text))
+    }
+
+    /// Adds a cell holding text which has already been formatted.
+    pub fn push_string(&mut self, text: String) {
+        self.row.push(Cell::Owned(text))
+    }
+
+    /// Styles every cell in this row.  Cells which carry their own style
+    /// keep it, the row's style is applied around them.
+    pub fn set_style(&mut self, style: console::Style) {
+        self.style = Some(style);
     }
 }
 
 impl TableFormatter {
+    /// Writes the header and then each row of the table into `w`.
+    pub fn write_table<W: std::fmt::Write>(&self, w: &mut W, rows: Vec<TableRow>) -> std::fmt::Result {
+        self.write_as(w, rows, TableFormat::Table)
+    }
 
-    pub fn new(width: usize) -> Self {
-        Self {
-            width,
-            col_widths: Vec::new(),
-            cols: Vec::new(),
+    /// Orders `rows` by the table's sort columns.  Rows which are equal
+    /// keep their original order.
+    pub fn sort_rows<'a>(&self, rows: Vec<TableRow<'a>>) -> Vec<TableRow<'a>> {
+        if self.sort.is_empty() {
+            return rows;
+        }
+
+        let mut keyed: Vec<(Vec<SortKey>, TableRow)> = rows
+            .into_iter()
+            .map(|row| {
+                let keys = self
+                    .sort
+                    .iter()
+                    .map(|&(col, _)| match row.row.get(col) {
+                        Some(cell) => (self.cols[col].sort_key)(&cell.text()),
+                        None => SortKey::Text(String::new()),
+                    })
+                    .collect();
+                (keys, row)
+            })
+            .collect();
+
+        keyed.sort_by(|(a, _), (b, _)| {
+            for (idx, &(_, order)) in self.sort.iter().enumerate() {
+                let ord = match order {
+                    Order::Asc => a[idx].cmp(&b[idx]),
+                    Order::Desc => b[idx].cmp(&a[idx]),
+                };
+                if ord != std::cmp::Ordering::Equal {
+                    return ord;
+                }
+            }
+            std::cmp::Ordering::Equal
+        });
+        keyed.into_iter().map(|(_, row)| row).collect()
+    }
+
+    /// Renders the table, with its header and `rows`, in the given format.
+    pub fn render_as(&self, rows: Vec<TableRow>, format: TableFormat) -> String {
+        let mut table = String::new();
+        self.write_as(&mut table, rows, format).expect("Failed to write table to a String");
+        table
+    }
+
+    fn write_as<W: std::fmt::Write>(&self, w: &mut W, rows: Vec<TableRow>, format: TableFormat) -> std::fmt::Result {
+        self.write_start(w, format)?;
+        for (idx, row) in self.sort_rows(rows).into_iter().enumerate() {
+            self.write_row_as(w, idx, row, format)?;
+        }
+        self.write_end(w, format)
+    }
+
+    /// Writes the table into `w` in the given format one row at a time, as
+    /// `rows` produces them, so that the whole table is never held in
+    /// memory.  If the table is sorted, then the rows are collected and
+    /// sorted first.
+    pub fn write_rows_io<'a, W, I>(&self, w: &mut W, rows: I, format: TableFormat) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+        I: IntoIterator<Item = TableRow<'a>>,
+    {
+        if self.sort.is_empty() {
+            self.stream(w, rows.into_iter(), format)
+        } else {
+            self.stream(w, self.sort_rows(rows.into_iter().collect()).into_iter(), format)
+        }
+    }
+
+    fn stream<'a, W, I>(&self, w: &mut W, rows: I, format: TableFormat) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+        I: Iterator<Item = TableRow<'a>>,
+    {
+        let to_io = std::io::Error::other;
+
+        // Each row is formatted into the same buffer, which is then written out
+        let mut buf = String::new();
+        self.write_start(&mut buf, format).map_err(to_io)?;
+        w.write_all(buf.as_bytes())?;
+        for (idx, row) in rows.enumerate() {
+            buf.clear();
+            self.write_row_as(&mut buf, idx, row, format).map_err(to_io)?;
+            w.write_all(buf.as_bytes())?;
+        }
+        buf.clear();
+        self.write_end(&mut buf, format).map_err(to_io)?;
+        w.write_all(buf.as_bytes())
+    }
+
+    /// Writes what comes before the rows of the table in the given format.
+    fn write_start<W: std::fmt::Write>(&self, w: &mut W, format: TableFormat) -> std::fmt::Result {
+        if !self.header {
+            // A bordered table is still closed at the top
+            return match (format, self.style.rules()) {
+                (TableFormat::Table, Some(rules)) => {
+                    self.write_rule(w, &rules[0])?;
+                    writeln!(w)
+                }
+                _ => Ok(()),
+            };
+        }
+        match format {
+            TableFormat::Table => {
+                self.write_header(w)?;
+                writeln!(w)
+            }
+            TableFormat::Markdown => {
+                let labels: Vec<String> = self.cols.iter().map(|c| markdown_field(&c.label)).collect();
+                writeln!(w, "| {} |", labels.join(" | "))?;
+
+                let rules: Vec<&str> = self
+                    .cols
+                    .iter()
+                    .map(|c| match c.align {
+                        Alignment::Left => ":---",
+                        Alignment::Right => "---:",
+                        Alignment::Center => ":---:",
+                    })
+                    .collect();
+                writeln!(w, "| {} |", rules.join(" | "))
+            }
+            TableFormat::Csv => self.write_labels(w, ',', csv_field),
+            TableFormat::Tsv => self.write_labels(w, '\t', tsv_field),
+        }
+    }
+
+    /// Writes the `idx`th row of the table in the given format.
+    fn write_row_as<W: std::fmt::Write>(&self, w: &mut W, idx: usize, row: TableRow, format: TableFormat) -> std::fmt::Result {
+        match format {
+            TableFormat::Table => {
+                if idx > 0 {
+                    self.write_divider(w)?;
+                }
+                let stripe = match &self.zebra {
+                    Some(zebra) if idx % 2 == 1 => style_codes(zebra),
+                    _ => String::new(),
+                };
+                self.write_styled_row(w, row, &stripe)
+            }
+            TableFormat::Markdown => {
+                let cells: Vec<String> = row.row.iter().map(|c| markdown_field(&c.text())).collect();
+                writeln!(w, "| {} |", cells.join(" | "))
+            }
+            TableFormat::Csv => write_delimited_row(w, &row, ',', csv_field),
+            TableFormat::Tsv => write_delimited_row(w, &row, '\t', tsv_field),
         }
     }
 
-    /// Sets the number of columns in the table, their labels, and
-    /// how wide the column is.  If not width is provided, then the
-    /// width is dynamically calculated based upon the width of the
-    /// table and how wide the other columns are.
-    pub fn set_columns(&mut self, cols: Vec<(&str, Option<usize>)>) {
-        // Add up the widths of the explicitly defined columns
-        // adding 1 to account for a space between each column
-        let with_width = cols.iter().filter(|x| x.1.is_some());
-        let allocated_width: usize = with_width.map(|x| x.1.unwrap() + 1).sum();
-
-        // Count the number of columsn without a width
-        let without_width = cols.iter().filter(|x| x.1.is_none());
-        let num_without_width = without_width.count();
+    /// Writes what comes after the rows of the table in the given format.
+    fn write_end<W: std::fmt::Write>(&self, w: &mut W, format: TableFormat) -> std::fmt::Result {
+        match format {
+            TableFormat::Table => self.write_footer(w),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_labels<W: std::fmt::Write>(&self, w: &mut W, delimiter: char, field: fn(&str) -> String) -> std::fmt::Result {
+        let labels: Vec<String> = self.cols.iter().map(|c| field(&c.label)).collect();
+        writeln!(w, "{}", labels.join(&delimiter.to_string()))
+    }
+
+    /// Writes the entire table into an `std::io::Write`, such as stdout or a file.
+    pub fn write_table_io<W: std::io::Write>(&self, w: &mut W, rows: Vec<TableRow>) -> std::io::Result<()> {
+        self.write_rows_io(w, rows, TableFormat::Table)
+    }
+
+    /// Returns a formatted string containing the label for each
+    /// column positioned and formatted to align with the formatted
+    /// table rows.
+    pub fn print_header(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let mut row = String::new();
+        self.write_header(&mut row)?;
+        Ok(row)
+    }
+
+    /// Writes the label for each column into `w`, positioned and formatted
+    /// to align with the formatted table rows.  If the table has borders,
+    /// then the top border and the separator below the labels are also
+    /// written.  No newline is written after the last line.
+    pub fn write_header<W: std::fmt::Write>(&self, w: &mut W) -> std::fmt::Result {
+        use console::Style;
+
+        // Without borders the header is set apart from the rows by underlining it
+        let ul = match self.style {
+             TableStyle::Plain => Style::new().underlined(),
+             _ => Style::new(),
+        };
+        let rules = self.style.rules();
+        if let Some(rules) = &rules {
+            self.write_rule(w, &rules[0])?;
+            writeln!(w)?;
+        }
+
+        let (left, sep, right) = self.style.edges();
+        write!(w, "{}", left)?;
+        for (i, &col) in self.shown.iter().enumerate() {
+            let label = ul.apply_to(&self.cols[col].label).to_string();
+            write!(w, "{}", pad(&label, self.col_widths[col], self.cols[col].align))?;
+            if i < self.shown.len() - 1 {
+                write!(w, "{}", sep)?;
+            }
+        }
+        write!(w, "{}", right)?;
+
+        if let Some(rules) = &rules {
+            writeln!(w)?;
+            self.write_rule(w, &rules[1])?;
+        }
+        Ok(())
+    }
+
+    /// Writes the line drawn between two rows, if the table has borders.
+    pub fn write_divider<W: std::fmt::Write>(&self, w: &mut W) -> std::fmt::Result {
+        match self.style.rules() {
+            Some(rules) => {
+                self.write_rule(w, &rules[2])?;
+                writeln!(w)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the bottom border of the table, if the table has borders.
+    pub fn write_footer<W: std::fmt::Write>(&self, w: &mut W) -> std::fmt::Result {
+        match self.style.rules() {
+            Some(rules) => {
+                self.write_rule(w, &rules[3])?;
+                writeln!(w)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn write_rule<W: std::fmt::Write>(&self, w: &mut W, rule: &Rule) -> std::fmt::Result {
+        write!(w, "{}", rule.left)?;
+        for (i, &col) in self.shown.iter().enumerate() {
+            for _ in 0..self.col_widths[col] + 2 {
+                write!(w, "{}", rule.line)?;
+            }
+            if i < self.shown.len() - 1 {
+                write!(w, "{}", rule.cross)?;
+            }
+        }
+        write!(w, "{}", rule.right)
+    }
+
+    /// Takes a single table row returns a string with each cell
+    /// formatted to fit within its column.  Cells which belong to
+    /// columns which were dropped from the table are skipped.
+    pub fn print_row(&self, cols: TableRow) -> Result<String, Box<dyn std::error::Error>> {
+        let mut row = String::new();
+        self.write_row(&mut row, cols)?;
+        Ok(row)
+    }
+
+    /// Writes a single table row into `w` with each cell formatted to fit
+    /// within its column.  Each line of the row ends with a newline.
+    pub fn write_row<W: std::fmt::Write>(&self, w: &mut W, cols: TableRow) -> std::fmt::Result {
+        self.write_styled_row(w, cols, "")
+    }
+
+    /// Writes a single table row with `codes` and then the row's own style
+    /// applied to each line of the row.
+    fn write_styled_row<W: std::fmt::Write>(&self, w: &mut W, cols: TableRow, codes: &str) -> std::fmt::Result {
+        use std::fmt::Write;
+
+        let mut codes = String::from(codes);
+        if let Some(style) = &cols.style {
+            codes.push_str(&style_codes(style));
+        }
+
+        // convert each cell which will be shown into a string
+        let shown: Vec<usize> = self.shown.iter().cloned().filter(|&c| c < cols.row.len()).collect();
+        let col_text: Vec<_> = shown.iter().map(|&col| cols.row[col].text()).collect();
+
+        let mut longest_column = 1;
+        let mut col_text_fmt = vec![];
+        for (i, &col) in shown.iter().enumerate() {
+            let text = &col_text[i];
+            let column = &self.cols[col];
+            let fitted_text = formatting::fit_to_column(text, self.col_widths[col], column.overflow, &column.break_chars);
+            if fitted_text.len() > longest_column {
+                longest_column = fitted_text.len();
+            }
+            col_text_fmt.push(fitted_text);
+        }
+
+        let (left, sep, right) = self.style.edges();
+        for line in 0..longest_column {
+            let mut text = String::new();
+            for (i, &col) in shown.iter().enumerate() {
+                if line < col_text_fmt[i].len() {
+                    let hyphenate = col_text_fmt[i][line].1;
+                    let align = self.cols[col].align;
+                    if hyphenate {
+                        write!(text, "{}-",
+                            pad(&col_text_fmt[i][line].0, self.col_widths[col] - 1, align),
+                        )?;
+                    } else {
+                        write!(text, "{}",
+                            pad(&col_text_fmt[i][line].0, self.col_widths[col], align),
+                        )?;
+                    }
+                } else {
+                    write!(text, "{0: <width$}", "", width = self.col_widths[col])?;
+                }
+
+                if i < shown.len() - 1 {
+                    write!(text, "{}", sep)?;
+                }
+            }
+            writeln!(w, "{}{}{}", left, apply_codes(&codes, &text), right)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_delimited_row<W: std::fmt::Write>(w: &mut W, row: &TableRow, delimiter: char, field: fn(&str) -> String) -> std::fmt::Result {
+    for (idx, cell) in row.row.iter().enumerate() {
+        if idx > 0 {
+            write!(w, "{}", delimiter)?;
+        }
+        write!(w, "{}", field(&cell.text()))?;
+    }
+    writeln!(w)
+}
+
+/// Writes `records` as CSV, after a line of their `labels`, quoting fields
+/// as the CSV tables do.  Unlike a table's rows, records have no width,
+/// styling, or sort order, so they are written exactly as given, e.g. for
+/// an export which a spreadsheet reads.
+pub fn write_csv<W: std::fmt::Write>(w: &mut W, labels: &[&str], records: &[Vec<String>]) -> std::fmt::Result {
+    writeln!(w, "{}", labels.iter().map(|l| csv_field(l)).collect::<Vec<_>>().join(","))?;
+    for record in records {
+        writeln!(w, "{}", record.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","))?;
+    }
+    Ok(())
+}
+
+/// Computes the width of every column in `cols` when only the columns
+/// in `shown` are put into a table `width` characters wide, where each
+/// column is followed by `sep_width` characters of separator (`width`
+/// should include the separator after the last column).  Columns
+/// with an explicit width get that width and the remaining space is
+/// divided evenly between the columns without a width, within their
+/// min and max widths.
+///
+/// Also returns whether the shown columns fit within the table while
+/// meeting their minimum widths.
+fn layout(width: usize, sep_width: usize, cols: &[Column], shown: &[usize]) -> (Vec<usize>, bool) {
+    let mut widths = vec![0; cols.len()];
+
+    // Add up the widths of the explicitly defined columns
+    // adding the separator after each column
+    let mut allocated_width = 0;
+    let mut flexible = vec![];
+    for &c in shown {
+        match cols[c].width {
+            Some(w) => {
+                widths[c] = w;
+                allocated_width += w + sep_width;
+            }
+            None => {
+                flexible.push(c);
+                allocated_width += sep_width;
+            }
+        }
+    }
+
+    if width < allocated_width {
+        return (widths, false);
+    }
+    let mut remaining_space = width - allocated_width;
+
+    // Divide the remaining space evenly between the columns without a width,
+    // any column which reaches its max width is given its max width and
+    // the space it does not need is divided amongst the other columns.
+    loop {
+        if flexible.is_empty() {
+            return (widths, true);
+        }
+
+        let width_per_col = remaining_space / flexible.len();
+        let capped: Vec<usize> = flexible
+            .iter()
+            .cloned()
+            .filter(|&c| cols[c].max_width.map(|max| max < width_per_col).unwrap_or(false))
+            .collect();
+
+        if capped.is_empty() {
+            let mut fits = true;
+            for &c in flexible.iter() {
+                widths[c] = width_per_col;
+                fits = fits && width_per_col >= cols[c].min_width;
+            }
+            return (widths, fits);
+        }
+
+        for c in capped {
+            let max = cols[c].max_width.unwrap();
+            widths[c] = max;
+            remaining_space -= max;
+            flexible.retain(|&f| f != c);
+        }
+    }
+}
+
+/// Escapes a cell for a markdown table: pipes are escaped and newlines
+/// are replaced with spaces so the cell stays within its row.
+fn markdown_field(cell: &str) -> String {
+    console::strip_ansi_codes(cell)
+        .replace('|', "\\|")
+        .replace('\n', " ")
+}
+
+/// Quotes a cell for CSV, if the cell contains a comma, quote, or newline
+/// then it is wrapped in quotes and any quotes are doubled.
+fn csv_field(cell: &str) -> String {
+    let cell = console::strip_ansi_codes(cell);
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.into_owned()
+    }
+}
+
+/// TSV has no quoting, so tabs and newlines in a cell are replaced with spaces.
+fn tsv_field(cell: &str) -> String {
+    console::strip_ansi_codes(cell).replace(['\t', '\n', '\r'], " ")
+}
+
+/// The escape codes which `style` writes before the text it is applied
+/// to.  This is empty if styling is disabled (e.g. stdout is not a terminal).
+fn style_codes(style: &console::Style) -> String {
+    let styled = style.apply_to('\u{0}').to_string();
+    styled.split('\u{0}').next().unwrap_or("").to_string()
+}
+
+/// Applies `style` to each of the `ranges` of `text`, e.g. to highlight the
+/// text which was searched for.  The ranges must be in order and must not
+/// overlap.  The result is a styled cell which wraps and pads like any other.
+pub fn highlight(text: &str, ranges: &[std::ops::Range<usize>], style: &console::Style) -> String {
+    let mut highlighted = String::new();
+    let mut end = 0;
+    for range in ranges {
+        highlighted.push_str(&text[end..range.start]);
+        highlighted.push_str(&style.apply_to(&text[range.clone()]).to_string());
+        end = range.end;
+    }
+    highlighted.push_str(&text[end..]);
+    highlighted
+}
+
+/// Applies the escape `codes` to all of `text`.  If `text` resets its
+/// own styles part way through, e.g. a styled cell, then `codes` are
+/// applied again after the reset.
+fn apply_codes(codes: &str, text: &str) -> String {
+    if codes.is_empty() {
+        return String::from(text);
+    }
+
+    let reset = "\x1b[0m";
+    let restyled = text.replace(reset, &format!("{}{}", reset, codes));
+    format!("{}{}{}", codes, restyled, reset)
+}
+
+/// Pads `text` with spaces until it is `width` characters wide, placing
+/// the text according to `align`.  Only visible characters count towards
+/// the width, so text containing ANSI escape codes (e.g. styled text) lines
+/// up with plain text.
+fn pad(text: &str, width: usize, align: Alignment) -> String {
+    let align = match align {
+        Alignment::Left => console::Alignment::Left,
+        Alignment::Right => console::Alignment::Right,
+        Alignment::Center => console::Alignment::Center,
+    };
+    console::pad_str(text, width, align, None).into_owned()
+}
+
+/// Fits the text of a cell into the width of its column.
+pub mod formatting {
+    use super::Overflow;
+    use std::io::{self, BufRead};
+    use unicode_segmentation::UnicodeSegmentation;
+    use unicode_width::UnicodeWidthChar;
+
+    pub type Hyphenate = bool;
+
+    /**
+     * Fits the contents of a cell into a column `width` characters wide,
+     * following the `overflow` policy of its column.  Wrapped cells may
+     * take up many lines, otherwise the cell is always a single line with
+     * any newlines replaced by spaces.
+     */
+    pub fn fit_to_column(
+        text: &str,
+        width: usize,
+        overflow: Overflow,
+        break_chars: &[char],
+    ) -> Vec<(String, Hyphenate)> {
+        let tail = match overflow {
+            Overflow::Wrap => return format_styled_to_column(text, width, 7, break_chars),
+            Overflow::Truncate if width > 1 => "…",
+            Overflow::Truncate | Overflow::Cut => "",
+        };
+
+        let line = text.replace('\n', " ");
+        if console::measure_text_width(&line) <= width {
+            vec![(line, false)]
+        } else {
+            vec![(console::truncate_str(&line, width, tail).into_owned(), false)]
+        }
+    }
+
+    /**
+     * Takes a given string and formats it into a vector of strings
+     * such that each string is no longer than the given width.  It will
+     * attempt to break lines at spaces but if a word is longer than
+     * the given column width it will split on the word.
+     *
+     * `text` - the text that needs to be formatted to fit within the width
+     * of a column.
+     * 
+     * `width` - the width of the column in characters.
+     * 
+     * `split_limit` - a word must be longer than this in order to be
+     * split across lines.  Unless `split_limit` > `width`, in which case,
+     * it will be ignored.
+     *
+     * Returns a vector of tuples where the first element is a slice from
+     * `text` representing a single line and the second element is a boolean
+     * indicating if the line should have a hyphen appended or not.  This
+     * will be `true` if a word was split across this line and the next.
+     *
+     * Newline characters, '\n', will cause a new line to be created in the
+     * vector of lines.  The new line character WILL be removed, as it no
+     * longer serves a formatting purpose and keeping it would act as duplicaation.
+     */
+    pub fn format_to_column(text: &str, width: usize, split_limit: usize) -> Vec<(String, Hyphenate)> {
+        format_to_column_at(text, width, split_limit, &[])
+    }
+
+    /**
+     * Formats text to fit within a column just like `format_to_column` but
+     * words which contain any of `break_chars` (e.g. '-' or '/') are broken
+     * after one of those characters in preference to being split.
+     */
+    pub fn format_to_column_at(
+        text: &str,
+        width: usize,
+        split_limit: usize,
+        break_chars: &[char],
+    ) -> Vec<(String, Hyphenate)> {
+        let hyphen_space = if width > 4 {1} else {0};
+        let mut lines = vec![];
+        for (start, end, midword) in column_breaks(text, width, split_limit, break_chars) {
+            let hyphenate = hyphen_space > 0 && midword;
+            lines.push((String::from(&text[start..end]), hyphenate));
+        }
+        lines
+    }
+
+    /**
+     * Formats text which may contain ANSI escape codes (e.g. styled text)
+     * to fit within a column.  The escape codes take up no space in the
+     * column, so the text is wrapped as if they were not there and then
+     * the codes are put back into the lines they belong to.
+     *
+     * Any style which is active at the end of a line is reset and then
+     * reapplied at the start of the next line, so that each line can be
+     * padded and printed on its own without the style bleeding into the
+     * neighboring columns.
+     */
+    pub fn format_styled_to_column(
+        text: &str,
+        width: usize,
+        split_limit: usize,
+        break_chars: &[char],
+    ) -> Vec<(String, Hyphenate)> {
+        // Split the text into the visible characters and the positions of
+        // the escape codes within those visible characters
+        let mut plain = String::new();
+        let mut codes = vec![];
+        for (s, is_ansi) in console::AnsiCodeIterator::new(text) {
+            if is_ansi {
+                codes.push((plain.len(), s));
+            } else {
+                plain.push_str(s);
+            }
+        }
+
+        if codes.is_empty() {
+            return format_to_column_at(&plain, width, split_limit, break_chars);
+        }
+
+        let hyphen_space = if width > 4 {1} else {0};
+        let mut codes = codes.into_iter().peekable();
+        let mut active = String::new();
+        let mut lines = vec![];
+        for (start, end, midword) in column_breaks(&plain, width, split_limit, break_chars) {
+
+            // Codes which come before this line only change which style is
+            // active at the start of the line
+            while let Some(&(_, code)) = codes.peek().filter(|(pos, _)| *pos < start) {
+                update_style(&mut active, code);
+                codes.next();
+            }
+            let mut line = active.clone();
+
+            for (idx, c) in plain[start..end].char_indices() {
+                while let Some(&(_, code)) = codes.peek().filter(|(pos, _)| *pos <= start + idx) {
+                    update_style(&mut active, code);
+                    line.push_str(code);
+                    codes.next();
+                }
+                line.push(c);
+            }
+
+            if !active.is_empty() {
+                line.push_str("\x1b[0m");
+            }
+            lines.push((line, hyphen_space > 0 && midword));
+        }
+        lines
+    }
+
+    /// Tracks which escape codes are in effect after `code` is applied.
+    fn update_style(active: &mut String, code: &str) {
+        if code == "\x1b[0m" || code == "\x1b[m" {
+            active.clear();
+        } else {
+            active.push_str(code);
+        }
+    }
+
+    /// The kinds of tokens that text is split into when it is wrapped.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum TokenKind {
+        /// A run of characters which contains no whitespace.
+        Word,
+        /// A single whitespace character, other than a newline.
+        Space,
+        /// A newline, which always ends the current line.
+        Newline,
+    }
+
+    /// A slice of the text being wrapped.  `start` is the byte offset of the
+    /// token within that text and `width` is the number of columns it takes
+    /// up when printed.  `breaks_after` is true if the token is part of a
+    /// word which ends at a soft break point, where the rest of the word
+    /// can be moved to the next line.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Token<'a> {
+        pub kind: TokenKind,
+        pub text: &'a str,
+        pub start: usize,
+        pub width: usize,
+        pub breaks_after: bool,
+    }
+
+    impl<'a> Token<'a> {
+        /// The byte offset just past the end of the token.
+        pub fn end(&self) -> usize {
+            self.start + self.text.len()
+        }
+
+        /// The clusters which make up this token, with their byte offsets
+        /// within the token.
+        pub fn clusters(&self) -> Clusters<'a> {
+            Clusters::new(self.text)
+        }
+    }
+
+    /// Splits a string into words, spaces, and newlines.  Tokens never split
+    /// a character from the combining characters which follow it.
+    pub struct CharTokenIter<'a> {
+        text: &'a str,
+        pos: usize,
+        break_chars: &'a [char],
+    }
+
+    impl<'a> CharTokenIter<'a> {
+        pub fn new(text: &'a str) -> CharTokenIter<'a> {
+            CharTokenIter::with_break_chars(text, &[])
+        }
+
+        /// Creates a tokenizer which also ends a word after any of the given
+        /// characters, e.g. the hyphens and slashes in a file path or URL,
+        /// so that the rest of the word can be wrapped onto the next line.
+        pub fn with_break_chars(text: &'a str, break_chars: &'a [char]) -> CharTokenIter<'a> {
+            CharTokenIter { text, pos: 0, break_chars }
+        }
+    }
+
+    impl<'a> Iterator for CharTokenIter<'a> {
+        type Item = Token<'a>;
+
+        fn next(&mut self) -> Option<Token<'a>> {
+            let rest = &self.text[self.pos..];
+            let first = Clusters::new(rest).next()?;
+            let kind = if first.text == "\n" {
+                TokenKind::Newline
+            } else if first.text.starts_with(char::is_whitespace) {
+                TokenKind::Space
+            } else {
+                TokenKind::Word
+            };
+
+            let (mut len, mut width, mut breaks_after) = (first.text.len(), first.width, false);
+            if kind == TokenKind::Word {
+                len = 0;
+                width = 0;
+                for c in Clusters::new(rest).take_while(|c| !c.text.starts_with(char::is_whitespace)) {
+                    len += c.text.len();
+                    width += c.width;
+                    if c.text.starts_with(self.break_chars) {
+                        breaks_after = true;
+                        break;
+                    }
+                }
+            }
+
+            let token = Token { kind, text: &rest[..len], start: self.pos, width, breaks_after };
+            self.pos += len;
+            Some(token)
+        }
+    }
+
+    /// A token read by a `ReadTokenIter`, which owns its text.  `start` is
+    /// the byte offset of the token from the start of the input.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct StreamToken {
+        pub kind: TokenKind,
+        pub text: String,
+        pub start: usize,
+        pub width: usize,
+        pub breaks_after: bool,
+    }
+
+    /// Splits the text read from a `BufRead` into tokens, just like
+    /// `CharTokenIter`, without reading all of the input into memory.  The
+    /// input is read one line at a time, since no token spans a newline.
+    pub struct ReadTokenIter<R: BufRead> {
+        reader: R,
+        line: String,
+        pos: usize,
+        offset: usize,
+        break_chars: Vec<char>,
+    }
+
+    impl<R: BufRead> ReadTokenIter<R> {
+        pub fn new(reader: R) -> ReadTokenIter<R> {
+            ReadTokenIter::with_break_chars(reader, &[])
+        }
+
+        pub fn with_break_chars(reader: R, break_chars: &[char]) -> ReadTokenIter<R> {
+            ReadTokenIter {
+                reader,
+                line: String::new(),
+                pos: 0,
+                offset: 0,
+                break_chars: break_chars.to_vec(),
+            }
+        }
+    }
+
+    impl<R: BufRead> Iterator for ReadTokenIter<R> {
+        type Item = io::Result<StreamToken>;
+
+        fn next(&mut self) -> Option<io::Result<StreamToken>> {
+            if self.pos >= self.line.len() {
+                self.offset += self.line.len();
+                self.line.clear();
+                self.pos = 0;
+                match self.reader.read_line(&mut self.line) {
+                    Ok(0) => return None,
+                    Ok(_) => (),
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+
+            let token = CharTokenIter::with_break_chars(&self.line[self.pos..], &self.break_chars).next()?;
+            let start = self.offset + self.pos;
+            self.pos += token.text.len();
+            Some(Ok(StreamToken {
+                kind: token.kind,
+                text: String::from(token.text),
+                start,
+                width: token.width,
+                breaks_after: token.breaks_after,
+            }))
+        }
+    }
+
+    /**
+     * Wraps the text read from `reader` to fit within a column, just like
+     * `format_to_column_at`, one line of input at a time.  This only ever
+     * holds a single line of the input in memory, which makes it suitable
+     * for very large or piped input.
+     */
+    pub fn wrap_reader<R: BufRead>(
+        reader: R,
+        width: usize,
+        split_limit: usize,
+        break_chars: &[char],
+    ) -> impl Iterator<Item = io::Result<(String, Hyphenate)>> {
+        let break_chars = break_chars.to_vec();
+        let hyphen_space = if width > 4 {1} else {0};
+        reader.lines().flat_map(move |line| {
+            let lines = match line {
+                Ok(line) => {
+                    let breaks = column_breaks(&line, width, split_limit, &break_chars);
+                    if breaks.is_empty() {
+                        vec![Ok((String::new(), false))]
+                    } else {
+                        breaks
+                            .into_iter()
+                            .map(|(start, end, midword)| Ok((String::from(&line[start..end]), hyphen_space > 0 && midword)))
+                            .collect()
+                    }
+                }
+                Err(err) => vec![Err(err)],
+            };
+            lines.into_iter()
+        })
+    }
+
+    /// A grapheme cluster: what a reader sees as one character, e.g. a letter
+    /// and its combining accents, a flag made of two regional indicators, or
+    /// an emoji sequence joined by zero width joiners.  Wrapping never splits
+    /// a cluster.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Cluster<'a> {
+        pub text: &'a str,
+        pub start: usize,
+        pub width: usize,
+    }
 
-        // Get the amount of space which is not explicitly assigned to a column
-        // Divide evenly between the columns without width
-        if self.width < allocated_width {
-            panic!("Total width of columns is greater than the width of the table")
-        }
-        let remaining_space = self.width - allocated_width;
-        let width_per_col = remaining_space / num_without_width;
+    /// Iterates over the `Cluster`s of a string.
+    pub struct Clusters<'a> {
+        text: &'a str,
+        pos: usize,
+    }
 
-        // Record the columns and their widths
-        for (label, width) in cols {
-            match width {
-                Some(w) => {
-                    self.cols.push(String::from(label));
-                    self.col_widths.push(w);
-                }
-                None => {
-                    self.cols.push(String::from(label));
-                    self.col_widths.push(width_per_col);
-                }
-            }
+    impl<'a> Clusters<'a> {
+        pub fn new(text: &'a str) -> Clusters<'a> {
+            Clusters { text, pos: 0 }
         }
     }
 
-    /// Returns a formatted string containing the label for each
-    /// column positioned and formatted to align with the formatted
-    /// table rows.
-    pub fn print_header(&self) -> Result<String,Box<dyn std::error::Error>> {
-        use std::fmt::Write;
+    impl<'a> Iterator for Clusters<'a> {
+        type Item = Cluster<'a>;
 
-        use console::Style;
-        let ul = Style::new().underlined();
-        let num_cols = self.cols.len();
-        let mut row = String::new();
-        for i in 0..num_cols {
-            write!( row,
-                "{0: <width$}",
-                ul.apply_to(&self.cols[i]),
-                width = self.col_widths[i]
-            )?;
-            if i < num_cols - 1 {
-                write!(row, " ")?;
-            }
+        fn next(&mut self) -> Option<Cluster<'a>> {
+            let text = self.text[self.pos..].graphemes(true).next()?;
+            let width = text.chars().map(|c| UnicodeWidthChar::width(c).unwrap_or(0)).sum();
+            let cluster = Cluster { text, start: self.pos, width };
+            self.pos += text.len();
+            Some(cluster)
         }
-        Ok(row)
     }
 
-    /// Takes a single table row returns a string with each cell
-    /// formatted to fit within its column.
-    pub fn print_row(&self, cols: TableRow) -> Result<String, Box<dyn std::error::Error>> {
-        use std::fmt::Write;
+    /// The width of `text`, control characters take up no space.  This matches
+    /// how `console` measures text so that wrapped lines are padded correctly.
+    fn str_width(text: &str) -> usize {
+        Clusters::new(text).map(|c| c.width).sum()
+    }
 
-        // convert each row into a string
-        let mut col_text = vec![];
-        for i in 0..cols.row.len() {
-            col_text.push(cols.row[i].to_string());
-        }
+    /**
+     * The byte offset into `word` at which to split it so that no more than
+     * `width` columns of the word, from `start`, are put on the line.  If one
+     * of the `syllables` break points fits then the word is split there,
+     * otherwise it is split after as many characters as fit.
+     */
+    pub(super) fn split_word(word: &str, start: usize, width: usize, syllables: &[usize]) -> usize {
+        let split = start + split_at_width(&word[start..], width);
+        syllables
+            .iter()
+            .rev()
+            .copied()
+            .find(|&b| b > start && b <= split)
+            .unwrap_or(split)
+    }
 
-        let mut longest_column = 1;
-        let mut col_text_fmt = vec![];
-        for i in 0..cols.row.len() {
-            let text = &col_text[i];
-            let fitted_text = formatting::format_to_column(&text, self.col_widths[i], 7);
-            if fitted_text.len() > longest_column {
-                longest_column = fitted_text.len();
-            }
-            col_text_fmt.push(fitted_text);
+    /// The byte offsets within `word` between its syllables, where the word
+    /// can be hyphenated.
+    #[cfg(feature = "hyphenation")]
+    fn syllable_breaks(word: &str) -> Vec<usize> {
+        use hyphenation::{Hyphenator, Language, Load, Standard};
+
+        thread_local! {
+            static DICTIONARY: Option<Standard> = Standard::from_embedded(Language::EnglishUS).ok();
         }
 
-        let mut row = String::new();
+        DICTIONARY.with(|dictionary| match dictionary {
+            Some(dictionary) => dictionary.hyphenate(word).breaks,
+            None => vec![],
+        })
+    }
 
-        for line in 0..longest_column {
-            for col in 0..cols.row.len() {
-                if line < col_text_fmt[col].len() {
-                    let hyphenate = col_text_fmt[col][line].1;
-                    if hyphenate {
-                        write!(row, 
-                            "{0: <width$}-",
-                            col_text_fmt[col][line].0,
-                            width = self.col_widths[col] - 1,
-                        )?;
-                    } else {
-                        write!(row, 
-                            "{0: <width$}",
-                            col_text_fmt[col][line].0,
-                            width = self.col_widths[col],
-                        )?;
-                    }
-                } else {
-                    write!(row, "{0: <width$}", "", width = self.col_widths[col])?;
-                }
+    /// Without the `hyphenation` feature, words have no known syllables and
+    /// are split wherever the column ends.
+    #[cfg(not(feature = "hyphenation"))]
+    fn syllable_breaks(_word: &str) -> Vec<usize> {
+        vec![]
+    }
 
-                if col < cols.row.len() - 1 {
-                    write!(row, " ")?;
-                }
+    /// The byte offset into `text` after as many clusters as fit within
+    /// `width` columns.
+    fn split_at_width(text: &str, width: usize) -> usize {
+        let mut used = 0;
+        for cluster in Clusters::new(text) {
+            if used + cluster.width > width {
+                return cluster.start;
             }
-            writeln!(row)?;
+            used += cluster.width;
         }
-        Ok(row)
+        text.len()
     }
-}
 
-mod formatting {
-    pub type Hyphenate = bool;
     /**
-     * Takes a given string and formats it into a vector of strings
-     * such that each string is no longer than the given width.  It will
-     * attempt to break lines at spaces but if a word is longer than
-     * the given column width it will split on the word.
-     *
-     * `text` - the text that needs to be formatted to fit within the width
-     * of a column.
-     * 
-     * `width` - the width of the column in characters.
-     * 
-     * `split_limit` - a word must be longer than this in order to be
-     * split across lines.  Unless `split_limit` > `width`, in which case,
-     * it will be ignored.
-     *
-     * Returns a vector of tuples where the first element is a slice from
-     * `text` representing a single line and the second element is a boolean
-     * indicating if the line should have a hyphen appended or not.  This
-     * will be `true` if a word was split across this line and the next.
+     * Computes where `text` should be broken in order to fit within a column
+     * of the given `width`.  Returns the start and end byte offset of each
+     * line within `text` and whether the line ends in the middle of a word.
+     * Newlines are not included in any line.
      *
-     * Newline characters, '\n', will cause a new line to be created in the
-     * vector of lines.  The new line character WILL be removed, as it no
-     * longer serves a formatting purpose and keeping it would act as duplicaation.
+     * Words containing any of `break_chars` will be broken after one of those
+     * characters, rather than split, when they do not fit on a line.
      */
-    pub fn format_to_column(text: &String, width: usize, split_limit: usize) -> Vec<(String,Hyphenate)> {
-        let mut breaks:Vec<(usize, usize, bool)> = vec![]; // start and length of each slice into `text`, true if midword
+    fn column_breaks(text: &str, width: usize, split_limit: usize, break_chars: &[char]) -> Vec<(usize, usize, bool)> {
+        if width == 0 {
+            return vec![(0, text.len(), false)];
+        }
+
+        let mut breaks = vec![];
         let mut line_start = 0;
         let mut line_len = 0;
-        let mut chars = text.chars().peekable();
         let hyphen_space = if width > 4 {1} else {0};   // If the column is wide enough to have hyphens in split words
                                                         // then this will make sure that an extra space is left to add the hyphen
-        while let Some(c) = chars.next() {
-            if c.is_whitespace() {
-                if line_len + 1 <= width && c != '\n' {
-                    line_len += 1;
-                } else {
-                    breaks.push((line_start, line_len, false));
-                    line_start += line_len;
-                    line_len = 1;
+        let adjusted_width = width - hyphen_space;
+        let mut follows_break = false;
+        for token in CharTokenIter::with_break_chars(text, break_chars) {
+            match token.kind {
+                TokenKind::Newline => {
+                    breaks.push((line_start, token.start, false));
+                    line_start = token.end();
+                    line_len = 0;
                 }
-            } else {
-                let word_start = line_start + line_len;
-                let mut word_len = 1;
-                while let Some(cp) = chars.peek() {
-                    if cp.is_whitespace() {
-                        break;
+                TokenKind::Space => {
+                    if line_len + token.width <= width {
+                        line_len += token.width;
+                    } else {
+                        breaks.push((line_start, token.start, false));
+                        line_start = token.start;
+                        line_len = token.width;
                     }
-                    chars.next();
-                    word_len += 1;
                 }
+                TokenKind::Word => {
+                    let word_len = token.width;
+                    if word_len + line_len <= width {
+                        line_len += word_len;
+                    } else if word_len <= width && (follows_break || word_len <= split_limit) {
+                        // Move the word onto the next line, if the word follows a soft
+                        // break point then this splits a longer word at that point
+                        breaks.push((line_start, token.start, false));
+                        line_start = token.start;
+                        line_len = word_len;
+                    } else {
+                        if follows_break && line_len > 0 {
+                            breaks.push((line_start, token.start, false));
+                            line_start = token.start;
+                            line_len = 0;
+                        }
 
-                if word_len + line_len <= width {
-                    line_len += word_len;
-                } else {
-                    let is_splittable = word_len > width || word_len > split_limit;
-                    if is_splittable {
-                        let adjusted_width = width - hyphen_space;
-                        let split = (word_start) + if width == line_len { 0 } else {adjusted_width - line_len};
-                        breaks.push((line_start, adjusted_width, split > word_start));
-                        line_start = split;
-                        line_len = 0;
-                        word_len = word_len - (split - word_start);
-                        while word_len > 0 {
-                            if word_len <= adjusted_width {
-                                line_len = word_len;
-                                word_len = 0;
+                        // Fill what is left of the line with the start of the word
+                        // and then put the rest of the word on as many lines as it needs
+                        let syllables = syllable_breaks(token.text);
+                        let mut pos = 0;
+                        if line_len > 0 {
+                            let fill = adjusted_width.saturating_sub(line_len);
+                            pos = split_word(token.text, 0, fill, &syllables);
+                            let split = token.start + pos;
+                            let line_end = if fill == 0 {
+                                // The line is already full, so it ends without its trailing space
+                                line_start + split_at_width(&text[line_start..], adjusted_width)
                             } else {
-                                breaks.push((line_start, width, true));
-                                line_start += adjusted_width;
-                                word_len -= adjusted_width;
-                                line_len = 0;
-                            }
+                                split
+                            };
+                            breaks.push((line_start, line_end, split > token.start));
+                            line_start = split;
                         }
-                    } else {
-                        breaks.push((line_start, line_len, false));
-                        line_start = word_start;
-                        line_len = word_len;
+
+                        while str_width(&token.text[pos..]) > adjusted_width {
+                            // Always move forward, even if a single wide character
+                            // does not fit within the column
+                            let rest = &token.text[pos..];
+                            let split = match split_word(token.text, pos, adjusted_width, &syllables) - pos {
+                                0 => Clusters::new(rest).next().map_or(rest.len(), |c| c.text.len()),
+                                split => split,
+                            };
+                            breaks.push((line_start, line_start + split, true));
+                            line_start += split;
+                            pos += split;
+                        }
+                        let rest = &token.text[pos..];
+                        line_len = str_width(rest);
                     }
                 }
             }
-
-            if chars.peek().is_none() && line_len > 0{
-                breaks.push((line_start, line_len, false));
-            }
+            follows_break = token.breaks_after;
         }
 
-        let mut lines = vec![];
-        for b in breaks {
-            let start = b.0;
-            let end = start + b.1;
-            let line = text.get(start..end).unwrap().replace("\n", "");
-            let hyphenate = hyphen_space > 0 && b.2;
-            lines.push((line, hyphenate));
+        if line_len > 0 {
+            breaks.push((line_start, text.len(), false));
         }
-        lines
+        breaks
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::formatting::*;
+    use super::{
+        highlight, Alignment, Column, Order, Overflow, SortKey, TableBuilder, TableError, TableFormat,
+        TableRow, TableStyle, write_csv,
+    };
 
     #[test]
     fn split_short_words() {
@@ -316,7 +1608,7 @@ mod tests {
         let lines = format_to_column(&text, 10, 5);
         assert_eq!(6, lines.len());
         for line in lines.iter() {
-            if line.1 == false {
+            if !line.1 {
                 assert_eq!(true, line.0.len() <= 10);
             } else {
                 assert_eq!(true, line.0.len() <= 9);
@@ -377,6 +1669,661 @@ mod tests {
         assert_eq!(("lazy dog".into(), false), lines[5]);
     }
 
+    #[test]
+    fn split_word_across_many_lines() {
+        let text = String::from("argleybargleyargleybargley");
+        let lines = format_to_column(&text, 10, 5);
+        assert_eq!(3, lines.len());
+        //          1234567890    <- column numbers
+        assert_eq!(("argleybar".into(), true), lines[0]);
+        assert_eq!(("gleyargle".into(), true), lines[1]);
+        assert_eq!(("ybargley".into(), false), lines[2]);
+    }
+
+    #[test]
+    fn split_on_newlines() {
+        let text = String::from("the quick\n\nbrown fox");
+        let lines = format_to_column(&text, 10, 5);
+        assert_eq!(3, lines.len());
+        assert_eq!(("the quick".into(), false), lines[0]);
+        assert_eq!(("".into(), false), lines[1]);
+        assert_eq!(("brown fox".into(), false), lines[2]);
+    }
+
+    #[test]
+    fn split_multibyte_words() {
+        let text = String::from("naïve café crème brûlée");
+        let lines = format_to_column(&text, 10, 5);
+        assert_eq!(3, lines.len());
+        assert_eq!(("naïve café".into(), false), lines[0]);
+        assert_eq!((" crème br".into(), true), lines[1]);
+        assert_eq!(("ûlée".into(), false), lines[2]);
+    }
+
+    #[test]
+    fn tokenize_text() {
+        let tokens: Vec<_> = CharTokenIter::new("ab  c\nd").map(|t| (t.kind, t.text, t.start)).collect();
+        assert_eq!(
+            vec![
+                (TokenKind::Word, "ab", 0),
+                (TokenKind::Space, " ", 2),
+                (TokenKind::Space, " ", 3),
+                (TokenKind::Word, "c", 4),
+                (TokenKind::Newline, "\n", 5),
+                (TokenKind::Word, "d", 6),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn split_wide_characters() {
+        let text = String::from("日本語のテキスト");
+        let lines = format_to_column(&text, 5, 5);
+        assert_eq!(4, lines.len());
+        assert_eq!(("日本".into(), true), lines[0]);
+        assert_eq!(("語の".into(), true), lines[1]);
+        assert_eq!(("テキ".into(), true), lines[2]);
+        assert_eq!(("スト".into(), false), lines[3]);
+    }
+
+    #[test]
+    fn split_keeps_combining_characters() {
+        let text = "e\u{301}".repeat(6);
+        let lines = format_to_column(&text, 5, 5);
+        assert_eq!(2, lines.len());
+        assert_eq!(("e\u{301}".repeat(4), true), lines[0]);
+        assert_eq!(("e\u{301}".repeat(2), false), lines[1]);
+    }
+
+    #[test]
+    fn split_keeps_flags_and_emoji_sequences() {
+        // Two flags, each a pair of regional indicators, are never split into their letters
+        let flags = "\u{1f1fa}\u{1f1f8}\u{1f1e9}\u{1f1ea}";
+        let lines = format_to_column(flags, 3, 5);
+        assert_eq!(vec![("\u{1f1fa}\u{1f1f8}".into(), false), ("\u{1f1e9}\u{1f1ea}".into(), false)], lines);
+
+        let clusters = |text: &str| -> Vec<String> {
+            let word = CharTokenIter::new(text).next().unwrap();
+            word.clusters().map(|c| String::from(c.text)).collect()
+        };
+        let family = "\u{1f468}\u{200d}\u{1f469}\u{200d}\u{1f467}";
+        assert_eq!(vec![family, "\u{1f1fa}\u{1f1f8}", "\u{1f1e9}\u{1f1ea}"], clusters(&format!("{}{}", family, flags)));
+
+        // Hangul jamo make up one syllable
+        assert_eq!(vec!["\u{1112}\u{1161}\u{11ab}", "a"], clusters("\u{1112}\u{1161}\u{11ab}a"));
+    }
+
+    #[test]
+    fn split_wide_character_in_narrow_column() {
+        let text = String::from("日本");
+        let lines = format_to_column(&text, 1, 5);
+        assert_eq!(vec![("日".into(), false), ("本".into(), false)], lines);
+    }
+
+    #[test]
+    fn token_widths() {
+        let tokens: Vec<_> = CharTokenIter::new("cafe\u{301} 日本").map(|t| (t.text, t.start, t.width)).collect();
+        assert_eq!(vec![("cafe\u{301}", 0, 4), (" ", 6, 1), ("日本", 7, 4)], tokens);
+
+        let word = CharTokenIter::new("cafe\u{301}").next().unwrap();
+        let clusters: Vec<_> = word.clusters().map(|c| (c.text, c.start, c.width)).collect();
+        assert_eq!(vec![("c", 0, 1), ("a", 1, 1), ("f", 2, 1), ("e\u{301}", 3, 1)], clusters);
+    }
+
+    #[test]
+    fn split_at_break_chars() {
+        let text = String::from("see /usr/local/share/doc");
+        let lines = format_to_column_at(&text, 10, 5, &['-', '/']);
+        assert_eq!(3, lines.len());
+        //          1234567890    <- column numbers
+        assert_eq!(("see /usr/".into(), false), lines[0]);
+        assert_eq!(("local/".into(), false), lines[1]);
+        assert_eq!(("share/doc".into(), false), lines[2]);
+
+        // Without break characters the path would be split mid-word
+        let lines = format_to_column(&text, 10, 5);
+        assert_eq!(("see /usr/".into(), true), lines[0]);
+    }
+
+    #[test]
+    fn split_long_segment_after_break_char() {
+        let text = String::from("well-documentedness");
+        let lines = format_to_column_at(&text, 10, 5, &['-']);
+        assert_eq!(3, lines.len());
+        assert_eq!(("well-".into(), false), lines[0]);
+        assert_eq!(("documente".into(), true), lines[1]);
+        assert_eq!(("dness".into(), false), lines[2]);
+    }
+
+    #[test]
+    fn tokenize_break_chars() {
+        let tokens: Vec<_> = CharTokenIter::with_break_chars("a-b/c d-", &['-', '/'])
+            .map(|t| (t.text, t.breaks_after))
+            .collect();
+        assert_eq!(vec![("a-", true), ("b/", true), ("c", false), (" ", false), ("d-", true)], tokens);
+    }
+
+    #[test]
+    fn tokenize_reader() {
+        let input = "ab  c\nd-e\n";
+        let reader = std::io::BufReader::with_capacity(2, input.as_bytes());
+        let tokens: Vec<_> = ReadTokenIter::with_break_chars(reader, &['-'])
+            .map(|t| t.unwrap())
+            .map(|t| (t.kind, t.text, t.start))
+            .collect();
+        assert_eq!(
+            vec![
+                (TokenKind::Word, "ab".into(), 0),
+                (TokenKind::Space, " ".into(), 2),
+                (TokenKind::Space, " ".into(), 3),
+                (TokenKind::Word, "c".into(), 4),
+                (TokenKind::Newline, "\n".into(), 5),
+                (TokenKind::Word, "d-".into(), 6),
+                (TokenKind::Word, "e".into(), 8),
+                (TokenKind::Newline, "\n".into(), 9),
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn wrap_from_reader() {
+        let input = "the quick brown fox\n\nargleybargley";
+        let lines: Vec<_> = wrap_reader(input.as_bytes(), 10, 5, &[]).map(|l| l.unwrap()).collect();
+        let expected: Vec<(String, bool)> = vec![
+            ("the quick ".into(), false),
+            ("brown fox".into(), false),
+            ("".into(), false),
+            ("argleybar".into(), true),
+            ("gley".into(), false),
+        ];
+        assert_eq!(expected, lines);
+
+        // Wrapping a reader gives the same lines as wrapping the whole text
+        let single: Vec<_> = wrap_reader("the quick brown fox".as_bytes(), 10, 5, &[]).map(|l| l.unwrap()).collect();
+        assert_eq!(format_to_column("the quick brown fox", 10, 5), single);
+    }
+
+    #[test]
+    fn split_word_at_syllables() {
+        // doc-u-ment-ed-ness
+        let syllables = vec![3, 4, 8, 10];
+        assert_eq!(8, split_word("documentedness", 0, 9, &syllables));
+        assert_eq!(4, split_word("documentedness", 0, 6, &syllables));
+        assert_eq!(10, split_word("documentedness", 8, 9, &syllables));
+        // No syllable fits so the word is split at the width
+        assert_eq!(2, split_word("documentedness", 0, 2, &syllables));
+        assert_eq!(9, split_word("documentedness", 0, 9, &[]));
+    }
+
+    #[cfg(feature = "hyphenation")]
+    #[test]
+    fn hyphenate_at_syllables() {
+        let text = String::from("the hyphenation");
+        let lines = format_to_column(&text, 10, 5);
+        assert_eq!(("the hy".into(), true), lines[0]);
+        assert_eq!(("phenation".into(), false), lines[1]);
+
+        // The last syllable break which fits is used, not the column's end
+        let lines = format_to_column("hyphenation", 8, 5);
+        assert_eq!(vec![("hyphen".into(), true), ("ation".into(), false)], lines);
+    }
+
+    #[test]
+    fn split_styled_words() {
+        let text = String::from("\x1b[31mthe quick brown\x1b[0m fox");
+        let lines = format_styled_to_column(&text, 10, 5, &[]);
+        assert_eq!(2, lines.len());
+        assert_eq!(("\x1b[31mthe quick \x1b[0m".into(), false), lines[0]);
+        assert_eq!(("\x1b[31mbrown\x1b[0m fox".into(), false), lines[1]);
+    }
+
+    #[test]
+    fn split_styled_substring() {
+        let text = String::from("the quick \x1b[1mbrown\x1b[0m");
+        let lines = format_styled_to_column(&text, 10, 5, &[]);
+        assert_eq!(2, lines.len());
+        assert_eq!(("the quick ".into(), false), lines[0]);
+        assert_eq!(("\x1b[1mbrown\x1b[0m".into(), false), lines[1]);
+    }
+
+    #[test]
+    fn highlight_ranges() {
+        let style = console::Style::new().bold().force_styling(true);
+        assert_eq!(
+            "a \x1b[1mfoo\x1b[0m and \x1b[1mfoo\x1b[0m",
+            highlight("a foo and foo", &[2..5, 10..13], &style)
+        );
+        assert_eq!("no match", highlight("no match", &[], &style));
+
+        let brown = 10..15;
+        let lines = format_styled_to_column(&highlight("the quick brown", &[brown], &style), 10, 5, &[]);
+        assert_eq!(("\x1b[1mbrown\x1b[0m".into(), false), lines[1]);
+    }
+
+    #[test]
+    fn pad_styled_text() {
+        let text = "\x1b[31mab\x1b[0m";
+        let padded = super::pad(text, 4, Alignment::Left);
+        assert_eq!(4, console::measure_text_width(&padded));
+        assert_eq!("\x1b[31mab\x1b[0m  ", padded);
+    }
+
+    #[test]
+    fn styled_header_width() {
+        console::set_colors_enabled(true);
+        let tf = TableBuilder::new(20)
+            .columns(vec![Column::new("ID", Some(4)), Column::new("Name", None)])
+            .build()
+            .unwrap();
+        let header = tf.print_header().unwrap();
+        assert_eq!(20, console::measure_text_width(&header));
+    }
+
+    #[test]
+    fn ascii_borders() {
+        let tf = TableBuilder::new(15)
+            .style(TableStyle::Ascii)
+            .columns(vec![Column::new("ID", Some(2)), Column::new("Name", None)])
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("test");
+        let mut row2 = TableRow::new();
+        row2.push(2);
+        row2.push("test 2");
+
+        let mut table = String::new();
+        tf.write_table(&mut table, vec![row, row2]).unwrap();
+        let expected = vec![
+            "+----+--------+",
+            "| ID | Name   |",
+            "+====+========+",
+            "| 1  | test   |",
+            "+----+--------+",
+            "| 2  | test 2 |",
+            "+----+--------+",
+        ];
+        assert_eq!(expected, table.lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn without_header() {
+        let rows = || {
+            let mut row = TableRow::new();
+            row.push(1);
+            row.push("test");
+            vec![row]
+        };
+        let columns = || vec![Column::new("ID", Some(2)), Column::new("Name", None)];
+
+        let tf = TableBuilder::new(15).header(false).columns(columns()).build().unwrap();
+        let table = tf.render_as(rows(), TableFormat::Table);
+        assert_eq!(vec!["1  test"], table.lines().map(str::trim_end).collect::<Vec<&str>>());
+        assert_eq!("1,test\n", tf.render_as(rows(), TableFormat::Csv));
+
+        let tf = TableBuilder::new(15)
+            .style(TableStyle::Ascii)
+            .header(false)
+            .columns(columns())
+            .build()
+            .unwrap();
+        let expected = vec!["+----+--------+", "| 1  | test   |", "+----+--------+"];
+        assert_eq!(expected, tf.render_as(rows(), TableFormat::Table).lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn unicode_borders() {
+        let tf = TableBuilder::new(15)
+            .columns(vec![Column::new("ID", Some(2)), Column::new("Name", None)])
+            .style(TableStyle::Unicode)
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("test");
+
+        let mut table = String::new();
+        tf.write_table(&mut table, vec![row]).unwrap();
+        let expected = vec![
+            "┌────┬────────┐",
+            "│ ID │ Name   │",
+            "╞════╪════════╡",
+            "│ 1  │ test   │",
+            "└────┴────────┘",
+        ];
+        assert_eq!(expected, table.lines().collect::<Vec<&str>>());
+    }
+
+    #[test]
+    fn align_cells() {
+        let tf = TableBuilder::new(14)
+            .columns(vec![
+                Column::new("ID", Some(4)).align(Alignment::Right),
+                Column::new("Name", None),
+                Column::new("Pri", Some(4)).align(Alignment::Center),
+            ])
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(7);
+        row.push("ab");
+        row.push(1);
+        //          12345678901234    <- column numbers
+        assert_eq!("   7 ab    1  \n", tf.print_row(row).unwrap());
+    }
+
+    #[test]
+    fn max_width_gives_space_to_other_columns() {
+        let tf = TableBuilder::new(20)
+            .columns(vec![
+                Column::new("ID", Some(4)),
+                Column::new("Name", None),
+                Column::new("Pri", None).max_width(3),
+            ])
+            .build()
+            .unwrap();
+
+        assert_eq!(vec![4, 11, 3], tf.col_widths);
+    }
+
+    #[test]
+    fn drop_low_priority_columns() {
+        let cols = || {
+            vec![
+                Column::new("ID", Some(4)),
+                Column::new("Date", Some(10)).drop_priority(2),
+                Column::new("Name", None).min_width(10),
+                Column::new("Nts", Some(3)).drop_priority(1),
+            ]
+        };
+
+        // Everything fits
+        let tf = TableBuilder::new(40).columns(cols()).build().unwrap();
+        assert_eq!(vec![0, 1, 2, 3], tf.shown);
+
+        // Too narrow for Nts
+        let tf = TableBuilder::new(28).columns(cols()).build().unwrap();
+        assert_eq!(vec![0, 1, 2], tf.shown);
+
+        // Too narrow for Nts and Date
+        let tf = TableBuilder::new(16).columns(cols()).build().unwrap();
+        assert_eq!(vec![0, 2], tf.shown);
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("2020-01-01");
+        row.push("test");
+        row.push(0);
+        //          1234567890123456    <- column numbers
+        assert_eq!("1    test       \n", tf.print_row(row).unwrap());
+    }
+
+    #[test]
+    fn write_table() {
+        let tf = TableBuilder::new(10)
+            .columns(vec![Column::new("ID", Some(2)), Column::new("Name", None)])
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("test");
+        let mut row2 = TableRow::new();
+        row2.push(2);
+        row2.push("test 2");
+
+        let mut table = String::new();
+        tf.write_table(&mut table, vec![row, row2]).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(3, lines.len());
+        assert_eq!("Name", console::strip_ansi_codes(lines[0]).split_whitespace().nth(1).unwrap());
+        assert_eq!("1  test   ", lines[1]);
+        assert_eq!("2  test 2 ", lines[2]);
+
+        let mut bytes: Vec<u8> = vec![];
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("test");
+        tf.write_table_io(&mut bytes, vec![row]).unwrap();
+        assert_eq!("1  test   ", String::from_utf8(bytes).unwrap().lines().nth(1).unwrap());
+    }
+
+    #[test]
+    fn truncate_to_column() {
+        let text = "the quick brown fox";
+        assert_eq!(vec![("the quick…".to_string(), false)], fit_to_column(text, 10, Overflow::Truncate, &[]));
+        assert_eq!(vec![("the quick ".to_string(), false)], fit_to_column(text, 10, Overflow::Cut, &[]));
+        assert_eq!(vec![("the quick brown fox".to_string(), false)], fit_to_column(text, 19, Overflow::Truncate, &[]));
+        assert_eq!(vec![("the quick".to_string(), false)], fit_to_column("the\nquick", 10, Overflow::Cut, &[]));
+
+        let styled = "\x1b[31mthe quick brown fox\x1b[0m";
+        assert_eq!(vec![("\x1b[31mthe quick…\x1b[0m".to_string(), false)], fit_to_column(styled, 10, Overflow::Truncate, &[]));
+    }
+
+    #[test]
+    fn truncated_rows_are_one_line() {
+        let tf = TableBuilder::new(14)
+            .columns(vec![
+                Column::new("ID", Some(2)),
+                Column::new("Name", None).overflow(Overflow::Truncate),
+            ])
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("the quick brown fox");
+        //          12345678901234    <- column numbers
+        assert_eq!("1  the quick …\n", tf.print_row(row).unwrap());
+    }
+
+    #[test]
+    fn minimal_layout() {
+        let builder = |width| {
+            TableBuilder::new(width).minimal_below(16).columns(vec![
+                Column::new("ID", Some(2)).minimal(),
+                Column::new("Date", Some(4)),
+                Column::new("Name", None).min_width(8).minimal(),
+                Column::new("Pri", Some(3)).drop_priority(1),
+            ])
+        };
+        let row = || {
+            let mut row = TableRow::new();
+            row.push(1);
+            row.push("2020");
+            row.push("the quick brown fox");
+            row.push(3);
+            row
+        };
+
+        let narrow = builder(10).build().unwrap();
+        //          1234567890    <- column numbers
+        assert_eq!("ID Name   ", narrow.print_header().unwrap());
+        assert_eq!("1  the qu…\n", narrow.print_row(row()).unwrap());
+        assert_eq!(Some(TableError::TooNarrow { width: 3, required: 4 }), builder(3).build().err());
+
+        // At the threshold the usual layout is used, which wraps the name
+        let wide = builder(16).build().unwrap();
+        assert_eq!(
+            "1  2020 the     \n        quick   \n        brown   \n        fox     \n",
+            wide.print_row(row()).unwrap()
+        );
+    }
+
+    #[test]
+    fn table_too_narrow() {
+        let cols = || vec![Column::new("ID", Some(4)), Column::new("Name", None)];
+
+        assert_eq!(
+            Some(TableError::TooNarrow { width: 5, required: 6 }),
+            TableBuilder::new(5).columns(cols()).build().err()
+        );
+        assert_eq!(
+            Some(TableError::TooNarrow { width: 0, required: 6 }),
+            TableBuilder::new(0).columns(cols()).build().err()
+        );
+        assert!(TableBuilder::new(6).columns(cols()).build().is_ok());
+        assert_eq!(
+            Some(TableError::TooNarrow { width: 6, required: 12 }),
+            TableBuilder::new(6).style(TableStyle::Ascii).columns(cols()).build().err()
+        );
+    }
+
+    #[test]
+    fn table_without_columns() {
+        assert_eq!(Some(TableError::NoColumns), TableBuilder::new(20).build().err());
+    }
+
+    #[test]
+    fn table_without_flexible_columns() {
+        let tf = TableBuilder::new(20)
+            .column(Column::new("ID", Some(4)))
+            .build()
+            .unwrap();
+        assert_eq!(vec![4], tf.col_widths);
+    }
+
+    #[test]
+    fn row_styles() {
+        let tf = TableBuilder::new(10)
+            .columns(vec![Column::new("ID", Some(2)), Column::new("Name", None)])
+            .zebra(console::Style::new().dim().force_styling(true))
+            .build()
+            .unwrap();
+
+        let mut row = TableRow::new();
+        row.push(1);
+        row.push("test");
+        let mut row2 = TableRow::new();
+        row2.push(2);
+        row2.push("test 2");
+        let mut row3 = TableRow::new();
+        row3.push(3);
+        row3.push(console::style("red").red().force_styling(true));
+        row3.set_style(console::Style::new().bold().force_styling(true));
+
+        let mut table = String::new();
+        tf.write_table(&mut table, vec![row, row2, row3]).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!("1  test   ", lines[1]);
+        assert_eq!("\x1b[2m2  test 2 \x1b[0m", lines[2]);
+        assert_eq!("\x1b[1m3  \x1b[31mred\x1b[0m\x1b[1m    \x1b[0m", lines[3]);
+    }
+
+    #[test]
+    fn sort_rows() {
+        let tf = TableBuilder::new(20)
+            .columns(vec![
+                Column::new("ID", Some(2)).sort_key(SortKey::number),
+                Column::new("Name", None),
+                Column::new("Pri", Some(3)).sort_key(SortKey::number),
+            ])
+            .sort_by("pri", Order::Desc)
+            .sort_by("Name", Order::Asc)
+            .build()
+            .unwrap();
+
+        let rows = vec![(1, "b", 2), (2, "a", 10), (3, "a", 2), (4, "c", 10)]
+            .into_iter()
+            .map(|(id, name, pri)| {
+                let mut row = TableRow::new();
+                row.push(id);
+                row.push(name);
+                row.push(pri);
+                row
+            })
+            .collect();
+
+        let ids: Vec<String> = tf
+            .sort_rows(rows)
+            .into_iter()
+            .map(|row| row.row[0].text().to_string())
+            .collect();
+        assert_eq!(vec!["2", "4", "3", "1"], ids);
+    }
+
+    #[test]
+    fn sort_by_unknown_column() {
+        let result = TableBuilder::new(20)
+            .column(Column::new("ID", Some(2)))
+            .sort_by("Pri", Order::Desc)
+            .build();
+        assert_eq!(Some(TableError::UnknownColumn("Pri".into())), result.err());
+    }
+
+    #[test]
+    fn render_as_other_formats() {
+        let tf = TableBuilder::new(10)
+            .columns(vec![
+                Column::new("ID", Some(2)).align(Alignment::Right),
+                Column::new("Name", None),
+            ])
+            .build()
+            .unwrap();
+        let rows = || {
+            let mut row = TableRow::new();
+            row.push(1);
+            row.push("a \"long\", | name");
+            let mut row2 = TableRow::new();
+            row2.push(2);
+            row2.push("two\tlines\nhere");
+            vec![row, row2]
+        };
+
+        assert_eq!(
+            "| ID | Name |\n| ---: | :--- |\n| 1 | a \"long\", \\| name |\n| 2 | two\tlines here |\n",
+            tf.render_as(rows(), TableFormat::Markdown)
+        );
+        assert_eq!(
+            "ID,Name\n1,\"a \"\"long\"\", | name\"\n2,\"two\tlines\nhere\"\n",
+            tf.render_as(rows(), TableFormat::Csv)
+        );
+        assert_eq!(
+            "ID\tName\n1\ta \"long\", | name\n2\ttwo lines here\n",
+            tf.render_as(rows(), TableFormat::Tsv)
+        );
+
+        let mut csv = String::new();
+        write_csv(&mut csv, &["ID", "Name"], &[vec!["1".into(), "say \"hi\", then\r\nleave".into()]]).unwrap();
+        assert_eq!("ID,Name\n1,\"say \"\"hi\"\", then\r\nleave\"\n", csv);
+    }
+
+    #[test]
+    fn stream_rows() {
+        let names = [String::from("write docs"), String::from("fix bug")];
+        let rows = || {
+            names.iter().enumerate().map(|(idx, name)| {
+                let mut row = TableRow::new();
+                row.push_number(idx as u64 + 1);
+                row.push_str(name);
+                row.push(3 - idx);
+                row
+            })
+        };
+        for sort in &[None, Some(("Pri", Order::Asc))] {
+            let mut builder = TableBuilder::new(30).style(TableStyle::Ascii).columns(vec![
+                Column::new("ID", Some(2)).align(Alignment::Right),
+                Column::new("Name", None),
+                Column::new("Pri", Some(3)).sort_key(SortKey::number),
+            ]);
+            if let Some((column, order)) = sort {
+                builder = builder.sort_by(column, *order);
+            }
+            let tf = builder.build().unwrap();
+            for &format in &[TableFormat::Table, TableFormat::Markdown, TableFormat::Csv, TableFormat::Tsv] {
+                let mut bytes: Vec<u8> = vec![];
+                tf.write_rows_io(&mut bytes, rows(), format).unwrap();
+                assert_eq!(tf.render_as(rows().collect(), format), String::from_utf8(bytes).unwrap());
+            }
+        }
+    }
+
     /*#[bench]
     fn bench_column_formatting(b: &mut test::Bencher) {
         b.iter(||{
@@ -1,20 +1,317 @@
+/*!
+ * How a project's tasks are persisted.  A `TaskList` only holds tasks in
+ * memory; a `Storage` reads them from somewhere and writes them back, so
+ * that the list knows nothing of the format they are stored in.  A
+ * project's metadata records which backend it uses.
+ */
+#[cfg(feature = "storage")]
+use super::list::TaskList;
+use super::task::Task;
+#[cfg(feature = "storage")]
+use crate::meta::{Backend, Layout, Meta, SINGLE_FILE};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+
+/// Where tasks are kept, one task at a time.  `TaskList::load` reads a
+/// list from a store and `TaskList::save` writes the changed tasks back,
+/// so the list works the same over files or over memory.
+pub trait TaskStore {
+    /// Every stored task.
+    fn load_all(&self) -> std::io::Result<Vec<Task>>;
+
+    /// The stored task `id`, if there is one.
+    fn load(&self, id: u32) -> std::io::Result<Option<Task>>;
+
+    /// Stores `task`, in place of the stored task with its ID if there is
+    /// one.  Returns whether anything was written, which it is not when
+    /// the task is already stored as it is.
+    fn save(&self, task: &Task) -> std::io::Result<bool>;
+
+    /// Removes the stored task `id`, if there is one.
+    fn delete(&self, id: u32) -> std::io::Result<()>;
+
+    /// An ID which has never been given to a saved task, even one which
+    /// has since been deleted.
+    fn next_id(&self) -> std::io::Result<u32>;
+}
+
+/// Keeps tasks in memory, e.g. for tests which should not touch the
+/// filesystem.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    tasks: RefCell<BTreeMap<u32, Task>>,
+    largest_id: Cell<u32>,
+}
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+impl TaskStore for MemoryStore {
+    fn load_all(&self) -> std::io::Result<Vec<Task>> {
+        Ok(self.tasks.borrow().values().cloned().collect())
+    }
+
+    fn load(&self, id: u32) -> std::io::Result<Option<Task>> {
+        Ok(self.tasks.borrow().get(&id).cloned())
+    }
+
+    fn save(&self, task: &Task) -> std::io::Result<bool> {
+        self.largest_id.set(self.largest_id.get().max(task.id()));
+        let replaced = self.tasks.borrow_mut().insert(task.id(), task.clone());
+        Ok(replaced.as_ref() != Some(task))
+    }
+
+    fn delete(&self, id: u32) -> std::io::Result<()> {
+        self.tasks.borrow_mut().remove(&id);
+        Ok(())
+    }
+
+    fn next_id(&self) -> std::io::Result<u32> {
+        Ok(self.largest_id.get() + 1)
+    }
+}
+
+/// Where the tasks of one project are read from and written to.
+#[cfg(feature = "storage")]
+pub trait Storage {
+    /// Reads every task, marked as unchanged since it was read.
+    fn read(&self) -> std::io::Result<TaskList>;
+
+    /// Writes the tasks which have changed since they were read, and
+    /// returns the paths of the files which were written.
+    fn write(&self, tasks: &TaskList) -> std::io::Result<Vec<std::path::PathBuf>>;
+}
+
+/// The storage of the project directory at `task_path`, with the backend
+/// recorded in its metadata.
+#[cfg(feature = "storage")]
+pub fn open<P: AsRef<std::path::Path>>(task_path: P) -> std::io::Result<Box<dyn Storage>> {
+    let meta = Meta::read(task_path.as_ref())?;
+    match meta.backend {
+        Backend::Yaml => Ok(Box::new(YamlStorage { task_path: task_path.as_ref().to_path_buf(), layout: meta.layout })),
+    }
+}
+
+/// Stores tasks as YAML in the project directory: a file for each task, or
+/// every task in `tasks.yaml`, as `layout` says.  The largest ID given to a
+/// task is recorded in the project's metadata.
+#[cfg(feature = "storage")]
+pub struct YamlStorage {
+    pub task_path: std::path::PathBuf,
+    pub layout: Layout,
+}
+
+#[cfg(feature = "storage")]
+impl YamlStorage {
+    fn read_single_file(&self) -> std::io::Result<TaskList> {
+        use std::io::{Error, ErrorKind};
+
+        match std::fs::read_to_string(self.task_path.join(SINGLE_FILE)) {
+            Ok(s) => serde_yaml::from_str(&s).map_err(|why| Error::new(ErrorKind::InvalidData, why)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(TaskList::new()),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write_single_file(&self, tasks: &TaskList) -> std::io::Result<bool> {
+        use std::io::{Error, ErrorKind};
+
+        let s = serde_yaml::to_string(tasks).map_err(|why| Error::new(ErrorKind::InvalidData, why))?;
+        crate::io::write_if_changed(&self.task_path.join(SINGLE_FILE), &s)
+    }
+
+    /// Records in the metadata that `id` has been given to a task, unless a
+    /// larger ID already has been.
+    fn record_id(&self, id: u32) -> std::io::Result<()> {
+        let mut meta = Meta::read(&self.task_path)?;
+        if id > meta.last_id {
+            meta.last_id = id;
+            meta.write(&self.task_path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "storage")]
+impl TaskStore for YamlStorage {
+    fn load_all(&self) -> std::io::Result<Vec<Task>> {
+        match self.layout {
+            Layout::SingleFile => Ok(self.read_single_file()?.get_all().into_iter().cloned().collect()),
+            Layout::PerTask => get_files(&self.task_path)?.iter().map(Task::read).collect(),
+        }
+    }
+
+    fn load(&self, id: u32) -> std::io::Result<Option<Task>> {
+        match self.layout {
+            Layout::SingleFile => Ok(self.read_single_file()?.get(id).cloned()),
+            Layout::PerTask => {
+                let path = self.task_path.join(format!("{}.yaml", id));
+                match path.is_file() {
+                    true => Task::read(&path).map(Some),
+                    false => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn save(&self, task: &Task) -> std::io::Result<bool> {
+        self.record_id(task.id())?;
+        match self.layout {
+            Layout::SingleFile => {
+                let mut tasks = self.read_single_file()?;
+                if tasks.replace(task.clone()).is_none() {
+                    tasks.insert(task.clone());
+                }
+                self.write_single_file(&tasks)
+            }
+            Layout::PerTask => Task::write(task, &self.task_path),
+        }
+    }
+
+    fn delete(&self, id: u32) -> std::io::Result<()> {
+        match self.layout {
+            Layout::SingleFile => {
+                let mut tasks = self.read_single_file()?;
+                if tasks.remove(id).is_some() {
+                    self.write_single_file(&tasks)?;
+                }
+                Ok(())
+            }
+            Layout::PerTask => crate::io::remove_task_file(id, &self.task_path),
+        }
+    }
+
+    fn next_id(&self) -> std::io::Result<u32> {
+        Ok(Meta::read(&self.task_path)?.last_id + 1)
+    }
+}
+
+#[cfg(feature = "storage")]
+impl Storage for YamlStorage {
+    fn read(&self) -> std::io::Result<TaskList> {
+        TaskList::load(self)
+    }
+
+    fn write(&self, tasks: &TaskList) -> std::io::Result<Vec<std::path::PathBuf>> {
+        self.record_id(tasks.next_id() - 1)?;
+        if self.layout == Layout::SingleFile {
+            // The whole file is written at once, rather than once for each task
+            return match self.write_single_file(tasks)? {
+                true => Ok(vec![self.task_path.join(SINGLE_FILE)]),
+                false => Ok(vec![]),
+            };
+        }
+
+        let saved = tasks.save(self)?;
+        Ok(saved.iter().map(|id| self.task_path.join(format!("{}.yaml", id))).collect())
+    }
+}
+
+/// Keeps the tasks last read from another storage, and only reads them
+/// again once the project directory has changed, so that something which
+/// reads the same project over and over, e.g. to render the list again,
+/// does not parse unchanged YAML each time.  Whether the directory has
+/// changed is told from the modification times of the directory and of
+/// every file in it, which only takes reading the directory.
+#[cfg(feature = "storage")]
+pub struct CachedStorage {
+    storage: Box<dyn Storage>,
+    task_path: std::path::PathBuf,
+    cached: RefCell<Option<(Stamp, TaskList)>>,
+}
+
+#[cfg(feature = "storage")]
+impl CachedStorage {
+    /// Caches what `storage`, which stores the project directory at
+    /// `task_path`, reads.
+    pub fn new(storage: Box<dyn Storage>, task_path: &std::path::Path) -> CachedStorage {
+        CachedStorage {
+            storage,
+            task_path: task_path.to_path_buf(),
+            cached: RefCell::new(None),
+        }
+    }
+
+    /// The cached storage of the project directory at `task_path`.
+    pub fn open<P: AsRef<std::path::Path>>(task_path: P) -> std::io::Result<CachedStorage> {
+        Ok(CachedStorage::new(open(task_path.as_ref())?, task_path.as_ref()))
+    }
+}
+
+#[cfg(feature = "storage")]
+impl Storage for CachedStorage {
+    fn read(&self) -> std::io::Result<TaskList> {
+        // The stamp is taken before reading, so a change made while reading is seen by the next read
+        let stamp = Stamp::of(&self.task_path)?;
+        if let Some((cached, tasks)) = &*self.cached.borrow() {
+            if *cached == stamp {
+                return Ok(tasks.clone());
+            }
+        }
+
+        let tasks = self.storage.read()?;
+        self.cached.replace(Some((stamp, tasks.clone())));
+        Ok(tasks)
+    }
+
+    fn write(&self, tasks: &TaskList) -> std::io::Result<Vec<std::path::PathBuf>> {
+        self.cached.replace(None);
+        self.storage.write(tasks)
+    }
+}
+
+/// The modification times, and sizes, of a project directory and the files
+/// in it.  Writing a task replaces its file, which changes the directory,
+/// and editing a file in place changes the file.
+#[cfg(feature = "storage")]
+#[derive(Debug, PartialEq)]
+struct Stamp {
+    dir: std::time::SystemTime,
+    files: Vec<(std::ffi::OsString, std::time::SystemTime, u64)>,
+}
+
+#[cfg(feature = "storage")]
+impl Stamp {
+    fn of(task_path: &std::path::Path) -> std::io::Result<Stamp> {
+        let mut files = vec![];
+        for entry in std::fs::read_dir(task_path)? {
+            let entry = entry?;
+            let md = entry.metadata()?;
+            if md.is_file() {
+                files.push((entry.file_name(), md.modified()?, md.len()));
+            }
+        }
+        files.sort();
+        Ok(Stamp { dir: std::fs::metadata(task_path)?.modified()?, files })
+    }
+}
+
+#[cfg(feature = "storage")]
 pub fn get_files(path: &std::path::PathBuf) -> std::io::Result<Vec<std::path::PathBuf>> {
     use std::fs;
 
-    let contents = fs::read_dir(path)?;
-    let yaml_files = contents.filter(|f| {
-        f.as_ref()
-            .unwrap()
-            .path()
-            .extension()
-            .map(|e| e == "yaml")
-            .unwrap_or(false)
-    });
     let mut files = vec![];
-    for yaml in yaml_files {
-        let file = yaml?;
-        files.push(file.path());
+    for entry in fs::read_dir(path)? {
+        let file = entry?.path();
+        if is_task_file(&file) {
+            files.push(file);
+        }
     }
 
     Ok(files)
 }
+
+/// Task files are named `<id>.yaml`, other yaml files (e.g. the project
+/// config) are not tasks.
+pub fn is_task_file(path: &std::path::Path) -> bool {
+    let is_yaml = path.extension().map(|e| e == "yaml").unwrap_or(false);
+    let is_task = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.parse::<u32>().is_ok())
+        .unwrap_or(false);
+    is_yaml && is_task
+}
@@ -1,37 +1,307 @@
+#[cfg(feature = "storage")]
 use super::io::get_files;
+use crate::error::TiskError;
+use super::io::TaskStore;
 use super::task::{Status, Task};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 
 /**
  * Stores a list of Tasks and provides functions for managing
  * those tasks.
+ *
+ * Tasks are kept in the order they were added, along with an index from
+ * each task's ID to its position in the list so that tasks can be looked
+ * up without searching the whole list.
+ *
+ * The list also tracks which tasks have been added or changed since they
+ * were read from their files, so that only those tasks are written back.
  */
+#[derive(Default, Clone)]
 pub struct TaskList {
     tasks: Vec<Task>,
+    index: HashMap<u32, usize>,
+    largest_id: u32,
+
+    /// The IDs of the tasks which have been added or changed since they were
+    /// read from their files.
+    modified: HashSet<u32>,
+
+    /// The tasks which have been removed from the list, kept so that they
+    /// can be moved to the trash when the list is written.
+    removed: Vec<Task>,
+
+    /// The tags which each tag implies, e.g. `frontend` implying `ui`.
+    implications: std::collections::BTreeMap<String, Vec<String>>,
+}
+
+/// How `TaskList::merge` assigns IDs to the tasks being merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdPolicy {
+    /// Every merged task is given a new ID, following the tasks already in the list.
+    Renumber,
+
+    /// Merged tasks keep their IDs, if an ID is already used then the
+    /// `Conflict` decides what happens.
+    Keep(Conflict),
+}
+
+/// What `TaskList::merge` does with a task whose ID is already used.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    /// The merge fails and the list is not changed.
+    Fail,
+
+    /// The task already in the list is kept and the merged task is left out.
+    Skip,
+
+    /// The merged task replaces the task already in the list.
+    Replace,
+
+    /// The merged task is given a new ID.
+    Renumber,
+}
+
+/// What `TaskList::remove_task` does with the subtasks of the task it removes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Orphans {
+    /// The subtasks become subtasks of the removed task's parent, or have
+    /// no parent if it had none.
+    Reparent,
+
+    /// The subtasks are left without a parent.
+    Orphan,
+
+    /// The subtasks, and their own subtasks, are removed too.
+    Cascade,
+}
+
+impl std::str::FromStr for Orphans {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<Orphans, TiskError> {
+        match s {
+            "reparent" => Ok(Orphans::Reparent),
+            "orphan" => Ok(Orphans::Orphan),
+            "cascade" => Ok(Orphans::Cascade),
+            _ => Err(TiskError::ParseError(format!(
+                "Invalid subtask policy: {} (expected reparent, orphan, or cascade)",
+                s
+            ))),
+        }
+    }
+}
+
+/// The tasks `TaskList::remove_task` removed and the subtasks it moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Removed {
+    /// The removed tasks, starting with the task which was asked to be removed.
+    pub tasks: Vec<Task>,
+
+    /// The subtasks which were given a new parent, or left without one.
+    pub moved: Vec<u32>,
 }
 
 impl TaskList {
+    /// Creates an empty `TaskList`.
+    pub fn new() -> TaskList {
+        TaskList::default()
+    }
+
+    /// Reads every task in `store`, as it was stored.
+    pub fn load(store: &dyn TaskStore) -> std::io::Result<TaskList> {
+        let mut tasks = TaskList::new();
+        for task in store.load_all()? {
+            tasks.insert(task);
+        }
+        tasks.reserve_ids(store.next_id()?.saturating_sub(1));
+        tasks.mark_read();
+        Ok(tasks)
+    }
+
+    /// Saves the tasks which have been added or changed since they were read
+    /// to `store`, and returns the IDs of those which it wrote.
+    pub fn save(&self, store: &dyn TaskStore) -> std::io::Result<Vec<u32>> {
+        let mut saved = vec![];
+        for task in self.get_modified() {
+            if store.save(task)? {
+                saved.push(task.id());
+            }
+        }
+        Ok(saved)
+    }
+
+    /// Reads every task in the project directory at `path`, with the storage
+    /// backend and layout recorded in the project's metadata.
+    #[cfg(feature = "storage")]
     pub fn read_tasks(path: &std::path::PathBuf) -> std::io::Result<TaskList> {
-        let paths = get_files(path)?;
-        let mut tasks = vec![];
-        for p in paths.into_iter() {
+        super::io::open(path)?.read()
+    }
+
+    /// Adds the tasks which `tisk archive` moved out of the project directory
+    /// at `path` to this list, as they were read, so that they are listed
+    /// along with the others.  Archived tasks are kept in a file each, with
+    /// any storage layout.
+    #[cfg(feature = "storage")]
+    pub fn read_archive(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        let archive = path.join(crate::io::ARCHIVE_DIR);
+        if !archive.is_dir() {
+            return Ok(());
+        }
+        for p in get_files(&archive)? {
             let task = Task::read(&p)?;
-            tasks.push(task);
+            let id = task.id();
+            if !self.index.contains_key(&id) {
+                self.insert(task);
+                self.modified.remove(&id);
+            }
         }
-        Ok(TaskList { tasks })
+        Ok(())
+    }
+
+    /// Marks every task as it was read from its file, except for the tasks
+    /// which hold their notes, which are written again to move the notes to
+    /// their own files.
+    pub(crate) fn mark_read(&mut self) {
+        self.modified = self.tasks.iter().filter(|t| t.has_inline_notes()).map(|t| t.id()).collect();
+    }
+
+    /// Whether the task `id` has been added or changed since it was read.
+    pub fn is_modified(&self, id: u32) -> bool {
+        self.modified.contains(&id)
+    }
+
+    /// The tasks which have been added or changed since they were read.
+    pub fn get_modified(&self) -> Vec<&Task> {
+        self.tasks.iter().filter(|task| self.is_modified(task.id())).collect()
     }
 
     pub fn next_id(&self) -> u32 {
-        if self.tasks.len() == 0 {
-            1
-        } else {
-            let mut largest_id = self.tasks[0].id();
-            for task in self.tasks.iter() {
-                if task.id() > largest_id {
-                    largest_id = task.id();
+        self.largest_id + 1
+    }
+
+    /// Keeps the IDs up to `id` from being given to new tasks, e.g. because
+    /// they were given to tasks which have since been deleted.
+    pub fn reserve_ids(&mut self, id: u32) {
+        self.largest_id = self.largest_id.max(id);
+    }
+
+    /// Adds `task` to the end of the list.  If there is already a task with
+    /// the same ID then that task is the one found by `get`.
+    pub(super) fn insert(&mut self, task: Task) {
+        let id = task.id();
+        self.modified.insert(id);
+        self.index.entry(id).or_insert(self.tasks.len());
+        self.largest_id = self.largest_id.max(id);
+        self.tasks.push(task);
+    }
+
+    /// Removes the task with the given ID from the list and returns it.  The
+    /// IDs of removed tasks are not given to tasks added to this list later.
+    pub fn remove(&mut self, id: u32) -> Option<Task> {
+        let idx = *self.index.get(&id)?;
+        let task = self.tasks.remove(idx);
+
+        // Every task after the removed one has moved
+        self.index.clear();
+        for (idx, task) in self.tasks.iter().enumerate() {
+            self.index.entry(task.id()).or_insert(idx);
+        }
+        self.removed.push(task.clone());
+        Some(task)
+    }
+
+    /// The task with the given ID, if it has been removed from this list.
+    pub fn removed(&self, id: u32) -> Option<&Task> {
+        self.removed.iter().find(|t| t.id() == id)
+    }
+
+    /// Puts `task`, e.g. one taken out of the trash, back into this list.
+    /// If its parent is no longer in the list then it is left without one,
+    /// and likewise for the tasks it depends on.
+    pub fn restore(&mut self, mut task: Task) -> Result<(), TiskError> {
+        if self.index.contains_key(&task.id()) {
+            return Err(TiskError::Conflict(format!("A task with ID {} already exists", task.id())));
+        }
+        if task.parent().map(|parent| !self.index.contains_key(&parent)).unwrap_or(false) {
+            task.set_parent(None);
+        }
+        let missing: Vec<u32> = task.depends_on().iter().copied().filter(|d| !self.index.contains_key(d)).collect();
+        missing.iter().for_each(|d| task.remove_dependency(*d));
+        self.removed.retain(|t| t.id() != task.id());
+        self.insert(task);
+        Ok(())
+    }
+
+    /// Removes the task with the given ID from the list, along with what
+    /// `orphans` says to do with its subtasks.  If there is no task with
+    /// that ID then the list is not changed.
+    pub fn remove_task(&mut self, id: u32, orphans: Orphans) -> Option<Removed> {
+        let parent = self.get(id)?.parent();
+        let children: Vec<u32> = self.tasks.iter().filter(|t| t.parent() == Some(id)).map(|t| t.id()).collect();
+        let removed = match orphans {
+            Orphans::Cascade => {
+                let subtasks: Vec<u32> = self.subtasks(id).iter().map(|t| t.id()).collect();
+                let mut tasks = vec![self.remove(id)?];
+                tasks.extend(subtasks.into_iter().filter_map(|id| self.remove(id)));
+                Removed { tasks, moved: vec![] }
+            }
+            Orphans::Reparent | Orphans::Orphan => {
+                let new_parent = if orphans == Orphans::Reparent { parent } else { None };
+                for child in &children {
+                    self.get_mut(*child).iter_mut().for_each(|t| t.set_parent(new_parent));
                 }
+                Removed { tasks: vec![self.remove(id)?], moved: children }
+            }
+        };
+        Some(removed)
+    }
+
+    /// Replaces the task which has the same ID as `task` and returns the
+    /// task which was replaced.  If there is no task with that ID then the
+    /// list is not changed.
+    pub fn replace(&mut self, task: Task) -> Option<Task> {
+        let idx = *self.index.get(&task.id())?;
+        self.modified.insert(task.id());
+        Some(std::mem::replace(&mut self.tasks[idx], task))
+    }
+
+    /**
+     * Adds all the tasks in `other` to this list, with IDs assigned by
+     * `policy`.  Returns the original and new ID of each task which was
+     * merged in, tasks which were skipped are left out.
+     *
+     * If `policy` is to fail on conflicting IDs and any task in `other`
+     * has the same ID as a task in this list then this list is not changed.
+     */
+    pub fn merge(&mut self, other: TaskList, policy: IdPolicy) -> Result<Vec<(u32, u32)>, TiskError> {
+        if let IdPolicy::Keep(Conflict::Fail) = policy {
+            if let Some(task) = other.tasks.iter().find(|t| self.index.contains_key(&t.id())) {
+                return Err(TiskError::Conflict(format!("A task with ID {} already exists", task.id())));
             }
-            largest_id + 1
         }
+
+        let mut merged = vec![];
+        for mut task in other.tasks.into_iter() {
+            let id = task.id();
+            let new_id = match (policy, self.index.get(&id)) {
+                (IdPolicy::Renumber, _) => self.next_id(),
+                (IdPolicy::Keep(_), None) => id,
+                (IdPolicy::Keep(Conflict::Renumber), Some(_)) => self.next_id(),
+                (IdPolicy::Keep(Conflict::Replace), Some(&idx)) => {
+                    self.modified.insert(id);
+                    self.tasks[idx] = task;
+                    merged.push((id, id));
+                    continue;
+                }
+                (IdPolicy::Keep(_), Some(_)) => continue,
+            };
+
+            task.set_id(new_id);
+            self.insert(task);
+            merged.push((id, new_id));
+        }
+        Ok(merged)
     }
 
     /**
@@ -42,26 +312,111 @@ impl TaskList {
      * If no task is found with the given ID then return `None`.
      */
     pub fn get_mut(&mut self, id: u32) -> Option<&mut Task> {
-        self.tasks.iter_mut().find(|t| t.id() == id)
+        let idx = *self.index.get(&id)?;
+        self.modified.insert(id);
+        self.tasks.get_mut(idx)
     }
 
     pub fn get(&self, id: u32) -> Option<&Task> {
-        self.tasks.iter().find(|t| t.id() == id)
+        let idx = *self.index.get(&id)?;
+        self.tasks.get(idx)
     }
 
     pub fn add_task(&mut self, name: &str, priority: u32) -> u32 {
         let id = self.next_id();
         let t = Task::new(id, String::from(name), Status::Open, priority);
-        self.tasks.push(t);
+        self.insert(t);
 
         id
     }
 
-    pub fn close_task(&mut self, id: u32) -> Option<&Task> {
-        self.get_mut(id).map(|task| {
-            task.close();
-            task as &Task
-        })
+    /**
+     * Closes the task with the given ID and returns the IDs of the tasks
+     * which were closed, starting with that task.
+     *
+     * A task with open subtasks is only closed if `cascade` is set, in
+     * which case its open subtasks are closed along with it.  Otherwise no
+     * task is changed.
+     */
+    pub fn close_task(&mut self, id: u32, cascade: bool) -> Result<Vec<u32>, TiskError> {
+        if self.get(id).is_none() {
+            return Err(TiskError::TaskNotFound(id));
+        }
+        let open: Vec<u32> = self
+            .subtasks(id)
+            .into_iter()
+            .filter(|t| t.status().is_open())
+            .map(|t| t.id())
+            .collect();
+        if !open.is_empty() && !cascade {
+            let ids: Vec<String> = open.iter().map(|id| id.to_string()).collect();
+            return Err(TiskError::Conflict(format!(
+                "Task {} has open subtasks: {}, close them first or close it with --cascade",
+                id,
+                ids.join(", ")
+            )));
+        }
+
+        let mut closed = vec![id];
+        closed.extend(open);
+        for id in &closed {
+            self.get_mut(*id).iter_mut().for_each(|t| t.close());
+        }
+        Ok(closed)
+    }
+
+    /// The subtasks of the task with the given ID, along with their own
+    /// subtasks, in the order they were added.
+    pub fn subtasks(&self, id: u32) -> Vec<&Task> {
+        let mut found = HashSet::new();
+        let mut parents = vec![id];
+        while let Some(parent) = parents.pop() {
+            for task in self.tasks.iter().filter(|t| t.parent() == Some(parent)) {
+                if task.id() != id && found.insert(task.id()) {
+                    parents.push(task.id());
+                }
+            }
+        }
+        self.tasks.iter().filter(|t| found.contains(&t.id())).collect()
+    }
+
+    /**
+     * Makes the task with ID `id` depend on the task with ID `on`.  Fails,
+     * without changing the list, if either task does not exist or if `on`
+     * already depends on `id`, directly or through other tasks, since then
+     * neither could be done first.
+     */
+    pub fn depend(&mut self, id: u32, on: u32) -> Result<(), TiskError> {
+        for id in &[id, on] {
+            if self.get(*id).is_none() {
+                return Err(TiskError::TaskNotFound(*id));
+            }
+        }
+        if id == on || self.dependencies(on).iter().any(|t| t.id() == id) {
+            return Err(TiskError::Conflict(format!("Task {} cannot depend on task {}, which depends on it", id, on)));
+        }
+        self.get_mut(id).iter_mut().for_each(|t| t.add_dependency(on));
+        Ok(())
+    }
+
+    /// The tasks which the task with the given ID depends on, along with
+    /// the tasks they depend on, in the order they were added.
+    pub fn dependencies(&self, id: u32) -> Vec<&Task> {
+        let mut found = HashSet::new();
+        let mut next = vec![id];
+        while let Some(id) = next.pop() {
+            for dep in self.get(id).map(|t| t.depends_on()).unwrap_or(&[]) {
+                if found.insert(*dep) {
+                    next.push(*dep);
+                }
+            }
+        }
+        self.tasks.iter().filter(|t| found.contains(&t.id())).collect()
+    }
+
+    /// The tasks which depend directly on the task with the given ID.
+    pub fn dependents(&self, id: u32) -> Vec<&Task> {
+        self.tasks.iter().filter(|t| t.depends_on().contains(&id)).collect()
     }
 
     pub fn set_priority(&mut self, id: u32, priority: u32) -> Option<(Task, &Task)> {
@@ -72,19 +427,25 @@ impl TaskList {
         })
     }
 
-    pub fn write_all(&self, task_path: &std::path::PathBuf) -> std::io::Result<()> {
-        self.tasks
-            .iter()
-            .map(|task| Task::write(&task, &task_path))
-            .collect()
+    /// Writes every task to the project directory at `task_path`, with the
+    /// storage backend and layout recorded in the project's metadata.  Tasks which have
+    /// not changed since they were read, and files which already hold what
+    /// would be written, are left alone.  Returns the paths of the files
+    /// which were written.
+    ///
+    /// The largest ID given to a task is recorded in the metadata.
+    #[cfg(feature = "storage")]
+    pub fn write_all(&self, task_path: &std::path::PathBuf) -> std::io::Result<Vec<std::path::PathBuf>> {
+        super::io::open(task_path)?.write(self)
     }
 
     pub fn get_all(&self) -> Vec<&Task> {
         self.tasks.iter().collect()
     }
 
+    /// Every task which is not closed, whatever its other status.
     pub fn get_open(&self) -> Vec<&Task> {
-        self.filter(Status::Open)
+        self.tasks.iter().filter(|t| t.status().is_open()).collect()
     }
 
     pub fn get_closed(&self) -> Vec<&Task> {
@@ -96,17 +457,81 @@ impl TaskList {
         let filtered_tasks: Vec<&Task> = iter.filter(|t| t.status() == status).collect();
         filtered_tasks
     }
+
+    /// Sets which tags each tag implies, a task given a tag through
+    /// `add_tag` is given every tag it implies too.
+    pub fn set_implications(&mut self, implications: std::collections::BTreeMap<String, Vec<String>>) {
+        self.implications = implications
+    }
+
+    /// `tag` followed by every tag it implies, directly or through the tags
+    /// it implies, each only once.
+    pub fn implied_tags(&self, tag: &str) -> Vec<String> {
+        let mut tags = vec![String::from(tag)];
+        let mut next = 0;
+        while next < tags.len() {
+            for implied in self.implications.get(&tags[next]).into_iter().flatten() {
+                if !tags.contains(implied) {
+                    tags.push(implied.clone());
+                }
+            }
+            next += 1;
+        }
+        tags
+    }
+
+    /// Gives the task with the given ID `tag` and every tag it implies.
+    pub fn add_tag(&mut self, id: u32, tag: &str) -> Option<&Task> {
+        let tags = self.implied_tags(tag);
+        let task = self.get_mut(id)?;
+        tags.iter().for_each(|tag| task.add_tag(tag));
+        Some(task)
+    }
+
+    /// The tasks which have `tag`, in the order they were added.
+    pub fn filter_by_tag(&self, tag: &str) -> Vec<&Task> {
+        self.tasks.iter().filter(|t| t.has_tag(tag)).collect()
+    }
+
+    /// Every tag which any task has, sorted and without duplicates.
+    pub fn tags(&self) -> Vec<&str> {
+        let tags: std::collections::BTreeSet<&str> =
+            self.tasks.iter().flat_map(|t| t.tags().iter().map(String::as_str)).collect();
+        tags.into_iter().collect()
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A `TaskList` is serialized as the list of its tasks, ordered by ID so
+/// that the same tasks are always written the same way whatever order they
+/// were read in.  The index is rebuilt when it is deserialized.
+impl Serialize for TaskList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tasks: Vec<&Task> = self.tasks.iter().collect();
+        tasks.sort_by_key(|task| task.id());
+        tasks.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TaskList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<TaskList, D::Error> {
+        let tasks = Vec::<Task>::deserialize(deserializer)?;
+        Ok(tasks.into_iter().collect())
+    }
+}
 
-    impl TaskList {
-        pub fn new() -> TaskList {
-            TaskList { tasks: vec![] }
+impl std::iter::FromIterator<Task> for TaskList {
+    fn from_iter<I: IntoIterator<Item = Task>>(iter: I) -> TaskList {
+        let mut list = TaskList::new();
+        for task in iter {
+            list.insert(task);
         }
+        list
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
     fn add_task() {
@@ -138,7 +563,7 @@ mod tests {
             let mut mtasks = TaskList::new();
             let t = mtasks.add_task("test", 1);
             mtasks.add_task("test 2", 2);
-            mtasks.close_task(t);
+            mtasks.close_task(t, false).unwrap();
             tasks = mtasks;
         }
 
@@ -192,6 +617,50 @@ mod tests {
         assert_eq!(Status::Open, t2.status());
     }
 
+    #[test]
+    fn close_task_with_subtasks() {
+        let mut tasks = TaskList::new();
+        let parent = tasks.add_task("release", 1);
+        for name in &["write notes", "tag", "announce"] {
+            let id = tasks.add_task(name, 1);
+            tasks.get_mut(id).unwrap().set_parent(Some(parent));
+        }
+        let grandchild = tasks.add_task("draft the post", 1);
+        tasks.get_mut(grandchild).unwrap().set_parent(Some(4));
+        tasks.close_task(2, false).unwrap();
+
+        assert!(tasks.close_task(parent, false).is_err());
+        assert_eq!(Status::Open, tasks.get(parent).unwrap().status());
+        assert!(tasks.close_task(9, false).is_err());
+
+        assert_eq!(vec![1, 3, 4, 5], tasks.close_task(parent, true).unwrap());
+        assert_eq!(true, tasks.get_all().iter().all(|t| t.status() == Status::Closed));
+    }
+
+    #[test]
+    fn depend() {
+        let mut tasks = TaskList::new();
+        for name in &["release", "write notes", "tag", "draft"] {
+            tasks.add_task(name, 1);
+        }
+        tasks.depend(1, 2).unwrap();
+        tasks.depend(1, 3).unwrap();
+        tasks.depend(2, 4).unwrap();
+        let ids = |found: Vec<&Task>| found.iter().map(|t| t.id()).collect::<Vec<u32>>();
+
+        assert_eq!(vec![2, 3, 4], ids(tasks.dependencies(1)));
+        assert_eq!(vec![1], ids(tasks.dependents(2)));
+        assert!(tasks.depend(4, 1).is_err());
+        assert!(tasks.depend(3, 3).is_err());
+        assert!(tasks.depend(3, 9).is_err());
+        assert_eq!(Vec::<u32>::new(), ids(tasks.dependencies(4)));
+
+        let removed = tasks.remove(2).unwrap();
+        tasks.remove(4);
+        tasks.restore(removed).unwrap();
+        assert_eq!(true, tasks.get(2).unwrap().depends_on().is_empty());
+    }
+
     #[test]
     fn get_open() {
         let tasks;
@@ -199,7 +668,7 @@ mod tests {
             let mut mtasks = TaskList::new();
             let t = mtasks.add_task("test", 1);
             mtasks.add_task("test 2", 2);
-            mtasks.close_task(t);
+            mtasks.close_task(t, false).unwrap();
             tasks = mtasks;
         }
 
@@ -216,7 +685,7 @@ mod tests {
             let mut mtasks = TaskList::new();
             let t = mtasks.add_task("test", 1);
             mtasks.add_task("test 2", 2);
-            mtasks.close_task(t);
+            mtasks.close_task(t, false).unwrap();
             tasks = mtasks;
         }
 
@@ -226,6 +695,34 @@ mod tests {
         assert_eq!(Status::Closed, filtered_tasks[0].status());
     }
 
+    #[test]
+    fn tags() {
+        let mut tasks = TaskList::new();
+        for (name, tags) in &[("login", vec!["ui", "bug"]), ("docs", vec![]), ("crash", vec!["bug"])] {
+            let id = tasks.add_task(name, 1);
+            tags.iter().for_each(|tag| tasks.get_mut(id).unwrap().add_tag(tag));
+        }
+
+        assert_eq!(vec!["bug", "ui"], tasks.tags());
+        let names = |tag| tasks.filter_by_tag(tag).iter().map(|t| t.name()).collect::<Vec<&str>>();
+        assert_eq!(vec!["login", "crash"], names("bug"));
+        assert_eq!(Vec::<&str>::new(), names("docs"));
+    }
+
+    #[test]
+    fn implied_tags() {
+        let mut tasks = list_of(&["a"]);
+        let rules: &[(&str, &[&str])] =
+            &[("frontend", &["ui"]), ("ui", &["product", "frontend"]), ("css", &["frontend"])];
+        let rule = |(tag, implied): &(&str, &[&str])| (tag.to_string(), implied.iter().map(|t| t.to_string()).collect());
+        tasks.set_implications(rules.iter().map(rule).collect());
+
+        assert_eq!(vec!["css", "frontend", "ui", "product"], tasks.implied_tags("css"));
+        assert_eq!(vec!["docs"], tasks.implied_tags("docs"));
+        assert_eq!(vec!["css", "frontend", "ui", "product"], tasks.add_tag(1, "css").unwrap().tags());
+        assert_eq!(true, tasks.add_tag(2, "css").is_none());
+    }
+
     #[test]
     fn get_all() {
         let tasks;
@@ -233,7 +730,7 @@ mod tests {
             let mut mtasks = TaskList::new();
             let t = mtasks.add_task("test", 1);
             mtasks.add_task("test 2", 2);
-            mtasks.close_task(t);
+            mtasks.close_task(t, false).unwrap();
             tasks = mtasks;
         }
 
@@ -245,21 +742,188 @@ mod tests {
         assert_eq!(Status::Open, filtered_tasks[1].status());
     }
 
+    #[test]
+    fn next_id_follows_largest_id() {
+        let mut tasks = TaskList::new();
+        assert_eq!(1, tasks.next_id());
+
+        tasks.insert(Task::new(7, "test".into(), Status::Open, 1));
+        tasks.insert(Task::new(3, "test 2".into(), Status::Open, 1));
+        assert_eq!(8, tasks.next_id());
+        assert_eq!("test 2", tasks.get(3).unwrap().name());
+        assert_eq!(8, tasks.add_task("test 3", 1));
+        assert_eq!("test 3", tasks.get(8).unwrap().name());
+        assert_eq!(true, tasks.get(4).is_none());
+    }
+
+    #[test]
+    fn load_and_save_a_store() {
+        use crate::tasks::MemoryStore;
+
+        let store = MemoryStore::new();
+        let mut tasks = TaskList::new();
+        tasks.add_task("one", 1);
+        tasks.add_task("two", 2);
+        assert_eq!(vec![1, 2], tasks.save(&store).unwrap());
+
+        let mut tasks = TaskList::load(&store).unwrap();
+        assert_eq!(true, tasks.get_modified().is_empty());
+        assert_eq!(Vec::<u32>::new(), tasks.save(&store).unwrap());
+        tasks.set_priority(2, 5);
+        assert_eq!(vec![2], tasks.save(&store).unwrap());
+        assert_eq!(5, store.load(2).unwrap().unwrap().priority());
+
+        // The ID of a deleted task is not given to a new task
+        store.delete(2).unwrap();
+        assert_eq!(None, store.load(2).unwrap());
+        assert_eq!(3, TaskList::load(&store).unwrap().add_task("three", 1));
+    }
+
+    #[test]
+    fn duplicate_ids_find_first_task() {
+        let mut tasks = TaskList::new();
+        tasks.insert(Task::new(1, "first".into(), Status::Open, 1));
+        tasks.insert(Task::new(1, "second".into(), Status::Open, 1));
+        assert_eq!("first", tasks.get(1).unwrap().name());
+        assert_eq!(2, tasks.get_all().len());
+    }
+
+    fn list_of(names: &[&str]) -> TaskList {
+        let mut tasks = TaskList::new();
+        for name in names {
+            tasks.add_task(name, 1);
+        }
+        tasks
+    }
+
+    fn names(tasks: &TaskList) -> Vec<(u32, &str)> {
+        tasks.get_all().iter().map(|t| (t.id(), t.name())).collect()
+    }
+
+    #[test]
+    fn remove_task() {
+        let mut tasks = list_of(&["a", "b", "c"]);
+        let removed = tasks.remove(2).expect("Task 2 should be removed");
+        assert_eq!("b", removed.name());
+        assert_eq!(true, tasks.get(2).is_none());
+        assert_eq!(vec![(1, "a"), (3, "c")], names(&tasks));
+        assert_eq!("c", tasks.get(3).unwrap().name());
+        assert_eq!(true, tasks.remove(2).is_none());
+        assert_eq!("b", tasks.removed(2).unwrap().name());
+
+        // Removed tasks can be put back, unless their ID is in use
+        assert!(tasks.restore(Task::new(1, "a".into(), Status::Open, 1)).is_err());
+        tasks.restore(removed).unwrap();
+        assert_eq!(vec![(1, "a"), (3, "c"), (2, "b")], names(&tasks));
+        assert_eq!(None, tasks.removed(2));
+        tasks.remove(2);
+
+        // IDs are not reused
+        tasks.remove(3);
+        assert_eq!(4, tasks.add_task("d", 1));
+    }
+
+    #[test]
+    fn remove_task_with_subtasks() {
+        // 1 <- 2 <- 3 <- 4, and 5 is a subtask of 2 too
+        let hierarchy = || {
+            let mut tasks = list_of(&["a", "b", "c", "d", "e"]);
+            for (id, parent) in &[(2, 1), (3, 2), (4, 3), (5, 2)] {
+                tasks.get_mut(*id).unwrap().set_parent(Some(*parent));
+            }
+            tasks
+        };
+        let parents = |tasks: &TaskList| tasks.get_all().iter().map(|t| (t.id(), t.parent())).collect::<Vec<_>>();
+
+        let mut tasks = hierarchy();
+        let removed = tasks.remove_task(2, Orphans::Reparent).unwrap();
+        assert_eq!(vec!["b"], removed.tasks.iter().map(|t| t.name()).collect::<Vec<&str>>());
+        assert_eq!(vec![3, 5], removed.moved);
+        assert_eq!(vec![(1, None), (3, Some(1)), (4, Some(3)), (5, Some(1))], parents(&tasks));
+
+        let mut tasks = hierarchy();
+        tasks.remove_task(2, Orphans::Orphan).unwrap();
+        assert_eq!(vec![(1, None), (3, None), (4, Some(3)), (5, None)], parents(&tasks));
+
+        let mut tasks = hierarchy();
+        let removed = tasks.remove_task(2, Orphans::Cascade).unwrap();
+        assert_eq!(vec![2, 3, 4, 5], removed.tasks.iter().map(|t| t.id()).collect::<Vec<u32>>());
+        assert_eq!(Vec::<u32>::new(), removed.moved);
+        assert_eq!(vec![(1, "a")], names(&tasks));
+
+        assert_eq!(None, tasks.remove_task(2, Orphans::Orphan));
+        assert_eq!(true, "adopt".parse::<Orphans>().is_err());
+    }
+
+    #[test]
+    fn replace_task() {
+        let mut tasks = list_of(&["a", "b"]);
+        let replacement = Task::new(2, "c".into(), Status::Closed, 3);
+        assert_eq!("b", tasks.replace(replacement).unwrap().name());
+        assert_eq!(vec![(1, "a"), (2, "c")], names(&tasks));
+        assert_eq!(Status::Closed, tasks.get(2).unwrap().status());
+
+        assert_eq!(true, tasks.replace(Task::new(7, "d".into(), Status::Open, 1)).is_none());
+        assert_eq!(vec![(1, "a"), (2, "c")], names(&tasks));
+    }
+
+    #[test]
+    fn merge_renumber() {
+        let mut tasks = list_of(&["a", "b"]);
+        let merged = tasks.merge(list_of(&["c", "d"]), IdPolicy::Renumber).unwrap();
+        assert_eq!(vec![(1, 3), (2, 4)], merged);
+        assert_eq!(vec![(1, "a"), (2, "b"), (3, "c"), (4, "d")], names(&tasks));
+    }
+
+    #[test]
+    fn merge_keep_ids() {
+        let mut other = list_of(&["x", "y", "z"]);
+        other.remove(1);
+
+        let mut tasks = list_of(&["a", "b"]);
+        assert_eq!(true, tasks.merge(list_of(&["c", "d", "e"]), IdPolicy::Keep(Conflict::Fail)).is_err());
+        assert_eq!(vec![(1, "a"), (2, "b")], names(&tasks));
+
+        let merged = tasks.merge(other, IdPolicy::Keep(Conflict::Skip)).unwrap();
+        assert_eq!(vec![(3, 3)], merged);
+        assert_eq!(vec![(1, "a"), (2, "b"), (3, "z")], names(&tasks));
+
+        let merged = tasks.merge(list_of(&["c", "d"]), IdPolicy::Keep(Conflict::Replace)).unwrap();
+        assert_eq!(vec![(1, 1), (2, 2)], merged);
+        assert_eq!(vec![(1, "c"), (2, "d"), (3, "z")], names(&tasks));
+
+        let merged = tasks.merge(list_of(&["e"]), IdPolicy::Keep(Conflict::Renumber)).unwrap();
+        assert_eq!(vec![(1, 4)], merged);
+        assert_eq!("e", tasks.get(4).unwrap().name());
+    }
+
     #[test]
     fn task_notes() {
         let mut mtasks = TaskList::new();
         let t = mtasks.add_task("test", 1);
         let task = mtasks.get_mut(t).expect("Task not created");
 
-        task.add_note("Test Note");
+        task.add_note("Test Note").unwrap();
         let notes = task.notes();
         assert_eq!(1, notes.len());
         assert_eq!("Test Note", notes[0].note());
 
-        task.add_note("Second Note");
+        task.add_note("Second Note").unwrap();
         let notes = task.notes();
         assert_eq!(2, notes.len());
         assert_eq!("Test Note", notes[0].note());
         assert_eq!("Second Note", notes[1].note());
     }
+
+    #[test]
+    fn serialized_in_id_order() {
+        let mut tasks = list_of(&["a", "b", "c"]);
+        let b = tasks.remove(2).unwrap();
+        tasks.insert(b);
+
+        let yaml = serde_yaml::to_string(&tasks).unwrap();
+        let read: TaskList = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(vec![(1, "a"), (2, "b"), (3, "c")], names(&read));
+        assert_eq!(yaml, serde_yaml::to_string(&read).unwrap());
+    }
 }
@@ -4,7 +4,12 @@
 
 mod io;
 mod list;
+mod order;
 mod task;
 
-pub use list::TaskList;
-pub use task::{Note, Task};
+pub use io::{is_task_file, MemoryStore, TaskStore};
+#[cfg(feature = "storage")]
+pub use io::{open as open_storage, CachedStorage, Storage, YamlStorage};
+pub use list::{Conflict, IdPolicy, Orphans, Removed, TaskList};
+pub use order::{Comparator, SortPolicy};
+pub use task::{find_references, Note, Reminder, Status, Task, TimeEntry};
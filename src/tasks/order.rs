@@ -0,0 +1,116 @@
+use super::Task;
+use crate::dates::Zone;
+use crate::error::TiskError;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// Compares two tasks by one property, the task which should be listed
+/// first is `Less`.  `today` is the date the tasks are listed on in `zone`,
+/// the project's time zone.
+pub type Comparator = fn(&Task, &Task, Zone, NaiveDate) -> Ordering;
+
+/// The order tasks are listed in when no column is sorted by.  Tasks are
+/// put into lanes by each comparator in turn, e.g. with `DueFirst` every
+/// task due today is listed before any other task and within those lanes
+/// tasks are ordered by priority.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortPolicy {
+    /// Tasks which are due today, or overdue, come first, then the
+    /// highest priority tasks.
+    #[default]
+    DueFirst,
+
+    /// The highest priority tasks come first, tasks due today come first
+    /// among those with the same priority.
+    PriorityFirst,
+}
+
+impl std::str::FromStr for SortPolicy {
+    type Err = TiskError;
+
+    fn from_str(s: &str) -> Result<SortPolicy, TiskError> {
+        match s {
+            "due-first" => Ok(SortPolicy::DueFirst),
+            "priority-first" => Ok(SortPolicy::PriorityFirst),
+            _ => Err(TiskError::ParseError(format!("Invalid sort policy: {}", s))),
+        }
+    }
+}
+
+impl SortPolicy {
+    /// The comparators which order tasks under this policy, in the order
+    /// they are applied.  Ties are broken by the next comparator.
+    pub fn comparators(&self) -> &'static [Comparator] {
+        match self {
+            SortPolicy::DueFirst => &[by_due_today, by_priority, by_created],
+            SortPolicy::PriorityFirst => &[by_priority, by_due_today, by_created],
+        }
+    }
+
+    /// Compares two tasks as of `today` in `zone`, the task which should be
+    /// listed first is `Less`.
+    pub fn compare(&self, a: &Task, b: &Task, zone: Zone, today: NaiveDate) -> Ordering {
+        self.comparators()
+            .iter()
+            .map(|compare| compare(a, b, zone, today))
+            .find(|ord| *ord != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Orders `tasks` under this policy as of the date it is now in `zone`.
+    pub fn sort(&self, tasks: &mut [&Task], zone: Zone) {
+        let today = zone.today();
+        tasks.sort_by(|a, b| self.compare(a, b, zone, today));
+    }
+}
+
+/// Open tasks which are due today, or are overdue, come first.
+pub fn by_due_today(a: &Task, b: &Task, zone: Zone, today: NaiveDate) -> Ordering {
+    let due_today =
+        |t: &Task| t.status().is_open() && t.due_at().map(|d| zone.naive(d).date() <= today).unwrap_or(false);
+    due_today(b).cmp(&due_today(a))
+}
+
+/// The highest priority comes first.
+pub fn by_priority(a: &Task, b: &Task, _: Zone, _: NaiveDate) -> Ordering {
+    b.priority().cmp(&a.priority())
+}
+
+/// The oldest task comes first.
+pub fn by_created(a: &Task, b: &Task, _: Zone, _: NaiveDate) -> Ordering {
+    a.created_at().cmp(&b.created_at())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::Status;
+    use chrono::{Local, TimeZone, Utc};
+
+    fn task(id: u32, priority: u32, due: Option<NaiveDate>) -> Task {
+        let mut task = Task::new(id, format!("task {}", id), Status::Open, priority);
+        task.set_due_at(due.map(|d| Local.from_local_date(&d).unwrap().and_hms(9, 0, 0).with_timezone(&Utc)));
+        task
+    }
+
+    #[test]
+    fn lanes() {
+        let today = NaiveDate::from_ymd(2020, 6, 3);
+        let tasks = [
+            task(1, 5, None),
+            task(2, 1, Some(today)),
+            task(3, 3, Some(NaiveDate::from_ymd(2020, 6, 1))),
+            task(4, 9, Some(NaiveDate::from_ymd(2020, 6, 4))),
+        ];
+        let order = |policy: SortPolicy| {
+            let mut sorted: Vec<&Task> = tasks.iter().collect();
+            sorted.sort_by(|a, b| policy.compare(a, b, Zone::Local, today));
+            sorted.iter().map(|t| t.id()).collect::<Vec<u32>>()
+        };
+
+        assert_eq!(vec![3, 2, 4, 1], order(SortPolicy::DueFirst));
+        assert_eq!(vec![4, 1, 3, 2], order(SortPolicy::PriorityFirst));
+    }
+}
@@ -1,20 +1,53 @@
 use chrono::prelude::*;
+use crate::dates::{DateContext, Zone};
+use crate::error::TiskError;
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "storage")]
 use std::fs::File;
+#[cfg(feature = "storage")]
 use std::io::prelude::*;
+use std::collections::BTreeMap;
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
 
+/// Where a task is in its life.  Every status but `Closed` counts as open,
+/// files written before `InProgress` and `Blocked` only have the others.
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Status {
     Open,
     Closed,
+
+    /// The task has been started, by `tisk start`.
+    InProgress,
+
+    /// The task cannot go on until something else happens, the reason is
+    /// kept in a note.
+    Blocked,
+}
+
+impl Status {
+    /// Whether a task with this status still needs to be done.
+    pub fn is_open(self) -> bool {
+        self != Status::Closed
+    }
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Status::Open => write!(f, "open"),
+            Status::Closed => write!(f, "closed"),
+            Status::InProgress => write!(f, "in progress"),
+            Status::Blocked => write!(f, "blocked"),
+        }
+    }
 }
 
 /**
  * A Note stores a comment or note about a specific Task
  */
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Note {
+    #[serde(with = "crate::dates::stored")]
     created_at: DateTime<Utc>,
     note: String,
 }
@@ -30,28 +63,214 @@ impl Note {
     pub fn note(&self) -> &str {
         &self.note
     }
+
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// The IDs of the tasks this note refers to with `#ID`, in the order
+    /// they are first referred to.
+    pub fn references(&self) -> Vec<u32> {
+        let mut ids = vec![];
+        for (_, id) in find_references(&self.note) {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    }
+}
+
+/// A stretch of time a task was checked out for, from `tisk checkout`
+/// until it was checked in or another task was checked out.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeEntry {
+    #[serde(with = "crate::dates::stored")]
+    pub started_at: DateTime<Utc>,
+
+    /// When the task was checked in, `None` while it is still checked out.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl TimeEntry {
+    /// How long the task was checked out for, or has been so far if it is
+    /// still checked out.
+    pub fn duration(&self, now: DateTime<Utc>) -> chrono::Duration {
+        self.ended_at.unwrap_or(now) - self.started_at
+    }
+}
+
+/// A time to remind the user about a task, set by `tisk remind` and
+/// delivered by `tisk notify`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    #[serde(with = "crate::dates::stored")]
+    pub at: DateTime<Utc>,
+
+    /// When the reminder was delivered, `None` until it is, so that it is
+    /// only delivered once.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Finds every reference to a task, written `#ID`, in `text` along with the
+/// byte range of the reference.  A `#` which follows a letter or digit, as in
+/// `C#1`, or digits which run into letters, as in `#1st`, are not references.
+pub fn find_references(text: &str) -> Vec<(std::ops::Range<usize>, u32)> {
+    let mut found = vec![];
+    let mut previous: Option<char> = None;
+    for (start, c) in text.char_indices() {
+        if c == '#' && !previous.map(char::is_alphanumeric).unwrap_or(false) {
+            let rest = &text[start + 1..];
+            let digits = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            let ends_word = !rest[digits..].starts_with(char::is_alphanumeric);
+            if let (true, Ok(id)) = (ends_word, rest[..digits].parse()) {
+                found.push((start..start + 1 + digits, id));
+            }
+        }
+        previous = Some(c);
+    }
+    found
+}
+
+/**
+ * The notes on a task.  With the per-task layout notes are kept in their own
+ * file, `<id>.notes.yaml`, next to the task's file so that reading a task
+ * (e.g. to list it) does not read all of its notes.  Those notes are read
+ * the first time they are needed.
+ */
+#[derive(Debug, Clone, Default)]
+struct Notes {
+    notes: std::cell::OnceCell<Result<Vec<Note>, String>>,
+
+    /// The file the notes are read from, `None` if they were read with the task.
+    file: Option<std::path::PathBuf>,
+
+    /// How many notes are in `file`, so they can be counted without reading them.
+    count: usize,
+}
+
+impl Notes {
+    fn inline(notes: Vec<Note>) -> Notes {
+        Notes { notes: std::cell::OnceCell::from(Ok(notes)), file: None, count: 0 }
+    }
+
+    #[cfg(feature = "storage")]
+    fn in_file(file: std::path::PathBuf, count: usize) -> Notes {
+        Notes { notes: std::cell::OnceCell::new(), file: Some(file), count }
+    }
+
+    /// The notes, reading them from their file if they have not been read yet.
+    fn get(&self) -> Result<&Vec<Note>, &str> {
+        let notes = self.notes.get_or_init(|| match &self.file {
+            None => Ok(vec![]),
+            Some(file) => std::fs::read_to_string(file)
+                .map_err(|why| why.to_string())
+                .and_then(|s| serde_yaml::from_str(&s).map_err(|why| why.to_string()))
+                .map_err(|why| format!("Failed to read notes from {}: {}", file.display(), why)),
+        });
+        notes.as_ref().map_err(String::as_str)
+    }
+
+    fn get_mut(&mut self) -> Result<&mut Vec<Note>, String> {
+        self.get().map_err(String::from)?;
+        self.notes.get_mut().unwrap().as_mut().map_err(|why| why.clone())
+    }
+
+    fn len(&self) -> usize {
+        match self.notes.get() {
+            None => self.count,
+            Some(notes) => notes.as_ref().map(Vec::len).unwrap_or(self.count),
+        }
+    }
+}
+
+impl PartialEq for Notes {
+    fn eq(&self, other: &Notes) -> bool {
+        self.get() == other.get()
+    }
+}
+
+impl Serialize for Notes {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.get().map_err(serde::ser::Error::custom)?.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Notes {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Notes, D::Error> {
+        Vec::<Note>::deserialize(deserializer).map(Notes::inline)
+    }
 }
 
 /**
  * A single Task its description,  status, and any other information related to this
  * task.
  */
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Task {
     id: u32,
     name: String,
     status: Status,
 
-    #[serde(default = "Utc::now")]
+    #[serde(default = "Utc::now", with = "crate::dates::stored")]
     created_at: DateTime<Utc>,
 
+    #[serde(default, with = "crate::dates::stored::option")]
     closed_at: Option<DateTime<Utc>>,
 
     #[serde(default)]
     priority: u32,
 
     #[serde(default)]
-    notes: Vec<Note>,
+    notes: Notes,
+
+    /// How many notes are in the task's notes file, only set in the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note_count: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    due_at: Option<DateTime<Utc>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    assignee: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    expires_at: Option<DateTime<Utc>>,
+
+    /// The task this is a subtask of.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    parent: Option<u32>,
+
+    /// The tasks which must be done before this one.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    depends_on: Vec<u32>,
+
+    /// When the task was last looked at by `tisk review`.
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "crate::dates::stored::option")]
+    reviewed_at: Option<DateTime<Utc>>,
+
+    /// Where the task came from, e.g. the key of the Jira issue it was
+    /// imported from under `jira`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+
+    /// Each time the task was checked out, oldest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    time_entries: Vec<TimeEntry>,
+
+    /// The reminders about the task, earliest first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    reminders: Vec<Reminder>,
+
+    /// Fields this version of tisk does not know about, e.g. written by a
+    /// newer version or another tool, kept so that they are written back.
+    #[serde(flatten)]
+    unknown: std::collections::BTreeMap<String, serde_yaml::Value>,
 }
 
 impl Task {
@@ -63,7 +282,19 @@ impl Task {
             priority,
             created_at: Utc::now(),
             closed_at: None,
-            notes: Vec::new(),
+            notes: Notes::default(),
+            note_count: None,
+            due_at: None,
+            tags: Vec::new(),
+            assignee: None,
+            expires_at: None,
+            parent: None,
+            depends_on: Vec::new(),
+            reviewed_at: None,
+            metadata: BTreeMap::new(),
+            time_entries: Vec::new(),
+            reminders: Vec::new(),
+            unknown: std::collections::BTreeMap::new(),
         }
     }
 
@@ -71,23 +302,58 @@ impl Task {
         &self.name
     }
 
+    pub fn set_name(&mut self, name: &str) {
+        self.name = String::from(name)
+    }
+
     pub fn id(&self) -> u32 {
         self.id
     }
 
+    /// Changes the ID of this task, used when merging tasks from another list.
+    pub(super) fn set_id(&mut self, id: u32) {
+        self.id = id
+    }
+
     pub fn created_at(&self) -> DateTime<Utc> {
         self.created_at
     }
 
+    /// Changes when this task was created, e.g. to keep the date of a task
+    /// imported from another tool.
+    pub fn set_created_at(&mut self, created_at: DateTime<Utc>) {
+        self.created_at = created_at
+    }
+
+    /// When the task was closed, `None` while it is open.
+    pub fn closed_at(&self) -> Option<DateTime<Utc>> {
+        self.closed_at
+    }
+
     pub fn status(&self) -> Status {
         self.status
     }
 
     pub fn close(&mut self) {
-        self.closed_at = Some(Utc::now());
+        self.close_at(Utc::now())
+    }
+
+    /// Closes this task as of `closed_at`, rather than now.
+    pub fn close_at(&mut self, closed_at: DateTime<Utc>) {
+        self.closed_at = Some(closed_at);
         self.status = Status::Closed;
     }
 
+    /// Changes the status of this task to one of the open statuses, use
+    /// `close` to close it.
+    pub fn set_status(&mut self, status: Status) {
+        if status == Status::Closed {
+            return self.close();
+        }
+        self.closed_at = None;
+        self.status = status;
+    }
+
     pub fn priority(&self) -> u32 {
         self.priority
     }
@@ -96,34 +362,362 @@ impl Task {
         self.priority = priority
     }
 
+    /// The notes on this task, oldest first.  If they are kept in their own
+    /// file and it cannot be read then there are no notes, and the task
+    /// cannot be written until they can be read.
     pub fn notes(&self) -> Vec<&Note> {
-        self.notes.iter().collect()
+        self.notes.get().map(|notes| notes.iter().collect()).unwrap_or_default()
+    }
+
+    /// How many notes this task has, without reading them.
+    pub fn note_count(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn add_note(&mut self, note: &str) -> Result<(), TiskError> {
+        self.add_note_at(note, Utc::now())
+    }
+
+    /// Adds a note which was written at `created_at`, keeping the notes
+    /// oldest first.  If the task's notes file cannot be read then the note
+    /// is not added.
+    pub fn add_note_at(&mut self, note: &str, created_at: DateTime<Utc>) -> Result<(), TiskError> {
+        let id = self.id;
+        let notes = self
+            .notes
+            .get_mut()
+            .map_err(|why| TiskError::Serde(format!("Could not add a note to task {}: {}", id, why)))?;
+        let at = notes.iter().position(|n| n.created_at > created_at).unwrap_or(notes.len());
+        notes.insert(at, Note { created_at, note: String::from(note) });
+        Ok(())
+    }
+
+    pub fn due_at(&self) -> Option<DateTime<Utc>> {
+        self.due_at
+    }
+
+    pub fn set_due_at(&mut self, due_at: Option<DateTime<Utc>>) {
+        self.due_at = due_at
+    }
+
+    /// Whether this task is still open and, as of `now`, was due on an
+    /// earlier day or, if it is due at a time of day, at an earlier time.
+    /// Days are those of `zone`.
+    pub fn is_overdue(&self, now: DateTime<Utc>, zone: Zone) -> bool {
+        let today = zone.naive(now).date();
+        let overdue = |due: DateTime<Utc>| {
+            if zone.naive(due).time() != NaiveTime::from_hms(0, 0, 0) {
+                due < now
+            } else {
+                zone.naive(due).date() < today
+            }
+        };
+        self.status.is_open() && self.due_at.map(overdue).unwrap_or(false)
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Adds `tag` to this task, unless the task already has it.
+    pub fn add_tag(&mut self, tag: &str) {
+        if !self.has_tag(tag) {
+            self.tags.push(String::from(tag));
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        self.tags.retain(|t| t != tag);
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    pub fn assignee(&self) -> Option<&str> {
+        self.assignee.as_deref()
+    }
+
+    pub fn set_assignee(&mut self, assignee: Option<&str>) {
+        self.assignee = assignee.map(String::from)
+    }
+
+    /// When this task is closed automatically, if it is still open.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.expires_at
+    }
+
+    pub fn set_expires_at(&mut self, expires_at: Option<DateTime<Utc>>) {
+        self.expires_at = expires_at
+    }
+
+    /// The ID of the task this is a subtask of, if any.
+    pub fn parent(&self) -> Option<u32> {
+        self.parent
+    }
+
+    pub fn set_parent(&mut self, parent: Option<u32>) {
+        self.parent = parent
+    }
+
+    /// The IDs of the tasks this task depends on, in the order they were
+    /// added.
+    pub fn depends_on(&self) -> &[u32] {
+        &self.depends_on
+    }
+
+    /// Makes this task depend on the task with ID `id`, if it does not
+    /// already.
+    pub fn add_dependency(&mut self, id: u32) {
+        if !self.depends_on.contains(&id) {
+            self.depends_on.push(id)
+        }
+    }
+
+    pub fn remove_dependency(&mut self, id: u32) {
+        self.depends_on.retain(|d| *d != id)
+    }
+
+    pub fn reviewed_at(&self) -> Option<DateTime<Utc>> {
+        self.reviewed_at
+    }
+
+    pub fn set_reviewed_at(&mut self, reviewed_at: Option<DateTime<Utc>>) {
+        self.reviewed_at = reviewed_at
+    }
+
+    /// What is known about where the task came from, e.g. `jira` for the
+    /// key of the issue it was imported from.
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, key: &str, value: &str) {
+        self.metadata.insert(String::from(key), String::from(value));
+    }
+
+    /// Each time this task was checked out, oldest first.
+    pub fn time_entries(&self) -> &[TimeEntry] {
+        &self.time_entries
+    }
+
+    /// Starts a time entry at `now`, because the task was checked out,
+    /// unless one is already going.
+    pub fn start_timer(&mut self, now: DateTime<Utc>) {
+        if self.time_entries.last().map(|entry| entry.ended_at.is_some()).unwrap_or(true) {
+            self.time_entries.push(TimeEntry { started_at: now, ended_at: None });
+        }
+    }
+
+    /// Ends the ongoing time entry at `now`, because the task was checked
+    /// in.  Returns whether there was one.
+    pub fn stop_timer(&mut self, now: DateTime<Utc>) -> bool {
+        match self.time_entries.last_mut().filter(|entry| entry.ended_at.is_none()) {
+            Some(entry) => {
+                entry.ended_at = Some(now);
+                true
+            }
+            None => false,
+        }
     }
 
-    pub fn add_note(&mut self, note: &str) {
-        self.notes.push(Note::new(note));
+    /// The total time this task has been checked out for as of `now`.
+    pub fn time_spent(&self, now: DateTime<Utc>) -> chrono::Duration {
+        self.time_entries.iter().map(|entry| entry.duration(now)).fold(chrono::Duration::zero(), |a, b| a + b)
     }
 
-    pub fn write(task: &Task, path: &std::path::PathBuf) -> std::io::Result<()> {
-        let mut path = std::path::PathBuf::from(path);
-        path.push(format!("{}.yaml", task.id));
-        let mut file = File::create(path)?;
+    /// The reminders about this task, earliest first, including those which
+    /// were delivered.
+    pub fn reminders(&self) -> &[Reminder] {
+        &self.reminders
+    }
 
-        let s = serde_yaml::to_string(task)
-            .map_err(|why| IoError::new(IoErrorKind::InvalidData, why))?;
+    /// Adds a reminder about this task at `at`, unless there already is one.
+    pub fn add_reminder(&mut self, at: DateTime<Utc>) {
+        if self.reminders.iter().all(|reminder| reminder.at != at) {
+            self.reminders.push(Reminder { at, delivered_at: None });
+            self.reminders.sort_by_key(|reminder| reminder.at);
+        }
+    }
+
+    /// Marks the reminders which are due at `now`, and have not been
+    /// delivered, as delivered and returns when each was for.
+    pub fn deliver_reminders(&mut self, now: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+        let mut delivered = vec![];
+        for reminder in self.reminders.iter_mut().filter(|r| r.delivered_at.is_none() && r.at <= now) {
+            reminder.delivered_at = Some(now);
+            delivered.push(reminder.at);
+        }
+        delivered
+    }
+
+    /// The last time anything was done with this task: it was added,
+    /// closed, noted, or reviewed.
+    pub fn last_touched(&self) -> DateTime<Utc> {
+        let notes = self.notes().iter().map(|n| n.created_at()).max();
+        [self.closed_at, notes, self.reviewed_at].iter().flatten().copied().fold(self.created_at, DateTime::max)
+    }
+
+    /// Whether this task is open and nothing has been done with it for at
+    /// least `days` days as of `now`.
+    pub fn is_stale(&self, now: DateTime<Utc>, days: i64) -> bool {
+        self.status.is_open() && self.last_touched() <= now - chrono::Duration::days(days)
+    }
 
-        file.write_all(s.as_bytes())
+    /// Whether this task is open and has expired as of `now`.
+    pub fn has_expired(&self, now: DateTime<Utc>) -> bool {
+        self.status.is_open() && self.expires_at.map(|e| e <= now).unwrap_or(false)
     }
 
+    /// Writes `task` to its file in the `.tisk` directory at `path`, and its
+    /// notes to its notes file if they have been read, unless the files
+    /// already hold the task as it is.  Returns whether the task's file was
+    /// written.
+    #[cfg(feature = "storage")]
+    pub fn write(task: &Task, path: &std::path::Path) -> std::io::Result<bool> {
+        let (yaml, notes) = task.to_stored_yaml()?;
+        match notes {
+            None => (),
+            Some(None) => crate::io::remove_file_if_exists(&task.notes_path(path))?,
+            Some(Some(notes)) => {
+                crate::io::write_if_changed(&task.notes_path(path), &notes)?;
+            }
+        }
+        crate::io::write_if_changed(&task.file_path(path), &yaml)
+    }
+
+    /// Reads the task in the file at `path`.  Its notes are read from its
+    /// notes file when they are first needed.
+    #[cfg(feature = "storage")]
     pub fn read(path: &std::path::PathBuf) -> std::io::Result<Task> {
         let mut file = File::open(path)?;
 
         let mut s = String::new();
         file.read_to_string(&mut s)?;
+        Task::from_stored_yaml(&s, path.parent().unwrap_or_else(|| std::path::Path::new(".")))
+    }
+
+    /// The path of the file this task is stored in, within the `.tisk`
+    /// directory at `task_path`.
+    pub fn file_path(&self, task_path: &std::path::Path) -> std::path::PathBuf {
+        let mut path = std::path::PathBuf::from(task_path);
+        path.push(format!("{}.yaml", self.id));
+        path
+    }
+
+    /// The path of the file this task's notes are stored in, within the
+    /// `.tisk` directory at `task_path`.
+    pub fn notes_path(&self, task_path: &std::path::Path) -> std::path::PathBuf {
+        task_path.join(format!("{}.notes.yaml", self.id))
+    }
+
+    /// Whether this task was read from a file which holds its notes, as task
+    /// files did before notes were kept in their own file.
+    pub(crate) fn has_inline_notes(&self) -> bool {
+        self.notes.file.is_none() && self.note_count() > 0
+    }
+
+    /// The contents of this task's file with the per-task layout, which
+    /// only counts the notes, and what to do with its notes file: `None` to
+    /// leave it alone because the notes were not read, `Some(None)` to
+    /// remove it because there are no notes, or the contents to write.
+    #[cfg(feature = "storage")]
+    pub(crate) fn to_stored_yaml(&self) -> std::io::Result<(String, Option<Option<String>>)> {
+        let notes = match self.notes.notes.get() {
+            None => None,
+            Some(Err(why)) => return Err(IoError::new(IoErrorKind::InvalidData, why.clone())),
+            Some(Ok(notes)) if notes.is_empty() => Some(None),
+            Some(Ok(notes)) => {
+                Some(Some(serde_yaml::to_string(notes).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))?))
+            }
+        };
+        let stored = Task {
+            notes: Notes::default(),
+            note_count: Some(self.note_count()).filter(|count| *count > 0),
+            unknown: self.unknown.clone(),
+            tags: self.tags.clone(),
+            name: self.name.clone(),
+            assignee: self.assignee.clone(),
+            depends_on: self.depends_on.clone(),
+            metadata: self.metadata.clone(),
+            time_entries: self.time_entries.clone(),
+            reminders: self.reminders.clone(),
+            ..*self
+        };
+        Ok((stored.to_yaml()?, notes))
+    }
+
+    /// The notes file this task's notes will be read from, if they are in
+    /// one and have not been read yet.
+    #[cfg(feature = "async")]
+    pub(crate) fn unread_notes_file(&self) -> Option<&std::path::Path> {
+        match self.notes.notes.get() {
+            None => self.notes.file.as_deref(),
+            Some(_) => None,
+        }
+    }
+
+    /// Sets this task's notes from the contents of its notes file, e.g.
+    /// when the file was read without blocking.
+    #[cfg(feature = "async")]
+    pub(crate) fn read_notes_from(&mut self, s: &str) -> std::io::Result<()> {
+        let notes = serde_yaml::from_str(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))?;
+        self.notes = Notes::inline(notes);
+        Ok(())
+    }
+
+    /// Parses a task from the contents of its file with the per-task
+    /// layout, its notes are in the notes file in `task_path` if the file
+    /// counts them.  Files written before notes had their own file hold the
+    /// notes themselves.
+    #[cfg(feature = "storage")]
+    pub(crate) fn from_stored_yaml(s: &str, task_path: &std::path::Path) -> std::io::Result<Task> {
+        let mut task = Task::from_yaml(s)?;
+        if let Some(count) = task.note_count.take() {
+            task.notes = Notes::in_file(task.notes_path(task_path), count);
+        }
+        Ok(task)
+    }
+
+    /// This task and every one of its notes, oldest first, as a markdown
+    /// document, e.g. to archive an investigation log outside of tisk.
+    /// Times are shown as `dates` says.
+    pub fn to_markdown(&self, dates: &DateContext) -> String {
+        let mut md = format!("# Task {}: {}\n\n", self.id, self.name);
+        md.push_str(&format!("- Status: {}\n- Priority: {}\n", self.status, self.priority));
+        md.push_str(&format!("- Created: {}\n", dates.format_time(self.created_at)));
+        if let Some(closed_at) = self.closed_at {
+            md.push_str(&format!("- Closed: {}\n", dates.format_time(closed_at)));
+        }
+        if let Some(due_at) = self.due_at {
+            md.push_str(&format!("- Due: {}\n", dates.format_due(due_at)));
+        }
+        if !self.tags.is_empty() {
+            md.push_str(&format!("- Tags: {}\n", self.tags.join(", ")));
+        }
+        if let Some(assignee) = &self.assignee {
+            md.push_str(&format!("- Assignee: {}\n", assignee));
+        }
+
+        md.push_str("\n## Notes\n");
+        let notes = self.notes();
+        if notes.is_empty() {
+            md.push_str("\nThere are no notes.\n");
+        }
+        for note in notes {
+            md.push_str(&format!("\n### {}\n\n{}\n", dates.format_time(note.created_at), note.note.trim_end()));
+        }
+        md
+    }
 
-        let y = serde_yaml::from_str::<Task>(&s)
-            .map_err(|why| IoError::new(IoErrorKind::InvalidData, why))?;
-        Ok(y)
+    /// The contents of the file this task is stored in.
+    pub fn to_yaml(&self) -> std::io::Result<String> {
+        serde_yaml::to_string(self).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
+    }
+
+    /// Parses a task from the contents of its file.
+    pub fn from_yaml(s: &str) -> std::io::Result<Task> {
+        serde_yaml::from_str::<Task>(s).map_err(|why| IoError::new(IoErrorKind::InvalidData, why))
     }
 }
 
@@ -139,12 +733,196 @@ mod tests {
         assert_eq!(true, task.closed_at.is_some());
     }
 
+    #[test]
+    fn statuses() {
+        let mut task = Task::new(1, "test".into(), Status::Open, 1);
+        task.set_status(Status::InProgress);
+        assert_eq!(true, task.status().is_open());
+        task.set_status(Status::Closed);
+        assert_eq!(false, task.status().is_open());
+        assert_eq!(true, task.closed_at.is_some());
+        task.set_status(Status::Blocked);
+        assert_eq!(true, task.closed_at.is_none());
+
+        let yaml = task.to_yaml().unwrap();
+        assert_eq!(true, yaml.contains("status: Blocked"));
+        assert_eq!(Status::Blocked, Task::from_yaml(&yaml).unwrap().status());
+    }
+
+    #[test]
+    fn stale() {
+        let now = Utc::now();
+        let mut task = Task::new(1, "test".into(), Status::Open, 1);
+        task.created_at = now - chrono::Duration::days(30);
+        assert_eq!(true, task.is_stale(now, 14));
+
+        task.set_reviewed_at(Some(now - chrono::Duration::days(3)));
+        assert_eq!(false, task.is_stale(now, 14));
+        assert_eq!(true, task.is_stale(now, 2));
+        task.add_note("still waiting").unwrap();
+        assert_eq!(false, task.is_stale(now + chrono::Duration::days(1), 2));
+
+        task.close();
+        assert_eq!(false, task.is_stale(now + chrono::Duration::days(90), 14));
+    }
+
     #[test]
     fn notes() {
         // add a note
         let mut task = Task::new(1, "test".into(), Status::Open, 1);
-        task.add_note("test note");
+        task.add_note("test note").unwrap();
         assert_eq!(1, task.notes().len());
         assert_eq!("test note", task.notes()[0].note);
     }
+
+    #[test]
+    fn time_entries() {
+        let start = Utc.ymd(2020, 6, 1).and_hms(9, 0, 0);
+        let mut task = Task::new(1, "Write docs".into(), Status::Open, 1);
+        assert_eq!(false, task.stop_timer(start));
+
+        task.start_timer(start);
+        task.start_timer(start + chrono::Duration::minutes(5));
+        assert_eq!(1, task.time_entries().len());
+        assert_eq!(chrono::Duration::minutes(30), task.time_spent(start + chrono::Duration::minutes(30)));
+        assert_eq!(true, task.stop_timer(start + chrono::Duration::hours(1)));
+
+        task.start_timer(start + chrono::Duration::hours(2));
+        task.stop_timer(start + chrono::Duration::hours(3));
+        assert_eq!(chrono::Duration::hours(2), task.time_spent(start + chrono::Duration::days(1)));
+
+        let read = Task::from_yaml(&task.to_yaml().unwrap()).unwrap();
+        assert_eq!(task.time_entries(), read.time_entries());
+    }
+
+    #[test]
+    fn reminders() {
+        let at = Utc.ymd(2020, 6, 1).and_hms(9, 0, 0);
+        let mut task = Task::new(1, "Call the bank".into(), Status::Open, 1);
+        task.add_reminder(at + chrono::Duration::days(1));
+        task.add_reminder(at);
+        task.add_reminder(at);
+        assert_eq!(vec![at, at + chrono::Duration::days(1)], task.reminders().iter().map(|r| r.at).collect::<Vec<_>>());
+
+        assert_eq!(Vec::<DateTime<Utc>>::new(), task.deliver_reminders(at - chrono::Duration::minutes(1)));
+        assert_eq!(vec![at], task.deliver_reminders(at + chrono::Duration::hours(1)));
+        assert_eq!(Vec::<DateTime<Utc>>::new(), task.deliver_reminders(at + chrono::Duration::hours(2)));
+        assert_eq!(Some(at + chrono::Duration::hours(1)), task.reminders()[0].delivered_at);
+    }
+
+    #[test]
+    fn markdown() {
+        let mut task = Task::new(7, "Fix the login bug".into(), Status::InProgress, 5);
+        task.add_tag("bug");
+        assert_eq!(true, task.to_markdown(&DateContext::default()).ends_with("## Notes\n\nThere are no notes.\n"));
+
+        task.add_note("Found the cause\n").unwrap();
+        task.add_note("Fixed it").unwrap();
+        let md = task.to_markdown(&DateContext::default());
+        assert_eq!(true, md.starts_with("# Task 7: Fix the login bug\n\n- Status: in progress\n- Priority: 5\n"));
+        assert_eq!(true, md.contains("- Tags: bug\n"));
+        let created_at = DateContext::default().format_time(task.notes()[0].created_at);
+        let note = format!("\n### {}\n\nFound the cause\n", created_at);
+        assert_eq!(true, md.contains(&note));
+        assert_eq!(true, md.find("Found the cause").unwrap() < md.find("Fixed it").unwrap());
+    }
+
+    #[test]
+    fn note_references() {
+        let note = Note::new("Blocked by #12 and #3, see #12; not C#4, #5th or #");
+        assert_eq!(vec![12, 3], note.references());
+        assert_eq!(vec![(0..2, 7)], find_references("#7"));
+        assert_eq!(vec![(11..14, 12)], find_references("Blocked by #12"));
+    }
+
+    #[test]
+    fn tags() {
+        let mut task = Task::new(1, "test".into(), Status::Open, 1);
+        task.add_tag("ui");
+        task.add_tag("bug");
+        task.add_tag("ui");
+        assert_eq!(vec!["ui", "bug"], task.tags());
+        task.remove_tag("ui");
+        assert_eq!(vec!["bug"], task.tags());
+    }
+
+    #[test]
+    fn expiry() {
+        let now = Utc::now();
+        let mut task = Task::new(1, "test".into(), Status::Open, 1);
+        assert_eq!(false, task.has_expired(now));
+        task.set_expires_at(Some(now - chrono::Duration::hours(1)));
+        assert_eq!(true, task.has_expired(now));
+        task.close();
+        assert_eq!(false, task.has_expired(now));
+    }
+
+    #[test]
+    fn overdue() {
+        let today = NaiveDate::from_ymd(2020, 6, 3);
+        let at = |d: NaiveDate, h| Local.from_local_date(&d).unwrap().and_hms(h, 0, 0).with_timezone(&Utc);
+        let now = at(today, 12);
+        let mut task = Task::new(1, "test".into(), Status::Open, 1);
+        assert_eq!(false, task.is_overdue(now, Zone::Local));
+        task.set_due_at(Some(at(today, 0)));
+        assert_eq!(false, task.is_overdue(now, Zone::Local));
+        task.set_due_at(Some(at(today, 17)));
+        assert_eq!(false, task.is_overdue(now, Zone::Local));
+        task.set_due_at(Some(at(today, 9)));
+        assert_eq!(true, task.is_overdue(now, Zone::Local));
+        task.set_due_at(Some(at(NaiveDate::from_ymd(2020, 6, 2), 0)));
+        assert_eq!(true, task.is_overdue(now, Zone::Local));
+        task.close();
+        assert_eq!(false, task.is_overdue(now, Zone::Local));
+    }
+
+    #[test]
+    fn old_files_have_no_due_date_tags_or_assignee() {
+        let yaml = "id: 1\nname: test\nstatus: Open\nclosed_at: ~\npriority: 2\n";
+        let task = Task::from_yaml(yaml).unwrap();
+        assert_eq!(None, task.due_at());
+        assert_eq!(true, task.tags().is_empty());
+        assert_eq!(None, task.assignee());
+        assert_eq!(None, task.expires_at());
+
+        // Fields which are not set are not written either
+        let yaml = task.to_yaml().unwrap();
+        assert_eq!(false, yaml.contains("due_at") || yaml.contains("tags") || yaml.contains("assignee"));
+        assert_eq!(false, yaml.contains("expires_at"));
+    }
+
+    #[test]
+    fn unchanged_task_is_written_as_it_was_read() {
+        let yaml = "---
+id: 3
+name: test
+status: Closed
+created_at: \"2020-06-01T09:00:00.066120031Z\"
+closed_at: \"2020-06-02T17:30:00Z\"
+priority: 2
+notes:
+  - created_at: \"2020-06-01T09:05:00.250Z\"
+    note: a note
+due_at: \"2020-06-03T00:00:00Z\"
+tags:
+  - docs
+estimate: 3h";
+        assert_eq!(yaml, Task::from_yaml(yaml).unwrap().to_yaml().unwrap());
+
+        // Times written by other tools are read, then written in UTC
+        let task = Task::from_yaml("id: 1\nname: test\nstatus: Open\ndue_at: \"2020-06-03T02:00:00+02:00\"\n").unwrap();
+        assert_eq!(true, task.to_yaml().unwrap().contains("due_at: \"2020-06-03T00:00:00Z\""));
+    }
+
+    #[test]
+    fn unknown_fields_are_kept() {
+        let yaml = "id: 1\nname: test\nstatus: Open\nclosed_at: ~\nestimate: 3h\nlinks:\n  - a\n  - b\n";
+        let mut task = Task::from_yaml(yaml).unwrap();
+        task.set_priority(4);
+
+        let task = Task::from_yaml(&task.to_yaml().unwrap()).unwrap();
+        assert_eq!(4, task.priority());
+        assert_eq!(Some(&serde_yaml::Value::from("3h")), task.unknown.get("estimate"));
+        assert_eq!(true, task.unknown["links"].is_sequence());
+    }
 }
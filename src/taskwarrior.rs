@@ -0,0 +1,164 @@
+/*!
+ * Imports tasks from Taskwarrior, from the JSON which `task export`
+ * prints, so that someone moving to tisk can bring their backlog along.
+ * Each Taskwarrior task becomes a task with a new ID, keeping when it was
+ * created and closed, and with its UUID kept in the task's `taskwarrior`
+ * metadata so that importing the same export again only adds the tasks
+ * which are new.
+ */
+use crate::error::TiskError;
+use crate::tasks::{Status, Task, TaskList};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+
+/// The metadata which the UUID of the Taskwarrior task a task was imported
+/// from is kept in.
+pub const KEY: &str = "taskwarrior";
+
+/// A task, as `task export` prints it.  Only the fields which tisk has a
+/// use for are read.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Exported {
+    pub uuid: String,
+    pub description: String,
+
+    /// `pending`, `waiting`, `completed`, `deleted`, or `recurring`.
+    pub status: String,
+
+    /// `H`, `M`, or `L`.
+    pub priority: Option<String>,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub annotations: Vec<Annotation>,
+
+    /// When the task was created.
+    pub entry: Option<String>,
+
+    /// When the task was started, if it is active.
+    pub start: Option<String>,
+
+    /// When the task was completed or deleted.
+    pub end: Option<String>,
+    pub due: Option<String>,
+}
+
+/// A note on a Taskwarrior task.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Annotation {
+    pub entry: Option<String>,
+    pub description: String,
+}
+
+impl Exported {
+    /// Whether this becomes a task.  Deleted tasks do not, and neither do
+    /// the templates of recurring tasks, since each time they recur is a
+    /// task of its own.
+    pub fn is_imported(&self) -> bool {
+        self.status != "deleted" && self.status != "recurring"
+    }
+
+    /// The task with ID `id` which this becomes.  Its priority becomes 4, 3,
+    /// or 2 for `H`, `M`, or `L`, and 1 without one, as Jira's High, Medium,
+    /// and Low do.  An active task is in progress.  Its project and tags
+    /// become tags and its annotations become notes.
+    pub fn to_task(&self, id: u32) -> Result<Task, TiskError> {
+        let priority = match self.priority.as_deref() {
+            Some("H") => 4,
+            Some("M") => 3,
+            Some("L") => 2,
+            _ => 1,
+        };
+        let mut task = Task::new(id, self.description.clone(), Status::Open, priority);
+        if let Some(entry) = &self.entry {
+            task.set_created_at(parse_time(entry)?);
+        }
+        match self.status.as_str() {
+            "completed" => {
+                let end = self.end.as_deref().or(self.entry.as_deref()).map(parse_time).transpose()?;
+                task.close_at(end.unwrap_or_else(Utc::now));
+            }
+            _ if self.start.is_some() => task.set_status(Status::InProgress),
+            _ => (),
+        }
+        task.set_due_at(self.due.as_deref().map(parse_time).transpose()?);
+        for tag in self.project.iter().chain(self.tags.iter()) {
+            task.add_tag(tag);
+        }
+        for annotation in &self.annotations {
+            let at = annotation.entry.as_deref().map(parse_time).transpose()?;
+            task.add_note_at(&annotation.description, at.unwrap_or_else(|| task.created_at()))?;
+        }
+        task.set_metadata(KEY, &self.uuid);
+        Ok(task)
+    }
+}
+
+/// The tasks which the tasks in `exported` become in the project with
+/// `tasks`.  A task which was imported before is given the ID of the task
+/// it became, the others are given new IDs, so that importing them with
+/// `IdPolicy::Keep(Conflict::Skip)` only adds the tasks which are new.
+pub fn to_tasks(exported: &[Exported], tasks: &TaskList) -> Result<Vec<Task>, TiskError> {
+    let mut next_id = tasks.next_id();
+    let mut imported = vec![];
+    for exported in exported.iter().filter(|e| e.is_imported()) {
+        let existing = tasks.get_all().into_iter().find(|t| t.metadata().get(KEY) == Some(&exported.uuid));
+        let id = match existing {
+            Some(task) => task.id(),
+            None => {
+                next_id += 1;
+                next_id - 1
+            }
+        };
+        imported.push(exported.to_task(id)?);
+    }
+    Ok(imported)
+}
+
+/// Parses a Taskwarrior time, e.g. `20240601T090000Z`, which is in UTC.
+fn parse_time(s: &str) -> Result<DateTime<Utc>, TiskError> {
+    NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")
+        .map(|time| DateTime::from_utc(time, Utc))
+        .or_else(|_| DateTime::parse_from_rfc3339(s).map(|time| time.with_timezone(&Utc)))
+        .map_err(|_| TiskError::Serde(format!("Invalid Taskwarrior tasks: invalid date {}", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tasks::{Conflict, IdPolicy};
+    use chrono::TimeZone;
+
+    const EXPORT: &str = r#"[
+{"id":1,"description":"Fix the \"login\" page","entry":"20200601T090000Z","modified":"20200602T090000Z",
+ "start":"20200602T090000Z","priority":"H","project":"web","status":"pending","tags":["bug"],
+ "uuid":"8a1f0b6e-0000-4000-8000-000000000001","urgency":8.1,
+ "annotations":[{"entry":"20200603T100000Z","description":"Only on Safari"},
+                {"entry":"20200602T100000Z","description":"Seen in the logs"}]},
+{"id":0,"description":"Write docs","end":"20200605T170000Z","entry":"20200601T090000Z","status":"completed",
+ "uuid":"8a1f0b6e-0000-4000-8000-000000000002"},
+{"id":0,"description":"Old idea","status":"deleted","uuid":"8a1f0b6e-0000-4000-8000-000000000003"}
+]"#;
+
+    #[test]
+    fn map_tasks() {
+        let exported: Vec<Exported> = serde_yaml::from_str(EXPORT).unwrap();
+        let mut tasks = TaskList::new();
+        tasks.add_task("existing", 1);
+
+        let imported = to_tasks(&exported, &tasks).unwrap();
+        assert_eq!(vec![2, 3], imported.iter().map(|t| t.id()).collect::<Vec<_>>());
+        let fix = &imported[0];
+        assert_eq!(("Fix the \"login\" page", Status::InProgress, 4), (fix.name(), fix.status(), fix.priority()));
+        assert_eq!(Utc.ymd(2020, 6, 1).and_hms(9, 0, 0), fix.created_at());
+        assert_eq!(vec!["web", "bug"], fix.tags());
+        assert_eq!(vec!["Seen in the logs", "Only on Safari"], fix.notes().iter().map(|n| n.note()).collect::<Vec<_>>());
+        assert_eq!(Some(Utc.ymd(2020, 6, 5).and_hms(17, 0, 0)), imported[1].closed_at());
+
+        // Importing the same export again finds the tasks it became
+        tasks.merge(imported.into_iter().take(1).collect(), IdPolicy::Keep(Conflict::Skip)).unwrap();
+        assert_eq!(vec![2, 3], to_tasks(&exported, &tasks).unwrap().iter().map(|t| t.id()).collect::<Vec<_>>());
+        assert!(parse_time("June 1st").is_err());
+    }
+}
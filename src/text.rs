@@ -0,0 +1,173 @@
+/*!
+ * Compares text the same way wherever tisk searches or filters: list
+ * `--contains`, tag lookups, and any later filter.  By default text is
+ * compared exactly.  The project's config can make comparisons ignore case,
+ * using Unicode lowercasing, and ignore accents, so that `cafe` matches
+ * `Café`.
+ */
+
+/// How text is compared when searching and filtering.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Matching {
+    pub ignore_case: bool,
+    pub ignore_accents: bool,
+}
+
+impl Matching {
+    /// Turns `s` into the form which is compared.
+    pub fn fold(&self, s: &str) -> String {
+        self.fold_with_spans(s).0
+    }
+
+    /// Returns true if `a` and `b` are the same text.
+    pub fn eq(&self, a: &str, b: &str) -> bool {
+        self.fold(a) == self.fold(b)
+    }
+
+    /// Returns true if `haystack` contains `needle`.
+    pub fn contains(&self, haystack: &str, needle: &str) -> bool {
+        self.fold(haystack).contains(&self.fold(needle))
+    }
+
+    /// Finds every occurrence of `needle` in `haystack`, as byte ranges of
+    /// the original `haystack` which do not overlap, e.g. for highlighting.
+    pub fn find_all(&self, haystack: &str, needle: &str) -> Vec<std::ops::Range<usize>> {
+        let needle = self.fold(needle);
+        if needle.is_empty() {
+            return vec![];
+        }
+
+        // A match which starts part way through a character that folds to
+        // many (e.g. `ß` to `ss`) overlaps the match before it and is skipped
+        let (folded, spans) = self.fold_with_spans(haystack);
+        let mut found: Vec<std::ops::Range<usize>> = vec![];
+        for (start, m) in folded.match_indices(&needle) {
+            let range = spans[start].start..spans[start + m.len() - 1].end;
+            if found.last().map(|last| last.end <= range.start).unwrap_or(true) {
+                found.push(range);
+            }
+        }
+        found
+    }
+
+    /// Folds `s` and, for every byte of the folded text, the range of the
+    /// character in `s` which it came from.
+    fn fold_with_spans(&self, s: &str) -> (String, Vec<std::ops::Range<usize>>) {
+        let mut folded = String::with_capacity(s.len());
+        let mut spans = Vec::with_capacity(s.len());
+        for (idx, c) in s.char_indices() {
+            let before = folded.len();
+            match unaccented(c).filter(|_| self.ignore_accents) {
+                Some(base) if c.is_uppercase() => folded.push_str(&base.to_uppercase()),
+                Some(base) => folded.push_str(base),
+                None => folded.push(c),
+            }
+            if self.ignore_case {
+                let tail = folded.split_off(before);
+                folded.extend(tail.chars().flat_map(char::to_lowercase));
+            }
+            spans.resize(folded.len(), idx..idx + c.len_utf8());
+        }
+        (folded, spans)
+    }
+}
+
+/// The letters, in lowercase, which an accented Latin letter is written
+/// with when its accent is removed.  Combining accents are removed entirely.
+fn unaccented(c: char) -> Option<&'static str> {
+    const BASES: &[(char, char, &str)] = &[
+        ('\u{c0}', '\u{c5}', "a"),
+        ('\u{c6}', '\u{c6}', "ae"),
+        ('\u{c7}', '\u{c7}', "c"),
+        ('\u{c8}', '\u{cb}', "e"),
+        ('\u{cc}', '\u{cf}', "i"),
+        ('\u{d0}', '\u{d0}', "d"),
+        ('\u{d1}', '\u{d1}', "n"),
+        ('\u{d2}', '\u{d6}', "o"),
+        ('\u{d8}', '\u{d8}', "o"),
+        ('\u{d9}', '\u{dc}', "u"),
+        ('\u{dd}', '\u{dd}', "y"),
+        ('\u{de}', '\u{de}', "th"),
+        ('\u{df}', '\u{df}', "ss"),
+        ('\u{e0}', '\u{e5}', "a"),
+        ('\u{e6}', '\u{e6}', "ae"),
+        ('\u{e7}', '\u{e7}', "c"),
+        ('\u{e8}', '\u{eb}', "e"),
+        ('\u{ec}', '\u{ef}', "i"),
+        ('\u{f0}', '\u{f0}', "d"),
+        ('\u{f1}', '\u{f1}', "n"),
+        ('\u{f2}', '\u{f6}', "o"),
+        ('\u{f8}', '\u{f8}', "o"),
+        ('\u{f9}', '\u{fc}', "u"),
+        ('\u{fd}', '\u{fd}', "y"),
+        ('\u{fe}', '\u{fe}', "th"),
+        ('\u{ff}', '\u{ff}', "y"),
+        ('\u{100}', '\u{105}', "a"),
+        ('\u{106}', '\u{10d}', "c"),
+        ('\u{10e}', '\u{111}', "d"),
+        ('\u{112}', '\u{11b}', "e"),
+        ('\u{11c}', '\u{123}', "g"),
+        ('\u{124}', '\u{127}', "h"),
+        ('\u{128}', '\u{131}', "i"),
+        ('\u{132}', '\u{133}', "ij"),
+        ('\u{134}', '\u{135}', "j"),
+        ('\u{136}', '\u{138}', "k"),
+        ('\u{139}', '\u{142}', "l"),
+        ('\u{143}', '\u{14b}', "n"),
+        ('\u{14c}', '\u{151}', "o"),
+        ('\u{152}', '\u{153}', "oe"),
+        ('\u{154}', '\u{159}', "r"),
+        ('\u{15a}', '\u{161}', "s"),
+        ('\u{162}', '\u{167}', "t"),
+        ('\u{168}', '\u{173}', "u"),
+        ('\u{174}', '\u{175}', "w"),
+        ('\u{176}', '\u{178}', "y"),
+        ('\u{179}', '\u{17e}', "z"),
+        ('\u{17f}', '\u{17f}', "s"),
+        ('\u{300}', '\u{36f}', ""),
+    ];
+
+    BASES
+        .iter()
+        .find(|(first, last, _)| *first <= c && c <= *last)
+        .map(|(_, _, base)| *base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXACT: Matching = Matching { ignore_case: false, ignore_accents: false };
+    const FOLDED: Matching = Matching { ignore_case: true, ignore_accents: true };
+
+    #[test]
+    fn exact_by_default() {
+        assert_eq!(EXACT, Matching::default());
+        assert_eq!(false, EXACT.eq("Bug", "bug"));
+        assert_eq!(true, EXACT.contains("fix the bug", "bug"));
+        assert_eq!(false, EXACT.contains("Café", "cafe"));
+    }
+
+    #[test]
+    fn ignore_case_and_accents() {
+        let case = Matching { ignore_case: true, ..EXACT };
+        assert_eq!(true, case.eq("Bug", "bUG"));
+        assert_eq!(true, case.eq("ÉCOLE", "école"));
+        assert_eq!(false, case.eq("école", "ecole"));
+
+        assert_eq!(true, FOLDED.eq("Crème Brûlée", "creme brulee"));
+        assert_eq!(true, FOLDED.contains("Straße", "STRASSE"));
+        assert_eq!(true, FOLDED.contains("cafe\u{301} au lait", "café"));
+    }
+
+    #[test]
+    fn find_all_in_original_text() {
+        let text = "Café, CAFE, café";
+        let found: Vec<&str> = FOLDED.find_all(text, "cafe").into_iter().map(|r| &text[r]).collect();
+        assert_eq!(vec!["Café", "CAFE", "café"], found);
+
+        assert_eq!(vec![4..6], FOLDED.find_all("Straße", "ss"));
+        assert_eq!(vec![0..2], FOLDED.find_all("ß", "s"));
+        assert_eq!(true, FOLDED.find_all("text", "").is_empty());
+    }
+}
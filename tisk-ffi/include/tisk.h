@@ -0,0 +1,71 @@
+#ifndef TISK_H
+#define TISK_H
+
+/* Generated by cbindgen from tisk-ffi/src/lib.rs, do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Which tasks `tisk_list` returns.
+ */
+typedef enum TiskFilter {
+  TISK_FILTER_OPEN,
+  TISK_FILTER_CLOSED,
+  TISK_FILTER_ALL,
+} TiskFilter;
+
+/**
+ * A tisk project opened by `tisk_project_open`.
+ */
+typedef struct TiskProject TiskProject;
+
+/**
+ * Adds a new task to the project.  If `id` is not NULL then it is set to
+ * the ID of the new task.  Returns 0 on success.
+ */
+int tisk_add(const TiskProject *project, const char *name, uint32_t priority, uint32_t *id);
+
+/**
+ * Closes the task `id`.  Returns 0 on success.
+ */
+int tisk_close(const TiskProject *project, uint32_t id);
+
+/**
+ * The reason the last function which failed on this thread failed, or NULL
+ * if nothing has failed.  The string is owned by tisk and is only valid
+ * until the next failure.
+ */
+const char *tisk_last_error(void);
+
+/**
+ * Returns the tasks in the project, as a JSON array, ordered by priority.
+ * Returns NULL on failure.
+ */
+char *tisk_list(const TiskProject *project, TiskFilter filter);
+
+/**
+ * Adds `note` to the task `id`.  Returns 0 on success.
+ */
+int tisk_note(const TiskProject *project, uint32_t id, const char *note);
+
+/**
+ * Frees a project opened by `tisk_project_open`.
+ */
+void tisk_project_free(TiskProject *project);
+
+/**
+ * Opens the tisk project which contains the directory `path`.  Returns
+ * NULL if `path` is not in a tisk project.  The project must be freed with
+ * `tisk_project_free`.
+ */
+TiskProject *tisk_project_open(const char *path);
+
+/**
+ * Frees a string returned by tisk.
+ */
+void tisk_string_free(char *s);
+
+#endif /* TISK_H */
@@ -0,0 +1,255 @@
+/*!
+ * A C interface to tisk, so that editors and plugins written in other
+ * languages can use a tisk project without running the `tisk` binary.
+ * The header for these functions is `include/tisk.h`.
+ *
+ * Functions which can fail return a negative number (or NULL) when they do
+ * and the reason can then be read with `tisk_last_error`.  Strings returned
+ * by tisk are owned by the caller and must be freed with `tisk_string_free`.
+ */
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::path::PathBuf;
+use tisk::engine::{self, Command, ListFilter, Output};
+use tisk::error::TiskError;
+use tisk::query::Query;
+use tisk::table::TableFormat;
+
+/// A tisk project opened by `tisk_project_open`.
+pub struct TiskProject {
+    task_path: PathBuf,
+}
+
+/// Which tasks `tisk_list` returns.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub enum TiskFilter {
+    Open,
+    Closed,
+    All,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(msg: String) {
+    LAST_ERROR.with(|err| *err.borrow_mut() = CString::new(msg).ok());
+}
+
+/// Records the error from `result`, if there is one, and returns the value
+/// of `result` or `failed`.
+fn or_error<T>(result: Result<T, TiskError>, failed: T) -> T {
+    result.unwrap_or_else(|why| {
+        set_error(why.to_string());
+        failed
+    })
+}
+
+unsafe fn to_str<'a>(s: *const c_char) -> Result<&'a str, TiskError> {
+    if s.is_null() {
+        return Err(TiskError::ParseError(String::from("Expected a string but was given NULL")));
+    }
+    CStr::from_ptr(s).to_str().map_err(|why| TiskError::ParseError(format!("Invalid UTF-8 string: {}", why)))
+}
+
+unsafe fn run(project: *const TiskProject, command: Command) -> Result<Output, TiskError> {
+    let project =
+        project.as_ref().ok_or_else(|| TiskError::ParseError(String::from("Expected a project but was given NULL")))?;
+    engine::run(&project.task_path, &command)
+}
+
+/// Opens the tisk project which contains the directory `path`.  Returns
+/// NULL if `path` is not in a tisk project.  The project must be freed with
+/// `tisk_project_free`.
+///
+/// # Safety
+///
+/// `path` must be NULL or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_project_open(path: *const c_char) -> *mut TiskProject {
+    let task_path = to_str(path).and_then(tisk::io::find_task_dir_from);
+
+    match task_path {
+        Ok(task_path) => Box::into_raw(Box::new(TiskProject { task_path })),
+        Err(why) => or_error(Err(why), std::ptr::null_mut()),
+    }
+}
+
+/// Frees a project opened by `tisk_project_open`.
+///
+/// # Safety
+///
+/// `project` must be NULL or a project from `tisk_project_open` which has
+/// not been freed, and it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_project_free(project: *mut TiskProject) {
+    if !project.is_null() {
+        drop(Box::from_raw(project));
+    }
+}
+
+/// Returns the tasks in the project, as a JSON array, ordered by priority.
+/// Returns NULL on failure.
+///
+/// # Safety
+///
+/// `project` must be NULL or a project from `tisk_project_open` which has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_list(project: *const TiskProject, filter: TiskFilter) -> *mut c_char {
+    let filter = match filter {
+        TiskFilter::Open => ListFilter::Open,
+        TiskFilter::Closed => ListFilter::Closed,
+        TiskFilter::All => ListFilter::All,
+    };
+    let command = Command::List {
+        query: Query::new(filter),
+        policy: Default::default(),
+        sort: None,
+        format: TableFormat::Table,
+        archived: false,
+    };
+
+    let json = run(project, command).and_then(|output| match output {
+        Output::Tasks { tasks, .. } => serde_json::to_string(&tasks).map_err(|why| TiskError::Serde(why.to_string())),
+        _ => Err(TiskError::Serde(String::from("Listing tasks did not return any tasks"))),
+    });
+    let json = json.and_then(|json| CString::new(json).map_err(|why| TiskError::Serde(why.to_string())));
+    or_error(json.map(CString::into_raw), std::ptr::null_mut())
+}
+
+/// Adds a new task to the project.  If `id` is not NULL then it is set to
+/// the ID of the new task.  Returns 0 on success.
+///
+/// # Safety
+///
+/// `project` must be NULL or a project from `tisk_project_open` which has
+/// not been freed, `name` must be NULL or a NUL terminated string, and `id`
+/// must be NULL or point to a `u32` which can be written.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_add(project: *const TiskProject, name: *const c_char, priority: u32, id: *mut u32) -> c_int {
+    let result = to_str(name).and_then(|name| {
+        let command = Command::Add {
+            name: String::from(name),
+            priority,
+            note: None,
+            tags: vec![],
+            due_at: None,
+            parent: None,
+        };
+        run(project, command)
+    });
+
+    or_error(
+        result.map(|output| {
+            if let (Output::Added(new_id), false) = (output, id.is_null()) {
+                *id = new_id;
+            }
+            0
+        }),
+        -1,
+    )
+}
+
+/// Closes the task `id`.  Returns 0 on success.
+///
+/// # Safety
+///
+/// `project` must be NULL or a project from `tisk_project_open` which has
+/// not been freed.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_close(project: *const TiskProject, id: u32) -> c_int {
+    let command = Command::Close { id: Some(id), note: None, follow_up: None, cascade: false };
+    or_error(run(project, command).map(|_| 0), -1)
+}
+
+/// Adds `note` to the task `id`.  Returns 0 on success.
+///
+/// # Safety
+///
+/// `project` must be NULL or a project from `tisk_project_open` which has
+/// not been freed, and `note` must be NULL or a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_note(project: *const TiskProject, id: u32, note: *const c_char) -> c_int {
+    let result = to_str(note).and_then(|note| {
+        let command = Command::Note {
+            id: Some(id),
+            note: Some(String::from(note)),
+        };
+        run(project, command)
+    });
+    or_error(result.map(|_| 0), -1)
+}
+
+/// The reason the last function which failed on this thread failed, or NULL
+/// if nothing has failed.  The string is owned by tisk and is only valid
+/// until the next failure.
+#[no_mangle]
+pub extern "C" fn tisk_last_error() -> *const c_char {
+    LAST_ERROR.with(|err| match err.borrow().as_ref() {
+        Some(err) => err.as_ptr(),
+        None => std::ptr::null(),
+    })
+}
+
+/// Frees a string returned by tisk.
+///
+/// # Safety
+///
+/// `s` must be NULL or a string returned by tisk which has not been freed,
+/// and it must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn tisk_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_note_close_and_list() {
+        let root = std::env::temp_dir().join(format!("tisk-ffi-{}", std::process::id()));
+        std::fs::create_dir_all(root.join(".tisk")).unwrap();
+        let path = CString::new(root.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let project = tisk_project_open(path.as_ptr());
+            assert!(!project.is_null());
+
+            let name = CString::new("test").unwrap();
+            let mut id = 0;
+            assert_eq!(0, tisk_add(project, name.as_ptr(), 2, &mut id));
+            assert_eq!(1, id);
+
+            let note = CString::new("a note").unwrap();
+            assert_eq!(0, tisk_note(project, id, note.as_ptr()));
+            assert_eq!(0, tisk_close(project, id));
+            assert_eq!(-1, tisk_close(project, 5));
+            assert!(!tisk_last_error().is_null());
+
+            let json = tisk_list(project, TiskFilter::Closed);
+            let tasks: serde_json::Value = serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            assert_eq!("test", tasks[0]["name"]);
+            assert_eq!("a note", tasks[0]["notes"][0]["note"]);
+            tisk_string_free(json);
+
+            tisk_project_free(project);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn open_outside_project() {
+        let path = CString::new("/").unwrap();
+        unsafe {
+            assert!(tisk_project_open(path.as_ptr()).is_null());
+            let err = CStr::from_ptr(tisk_last_error()).to_str().unwrap();
+            assert!(err.starts_with("Invalid tisk project"));
+        }
+    }
+}